@@ -0,0 +1,125 @@
+//! Instructions a second through the interpreter, fetch, decode and execute,
+//! over small synthetic loops and the ROMs in the repository.
+//!
+//! ```text
+//! cargo bench --bench interpreter
+//! ```
+
+use chippers::Chip8;
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use std::hint::black_box;
+
+/// Instructions run per iteration.
+const INSTRUCTIONS: u64 = 10_000;
+
+/// Instructions between timer ticks, as at the default 700 a second.
+const TICK_EVERY: u64 = 700 / 60;
+
+/// Arithmetic and jumps only.
+const ALU: [u8; 14] = [
+    0x60, 0x01, // V0 = 1
+    0x71, 0x03, // V1 += 3
+    0x82, 0x14, // V2 += V1
+    0x83, 0x26, // V3 = V2 >> 1
+    0x84, 0x32, // V4 &= V3
+    0x34, 0x00, // skip if V4 == 0
+    0x12, 0x02, // loop
+];
+
+/// BCD, stores and loads.
+const MEMORY: [u8; 12] = [
+    0xA3, 0x00, // I = 0x300
+    0xF2, 0x33, // BCD of V2 at I
+    0xF2, 0x55, // store V0..V2 at I
+    0xF2, 0x65, // load V0..V2 from I
+    0x72, 0x07, // V2 += 7
+    0x12, 0x00, // loop
+];
+
+/// Font sprites drawn at random places.
+const DRAW: [u8; 12] = [
+    0xC0, 0x3F, // V0 = random 0..64
+    0xC1, 0x1F, // V1 = random 0..32
+    0xC2, 0x0F, // V2 = random digit
+    0xF2, 0x29, // I = font(V2)
+    0xD0, 0x15, // draw it at (V0, V1)
+    0x12, 0x00, // loop
+];
+
+const ROMS: [(&str, &[u8]); 5] = [
+    ("ibm-logo", include_bytes!("../IBM Logo.ch8")),
+    (
+        "sierpinski",
+        include_bytes!("../Sierpinski [Sergey Naydenov, 2010].ch8"),
+    ),
+    (
+        "brix",
+        include_bytes!("../Brix [Andreas Gustafsson, 1990].ch8"),
+    ),
+    (
+        "maze",
+        include_bytes!("../Maze (alt) [David Winter, 199x].ch8"),
+    ),
+    ("test-opcode", include_bytes!("../test_opcode.ch8")),
+];
+
+fn machine(rom: &[u8]) -> Chip8 {
+    let mut chip8 = Chip8::new();
+    chip8.set_seed(0x2545_F491);
+    chip8.load_font_set();
+    chip8.load_rom(rom).unwrap();
+    chip8
+}
+
+/// Runs [`INSTRUCTIONS`] on `chip8`, ticking its timers along the way. The
+/// machine carries on from where the last iteration left it.
+fn run(chip8: &mut Chip8) {
+    for n in 0..INSTRUCTIONS {
+        black_box(chip8.step());
+        if n % TICK_EVERY == 0 {
+            chip8.tick_timers();
+        }
+    }
+}
+
+fn bench_synthetic(c: &mut Criterion) {
+    let mut group = c.benchmark_group("synthetic");
+    group.throughput(Throughput::Elements(INSTRUCTIONS));
+    for (name, rom) in [("alu", &ALU[..]), ("memory", &MEMORY), ("draw", &DRAW)] {
+        let mut chip8 = machine(rom);
+        group.bench_function(name, |b| b.iter(|| run(&mut chip8)));
+    }
+    group.finish();
+}
+
+fn bench_roms(c: &mut Criterion) {
+    let mut group = c.benchmark_group("roms");
+    group.throughput(Throughput::Elements(INSTRUCTIONS));
+    for (name, rom) in ROMS {
+        let mut chip8 = machine(rom);
+        group.bench_with_input(BenchmarkId::from_parameter(name), rom, |b, _| {
+            b.iter(|| run(&mut chip8))
+        });
+    }
+    group.finish();
+}
+
+/// The same loops with and without [`Cpu::decode_cache`].
+///
+/// [`Cpu::decode_cache`]: chippers::Cpu::decode_cache
+fn bench_decode_cache(c: &mut Criterion) {
+    let mut group = c.benchmark_group("decode-cache");
+    group.throughput(Throughput::Elements(INSTRUCTIONS));
+    for (name, rom) in [("alu", &ALU[..]), ("brix", ROMS[2].1)] {
+        for cached in [true, false] {
+            let mut chip8 = machine(rom);
+            chip8.cpu.decode_cache = cached;
+            let id = BenchmarkId::new(name, if cached { "on" } else { "off" });
+            group.bench_function(id, |b| b.iter(|| run(&mut chip8)));
+        }
+    }
+    group.finish();
+}
+
+criterion_group!(benches, bench_synthetic, bench_roms, bench_decode_cache);
+criterion_main!(benches);
@@ -0,0 +1,78 @@
+//! Frames a second through the framebuffer: packing, conversion to palette
+//! indices, PNG encoding, and drawing to a terminal.
+//!
+//! ```text
+//! cargo bench --bench render
+//! ```
+
+use chippers::backend::Palette;
+use chippers::display::DirtyRows;
+use chippers::terminal::{RenderMode, Terminal};
+use chippers::{image, Chip8, Display, DisplayBackend};
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use std::hint::black_box;
+
+/// Two consecutive frames of Sierpinski, half the screen drawn and a few rows
+/// changed between them.
+fn frames() -> (Display, Display) {
+    let mut chip8 = Chip8::new();
+    chip8.load_font_set();
+    chip8
+        .load_rom(include_bytes!("../Sierpinski [Sergey Naydenov, 2010].ch8"))
+        .unwrap();
+    for _ in 0..20_000 {
+        chip8.step();
+    }
+    let first = chip8.cpu.disp.clone();
+    while chip8.cpu.disp == first {
+        chip8.step();
+    }
+    (first, chip8.cpu.disp.clone())
+}
+
+fn bench_framebuffer(c: &mut Criterion) {
+    let (disp, _) = frames();
+    let frame = disp.pack();
+    let mut group = c.benchmark_group("framebuffer");
+    group.throughput(Throughput::Elements(1));
+    group.bench_function("pack", |b| b.iter(|| black_box(&disp).pack()));
+    let mut pixels = vec![0; frame.width * frame.height];
+    group.bench_function("8bpp", |b| {
+        b.iter(|| black_box(&frame).copy_8bpp(&mut pixels))
+    });
+    group.bench_function("png", |b| {
+        b.iter(|| image::png(black_box(&frame), 4, Palette::AMBER))
+    });
+    group.finish();
+}
+
+fn bench_terminal(c: &mut Criterion) {
+    let (first, second) = frames();
+    let mut group = c.benchmark_group("terminal");
+    group.throughput(Throughput::Elements(1));
+    for name in RenderMode::NAMES {
+        let mode = RenderMode::from_name(name).unwrap();
+        let mut term = Terminal::with_writer(Vec::new()).with_mode(mode);
+        group.bench_function(BenchmarkId::new("full", name), |b| {
+            b.iter(|| {
+                term.writer().clear();
+                // forgets what is on screen, so the whole frame is drawn
+                term.set_palette(Palette::MONOCHROME).unwrap();
+                term.draw_screen(black_box(&first)).unwrap();
+            })
+        });
+        let mut flip = false;
+        group.bench_function(BenchmarkId::new("changes", name), |b| {
+            b.iter(|| {
+                term.writer().clear();
+                flip = !flip;
+                let disp = if flip { &second } else { &first };
+                term.draw_rows(black_box(disp), DirtyRows::ALL).unwrap();
+            })
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, bench_framebuffer, bench_terminal);
+criterion_main!(benches);
@@ -0,0 +1,56 @@
+//! Random instructions run on a machine in a random state: the program
+//! counter, I, the registers, the quirks, a held key and a memory image all
+//! come from the input. No instruction may panic, whatever it finds.
+//!
+//! The input is a 24 byte header, then the instructions, two bytes each, up
+//! to `MAX_INSTRUCTIONS`; whatever follows them is the memory image, loaded
+//! at I.
+
+#![no_main]
+
+use chippers_core::cpu::{Cpu, OnUnknown};
+use chippers_core::opcode::Disassembly;
+use chippers_core::quirks::Quirks;
+use chippers_core::rng::Rng;
+use libfuzzer_sys::fuzz_target;
+
+const HEADER: usize = 24;
+const MAX_INSTRUCTIONS: usize = 64;
+
+fuzz_target!(|data: &[u8]| {
+    let Some((header, rest)) = data.split_first_chunk::<HEADER>() else {
+        return;
+    };
+    let word = |at: usize| u16::from_be_bytes([header[at], header[at + 1]]);
+    let bits = header[4];
+    let count = usize::from(header[5]) % MAX_INSTRUCTIONS + 1;
+    let (insts, image) = rest.split_at((count * 2).min(rest.len() & !1));
+
+    let mut cpu = Cpu::with_rng(Rng::new(1));
+    cpu.quirks = Quirks {
+        shift_uses_vy: bits & 0x01 != 0,
+        jump_uses_vx: bits & 0x02 != 0,
+        load_store_increments_i: bits & 0x04 != 0,
+        logic_resets_vf: bits & 0x08 != 0,
+        draw_wraps: bits & 0x10 != 0,
+        display_wait: bits & 0x20 != 0,
+    };
+    cpu.on_unknown = if bits & 0x40 != 0 {
+        OnUnknown::Skip
+    } else {
+        OnUnknown::Halt
+    };
+    cpu.set_pc(word(0));
+    cpu.set_index(word(2));
+    cpu.registers_mut().copy_from_slice(&header[6..22]);
+    cpu.keypad.tap(header[22] & 0xF);
+    let at = usize::from(word(2)).min(cpu.mem.len());
+    let len = image.len().min(cpu.mem.len() - at);
+    cpu.mem[at..at + len].copy_from_slice(&image[..len]);
+
+    for inst in insts.chunks_exact(2) {
+        let raw = u16::from_be_bytes([inst[0], inst[1]]);
+        let _ = Disassembly(raw).to_string();
+        cpu.execute_instruction(raw);
+    }
+});
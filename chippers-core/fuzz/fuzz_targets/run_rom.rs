@@ -0,0 +1,19 @@
+//! Random ROMs run from a fresh machine, through `Chip8::step`, the decode
+//! cache and the timers, as a frontend runs them. The run stops at a fault or
+//! after `MAX_INSTRUCTIONS`, and must not panic before either.
+
+#![no_main]
+
+use chippers_core::oracle::{run_rom_until, Limits, MAX_ROM_SIZE};
+use libfuzzer_sys::fuzz_target;
+
+const MAX_INSTRUCTIONS: u64 = 10_000;
+
+fuzz_target!(|rom: &[u8]| {
+    let rom = &rom[..rom.len().min(MAX_ROM_SIZE)];
+    let limits = Limits {
+        max_instructions: MAX_INSTRUCTIONS,
+        ..Limits::default()
+    };
+    let _ = run_rom_until(rom, |_| false, limits);
+});
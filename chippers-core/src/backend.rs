@@ -0,0 +1,412 @@
+use crate::display::{DirtyRows, Display};
+use core::marker::PhantomData;
+
+/// Size of the emulated display in pixels.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Resolution {
+    pub width: u16,
+    pub height: u16,
+}
+
+impl Resolution {
+    /// The standard 64x32 CHIP-8 display.
+    pub const LORES: Resolution = Resolution {
+        width: 64,
+        height: 32,
+    };
+    /// The 256x192 color screen of MegaChip programs, see
+    /// [`megachip`](crate::megachip).
+    pub const MEGACHIP: Resolution = Resolution {
+        width: 256,
+        height: 192,
+    };
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Rgb {
+    pub r: u8,
+    pub g: u8,
+    pub b: u8,
+}
+
+impl Rgb {
+    pub const BLACK: Rgb = Rgb::new(0, 0, 0);
+    pub const WHITE: Rgb = Rgb::new(0xFF, 0xFF, 0xFF);
+
+    pub const fn new(r: u8, g: u8, b: u8) -> Self {
+        Rgb { r, g, b }
+    }
+
+    /// Reads a color written as `#rrggbb`, `rrggbb` or one of a few names:
+    /// black, white, gray, red, green, blue, yellow, cyan, magenta and amber.
+    pub fn parse(s: &str) -> Option<Rgb> {
+        let named = match s {
+            "black" => Rgb::BLACK,
+            "white" => Rgb::WHITE,
+            "gray" | "grey" => Rgb::new(0x80, 0x80, 0x80),
+            "red" => Rgb::new(0xFF, 0, 0),
+            "green" => Rgb::new(0, 0xFF, 0),
+            "blue" => Rgb::new(0, 0, 0xFF),
+            "yellow" => Rgb::new(0xFF, 0xFF, 0),
+            "cyan" => Rgb::new(0, 0xFF, 0xFF),
+            "magenta" => Rgb::new(0xFF, 0, 0xFF),
+            "amber" => Rgb::new(0xFF, 0xB0, 0),
+            _ => {
+                let hex = s.strip_prefix('#').unwrap_or(s);
+                if hex.len() != 6 || !hex.bytes().all(|b| b.is_ascii_hexdigit()) {
+                    return None;
+                }
+                let channel = |i| u8::from_str_radix(&hex[i..i + 2], 16).ok();
+                return Some(Rgb::new(channel(0)?, channel(2)?, channel(4)?));
+            }
+        };
+        Some(named)
+    }
+}
+
+/// Colors a frontend should use for lit and unlit pixels, if it can.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Palette {
+    pub on: Rgb,
+    pub off: Rgb,
+}
+
+impl Palette {
+    pub const MONOCHROME: Palette = Palette {
+        on: Rgb::WHITE,
+        off: Rgb::BLACK,
+    };
+    /// A green P1 phosphor CRT.
+    pub const GREEN_PHOSPHOR: Palette = Palette {
+        on: Rgb::new(0x33, 0xFF, 0x33),
+        off: Rgb::new(0x0A, 0x14, 0x0A),
+    };
+    /// An amber P3 phosphor terminal.
+    pub const AMBER: Palette = Palette {
+        on: Rgb::new(0xFF, 0xB0, 0x00),
+        off: Rgb::new(0x1A, 0x10, 0x00),
+    };
+    /// Dark ink on off-white paper.
+    pub const PAPER_WHITE: Palette = Palette {
+        on: Rgb::new(0x22, 0x22, 0x22),
+        off: Rgb::new(0xF4, 0xF1, 0xE8),
+    };
+
+    /// Names accepted by [`theme`](Palette::theme).
+    pub const THEMES: [&'static str; 4] = ["monochrome", "green-phosphor", "amber", "paper-white"];
+
+    /// Looks up a built-in theme by name, `monochrome` being the default.
+    pub fn theme(name: &str) -> Option<Palette> {
+        match name {
+            "monochrome" => Some(Palette::MONOCHROME),
+            "green-phosphor" => Some(Palette::GREEN_PHOSPHOR),
+            "amber" => Some(Palette::AMBER),
+            "paper-white" => Some(Palette::PAPER_WHITE),
+            _ => None,
+        }
+    }
+}
+
+impl Default for Palette {
+    fn default() -> Self {
+        Self::MONOCHROME
+    }
+}
+
+/// What a display backend is able to do, so callers can skip work it would ignore.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Capabilities {
+    /// Largest resolution the backend can show.
+    pub max_resolution: Resolution,
+    /// Whether `set_palette` has a visible effect.
+    pub color: bool,
+    /// Whether `beep` makes a sound or otherwise signals the user.
+    pub sound: bool,
+    /// Whether `set_title` has a visible effect.
+    pub title: bool,
+}
+
+impl Capabilities {
+    /// A plain 64x32 monochrome display with no extras.
+    pub const MINIMAL: Capabilities = Capabilities {
+        max_resolution: Resolution::LORES,
+        color: false,
+        sound: false,
+        title: false,
+    };
+}
+
+/// Something that can show the CHIP-8 display to the user.
+///
+/// Only `clear_screen` and `draw_screen` are required; the other hooks default
+/// to doing nothing so simple frontends stay simple.
+pub trait DisplayBackend {
+    type Error;
+    const CAPABILITIES: Capabilities = Capabilities::MINIMAL;
+
+    fn clear_screen(&mut self) -> core::result::Result<(), Self::Error>;
+    fn draw_screen(&mut self, display: &Display) -> core::result::Result<(), Self::Error>;
+
+    /// Called after a sprite draw that changed at most `rows`. Backends that can
+    /// redraw part of the screen override this; the default redraws it all.
+    fn draw_rows(
+        &mut self,
+        display: &Display,
+        _rows: DirtyRows,
+    ) -> core::result::Result<(), Self::Error> {
+        self.draw_screen(display)
+    }
+
+    /// Called before the first frame and whenever the program switches resolution.
+    fn set_resolution(&mut self, _resolution: Resolution) -> core::result::Result<(), Self::Error> {
+        Ok(())
+    }
+    fn set_palette(&mut self, _palette: Palette) -> core::result::Result<(), Self::Error> {
+        Ok(())
+    }
+    /// Called when the sound timer starts (`true`) or stops (`false`) running.
+    fn beep(&mut self, _on: bool) -> core::result::Result<(), Self::Error> {
+        Ok(())
+    }
+    fn set_title(&mut self, _title: &str) -> core::result::Result<(), Self::Error> {
+        Ok(())
+    }
+    /// Called when something else has drawn over the screen, such as a
+    /// debugger, so the next frame is drawn in full. Backends that only draw
+    /// what changed override this.
+    fn invalidate(&mut self) -> core::result::Result<(), Self::Error> {
+        Ok(())
+    }
+    /// Shows a picture in color in place of the display, row by row at
+    /// `resolution`, for MegaChip programs. Backends that can show it
+    /// override this, setting their size from `resolution`, and go back to
+    /// the display at the next `draw_screen`; the default draws the picture
+    /// shrunk to 64x32, a pixel lit where its part of the picture is bright.
+    fn draw_color(
+        &mut self,
+        resolution: Resolution,
+        pixels: &[Rgb],
+    ) -> core::result::Result<(), Self::Error> {
+        let (width, height) = (
+            usize::from(resolution.width),
+            usize::from(resolution.height),
+        );
+        let (block_w, block_h) = (width / Display::WIDTH, height / Display::HEIGHT);
+        let mut display = Display::new();
+        for y in 0..Display::HEIGHT {
+            for x in 0..Display::WIDTH {
+                let mut sum = 0u32;
+                for row in y * block_h..(y + 1) * block_h {
+                    let start = row * width + x * block_w;
+                    for rgb in pixels.get(start..start + block_w).unwrap_or(&[]) {
+                        sum += u32::from(rgb.r) + u32::from(rgb.g) + u32::from(rgb.b);
+                    }
+                }
+                let area = (block_w * block_h).max(1) as u32;
+                display.set_pixel(x, y, sum / area > 3 * 0x40);
+            }
+        }
+        self.draw_screen(&display)
+    }
+}
+
+/// A request from the user to the run loop, rather than input for the program.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ControlMessage {
+    /// Saves the machine, see [`Chip8::save_state`](crate::chip::Chip8::save_state).
+    SaveState,
+    /// Restores the last saved machine.
+    LoadState,
+    /// Stops executing instructions and counting down the timers.
+    Pause,
+    /// Carries on after a [`Pause`](ControlMessage::Pause).
+    Resume,
+    /// While paused, runs a single 60 Hz frame, a timer tick and all, and
+    /// shows it. Ignored while running.
+    FrameAdvance,
+    /// Starts the loaded ROM over, see [`Chip8::reset`](crate::chip::Chip8::reset).
+    Reset,
+    /// Saves the display as a PNG, see [`image`](crate::image).
+    Screenshot,
+    /// Runs [`FAST_FORWARD`](crate::chip::FAST_FORWARD) times as fast while
+    /// `true`, see [`Chip8::set_pace`](crate::chip::Chip8::set_pace).
+    FastForward(bool),
+    /// Runs [`SLOW_MOTION`](crate::chip::SLOW_MOTION) times as slow while
+    /// `true`, unless fast forward is on.
+    SlowMotion(bool),
+    /// Starts a different ROM in place of the running one.
+    #[cfg(feature = "std")]
+    LoadRom(std::vec::Vec<u8>),
+    /// Hands the machine to [`Frontend::debug`], the run stopped until it
+    /// returns.
+    Debug,
+    /// Ends the run successfully, tearing the frontend down as usual.
+    Quit,
+}
+
+/// Something that can report key presses on the 16-key CHIP-8 keypad.
+pub trait InputBackend {
+    type Error;
+    /// Returns the keypad value (0x0-0xF) of a pending key press, if any.
+    fn poll_key(&mut self) -> core::result::Result<Option<u8>, Self::Error>;
+    /// Returns a pending control message, if any; the run loop asks after
+    /// every `poll_key` or `wait_key`. The default never has one.
+    fn poll_control(&mut self) -> core::result::Result<Option<ControlMessage>, Self::Error> {
+        Ok(None)
+    }
+    /// Like `poll_key`, but may block for up to `timeout` waiting for a press;
+    /// the run loop calls this while the program is idle. The default does
+    /// not block.
+    fn wait_key(
+        &mut self,
+        _timeout: core::time::Duration,
+    ) -> core::result::Result<Option<u8>, Self::Error> {
+        self.poll_key()
+    }
+}
+
+/// Something that can play the CHIP-8 buzzer.
+pub trait AudioBackend {
+    type Error;
+    /// Starts (`true`) or stops (`false`) the tone.
+    fn set_tone(&mut self, on: bool) -> core::result::Result<(), Self::Error>;
+}
+
+/// An [`AudioBackend`] for frontends without sound.
+///
+/// `Send` and `Sync` whatever the error type, since it never holds one.
+#[derive(Debug)]
+pub struct Silent<E>(PhantomData<fn() -> E>);
+
+impl<E> Silent<E> {
+    pub fn new() -> Self {
+        Silent(PhantomData)
+    }
+}
+
+impl<E> Default for Silent<E> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<E> AudioBackend for Silent<E> {
+    type Error = E;
+    fn set_tone(&mut self, _on: bool) -> core::result::Result<(), Self::Error> {
+        Ok(())
+    }
+}
+
+/// An [`InputBackend`] fed through a channel, so a single thread reading the
+/// host keyboard can serve any number of machines.
+///
+/// `Send` but not `Sync`, like the receiver it wraps.
+#[cfg(feature = "std")]
+#[derive(Debug)]
+pub struct ChannelInput<E> {
+    keys: std::sync::mpsc::Receiver<u8>,
+    _error: PhantomData<fn() -> E>,
+}
+
+#[cfg(feature = "std")]
+impl<E> ChannelInput<E> {
+    pub fn new(keys: std::sync::mpsc::Receiver<u8>) -> Self {
+        ChannelInput {
+            keys,
+            _error: PhantomData,
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl<E> InputBackend for ChannelInput<E> {
+    type Error = E;
+    /// Returns the next queued key; a disconnected sender just means no more keys.
+    fn poll_key(&mut self) -> core::result::Result<Option<u8>, Self::Error> {
+        Ok(self.keys.try_recv().ok().map(|k| k & 0xF))
+    }
+
+    fn wait_key(
+        &mut self,
+        timeout: core::time::Duration,
+    ) -> core::result::Result<Option<u8>, Self::Error> {
+        Ok(self.keys.recv_timeout(timeout).ok().map(|k| k & 0xF))
+    }
+}
+
+/// A complete frontend: display, keypad and audio sharing one error type,
+/// plus setup and teardown around a run. Implement it in any crate to run
+/// the machine on a screen, keys and speaker of your own with
+/// [`Chip8::run_with`](crate::chip::Chip8::run_with).
+pub trait Frontend {
+    type Error;
+    type Display: DisplayBackend<Error = Self::Error>;
+    type Input: InputBackend<Error = Self::Error>;
+    type Audio: AudioBackend<Error = Self::Error>;
+
+    /// Called once before the first instruction runs.
+    fn init(&mut self) -> core::result::Result<(), Self::Error> {
+        Ok(())
+    }
+    /// Called once when the run ends, including when it ends in an error.
+    fn teardown(&mut self) -> core::result::Result<(), Self::Error> {
+        Ok(())
+    }
+    fn parts(&mut self) -> (&mut Self::Display, &mut Self::Input, &mut Self::Audio);
+    /// Called for [`ControlMessage::Debug`] between instructions, to inspect
+    /// or step the machine. The run carries on from wherever this leaves it,
+    /// its display shown in full. The default does nothing.
+    fn debug(&mut self, _chip8: &mut crate::chip::Chip8) -> core::result::Result<(), Self::Error> {
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_parse_colors_and_themes() {
+        assert_eq!(Rgb::parse("#ffb000"), Some(Rgb::new(0xFF, 0xB0, 0)));
+        assert_eq!(Rgb::parse("33FF33"), Some(Rgb::new(0x33, 0xFF, 0x33)));
+        assert_eq!(Rgb::parse("amber"), Some(Rgb::new(0xFF, 0xB0, 0)));
+        assert_eq!(Rgb::parse("#fff"), None);
+        assert_eq!(Rgb::parse("+12345"), None);
+        assert_eq!(Rgb::parse("chartreuse"), None);
+        for name in Palette::THEMES {
+            assert!(Palette::theme(name).is_some(), "{}", name);
+        }
+        assert_eq!(Palette::theme("monochrome"), Some(Palette::default()));
+        assert_eq!(Palette::theme("sepia"), None);
+    }
+
+    #[test]
+    fn test_draw_color_shrinks() {
+        struct Screen(Display);
+
+        impl DisplayBackend for Screen {
+            type Error = ();
+
+            fn clear_screen(&mut self) -> core::result::Result<(), Self::Error> {
+                self.0.clear();
+                Ok(())
+            }
+
+            fn draw_screen(&mut self, display: &Display) -> core::result::Result<(), Self::Error> {
+                self.0 = display.clone();
+                Ok(())
+            }
+        }
+
+        // a 4x6 block of white at the top left, and a dark red one beside it
+        let mut pixels = std::vec![Rgb::BLACK; 256 * 192];
+        for y in 0..6 {
+            pixels[y * 256..y * 256 + 4].fill(Rgb::WHITE);
+            pixels[y * 256 + 4..y * 256 + 8].fill(Rgb::new(0x80, 0, 0));
+        }
+        let mut screen = Screen(Display::new());
+        screen.draw_color(Resolution::MEGACHIP, &pixels).unwrap();
+        assert!(screen.0.pixel(0, 0));
+        assert_eq!(screen.0.iter_set_pixels().count(), 1);
+    }
+}
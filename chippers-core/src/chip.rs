@@ -0,0 +1,2114 @@
+#[cfg(feature = "std")]
+use crate::backend::*;
+use crate::cpu::*;
+use crate::display::{DirtyRows, PackedFrame};
+#[cfg(feature = "std")]
+use crate::extension::Extensions;
+#[cfg(feature = "std")]
+use crate::hooks::Hooks;
+#[cfg(feature = "std")]
+use crate::image::{ScreenshotError, Screenshots};
+#[cfg(feature = "std")]
+use crate::megachip::MegaChip;
+use crate::opcode::{Instruction, Opcode};
+#[cfg(feature = "std")]
+use crate::replay::{Event, Replay, Session};
+use crate::rng::Rng;
+#[cfg(feature = "std")]
+use crate::state::{StateError, StateSlot};
+use crate::timing::{cosmac_micros, Timing, FRAME_MICROS, UNKNOWN_MICROS};
+
+use core::time::Duration;
+#[cfg(feature = "std")]
+use std::time::Instant;
+
+/// A complete machine: CPU, memory, display and timers.
+///
+/// `Chip8` owns all of its state and holds no references, handles or
+/// thread-local data, so it is `Send` and `Sync`: a machine can be moved onto a
+/// worker thread or into an async task, and shared behind a lock. Frontends are
+/// passed in per call and are not part of the machine.
+#[derive(Debug)]
+pub struct Chip8 {
+    pub cpu: Cpu,
+    // address and delay timer value of the last FX07, to spot polling loops
+    last_dt_read: Option<(u16, u8)>,
+    polling_dt: bool,
+    pub(crate) instructions: u64,
+    frames: u64,
+    load_addr: u16,
+    speed: u32,
+    // sixtieths of an instruction owed to the next frame, see `step_frame`
+    frame_carry: u32,
+    timing: Timing,
+    // microseconds the instructions so far would have taken on the COSMAC
+    // VIP, and where on that count the last frame `step_frame` ran ended
+    vip_micros: u64,
+    vip_frame_end: u64,
+    /// Callbacks run as the machine executes, see [`Hooks`].
+    #[cfg(feature = "std")]
+    pub hooks: Hooks,
+    /// Handlers for opcodes outside the standard set, see [`Extensions`].
+    #[cfg(feature = "std")]
+    pub extensions: Extensions,
+    /// MegaChip support, turned on by
+    /// [`load_megachip`](Chip8::load_megachip).
+    #[cfg(feature = "std")]
+    pub megachip: Option<MegaChip>,
+    /// Where [`ControlMessage::SaveState`] saves the machine to and
+    /// [`ControlMessage::LoadState`] restores it from.
+    #[cfg(feature = "std")]
+    pub state_slot: StateSlot,
+    // the state saved in `StateSlot::Memory`
+    #[cfg(feature = "std")]
+    pub(crate) saved_state: Option<Vec<u8>>,
+    /// Where and how [`ControlMessage::Screenshot`] saves the display.
+    #[cfg(feature = "std")]
+    pub screenshots: Screenshots,
+    // the last ROM loaded and where, for `reset`
+    #[cfg(feature = "std")]
+    rom: Vec<u8>,
+    #[cfg(feature = "std")]
+    rom_addr: u16,
+    #[cfg(feature = "std")]
+    clock: Clock,
+    #[cfg(feature = "std")]
+    pace: Pace,
+    // the shortest time between frames `run_with` shows
+    #[cfg(feature = "std")]
+    frame_period: Duration,
+    #[cfg(feature = "std")]
+    timer: TimerSchedule,
+    #[cfg(feature = "std")]
+    session: Session,
+}
+
+impl Default for Chip8 {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Chip8 {
+    pub fn new() -> Self {
+        Self::with_cpu(Cpu::new())
+    }
+    pub fn with_cpu(cpu: Cpu) -> Self {
+        Chip8 {
+            cpu,
+            last_dt_read: None,
+            polling_dt: false,
+            instructions: 0,
+            frames: 0,
+            load_addr: DEFAULT_LOAD_ADDR,
+            speed: DEFAULT_SPEED,
+            frame_carry: 0,
+            timing: Timing::Uniform,
+            vip_micros: 0,
+            vip_frame_end: 0,
+            #[cfg(feature = "std")]
+            hooks: Hooks::new(),
+            #[cfg(feature = "std")]
+            extensions: Extensions::new(),
+            #[cfg(feature = "std")]
+            megachip: None,
+            #[cfg(feature = "std")]
+            state_slot: StateSlot::Memory,
+            #[cfg(feature = "std")]
+            saved_state: None,
+            #[cfg(feature = "std")]
+            screenshots: Screenshots::default(),
+            #[cfg(feature = "std")]
+            rom: Vec::new(),
+            #[cfg(feature = "std")]
+            rom_addr: DEFAULT_LOAD_ADDR,
+            #[cfg(feature = "std")]
+            clock: Clock::default(),
+            #[cfg(feature = "std")]
+            pace: Pace::Normal,
+            #[cfg(feature = "std")]
+            frame_period: Duration::from_secs(1) / DEFAULT_FRAME_RATE,
+            #[cfg(feature = "std")]
+            timer: TimerSchedule::new(Instant::now(), TIMER_PERIOD),
+            #[cfg(feature = "std")]
+            session: Session::Live,
+        }
+    }
+    /// Fetches and executes a single instruction.
+    pub fn step(&mut self) -> Chip8Message {
+        let addr = self.cpu.pc();
+        #[cfg(feature = "std")]
+        let key = self.cpu.keypad.last_press();
+        #[cfg(feature = "std")]
+        self.hooks.fetch(addr);
+        let raw = match self.cpu.fetch_next() {
+            Ok(raw) => raw,
+            Err(fault) => {
+                #[cfg(feature = "std")]
+                self.hooks.fault(fault, &self.cpu);
+                return Chip8Message::Halted(fault);
+            }
+        };
+        let inst = self.cpu.decode(addr, raw);
+        let opcode = inst.map(Instruction::opcode);
+        self.instructions += 1;
+        self.vip_micros += u64::from(opcode.map_or(UNKNOWN_MICROS, cosmac_micros));
+        self.polling_dt = false;
+        if opcode == Ok(Opcode::SetVXToDT) {
+            let read = (addr, self.cpu.dt);
+            self.polling_dt = self.cpu.dt > 0 && self.last_dt_read == Some(read);
+            self.last_dt_read = Some(read);
+        }
+        #[cfg(feature = "std")]
+        let next_inst = raw;
+        #[cfg(feature = "std")]
+        let msg = match self.megachip.as_mut() {
+            Some(mega) => mega.execute(next_inst, &mut self.cpu),
+            None => None,
+        };
+        #[cfg(feature = "std")]
+        let msg = msg.or_else(|| self.extensions.dispatch(next_inst, &mut self.cpu));
+        #[cfg(not(feature = "std"))]
+        let msg = None;
+        let msg = match (msg, inst) {
+            (Some(msg), _) => msg,
+            (None, Ok(inst)) => self.cpu.execute(inst),
+            (None, Err(error)) => self.cpu.reject(error),
+        };
+        #[cfg(feature = "std")]
+        {
+            if matches!(next_inst & 0xF0FF, 0xE09E | 0xE0A1 | 0xF00A) {
+                self.hooks.key_read(key);
+            }
+            self.hooks.instruction(addr, next_inst, &self.cpu);
+            match msg {
+                Chip8Message::DrawScreen(_) => self.hooks.draw(&self.cpu.disp),
+                Chip8Message::Halted(fault) => self.hooks.fault(fault, &self.cpu),
+                _ => {}
+            }
+        }
+        msg
+    }
+    /// Restarts the random number generator behind CXNN from `seed`, so the
+    /// program draws the same numbers every run.
+    pub fn set_seed(&mut self, seed: u32) {
+        self.cpu.rng = Rng::new(seed);
+    }
+    /// Sets how many instructions a second [`run_with`](Chip8::run_with) and
+    /// [`step_frame`](Chip8::step_frame) run, [`DEFAULT_SPEED`] to begin with.
+    pub fn set_speed(&mut self, hz: u32) {
+        self.speed = hz.max(1);
+        self.frame_carry = 0;
+        #[cfg(feature = "std")]
+        {
+            self.clock = Clock::new(hz).paced(self.pace);
+        }
+    }
+    /// Runs [`run_with`](Chip8::run_with) fast forward, in slow motion or in
+    /// real time. Instructions and timer ticks speed up or slow down alike,
+    /// so the program sees no difference.
+    #[cfg(feature = "std")]
+    pub fn set_pace(&mut self, pace: Pace) {
+        self.pace = pace;
+        self.clock = Clock::new(self.speed).paced(pace);
+        self.timer = TimerSchedule::new(Instant::now(), pace.wall_time(TIMER_PERIOD));
+    }
+    /// How fast [`run_with`](Chip8::run_with) runs, see
+    /// [`set_pace`](Chip8::set_pace).
+    #[cfg(feature = "std")]
+    pub fn pace(&self) -> Pace {
+        self.pace
+    }
+    /// Sets how many times a second, at most, [`run_with`](Chip8::run_with)
+    /// shows the display, [`DEFAULT_FRAME_RATE`] to begin with. Everything
+    /// drawn between frames goes to the display at once, as the rows changed
+    /// since the last.
+    #[cfg(feature = "std")]
+    pub fn set_frame_rate(&mut self, hz: u32) {
+        self.frame_period = Duration::from_secs(1) / hz.max(1);
+    }
+    /// Instructions a second, see [`set_speed`](Chip8::set_speed).
+    pub fn speed(&self) -> u32 {
+        self.speed
+    }
+    /// Sets how [`step_frame`](Chip8::step_frame) and
+    /// [`run_with`](Chip8::run_with) count the time instructions take,
+    /// [`Timing::Uniform`] to begin with.
+    pub fn set_timing(&mut self, timing: Timing) {
+        self.timing = timing;
+        self.vip_frame_end = self.vip_micros;
+    }
+    /// How instructions are timed, see [`set_timing`](Chip8::set_timing).
+    pub fn timing(&self) -> Timing {
+        self.timing
+    }
+    /// Runs one 60 Hz frame: a sixtieth of a second's worth of instructions
+    /// at the [`speed`](Chip8::speed), then a timer tick.
+    ///
+    /// Speeds that are not a multiple of 60 spread the odd instructions over
+    /// the frames, so every second runs exactly `speed` of them. Under
+    /// [`Timing::Cosmac`] the frame runs instructions until they would have
+    /// taken a sixtieth of a second on the VIP instead, and one that runs
+    /// past the end of the frame takes its time from the next. Unlike
+    /// [`run_with`](Chip8::run_with) this neither sleeps nor reads keys:
+    /// library users and debuggers call it at whatever pace suits them.
+    pub fn step_frame(&mut self) -> FrameReport {
+        let end = self.next_frame_end();
+        self.run_until(end)
+    }
+    /// How many instructions the next frame at the current speed runs.
+    fn frame_instructions(&mut self) -> u32 {
+        let owed = self.speed + self.frame_carry;
+        self.frame_carry = owed % 60;
+        owed / 60
+    }
+    /// Where the next frame ends under the current timing.
+    fn next_frame_end(&mut self) -> FrameEnd {
+        match self.timing {
+            Timing::Uniform => {
+                let instructions = self.frame_instructions();
+                FrameEnd::Instructions(self.instructions + u64::from(instructions))
+            }
+            Timing::Cosmac => {
+                // carry over a frame's overrun, but not time spent outside
+                // frames, stepping in a debugger say
+                let overrun = u64::from(cosmac_micros(Opcode::Draw));
+                let start = self
+                    .vip_frame_end
+                    .max(self.vip_micros.saturating_sub(overrun));
+                self.vip_frame_end = start + u64::from(FRAME_MICROS);
+                FrameEnd::Micros(self.vip_frame_end)
+            }
+        }
+    }
+    /// Runs one frame of `instructions` instructions, then ticks the timers.
+    ///
+    /// A fault ends the frame early, leaving the program counter on the
+    /// faulting instruction; the timers tick all the same.
+    pub fn run_frame(&mut self, instructions: u32) -> FrameReport {
+        self.run_until(FrameEnd::Instructions(
+            self.instructions + u64::from(instructions),
+        ))
+    }
+    fn run_until(&mut self, end: FrameEnd) -> FrameReport {
+        let mut report = FrameReport::default();
+        while !end.reached(self) {
+            let msg = self.step();
+            match msg {
+                Chip8Message::ClearScreen | Chip8Message::DrawScreen(_) => report.drawn = true,
+                Chip8Message::Halted(fault) => {
+                    report.fault = Some(fault);
+                    break;
+                }
+                _ => {}
+            }
+            report.instructions += 1;
+        }
+        self.tick_timers();
+        report.beeping = self.cpu.st > 0;
+        report
+    }
+    /// Number of instructions executed so far.
+    pub fn instructions(&self) -> u64 {
+        self.instructions
+    }
+    /// Number of 60 Hz frames, i.e. timer ticks, so far.
+    pub fn frames(&self) -> u64 {
+        self.frames
+    }
+    /// Counts `n` instructions executed without [`step`](Chip8::step), for
+    /// engines that run several at once.
+    pub fn retire(&mut self, n: u64) {
+        self.instructions += n;
+    }
+    /// Whether the program is idle until the next key press or timer tick:
+    /// blocked in FX0A, holding a DXYN for the next frame under the
+    /// [`display_wait`](crate::quirks::Quirks::display_wait) quirk, or
+    /// re-reading an unchanged delay timer in a loop.
+    ///
+    /// Hosts can sleep until then instead of running instructions that cannot
+    /// make progress.
+    pub fn waiting(&self) -> bool {
+        self.polling_dt || self.cpu.waiting_for_key() || self.cpu.waiting_for_vblank()
+    }
+    /// Counts the delay and sound timers down by one; call this at 60 Hz.
+    ///
+    /// Returns [`Chip8Message::FrameComplete`], or
+    /// [`Chip8Message::Beep(false)`](Chip8Message::Beep) when the sound timer
+    /// runs out.
+    pub fn tick_timers(&mut self) -> Chip8Message {
+        if self.cpu.dt > 0 {
+            self.cpu.dt -= 1;
+        }
+        let mut msg = Chip8Message::FrameComplete;
+        if self.cpu.st > 0 {
+            self.cpu.st -= 1;
+            if self.cpu.st == 0 {
+                msg = Chip8Message::Beep(false);
+            }
+        }
+        self.cpu.vblank();
+        self.frames += 1;
+        #[cfg(feature = "std")]
+        self.hooks.timer_tick(self.frames, &self.cpu);
+        msg
+    }
+    /// Runs the loaded program on `frontend` until the frontend sends
+    /// [`ControlMessage::Quit`], a backend reports an error or the program
+    /// faults.
+    #[cfg(feature = "std")]
+    pub fn run_with<F: Frontend>(
+        &mut self,
+        frontend: &mut F,
+    ) -> std::result::Result<(), RunError<F::Error>> {
+        self.run_with_engine(frontend, &mut Interpreter)
+    }
+    /// Like [`run_with`](Chip8::run_with), executing through `engine`.
+    #[cfg(feature = "std")]
+    pub fn run_with_engine<F: Frontend, E: Engine>(
+        &mut self,
+        frontend: &mut F,
+        engine: &mut E,
+    ) -> std::result::Result<(), RunError<F::Error>> {
+        frontend.init()?;
+        let result = self.run_loop(frontend, engine);
+        let teardown = frontend.teardown();
+        result.and(teardown.map_err(RunError::Frontend))
+    }
+    #[cfg(feature = "std")]
+    fn run_loop<F: Frontend, E: Engine>(
+        &mut self,
+        frontend: &mut F,
+        engine: &mut E,
+    ) -> std::result::Result<(), RunError<F::Error>> {
+        let (display, _, _) = frontend.parts();
+        display.set_resolution(Resolution::LORES)?;
+        display.clear_screen()?;
+        self.timer.restart(Instant::now());
+        let mut paused = false;
+        // the frontend asked to debug, which it can only do once the parts
+        // are handed back at the end of the iteration
+        let mut debug = false;
+        let mut fast_forward = self.pace == Pace::FastForward;
+        let mut slow_motion = self.pace == Pace::SlowMotion;
+        // rows drawn on since the display was last shown, if anything was,
+        // and when it may be shown next
+        let mut dirty: Option<DirtyRows> = None;
+        let mut next_frame = Instant::now();
+        loop {
+            let debugged = core::mem::take(&mut debug);
+            if debugged {
+                frontend.debug(self)?;
+                // nor is the time spent debugging owed to the timers
+                self.timer.restart(Instant::now());
+            }
+            let (display, input, audio) = frontend.parts();
+            // show what is drawn before waiting on a key or while paused,
+            // else at most once a frame
+            if let Some(rows) = dirty {
+                let now = Instant::now();
+                if paused || self.waiting() || now >= next_frame {
+                    if !self.show_megachip(display)? {
+                        display.draw_rows(&self.cpu.disp, rows)?;
+                    }
+                    dirty = None;
+                    next_frame = now + self.frame_period;
+                }
+            }
+            let playing = matches!(self.session, Session::Playing { .. });
+            let key = if paused {
+                input.wait_key(TIMER_PERIOD)?
+            } else if self.waiting() && !playing {
+                input.wait_key(self.timer.until_next(Instant::now()))?
+            } else {
+                input.poll_key()?
+            };
+            // the program does not see keys pressed while it is paused, and
+            // sees only the replay's while one plays
+            if let Some(key) = key.filter(|_| !paused && !playing) {
+                self.note(Event::Key(key));
+                self.cpu.press_key(key);
+            }
+            let replaced = match input.poll_control()? {
+                Some(ControlMessage::SaveState) => {
+                    self.quick_save().map_err(RunError::State)?;
+                    false
+                }
+                Some(ControlMessage::LoadState) => self.quick_load().map_err(RunError::State)?,
+                Some(ControlMessage::Pause) => {
+                    paused = true;
+                    display.beep(false)?;
+                    audio.set_tone(false)?;
+                    false
+                }
+                Some(ControlMessage::FrameAdvance) if paused => {
+                    self.advance_frame(engine, display, audio)?;
+                    // shown in full below
+                    true
+                }
+                Some(ControlMessage::FrameAdvance) => false,
+                Some(ControlMessage::Resume) => {
+                    // the time spent paused is not owed to the timers
+                    self.timer.restart(Instant::now());
+                    paused = false;
+                    display.beep(self.cpu.st > 0)?;
+                    audio.set_tone(self.cpu.st > 0)?;
+                    false
+                }
+                Some(ControlMessage::Reset) => {
+                    self.note(Event::Reset);
+                    self.reset();
+                    true
+                }
+                Some(ControlMessage::Screenshot) => {
+                    self.screenshot().map_err(RunError::Screenshot)?;
+                    false
+                }
+                Some(ControlMessage::FastForward(on)) => {
+                    fast_forward = on;
+                    self.set_pace(Pace::of(fast_forward, slow_motion));
+                    false
+                }
+                Some(ControlMessage::SlowMotion(on)) => {
+                    slow_motion = on;
+                    self.set_pace(Pace::of(fast_forward, slow_motion));
+                    false
+                }
+                Some(ControlMessage::LoadRom(rom)) => {
+                    self.swap_rom(&rom).map_err(RunError::Load)?;
+                    true
+                }
+                Some(ControlMessage::Debug) => {
+                    debug = true;
+                    display.beep(false)?;
+                    audio.set_tone(false)?;
+                    continue;
+                }
+                Some(ControlMessage::Quit) => return Ok(()),
+                None => false,
+            };
+            if replaced || debugged {
+                dirty = None;
+                if paused {
+                    if !self.show_megachip(display)? {
+                        display.draw_screen(&self.cpu.disp)?;
+                    }
+                } else {
+                    self.present(Chip8Message::StateChanged, display, audio)?;
+                }
+            }
+            if paused {
+                continue;
+            }
+            self.play_due(display, audio)?;
+            let vip_micros = self.vip_micros;
+            match engine.step(self) {
+                Chip8Message::ClearScreen => dirty = Some(DirtyRows::ALL),
+                Chip8Message::DrawScreen(rows) => {
+                    dirty = Some(dirty.unwrap_or(DirtyRows::NONE).union(rows))
+                }
+                msg => self.present(msg, display, audio)?,
+            }
+            if !matches!(self.session, Session::Playing { .. }) {
+                for _ in 0..self.timer.due(Instant::now()) {
+                    self.note(Event::Tick);
+                    let msg = self.tick_timers();
+                    self.present(msg, display, audio)?;
+                }
+            }
+
+            match self.timing {
+                Timing::Uniform => self.clock.tick(),
+                Timing::Cosmac => {
+                    let took = Duration::from_micros(self.vip_micros - vip_micros);
+                    self.clock.wait(self.pace.wall_time(took));
+                }
+            }
+        }
+    }
+    /// Runs a frame's worth of instructions through `engine` and ticks the
+    /// timers, for [`ControlMessage::FrameAdvance`]. The buzzer stays quiet,
+    /// as the machine is paused either side of the frame, and the caller
+    /// shows the display.
+    #[cfg(feature = "std")]
+    fn advance_frame<D, A, E>(
+        &mut self,
+        engine: &mut E,
+        display: &mut D,
+        audio: &mut A,
+    ) -> std::result::Result<(), RunError<D::Error>>
+    where
+        D: DisplayBackend,
+        A: AudioBackend<Error = D::Error>,
+        E: Engine,
+    {
+        let end = self.next_frame_end();
+        while !end.reached(self) {
+            self.play_due(display, audio)?;
+            if let msg @ Chip8Message::Halted(_) = engine.step(self) {
+                return self.present(msg, display, audio);
+            }
+        }
+        if !matches!(self.session, Session::Playing { .. }) {
+            self.note(Event::Tick);
+            self.tick_timers();
+        }
+        Ok(())
+    }
+    /// Plays the replay's events due before the next instruction, going live
+    /// once they run out.
+    #[cfg(feature = "std")]
+    fn play_due<D, A>(
+        &mut self,
+        display: &mut D,
+        audio: &mut A,
+    ) -> std::result::Result<(), RunError<D::Error>>
+    where
+        D: DisplayBackend,
+        A: AudioBackend<Error = D::Error>,
+    {
+        loop {
+            let Session::Playing { replay, next } = &mut self.session else {
+                return Ok(());
+            };
+            let Some(&(at, event)) = replay.events.get(*next) else {
+                self.session = Session::Live;
+                self.timer.restart(Instant::now());
+                return Ok(());
+            };
+            if at > self.instructions {
+                return Ok(());
+            }
+            *next += 1;
+            match event {
+                Event::Key(key) => self.cpu.press_key(key),
+                Event::Tick => {
+                    let msg = self.tick_timers();
+                    self.present(msg, display, audio)?;
+                }
+                Event::Reset => {
+                    self.reset();
+                    self.present(Chip8Message::StateChanged, display, audio)?;
+                }
+            }
+        }
+    }
+    /// Adds `event` to the recording, if one is being made.
+    #[cfg(feature = "std")]
+    fn note(&mut self, event: Event) {
+        if let Session::Recording(replay) = &mut self.session {
+            replay.events.push((self.instructions, event));
+        }
+    }
+    /// Seeds the random number generator with `seed` and starts noting down
+    /// the keys, timer ticks and resets of [`run_with`](Chip8::run_with), for
+    /// [`take_recording`](Chip8::take_recording).
+    #[cfg(feature = "std")]
+    pub fn record(&mut self, seed: u32) {
+        self.set_seed(seed);
+        self.session = Session::Recording(Replay::new(seed));
+    }
+    /// Stops recording, returning what was recorded.
+    #[cfg(feature = "std")]
+    pub fn take_recording(&mut self) -> Option<Replay> {
+        match core::mem::take(&mut self.session) {
+            Session::Recording(replay) => Some(replay),
+            session => {
+                self.session = session;
+                None
+            }
+        }
+    }
+    /// Seeds the random number generator as `replay` was and has
+    /// [`run_with`](Chip8::run_with) take keys and timer ticks from it rather
+    /// than from the frontend and the clock, until it runs out. Call this
+    /// with the ROM just loaded, as it was when the recording started.
+    #[cfg(feature = "std")]
+    pub fn play(&mut self, replay: Replay) {
+        self.set_seed(replay.seed);
+        self.session = Session::Playing { replay, next: 0 };
+    }
+    /// Whether a replay is feeding the machine its input.
+    #[cfg(feature = "std")]
+    pub fn is_playing(&self) -> bool {
+        matches!(self.session, Session::Playing { .. })
+    }
+    /// Passes `msg` on to the backends.
+    #[cfg(feature = "std")]
+    fn present<D, A>(
+        &self,
+        msg: Chip8Message,
+        display: &mut D,
+        audio: &mut A,
+    ) -> std::result::Result<(), RunError<D::Error>>
+    where
+        D: DisplayBackend,
+        A: AudioBackend<Error = D::Error>,
+    {
+        match msg {
+            Chip8Message::None => {}
+            Chip8Message::ClearScreen => display.clear_screen()?,
+            Chip8Message::DrawScreen(rows) => {
+                if !self.show_megachip(display)? {
+                    display.draw_rows(&self.cpu.disp, rows)?;
+                }
+            }
+            Chip8Message::Beep(on) => {
+                display.beep(on)?;
+                audio.set_tone(on)?;
+            }
+            Chip8Message::FrameComplete => {}
+            Chip8Message::StateChanged => {
+                if !self.show_megachip(display)? {
+                    display.draw_screen(&self.cpu.disp)?;
+                }
+                display.beep(self.cpu.st > 0)?;
+                audio.set_tone(self.cpu.st > 0)?;
+            }
+            Chip8Message::Halted(fault) => {
+                return Err(RunError::Fault {
+                    fault,
+                    registers: self.cpu.dump(),
+                })
+            }
+        }
+        Ok(())
+    }
+    /// Shows the MegaChip picture in place of the display if the program has
+    /// turned MegaChip mode on, returning whether it did.
+    #[cfg(feature = "std")]
+    fn show_megachip<D: DisplayBackend>(
+        &self,
+        display: &mut D,
+    ) -> std::result::Result<bool, D::Error> {
+        match self.megachip.as_ref().filter(|mega| mega.is_on()) {
+            Some(mega) => {
+                display.draw_color(Resolution::MEGACHIP, mega.frame())?;
+                Ok(true)
+            }
+            None => Ok(false),
+        }
+    }
+    /// Runs as fast as possible with no frontend and no sleeping until `limit`
+    /// or a fault, ticking the timers every `instructions_per_frame`
+    /// instructions rather than by the wall clock.
+    #[cfg(feature = "std")]
+    pub fn run_turbo<E: Engine>(
+        &mut self,
+        engine: &mut E,
+        instructions_per_frame: u32,
+        limit: TurboLimit,
+    ) -> Throughput {
+        let per_frame = u64::from(instructions_per_frame.max(1));
+        let (start, first) = (Instant::now(), self.instructions);
+        let mut frames = 0;
+        loop {
+            let executed = self.instructions - first;
+            if matches!(limit, TurboLimit::Instructions(n) if executed >= n) {
+                break;
+            }
+            if let Chip8Message::Halted(_) = engine.step(self) {
+                break;
+            }
+            while frames < (self.instructions - first) / per_frame {
+                frames += 1;
+                self.tick_timers();
+            }
+            // reading the clock costs more than an instruction, so only look
+            // every 1024, however many the engine ran in that step
+            if let TurboLimit::Duration(d) = limit {
+                let passed = (self.instructions - first) / 1024 != executed / 1024;
+                if passed && start.elapsed() >= d {
+                    break;
+                }
+            }
+        }
+        Throughput {
+            instructions: self.instructions - first,
+            frames,
+            elapsed: start.elapsed(),
+        }
+    }
+    /// A copy of the display in a stable layout, see [`PackedFrame`].
+    pub fn framebuffer(&self) -> PackedFrame {
+        self.cpu.disp.pack()
+    }
+    pub fn load_font_set(&mut self) {
+        for (i, byte) in FONT_SET.iter().enumerate() {
+            self.cpu.mem[i + 0x50] = *byte;
+        }
+    }
+    /// Sets where [`load_rom`](Chip8::load_rom) and
+    /// [`swap_rom`](Chip8::swap_rom) put programs and start running them,
+    /// [`DEFAULT_LOAD_ADDR`] to begin with. Programs for the ETI-660 expect
+    /// [`ETI_660_LOAD_ADDR`].
+    pub fn set_load_addr(&mut self, addr: u16) {
+        self.load_addr = addr;
+    }
+    /// Where programs are loaded, see [`set_load_addr`](Chip8::set_load_addr).
+    pub fn load_addr(&self) -> u16 {
+        self.load_addr
+    }
+    /// Copies `rom` into memory at the [load address](Chip8::set_load_addr)
+    /// and starts the program there, unless it is empty or does not fit.
+    pub fn load_rom(&mut self, rom: &[u8]) -> core::result::Result<RomInfo, LoadError> {
+        let info = check_rom(rom, self.load_addr)?;
+        let start = usize::from(info.entry);
+        self.cpu.mem[start..start + rom.len()].copy_from_slice(rom);
+        self.cpu.set_pc(info.entry);
+        #[cfg(feature = "std")]
+        {
+            self.rom = rom.to_vec();
+            self.rom_addr = info.entry;
+        }
+        Ok(info)
+    }
+    /// Loads a MegaChip program of up to [`megachip::MAX_ROM_SIZE`] bytes
+    /// and turns on MegaChip support, see [`megachip`]. As much of the ROM
+    /// as fits is copied into memory, where the program runs; the rest is
+    /// for sprites and palettes it reaches with 01NN NNNN.
+    ///
+    /// [`megachip`]: crate::megachip
+    /// [`megachip::MAX_ROM_SIZE`]: crate::megachip::MAX_ROM_SIZE
+    #[cfg(feature = "std")]
+    pub fn load_megachip(&mut self, rom: &[u8]) -> core::result::Result<RomInfo, LoadError> {
+        let max = crate::megachip::MAX_ROM_SIZE - usize::from(self.load_addr);
+        if rom.len() > max {
+            return Err(LoadError::TooLarge {
+                size: rom.len(),
+                max,
+            });
+        }
+        let fits = rom
+            .len()
+            .min(4096usize.saturating_sub(usize::from(self.load_addr)));
+        let info = self.load_rom(&rom[..fits])?;
+        self.megachip = Some(MegaChip::new(rom, info.entry));
+        Ok(RomInfo {
+            size: rom.len(),
+            ..info
+        })
+    }
+    /// Puts the machine back as it was when the last ROM was loaded, with the
+    /// font set in place: memory, registers, stack, timers, display and keys
+    /// all start over.
+    ///
+    /// Quirks, hooks, extensions and saved states are kept, and the random
+    /// number generator carries on rather than repeating itself.
+    #[cfg(feature = "std")]
+    pub fn reset(&mut self) {
+        let rng = self.cpu.rng.clone();
+        let (quirks, on_unknown) = (self.cpu.quirks, self.cpu.on_unknown);
+        let (decode_cache, flags) = (self.cpu.decode_cache, self.cpu.flags);
+        self.cpu = Cpu::with_rng(rng);
+        self.cpu.flags = flags;
+        self.cpu.quirks = quirks;
+        self.cpu.on_unknown = on_unknown;
+        self.cpu.decode_cache = decode_cache;
+        self.last_dt_read = None;
+        self.polling_dt = false;
+        self.instructions = 0;
+        self.frames = 0;
+        self.vip_micros = 0;
+        self.vip_frame_end = 0;
+        self.load_font_set();
+        // the ROM fitted when it was first loaded
+        let start = usize::from(self.rom_addr);
+        self.cpu.mem[start..start + self.rom.len()].copy_from_slice(&self.rom);
+        self.cpu.set_pc(self.rom_addr);
+        if let Some(mega) = &mut self.megachip {
+            mega.reset();
+        }
+    }
+    /// Replaces the running program with `rom`, starting it on a machine
+    /// reset as by [`reset`](Chip8::reset). On error nothing changes.
+    #[cfg(feature = "std")]
+    pub fn swap_rom(&mut self, rom: &[u8]) -> core::result::Result<RomInfo, LoadError> {
+        let info = check_rom(rom, self.load_addr)?;
+        self.rom = rom.to_vec();
+        self.rom_addr = info.entry;
+        self.megachip = None;
+        self.reset();
+        Ok(info)
+    }
+}
+
+/// Where `rom` would go loaded at `addr`, if it can be loaded at all.
+fn check_rom(rom: &[u8], addr: u16) -> core::result::Result<RomInfo, LoadError> {
+    if rom.is_empty() {
+        return Err(LoadError::Empty);
+    }
+    let max = 4096usize.saturating_sub(usize::from(addr));
+    if rom.len() > max {
+        return Err(LoadError::TooLarge {
+            size: rom.len(),
+            max,
+        });
+    }
+    Ok(RomInfo {
+        size: rom.len(),
+        entry: addr,
+    })
+}
+
+/// Where programs are loaded and start running unless told otherwise.
+pub const DEFAULT_LOAD_ADDR: u16 = 0x200;
+
+/// Where programs for the ETI-660 are loaded, after its larger interpreter.
+pub const ETI_660_LOAD_ADDR: u16 = 0x600;
+
+/// Largest ROM that fits between the default load address and the end of
+/// memory.
+pub const MAX_ROM_SIZE: usize = 4096 - DEFAULT_LOAD_ADDR as usize;
+
+/// What [`Chip8::load_rom`] put in memory.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct RomInfo {
+    /// Length of the ROM in bytes.
+    pub size: usize,
+    /// Where the program starts running.
+    pub entry: u16,
+}
+
+impl core::fmt::Display for RomInfo {
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+        write!(
+            f,
+            "loaded {} bytes, entry point {:#05x}",
+            self.size, self.entry
+        )
+    }
+}
+
+/// Why [`Chip8::load_rom`] refused a ROM.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum LoadError {
+    /// The ROM has no bytes at all.
+    Empty,
+    /// The ROM is longer than the `max` bytes between the load address and
+    /// the end of memory, [`MAX_ROM_SIZE`] at the default address.
+    TooLarge { size: usize, max: usize },
+}
+
+impl core::fmt::Display for LoadError {
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+        match self {
+            LoadError::Empty => writeln!(f, "error: rom is empty")?,
+            LoadError::TooLarge { size, max } => writeln!(
+                f,
+                "error: rom is {} bytes, at most {} fit in memory",
+                size, max
+            )?,
+        }
+        Ok(())
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for LoadError {}
+
+/// Why [`Chip8::run_with`] stopped.
+#[cfg(feature = "std")]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum RunError<E> {
+    /// A backend failed.
+    Frontend(E),
+    /// The program ran an instruction the machine cannot carry out, leaving
+    /// the registers as they were before it.
+    Fault {
+        fault: Fault,
+        registers: RegisterDump,
+    },
+    /// Saving or loading the machine state failed.
+    State(StateError),
+    /// A ROM sent with [`ControlMessage::LoadRom`] could not be loaded.
+    Load(LoadError),
+    /// A screenshot could not be saved.
+    Screenshot(ScreenshotError),
+}
+
+#[cfg(feature = "std")]
+impl<E> From<E> for RunError<E> {
+    fn from(e: E) -> Self {
+        RunError::Frontend(e)
+    }
+}
+
+#[cfg(feature = "std")]
+impl<E: core::fmt::Display> core::fmt::Display for RunError<E> {
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+        match self {
+            RunError::Frontend(e) => e.fmt(f),
+            RunError::Fault { fault, registers } => {
+                fault.fmt(f)?;
+                registers.fmt(f)
+            }
+            RunError::State(e) => e.fmt(f),
+            RunError::Load(e) => e.fmt(f),
+            RunError::Screenshot(e) => e.fmt(f),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl<E: std::error::Error> std::error::Error for RunError<E> {}
+
+/// Everything that can keep a ROM from running to the end, for callers such
+/// as the command line that report an error and stop rather than handle each
+/// kind: reading the ROM, loading it, a saved state, a screenshot, a fault in
+/// the program or the frontend failing.
+#[cfg(feature = "std")]
+#[derive(Debug)]
+pub enum ChipError {
+    Io(std::io::Error),
+    Load(LoadError),
+    State(StateError),
+    Screenshot(ScreenshotError),
+    Fault {
+        fault: Fault,
+        registers: RegisterDump,
+    },
+    /// The display, keyboard or sound failed, or the channel feeding them
+    /// closed.
+    Frontend(Box<dyn std::error::Error>),
+}
+
+#[cfg(feature = "std")]
+impl core::fmt::Display for ChipError {
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+        match self {
+            ChipError::Io(e) => writeln!(f, "error: {}", e),
+            ChipError::Load(e) => e.fmt(f),
+            ChipError::State(e) => e.fmt(f),
+            ChipError::Screenshot(e) => e.fmt(f),
+            ChipError::Fault { fault, registers } => {
+                fault.fmt(f)?;
+                registers.fmt(f)
+            }
+            ChipError::Frontend(e) => e.fmt(f),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for ChipError {}
+
+#[cfg(feature = "std")]
+impl From<std::io::Error> for ChipError {
+    fn from(e: std::io::Error) -> Self {
+        ChipError::Io(e)
+    }
+}
+
+#[cfg(feature = "std")]
+impl From<LoadError> for ChipError {
+    fn from(e: LoadError) -> Self {
+        ChipError::Load(e)
+    }
+}
+
+#[cfg(feature = "std")]
+impl From<StateError> for ChipError {
+    fn from(e: StateError) -> Self {
+        ChipError::State(e)
+    }
+}
+
+#[cfg(feature = "std")]
+impl<E: std::error::Error + 'static> From<RunError<E>> for ChipError {
+    fn from(e: RunError<E>) -> Self {
+        match e {
+            RunError::Frontend(e) => ChipError::Frontend(Box::new(e)),
+            RunError::Fault { fault, registers } => ChipError::Fault { fault, registers },
+            RunError::State(e) => ChipError::State(e),
+            RunError::Load(e) => ChipError::Load(e),
+            RunError::Screenshot(e) => ChipError::Screenshot(e),
+        }
+    }
+}
+
+/// When [`Chip8::run_turbo`] stops.
+#[cfg(feature = "std")]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TurboLimit {
+    Instructions(u64),
+    Duration(Duration),
+}
+
+/// What a [`Chip8::run_turbo`] run achieved.
+#[cfg(feature = "std")]
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Throughput {
+    pub instructions: u64,
+    /// Emulated 60 Hz frames, i.e. timer ticks.
+    pub frames: u64,
+    pub elapsed: Duration,
+}
+
+#[cfg(feature = "std")]
+impl Throughput {
+    pub fn instructions_per_second(&self) -> f64 {
+        self.instructions as f64 / self.elapsed.as_secs_f64()
+    }
+}
+
+#[cfg(feature = "std")]
+impl core::fmt::Display for Throughput {
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+        write!(
+            f,
+            "{} instructions, {} frames in {:.3}s ({:.0} instructions/s)",
+            self.instructions,
+            self.frames,
+            self.elapsed.as_secs_f64(),
+            self.instructions_per_second()
+        )
+    }
+}
+
+/// Executes instructions on behalf of a run loop.
+///
+/// One call runs at least one instruction; an engine may run a whole block of
+/// instructions that neither draw nor touch the keypad or timers in one go.
+pub trait Engine {
+    fn step(&mut self, chip8: &mut Chip8) -> Chip8Message;
+}
+
+/// The plain interpreter: one [`Chip8::step`] per call.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Interpreter;
+
+impl Engine for Interpreter {
+    fn step(&mut self, chip8: &mut Chip8) -> Chip8Message {
+        chip8.step()
+    }
+}
+
+/// What the machine tells its frontend: returned by [`Chip8::step`] and
+/// [`Chip8::tick_timers`], and passed on to the backends by
+/// [`Chip8::run_with`].
+pub enum Chip8Message {
+    None,
+    ClearScreen,
+    /// A sprite was drawn; the display changed at most in these rows.
+    DrawScreen(DirtyRows),
+    /// The buzzer should sound (`true`), the sound timer having been set from
+    /// zero, or stop (`false`), the sound timer having reached or been set
+    /// to zero.
+    Beep(bool),
+    /// The instruction could not run, see [`Fault`]; the machine stops.
+    Halted(Fault),
+    /// The timers ticked, ending a 60 Hz frame.
+    FrameComplete,
+    /// The whole machine changed at once, by a reset, a loaded state or a
+    /// new ROM: the display is to be drawn afresh and the buzzer set from the
+    /// sound timer.
+    StateChanged,
+}
+
+/// Where a frame run by [`Chip8::step_frame`] ends: once the machine has run
+/// so many instructions, or so many microseconds of them on the VIP.
+#[derive(Clone, Copy, Debug)]
+enum FrameEnd {
+    Instructions(u64),
+    Micros(u64),
+}
+
+impl FrameEnd {
+    fn reached(self, chip8: &Chip8) -> bool {
+        match self {
+            FrameEnd::Instructions(n) => chip8.instructions >= n,
+            FrameEnd::Micros(micros) => chip8.vip_micros >= micros,
+        }
+    }
+}
+
+/// What one frame of [`Chip8::step_frame`] or [`Chip8::run_frame`] did.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct FrameReport {
+    /// Instructions executed, the faulting one not counted.
+    pub instructions: u32,
+    /// Whether the display was cleared or drawn on.
+    pub drawn: bool,
+    /// Whether the buzzer is sounding at the end of the frame.
+    pub beeping: bool,
+    /// The fault that ended the frame early, if one did.
+    pub fault: Option<Fault>,
+}
+
+#[cfg(feature = "std")]
+const TIMER_PERIOD: Duration = Duration::from_nanos(1_000_000_000 / 60);
+
+/// How many times as fast as real time [`Pace::FastForward`] runs.
+pub const FAST_FORWARD: u32 = 8;
+
+/// How many times as slow as real time [`Pace::SlowMotion`] runs.
+pub const SLOW_MOTION: u32 = 4;
+
+/// How fast a machine runs against the wall clock.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub enum Pace {
+    #[default]
+    Normal,
+    /// [`FAST_FORWARD`] times as fast.
+    FastForward,
+    /// [`SLOW_MOTION`] times as slow.
+    SlowMotion,
+}
+
+impl Pace {
+    /// The pace with fast forward held and slow motion switched on or not,
+    /// fast forward winning while both are.
+    pub fn of(fast_forward: bool, slow_motion: bool) -> Self {
+        match (fast_forward, slow_motion) {
+            (true, _) => Pace::FastForward,
+            (false, true) => Pace::SlowMotion,
+            (false, false) => Pace::Normal,
+        }
+    }
+
+    /// How long `time` of the machine's takes on the wall clock.
+    pub fn wall_time(self, time: Duration) -> Duration {
+        match self {
+            Pace::Normal => time,
+            Pace::FastForward => time / FAST_FORWARD,
+            Pace::SlowMotion => time * SLOW_MOTION,
+        }
+    }
+}
+
+/// Absolute 60 Hz deadlines for the delay and sound timers.
+///
+/// Each deadline is a fixed period after the one before rather than after
+/// the moment the last tick happened, so the timers keep time however long
+/// the instructions, sleeps and key waits in between take.
+#[cfg(feature = "std")]
+#[derive(Clone, Copy, Debug)]
+struct TimerSchedule {
+    next: Instant,
+    period: Duration,
+}
+
+#[cfg(feature = "std")]
+impl TimerSchedule {
+    /// Ticks owed beyond which the schedule gives up catching up, as after
+    /// the process was suspended.
+    const MAX_BEHIND: u32 = 60;
+
+    fn new(now: Instant, period: Duration) -> Self {
+        TimerSchedule {
+            next: now + period,
+            period,
+        }
+    }
+
+    /// Starts the schedule over from `now`, owing no ticks.
+    fn restart(&mut self, now: Instant) {
+        *self = Self::new(now, self.period);
+    }
+
+    /// How many ticks are due by `now`, moving the deadline past them.
+    fn due(&mut self, now: Instant) -> u32 {
+        let mut ticks = 0;
+        while self.next <= now {
+            self.next += self.period;
+            ticks += 1;
+            if ticks == Self::MAX_BEHIND {
+                self.restart(now);
+                break;
+            }
+        }
+        ticks
+    }
+
+    /// How long until the next tick is due.
+    fn until_next(&self, now: Instant) -> Duration {
+        self.next.saturating_duration_since(now)
+    }
+}
+
+pub const CLOCK_RATE: f64 = 100.; // Hz, 700 instructions per second
+
+/// Instructions a second [`Chip8::run_with`] aims for unless told otherwise.
+pub const DEFAULT_SPEED: u32 = 500;
+
+/// Times a second, at most, [`Chip8::run_with`] shows the display unless told
+/// otherwise.
+pub const DEFAULT_FRAME_RATE: u32 = 60;
+
+/// Paces [`Chip8::run_with`] by sleeping after each instruction.
+#[cfg(feature = "std")]
+#[derive(Debug)]
+pub struct Clock {
+    period: Duration,
+    // time waited for but not yet slept, see `wait`
+    owed: Duration,
+}
+
+#[cfg(feature = "std")]
+impl Default for Clock {
+    fn default() -> Self {
+        Clock::new(DEFAULT_SPEED)
+    }
+}
+
+#[cfg(feature = "std")]
+impl Clock {
+    /// A clock for `hz` instructions a second, at least one.
+    pub fn new(hz: u32) -> Self {
+        Clock {
+            period: Duration::from_secs(1) / hz.max(1),
+            owed: Duration::ZERO,
+        }
+    }
+
+    /// This clock with its ticks stretched or shrunk to `pace`.
+    pub fn paced(self, pace: Pace) -> Self {
+        Clock {
+            period: pace.wall_time(self.period),
+            ..self
+        }
+    }
+
+    pub fn tick(&self) {
+        std::thread::sleep(self.period);
+    }
+
+    /// Waits `time` rather than a tick, for an instruction that took that
+    /// long. Waits too short to sleep for accurately add up until they are
+    /// not.
+    pub fn wait(&mut self, time: Duration) {
+        self.owed += time;
+        if self.owed >= Duration::from_millis(1) {
+            std::thread::sleep(core::mem::take(&mut self.owed));
+        }
+    }
+}
+
+#[cfg(all(test, feature = "std"))]
+mod test {
+    use super::*;
+    use crate::display::Display;
+    use crate::quirks::Quirks;
+
+    #[derive(Default)]
+    struct Recorder {
+        events: Vec<&'static str>,
+    }
+
+    impl DisplayBackend for Recorder {
+        type Error = &'static str;
+        fn clear_screen(&mut self) -> Result<(), Self::Error> {
+            self.events.push("clear");
+            Ok(())
+        }
+        fn draw_screen(&mut self, _display: &Display) -> Result<(), Self::Error> {
+            Err("draw")
+        }
+    }
+
+    impl InputBackend for Recorder {
+        type Error = &'static str;
+        fn poll_key(&mut self) -> Result<Option<u8>, Self::Error> {
+            Ok(None)
+        }
+    }
+
+    #[derive(Default)]
+    struct Lifecycle {
+        recorder: Recorder,
+        input: Recorder,
+        audio: Silent<&'static str>,
+    }
+
+    impl Frontend for Lifecycle {
+        type Error = &'static str;
+        type Display = Recorder;
+        type Input = Recorder;
+        type Audio = Silent<&'static str>;
+        fn init(&mut self) -> Result<(), Self::Error> {
+            self.recorder.events.push("init");
+            Ok(())
+        }
+        fn teardown(&mut self) -> Result<(), Self::Error> {
+            self.recorder.events.push("teardown");
+            Ok(())
+        }
+        fn parts(&mut self) -> (&mut Recorder, &mut Recorder, &mut Self::Audio) {
+            (&mut self.recorder, &mut self.input, &mut self.audio)
+        }
+    }
+
+    #[test]
+    fn test_load_rom() {
+        let mut chip8 = Chip8::new();
+        let info = chip8.load_rom(&[0x12, 0x34]).unwrap();
+        assert_eq!(info.to_string(), "loaded 2 bytes, entry point 0x200");
+        assert_eq!(&chip8.cpu.mem[0x200..0x202], &[0x12, 0x34]);
+        assert_eq!(chip8.load_rom(&[]), Err(LoadError::Empty));
+        let rom = [0; MAX_ROM_SIZE + 1];
+        assert_eq!(
+            chip8.load_rom(&rom),
+            Err(LoadError::TooLarge {
+                size: MAX_ROM_SIZE + 1,
+                max: MAX_ROM_SIZE
+            })
+        );
+        chip8.load_rom(&rom[1..]).unwrap();
+    }
+
+    #[test]
+    fn test_load_addr() {
+        let mut chip8 = Chip8::new();
+        chip8.set_load_addr(ETI_660_LOAD_ADDR);
+        assert_eq!(chip8.load_addr(), 0x600);
+        // 6105: V1 = 5, 00E0: clear
+        let info = chip8.load_rom(&[0x61, 0x05, 0x00, 0xE0]).unwrap();
+        assert_eq!(info.to_string(), "loaded 4 bytes, entry point 0x600");
+        assert_eq!(&chip8.cpu.mem[0x200..0x202], &[0, 0]);
+        assert_eq!(chip8.cpu.pc(), 0x600);
+        chip8.step();
+        assert_eq!(chip8.cpu.registers()[1], 5);
+        chip8.reset();
+        assert_eq!(chip8.cpu.pc(), 0x600);
+        assert_eq!(&chip8.cpu.mem[0x600..0x602], &[0x61, 0x05]);
+        let rom = [0; 4096 - 0x600 + 1];
+        let error = chip8.swap_rom(&rom).unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "error: rom is 2561 bytes, at most 2560 fit in memory\n"
+        );
+        // a new load address applies to the next ROM, not a reset
+        chip8.set_load_addr(DEFAULT_LOAD_ADDR);
+        chip8.reset();
+        assert_eq!(chip8.cpu.pc(), 0x600);
+        chip8.swap_rom(&rom[..10]).unwrap();
+        assert_eq!(chip8.cpu.pc(), 0x200);
+    }
+
+    #[test]
+    fn test_megachip() {
+        // 0011: MegaChip on, 00E0: show the picture, 0010: off, and data
+        // past what fits in memory
+        let mut rom = vec![0x00, 0x11, 0x00, 0xE0, 0x00, 0x10];
+        rom.resize(8192, 0xAA);
+        let mut chip8 = Chip8::new();
+        assert!(chip8.load_rom(&rom).is_err());
+        let info = chip8.load_megachip(&rom).unwrap();
+        assert_eq!(info.size, 8192);
+        assert_eq!(chip8.cpu.mem[0xFFF], 0xAA);
+        assert!(matches!(chip8.step(), Chip8Message::DrawScreen(_)));
+        assert!(chip8.megachip.as_ref().unwrap().is_on());
+        // 00E0 shows the picture rather than clearing the display
+        assert!(matches!(chip8.step(), Chip8Message::DrawScreen(_)));
+        chip8.reset();
+        assert!(!chip8.megachip.as_ref().unwrap().is_on());
+        assert_eq!(chip8.cpu.pc(), 0x200);
+        chip8.swap_rom(&rom[..6]).unwrap();
+        assert!(chip8.megachip.is_none());
+    }
+
+    #[test]
+    fn test_run_with_tears_down_on_error() {
+        let mut chip8 = Chip8::new();
+        chip8.cpu.mem[0x200..0x202].copy_from_slice(&[0xD0, 0x01]);
+        let mut frontend = Lifecycle::default();
+        assert_eq!(
+            chip8.run_with(&mut frontend),
+            Err(RunError::Frontend("draw"))
+        );
+        assert_eq!(frontend.recorder.events, ["init", "clear", "teardown"]);
+    }
+
+    #[test]
+    fn test_beep_messages() {
+        let mut chip8 = Chip8::new();
+        // 6002: V0 = 2, F018: ST = V0, F018 again
+        chip8
+            .load_rom(&[0x60, 0x02, 0xF0, 0x18, 0xF0, 0x18])
+            .unwrap();
+        chip8.step();
+        assert!(matches!(chip8.step(), Chip8Message::Beep(true)));
+        assert!(matches!(chip8.step(), Chip8Message::None));
+        assert!(matches!(chip8.tick_timers(), Chip8Message::FrameComplete));
+        assert!(matches!(chip8.tick_timers(), Chip8Message::Beep(false)));
+        assert!(matches!(chip8.tick_timers(), Chip8Message::FrameComplete));
+    }
+
+    #[test]
+    fn test_run_with_stops_on_fault() {
+        let mut chip8 = Chip8::new();
+        chip8.cpu.mem[0x200..0x202].copy_from_slice(&[0x00, 0xEE]);
+        let mut frontend = Lifecycle::default();
+        let Err(RunError::Fault { fault, registers }) = chip8.run_with(&mut frontend) else {
+            panic!("expected a fault");
+        };
+        assert_eq!(fault, Fault::StackUnderflow { addr: 0x200 });
+        assert_eq!(registers, chip8.cpu.dump());
+        assert_eq!(registers.pc, 0x200);
+        assert_eq!(frontend.recorder.events, ["init", "clear", "teardown"]);
+    }
+
+    /// Sends the scripted keys and control messages one per poll, noting how
+    /// it was asked.
+    #[derive(Default)]
+    struct Script {
+        keys: std::collections::VecDeque<Option<u8>>,
+        controls: std::collections::VecDeque<Option<ControlMessage>>,
+        events: Vec<&'static str>,
+    }
+
+    impl DisplayBackend for Display {
+        type Error = &'static str;
+        fn clear_screen(&mut self) -> Result<(), Self::Error> {
+            self.clear();
+            Ok(())
+        }
+        fn draw_screen(&mut self, display: &Display) -> Result<(), Self::Error> {
+            self.clone_from(display);
+            Ok(())
+        }
+    }
+
+    impl InputBackend for Script {
+        type Error = &'static str;
+        fn poll_key(&mut self) -> Result<Option<u8>, Self::Error> {
+            self.events.push("poll");
+            Ok(self.keys.pop_front().flatten())
+        }
+        fn wait_key(&mut self, _timeout: Duration) -> Result<Option<u8>, Self::Error> {
+            self.events.push("wait");
+            Ok(self.keys.pop_front().flatten())
+        }
+        fn poll_control(&mut self) -> Result<Option<ControlMessage>, Self::Error> {
+            Ok(self.controls.pop_front().flatten())
+        }
+    }
+
+    #[derive(Default)]
+    struct Scripted {
+        display: Display,
+        script: Script,
+        audio: Silent<&'static str>,
+    }
+
+    impl Frontend for Scripted {
+        type Error = &'static str;
+        type Display = Display;
+        type Input = Script;
+        type Audio = Silent<&'static str>;
+        fn parts(&mut self) -> (&mut Display, &mut Script, &mut Self::Audio) {
+            (&mut self.display, &mut self.script, &mut self.audio)
+        }
+    }
+
+    #[test]
+    fn test_pause_and_resume() {
+        let mut chip8 = Chip8::new();
+        // 7001: V0 += 1, 00EE: return from nowhere
+        chip8.load_rom(&[0x70, 0x01, 0x00, 0xEE]).unwrap();
+        let mut frontend = Scripted::default();
+        frontend.script.controls = [
+            Some(ControlMessage::Pause),
+            None,
+            Some(ControlMessage::Resume),
+        ]
+        .into();
+        assert!(matches!(
+            chip8.run_with(&mut frontend),
+            Err(RunError::Fault { .. })
+        ));
+        // paused, the loop only waits for input; resumed, it runs both instructions
+        assert_eq!(frontend.script.events, ["poll", "wait", "wait", "poll"]);
+        assert_eq!(chip8.cpu.registers()[0], 1);
+    }
+
+    /// A scripted frontend whose debugger sets V1 to 9.
+    #[derive(Default)]
+    struct Debugged(Scripted);
+
+    impl Frontend for Debugged {
+        type Error = &'static str;
+        type Display = Display;
+        type Input = Script;
+        type Audio = Silent<&'static str>;
+        fn parts(&mut self) -> (&mut Display, &mut Script, &mut Self::Audio) {
+            self.0.parts()
+        }
+        fn debug(&mut self, chip8: &mut Chip8) -> Result<(), Self::Error> {
+            self.0.script.events.push("debug");
+            chip8.cpu.registers_mut()[1] = 9;
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_debug() {
+        let mut chip8 = Chip8::new();
+        // 7001: V0 += 1, 00EE: return from nowhere
+        chip8.load_rom(&[0x70, 0x01, 0x00, 0xEE]).unwrap();
+        let mut frontend = Debugged::default();
+        frontend.0.script.controls = [Some(ControlMessage::Debug)].into();
+        assert!(matches!(
+            chip8.run_with(&mut frontend),
+            Err(RunError::Fault { .. })
+        ));
+        // nothing runs until the debugger is done with the machine
+        assert_eq!(frontend.0.script.events, ["poll", "debug", "poll", "poll"]);
+        assert_eq!(chip8.cpu.registers()[..2], [1, 9]);
+    }
+
+    /// Counts the times it is asked to show the display.
+    #[derive(Default)]
+    struct Frames {
+        shown: usize,
+        script: Script,
+        audio: Silent<&'static str>,
+    }
+
+    impl DisplayBackend for usize {
+        type Error = &'static str;
+        fn clear_screen(&mut self) -> Result<(), Self::Error> {
+            Ok(())
+        }
+        fn draw_screen(&mut self, _: &Display) -> Result<(), Self::Error> {
+            *self += 1;
+            Ok(())
+        }
+    }
+
+    impl Frontend for Frames {
+        type Error = &'static str;
+        type Display = usize;
+        type Input = Script;
+        type Audio = Silent<&'static str>;
+        fn parts(&mut self) -> (&mut usize, &mut Script, &mut Self::Audio) {
+            (&mut self.shown, &mut self.script, &mut self.audio)
+        }
+    }
+
+    #[test]
+    fn test_frame_advance() {
+        let mut chip8 = Chip8::new();
+        // 7001: V0 += 1, 1200: loop
+        chip8.load_rom(&[0x70, 0x01, 0x12, 0x00]).unwrap();
+        chip8.set_speed(120);
+        chip8.cpu.dt = 5;
+        let mut frontend = Frames::default();
+        frontend.script.controls = [
+            Some(ControlMessage::Pause),
+            Some(ControlMessage::FrameAdvance),
+            None,
+            Some(ControlMessage::FrameAdvance),
+            Some(ControlMessage::Quit),
+        ]
+        .into();
+        chip8.run_with(&mut frontend).unwrap();
+        // two instructions and a tick a frame, each frame shown
+        assert_eq!(chip8.instructions(), 4);
+        assert_eq!(chip8.cpu.registers()[0], 2);
+        assert_eq!(chip8.cpu.dt, 3);
+        assert_eq!(frontend.shown, 2);
+    }
+
+    #[test]
+    fn test_draws_shown_once_a_frame() {
+        let mut chip8 = Chip8::new();
+        chip8.load_font_set();
+        // A050: I = font "0", D005: draw it, 1202: draw it again
+        chip8
+            .load_rom(&[0xA0, 0x50, 0xD0, 0x05, 0x12, 0x02])
+            .unwrap();
+        chip8.set_speed(10_000);
+        chip8.set_frame_rate(1);
+        let mut frontend = Frames::default();
+        let mut controls = vec![None; 200];
+        controls.push(Some(ControlMessage::Quit));
+        frontend.script.controls = controls.into();
+        chip8.run_with(&mut frontend).unwrap();
+        // a hundred draws, and only the first shown within the second
+        assert_eq!(chip8.instructions(), 200);
+        assert_eq!(frontend.shown, 1);
+    }
+
+    #[test]
+    fn test_set_seed() {
+        // C0FF: V0 = random, C1FF: V1 = random
+        let rom = [0xC0, 0xFF, 0xC1, 0xFF];
+        let draw = |seed| {
+            let mut chip8 = Chip8::new();
+            chip8.load_rom(&rom).unwrap();
+            chip8.set_seed(seed);
+            chip8.step();
+            chip8.step();
+            [chip8.cpu.registers()[0], chip8.cpu.registers()[1]]
+        };
+        assert_eq!(draw(7), draw(7));
+        assert_ne!(draw(7), draw(8));
+    }
+
+    #[test]
+    fn test_step_frame() {
+        // 6005: V0 = 5, F018: ST = V0, 00E0: clear, 1206: loop,
+        // 00EE: return with nothing to return to
+        let rom = [0x60, 0x05, 0xF0, 0x18, 0x00, 0xE0, 0x12, 0x06, 0x00, 0xEE];
+        let mut chip8 = Chip8::new();
+        chip8.load_rom(&rom).unwrap();
+        chip8.set_speed(90);
+        // 1.5 instructions a frame: one, then two
+        let frame = chip8.step_frame();
+        assert_eq!(frame.instructions, 1);
+        assert!(!frame.drawn && !frame.beeping);
+        let frame = chip8.step_frame();
+        assert_eq!(frame.instructions, 2);
+        assert!(frame.drawn && frame.beeping);
+        assert_eq!(chip8.cpu.st, 4);
+        assert_eq!(chip8.instructions(), 3);
+        for _ in 0..58 {
+            chip8.step_frame();
+        }
+        assert_eq!(chip8.instructions(), 90);
+
+        chip8.cpu.set_pc(0x208);
+        let frame = chip8.run_frame(10);
+        assert_eq!(frame.instructions, 0);
+        assert!(frame.fault.is_some());
+        assert_eq!(chip8.cpu.pc(), 0x208);
+    }
+
+    #[test]
+    fn test_cosmac_timing() {
+        // 7001: V0 += 1, 1200: loop, 45 and 105 microseconds
+        let mut chip8 = Chip8::new();
+        chip8.load_rom(&[0x70, 0x01, 0x12, 0x00]).unwrap();
+        chip8.set_timing(Timing::Cosmac);
+        assert_eq!(chip8.timing(), Timing::Cosmac);
+        // 111 loops take 16650, and the next add finishes the frame
+        assert_eq!(chip8.step_frame().instructions, 223);
+        assert_eq!(chip8.cpu.registers()[0], 112);
+
+        // D005: draw, 1200: loop; a draw takes more than a frame, which
+        // the next frames make up for
+        let mut chip8 = Chip8::new();
+        chip8.load_rom(&[0xD0, 0x05, 0x12, 0x00]).unwrap();
+        chip8.set_timing(Timing::Cosmac);
+        chip8.set_speed(1_000_000);
+        let frames: Vec<u32> = (0..4).map(|_| chip8.step_frame().instructions).collect();
+        assert_eq!(frames, [1, 2, 2, 0]);
+        // stepping by hand puts the frames a long way behind, but they only
+        // make up for a draw's worth of it
+        for _ in 0..100 {
+            chip8.step();
+        }
+        let frames: Vec<u32> = (0..2).map(|_| chip8.step_frame().instructions).collect();
+        assert_eq!(frames, [0, 2]);
+    }
+
+    #[test]
+    fn test_record_and_play() {
+        // F00A: V0 = next key, C1FF: V1 = random, 1204: loop
+        let rom = [0xF0, 0x0A, 0xC1, 0xFF, 0x12, 0x04];
+        let quit_after = |polls: usize| {
+            let mut controls = vec![None; polls];
+            controls.push(Some(ControlMessage::Quit));
+            controls.into()
+        };
+
+        let mut chip8 = Chip8::new();
+        chip8.load_rom(&rom).unwrap();
+        chip8.record(99);
+        let mut frontend = Scripted::default();
+        frontend.script.keys = [None, None, Some(7)].into();
+        frontend.script.controls = quit_after(6);
+        chip8.run_with(&mut frontend).unwrap();
+        let replay = chip8.take_recording().unwrap();
+        assert_eq!(replay.seed, 99);
+        assert!(replay.events.contains(&(2, Event::Key(7))));
+        assert_eq!(chip8.take_recording(), None);
+
+        let mut again = Chip8::new();
+        again.load_rom(&rom).unwrap();
+        again.play(replay);
+        assert!(again.is_playing());
+        let mut frontend = Scripted::default();
+        // the replay's keys are pressed, not the frontend's
+        frontend.script.keys = [Some(3), Some(3)].into();
+        frontend.script.controls = quit_after(6);
+        again.run_with(&mut frontend).unwrap();
+        assert!(!again.is_playing());
+        assert_eq!(again.cpu.registers()[0], 7);
+        assert_eq!(again.cpu.registers()[..2], chip8.cpu.registers()[..2]);
+        assert_eq!(again.instructions(), chip8.instructions());
+    }
+
+    #[test]
+    fn test_quit() {
+        let mut chip8 = Chip8::new();
+        // 7001: V0 += 1, 1200: loop
+        chip8.load_rom(&[0x70, 0x01, 0x12, 0x00]).unwrap();
+        let mut frontend = Scripted::default();
+        frontend.script.controls = [None, None, None, Some(ControlMessage::Quit)].into();
+        assert_eq!(chip8.run_with(&mut frontend), Ok(()));
+        assert_eq!(chip8.instructions(), 3);
+        assert_eq!(chip8.cpu.registers()[0], 2);
+    }
+
+    #[test]
+    fn test_reset_and_swap_rom() {
+        let mut chip8 = Chip8::new();
+        chip8.cpu.quirks = Quirks::COSMAC;
+        // 6005: V0 = 5, F029: I = font(V0), D005: draw it
+        chip8
+            .load_rom(&[0x60, 0x05, 0xF0, 0x29, 0xD0, 0x05])
+            .unwrap();
+        chip8.load_font_set();
+        for _ in 0..3 {
+            chip8.step();
+        }
+        chip8.cpu.mem[0x300] = 0xAA;
+        chip8.cpu.flags[0] = 9;
+        chip8.reset();
+        assert_eq!(chip8.cpu.pc(), 0x200);
+        assert_eq!(chip8.cpu.flags[0], 9);
+        assert_eq!(chip8.cpu.registers()[0], 0);
+        assert_eq!(chip8.cpu.mem[0x300], 0);
+        assert_eq!(chip8.framebuffer(), Display::new().pack());
+        assert_eq!(chip8.instructions(), 0);
+        assert_eq!(chip8.cpu.quirks, Quirks::COSMAC);
+        // let the draw through the display wait
+        chip8.tick_timers();
+        for _ in 0..3 {
+            chip8.step();
+        }
+        assert!(chip8.cpu.disp.pixel(5, 5));
+
+        let rom = [0; MAX_ROM_SIZE + 1];
+        assert!(chip8.swap_rom(&rom).is_err());
+        assert!(chip8.cpu.disp.pixel(5, 5));
+        chip8.swap_rom(&[0x12, 0x00]).unwrap();
+        assert_eq!(&chip8.cpu.mem[0x200..0x204], &[0x12, 0x00, 0x00, 0x00]);
+        chip8.reset();
+        assert_eq!(&chip8.cpu.mem[0x200..0x202], &[0x12, 0x00]);
+    }
+
+    #[test]
+    fn test_hooks() {
+        use std::sync::{Arc, Mutex};
+        let log = Arc::new(Mutex::new(Vec::new()));
+        let mut chip8 = Chip8::new();
+        // 6005: V0 = 5, E09E: skip if key V0, D001: draw, 00EE: return from nowhere
+        chip8.cpu.mem[0x200..0x208]
+            .copy_from_slice(&[0x60, 0x05, 0xE0, 0x9E, 0xD0, 0x01, 0x00, 0xEE]);
+        let l = log.clone();
+        chip8
+            .hooks
+            .on_fetch(move |addr| l.lock().unwrap().push(format!("fetch {:03x}", addr)));
+        let l = log.clone();
+        chip8.hooks.on_instruction(move |addr, inst, cpu| {
+            l.lock().unwrap().push(format!(
+                "{:03x}:{:04x} v0={}",
+                addr,
+                inst,
+                cpu.registers()[0]
+            ))
+        });
+        let l = log.clone();
+        chip8
+            .hooks
+            .on_key_read(move |key| l.lock().unwrap().push(format!("key {:?}", key)));
+        let l = log.clone();
+        chip8
+            .hooks
+            .on_draw(move |_| l.lock().unwrap().push("draw".to_string()));
+        let l = log.clone();
+        chip8
+            .hooks
+            .on_timer_tick(move |dt, st| l.lock().unwrap().push(format!("tick {} {}", dt, st)));
+        let l = log.clone();
+        chip8.hooks.on_fault(move |fault, cpu| {
+            l.lock()
+                .unwrap()
+                .push(format!("fault {:?} pc={:03x}", fault, cpu.pc()))
+        });
+        chip8.step();
+        chip8.cpu.press_key(3);
+        chip8.step();
+        chip8.step();
+        chip8.cpu.dt = 2;
+        chip8.tick_timers();
+        chip8.step();
+        assert_eq!(
+            *log.lock().unwrap(),
+            [
+                "fetch 200",
+                "200:6005 v0=5",
+                "fetch 202",
+                "key Some(3)",
+                "202:e09e v0=5",
+                "fetch 204",
+                "204:d001 v0=5",
+                "draw",
+                "tick 1 0",
+                "fetch 206",
+                "206:00ee v0=5",
+                "fault StackUnderflow { addr: 518 } pc=206",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_observer() {
+        use crate::hooks::Observer;
+        use std::sync::{Arc, Mutex};
+
+        #[derive(Default)]
+        struct Log(Vec<String>);
+
+        impl Observer for Log {
+            fn on_instruction(&mut self, addr: u16, opcode: u16, _: &Cpu) {
+                self.0.push(format!("{:03x}:{:04x}", addr, opcode));
+            }
+            fn on_frame(&mut self, frame: u64, cpu: &Cpu) {
+                self.0.push(format!("frame {} dt={}", frame, cpu.dt));
+            }
+            fn on_fault(&mut self, fault: Fault, _: &Cpu) {
+                self.0.push(format!("fault {:?}", fault));
+            }
+        }
+
+        let log = Arc::new(Mutex::new(Log::default()));
+        let mut chip8 = Chip8::new();
+        // 6005: V0 = 5, F015: DT = V0, 00EE: return from nowhere
+        chip8
+            .load_rom(&[0x60, 0x05, 0xF0, 0x15, 0x00, 0xEE])
+            .unwrap();
+        chip8.hooks.observe(log.clone());
+        assert!(!chip8.hooks.is_empty());
+        chip8.step();
+        chip8.step();
+        chip8.tick_timers();
+        chip8.step();
+        assert_eq!(
+            log.lock().unwrap().0,
+            [
+                "200:6005",
+                "202:f015",
+                "frame 1 dt=4",
+                "204:00ee",
+                "fault StackUnderflow { addr: 516 }",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_extension_opcode() {
+        use std::sync::{Arc, Mutex};
+        let serial = Arc::new(Mutex::new(Vec::new()));
+        let mut chip8 = Chip8::new();
+        // 6041: V0 = 'A', F0F8: write V0 to the serial port, 0123: clear the port
+        chip8.cpu.mem[0x200..0x206].copy_from_slice(&[0x60, 0x41, 0xF0, 0xF8, 0x01, 0x23]);
+        let out = serial.clone();
+        chip8.extensions.register(0xF0FF, 0xF0F8, move |inst, cpu| {
+            out.lock()
+                .unwrap()
+                .push(cpu.registers()[(inst >> 8 & 0xF) as usize]);
+            Chip8Message::None
+        });
+        let out = serial.clone();
+        chip8.extensions.register(0xF000, 0x0000, move |_, cpu| {
+            out.lock().unwrap().clear();
+            cpu.registers_mut()[0xF] = 1;
+            Chip8Message::None
+        });
+        chip8.step();
+        chip8.step();
+        assert_eq!(*serial.lock().unwrap(), b"A");
+        chip8.step();
+        assert!(serial.lock().unwrap().is_empty());
+        assert_eq!(chip8.cpu.registers()[0xF], 1);
+        assert_eq!(chip8.cpu.pc(), 0x206);
+    }
+
+    #[test]
+    fn test_waiting() {
+        let mut chip8 = Chip8::new();
+        // F007: V0 = DT, 3000: skip if V0 == 0, 1200: loop, F10A: wait for a key
+        chip8.cpu.mem[0x200..0x208]
+            .copy_from_slice(&[0xF0, 0x07, 0x30, 0x00, 0x12, 0x00, 0xF1, 0x0A]);
+        chip8.cpu.dt = 2;
+        for _ in 0..3 {
+            chip8.step();
+            assert!(!chip8.waiting());
+        }
+        // the second read of an unchanged timer gives the loop away
+        chip8.step();
+        assert!(chip8.waiting());
+        chip8.tick_timers();
+        chip8.step();
+        assert!(!chip8.waiting());
+        chip8.tick_timers();
+        for _ in 0..5 {
+            chip8.step();
+        }
+        assert_eq!(chip8.cpu.pc(), 0x206);
+        assert!(chip8.waiting());
+        chip8.cpu.press_key(1);
+        chip8.step();
+        assert!(!chip8.waiting());
+    }
+
+    #[test]
+    fn test_display_wait() {
+        // D015: draw, D015: draw, 1200: loop
+        let rom = [0xD0, 0x15, 0xD0, 0x15, 0x12, 0x00];
+        let mut chip8 = Chip8::new();
+        chip8.load_rom(&rom).unwrap();
+        chip8.cpu.quirks.display_wait = true;
+        // the first draw waits for a frame to begin
+        chip8.step();
+        assert_eq!(chip8.cpu.pc(), 0x200);
+        assert!(chip8.waiting());
+        chip8.tick_timers();
+        assert!(!chip8.waiting());
+        chip8.step();
+        assert_eq!(chip8.cpu.pc(), 0x202);
+        assert!(chip8.waiting());
+        chip8.step();
+        assert_eq!(chip8.cpu.pc(), 0x202);
+        chip8.tick_timers();
+        chip8.step();
+        assert_eq!(chip8.cpu.pc(), 0x204);
+
+        // one sprite a frame however fast the machine runs
+        chip8.set_speed(6000);
+        for _ in 0..10 {
+            chip8.step_frame();
+        }
+        assert_eq!(chip8.cpu.pc(), 0x202);
+        chip8.cpu.quirks.display_wait = false;
+        chip8.step();
+        assert_eq!(chip8.cpu.pc(), 0x204);
+    }
+
+    #[test]
+    fn test_run_turbo() {
+        let mut chip8 = Chip8::new();
+        // 7001: V0 += 1, 1200: loop
+        chip8.cpu.mem[0x200..0x204].copy_from_slice(&[0x70, 0x01, 0x12, 0x00]);
+        chip8.cpu.dt = 10;
+        let run = chip8.run_turbo(&mut Interpreter, 10, TurboLimit::Instructions(100));
+        assert_eq!((run.instructions, run.frames), (100, 10));
+        assert_eq!(chip8.cpu.dt, 0);
+        assert_eq!(chip8.cpu.registers()[0], 50);
+        assert_eq!(chip8.instructions(), 100);
+        let run = chip8.run_turbo(&mut Interpreter, 10, TurboLimit::Duration(Duration::ZERO));
+        assert_eq!(run.instructions, 1024);
+    }
+
+    #[test]
+    fn test_run_turbo_with_blocks() {
+        /// Retires four instructions a step, as the JIT does for a compiled
+        /// loop, so the count never lands on 1023.
+        struct Blocks;
+
+        impl Engine for Blocks {
+            fn step(&mut self, chip8: &mut Chip8) -> Chip8Message {
+                let msg = chip8.step();
+                chip8.retire(3);
+                msg
+            }
+        }
+
+        let mut chip8 = Chip8::new();
+        chip8.cpu.mem[0x200..0x202].copy_from_slice(&[0x12, 0x00]);
+        let run = chip8.run_turbo(&mut Blocks, 10, TurboLimit::Duration(Duration::ZERO));
+        assert_eq!(run.instructions, 1024);
+    }
+
+    #[test]
+    fn test_timer_schedule_keeps_time() {
+        let start = Instant::now();
+        let mut timer = TimerSchedule::new(start, TIMER_PERIOD);
+        assert_eq!(timer.due(start), 0);
+        assert_eq!(timer.until_next(start), TIMER_PERIOD);
+        // checked every 7 ms, with the odd 100 ms stall, one second still has 60 ticks
+        let mut ticks = 0;
+        let mut now = start;
+        while now < start + Duration::from_secs(1) {
+            now += Duration::from_millis(if ticks == 20 { 100 } else { 7 });
+            ticks += timer.due(now);
+        }
+        assert_eq!(ticks, 60);
+        // after a long suspension it starts afresh instead of racing through the backlog
+        let later = now + Duration::from_secs(10);
+        assert_eq!(timer.due(later), TimerSchedule::MAX_BEHIND);
+        assert_eq!(timer.due(later), 0);
+        assert_eq!(timer.until_next(later), TIMER_PERIOD);
+    }
+
+    #[test]
+    fn test_pace() {
+        assert_eq!(Pace::of(false, false), Pace::Normal);
+        assert_eq!(Pace::of(true, true), Pace::FastForward);
+        assert_eq!(Pace::of(false, true), Pace::SlowMotion);
+        let second = Duration::from_secs(1);
+        assert_eq!(Pace::FastForward.wall_time(second), second / 8);
+        assert_eq!(Pace::SlowMotion.wall_time(second), second * 4);
+
+        let mut chip8 = Chip8::new();
+        chip8.set_speed(600);
+        chip8.set_pace(Pace::FastForward);
+        assert_eq!(chip8.pace(), Pace::FastForward);
+        assert_eq!(chip8.clock.period, second / 600 / 8);
+        assert!(chip8.timer.until_next(Instant::now()) <= TIMER_PERIOD / 8);
+        // a change of speed keeps the pace
+        chip8.set_speed(60);
+        assert_eq!(chip8.clock.period, second / 60 / 8);
+    }
+
+    fn assert_send<T: Send>() {}
+    fn assert_sync<T: Sync>() {}
+
+    #[test]
+    fn test_thread_safety_bounds() {
+        assert_send::<Chip8>();
+        assert_sync::<Chip8>();
+        assert_send::<Cpu>();
+        assert_sync::<Cpu>();
+        assert_send::<crate::display::Display>();
+        assert_sync::<crate::display::Display>();
+        assert_send::<Silent<std::rc::Rc<()>>>();
+        assert_sync::<Silent<std::rc::Rc<()>>>();
+        // a receiver can move to another thread but not be shared between them
+        assert_send::<ChannelInput<()>>();
+    }
+
+    #[test]
+    fn test_machines_run_on_separate_threads() {
+        let machines: Vec<_> = [3u8, 9]
+            .into_iter()
+            .map(|key| {
+                let (tx, rx) = std::sync::mpsc::channel();
+                let machine = std::thread::spawn(move || {
+                    let mut chip8 = Chip8::new();
+                    let mut input = ChannelInput::<()>::new(rx);
+                    // F00A: wait for key into V0, A300: I = 0x300, F055: store V0 at I
+                    let rom = [0xF0, 0x0A, 0xA3, 0x00, 0xF0, 0x55];
+                    chip8.cpu.mem[0x200..0x206].copy_from_slice(&rom);
+                    while chip8.cpu.mem[0x300] == 0 {
+                        if let Some(key) = input.poll_key().unwrap() {
+                            chip8.cpu.press_key(key);
+                        }
+                        chip8.step();
+                    }
+                    chip8
+                });
+                tx.send(key).unwrap();
+                (key, machine)
+            })
+            .collect();
+        for (key, machine) in machines {
+            assert_eq!(machine.join().unwrap().cpu.mem[0x300], key);
+        }
+    }
+}
@@ -0,0 +1,1537 @@
+use crate::chip::Chip8Message;
+use crate::display::{DirtyRows, Display};
+use crate::keypad::Keypad;
+use crate::opcode::*;
+use crate::quirks::Quirks;
+use crate::rng::Rng;
+
+type Memory = [u8; 4096];
+type I = u16;
+type Stack = [u16; 16];
+type DelayTimer = u8;
+type SoundTimer = u8;
+type Register = [u8; 16];
+type ProgramCounter = u16;
+
+/// How many RPL user flags there are; FX75 and FX85 with a larger X stop at
+/// the last.
+pub const FLAGS: usize = 8;
+
+/// All CHIP-8 programs start the program counter here.
+const START: u16 = crate::chip::DEFAULT_LOAD_ADDR;
+
+/// Registers, memory and display of the interpreter. `Send` and `Sync`, like
+/// [`Chip8`](crate::chip::Chip8).
+#[derive(Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Cpu {
+    #[cfg_attr(feature = "serde", serde(with = "crate::serde_array"))]
+    pub mem: Memory,
+    pub disp: Display,
+    index: I,
+    pub(crate) stack: Stack,
+    // number of return addresses on the stack
+    pub(crate) sp: u8,
+    pub dt: DelayTimer,
+    pub st: SoundTimer,
+    reg: Register,
+    pc: ProgramCounter,
+    /// The keys the program sees, updated by the frontend.
+    pub keypad: Keypad,
+    /// The SUPER-CHIP RPL user flags, which FX75 saves V0 to VX into and
+    /// FX85 reads back. They outlive a [reset](crate::chip::Chip8::reset), as
+    /// they did on the HP-48, so games keep high scores in them.
+    pub flags: [u8; FLAGS],
+    /// Which variant of the ambiguous instructions to run.
+    pub quirks: Quirks,
+    /// What to do with an instruction that does not decode.
+    pub on_unknown: OnUnknown,
+    /// Whether [`decode`](Cpu::decode) reuses the decode of an
+    /// instruction it has run before. On unless turned off to measure it.
+    pub decode_cache: bool,
+    pub(crate) rng: Rng,
+    #[cfg_attr(feature = "serde", serde(skip))]
+    decoded: DecodeCache,
+    // a 60 Hz frame began and nothing has been drawn since, for the
+    // display_wait quirk
+    vblank: bool,
+    // the memory the instruction last executed read or wrote
+    #[cfg_attr(feature = "serde", serde(skip))]
+    access: Option<Access>,
+}
+
+/// Memory an instruction read or wrote, as reported by
+/// [`last_access`](Cpu::last_access).
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Access {
+    /// The first address touched.
+    pub addr: u16,
+    /// How many bytes from `addr` were touched.
+    pub len: u16,
+    pub write: bool,
+}
+
+impl Access {
+    /// Whether the access touched any of the `len` bytes from `addr`.
+    pub fn overlaps(&self, addr: u16, len: u16) -> bool {
+        addr < self.addr + self.len && self.addr < addr.saturating_add(len)
+    }
+}
+
+/// An instruction the machine cannot carry out. The program counter is left
+/// on the instruction, which has had no effect.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Fault {
+    /// 2NNN with all 16 stack entries in use.
+    StackOverflow { addr: u16 },
+    /// 00EE outside any subroutine.
+    StackUnderflow { addr: u16 },
+    /// `raw` is not an instruction this machine knows.
+    UnknownOpcode { addr: u16, raw: u16 },
+    /// An instruction, or fetching one, reached memory at `target`, past
+    /// the 4 KiB the machine has.
+    OutOfBounds { addr: u16, target: usize },
+}
+
+impl core::fmt::Display for Fault {
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+        match self {
+            Fault::StackOverflow { addr } => writeln!(
+                f,
+                "error: stack overflow at {:#05x}: subroutines nested more than 16 deep",
+                addr
+            )?,
+            Fault::StackUnderflow { addr } => writeln!(
+                f,
+                "error: stack underflow at {:#05x}: return outside any subroutine",
+                addr
+            )?,
+            Fault::UnknownOpcode { addr, raw } => {
+                writeln!(f, "error: unknown opcode {:04x} at {:#05x}", raw, addr)?
+            }
+            Fault::OutOfBounds { addr, target } => writeln!(
+                f,
+                "error: memory access out of bounds at {:#05x}: address {:#06x} is past 0xfff",
+                addr, target
+            )?,
+        }
+        Ok(())
+    }
+}
+
+/// What the CPU does with an instruction it cannot decode.
+///
+/// Some ROMs probe for SUPER-CHIP by running one of its instructions and
+/// carrying on if nothing happens; skipping lets them run.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum OnUnknown {
+    /// Stop with [`Fault::UnknownOpcode`].
+    #[default]
+    Halt,
+    /// Treat it as a no-op and go on to the next instruction.
+    Skip,
+}
+
+/// The registers when a fault happened, reported alongside it.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct RegisterDump {
+    pub pc: u16,
+    pub index: u16,
+    pub v: [u8; 16],
+    pub sp: u8,
+    pub dt: u8,
+    pub st: u8,
+}
+
+impl core::fmt::Display for RegisterDump {
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+        writeln!(
+            f,
+            "pc {:#05x}  i {:#05x}  sp {}  dt {}  st {}",
+            self.pc, self.index, self.sp, self.dt, self.st
+        )?;
+        for (row, regs) in self.v.chunks(8).enumerate() {
+            for (i, v) in regs.iter().enumerate() {
+                let sep = if i == 0 { "" } else { "  " };
+                write!(f, "{}v{:x} {:02x}", sep, row * 8 + i, v)?;
+            }
+            writeln!(f)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for Fault {}
+
+pub const FONT_SET: [u8; 80] = [
+    0xF0, 0x90, 0x90, 0x90, 0xF0, // 0
+    0x20, 0x60, 0x20, 0x20, 0x70, // 1
+    0xF0, 0x10, 0xF0, 0x80, 0xF0, // 2
+    0xF0, 0x10, 0xF0, 0x10, 0xF0, // 3
+    0x90, 0x90, 0xF0, 0x10, 0x10, // 4
+    0xF0, 0x80, 0xF0, 0x10, 0xF0, // 5
+    0xF0, 0x80, 0xF0, 0x90, 0xF0, // 6
+    0xF0, 0x10, 0x20, 0x40, 0x40, // 7
+    0xF0, 0x90, 0xF0, 0x90, 0xF0, // 8
+    0xF0, 0x90, 0xF0, 0x10, 0xF0, // 9
+    0xF0, 0x90, 0xF0, 0x90, 0x90, // A
+    0xE0, 0x90, 0xE0, 0x90, 0xE0, // B
+    0xF0, 0x80, 0x80, 0x80, 0xF0, // C
+    0xE0, 0x90, 0x90, 0x90, 0xE0, // D
+    0xF0, 0x80, 0xF0, 0x80, 0xF0, // E
+    0xF0, 0x80, 0xF0, 0x80, 0x80, // F
+];
+
+impl Default for Cpu {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Cpu {
+    pub fn new() -> Self {
+        Self::with_rng(Rng::default())
+    }
+
+    /// Creates a CPU whose CXNN instruction draws from the given generator.
+    pub fn with_rng(rng: Rng) -> Self {
+        let mem = [0u8; 4096];
+        let disp = Display::new();
+        let index = 0;
+        let stack = [0u16; 16];
+        let dt = 0;
+        let st = 0;
+        let reg = [0u8; 16];
+        let pc = START;
+
+        Self {
+            mem,
+            disp,
+            index,
+            stack,
+            sp: 0,
+            dt,
+            st,
+            reg,
+            pc,
+            keypad: Keypad::new(),
+            flags: [0; FLAGS],
+            quirks: Quirks::default(),
+            on_unknown: OnUnknown::default(),
+            rng,
+            decode_cache: true,
+            decoded: DecodeCache::new(),
+            vblank: false,
+            access: None,
+        }
+    }
+
+    /// Whether the next instruction is FX0A with no key press to satisfy it.
+    pub fn waiting_for_key(&self) -> bool {
+        let pc = self.pc as usize;
+        self.keypad.last_press().is_none()
+            && self.mem.get(pc).is_some_and(|b| b & 0xF0 == 0xF0)
+            && self.mem.get(pc + 1) == Some(&0x0A)
+    }
+
+    /// Whether the next instruction is DXYN waiting for the next frame to
+    /// begin, under the [`display_wait`](Quirks::display_wait) quirk.
+    pub fn waiting_for_vblank(&self) -> bool {
+        let pc = self.pc as usize;
+        self.quirks.display_wait
+            && !self.vblank
+            && self.mem.get(pc).is_some_and(|b| b & 0xF0 == 0xD0)
+    }
+
+    /// Marks the start of a 60 Hz frame, letting a DXYN waiting on it draw.
+    pub fn vblank(&mut self) {
+        self.vblank = true;
+    }
+
+    /// Latches a key press reported by the frontend until a key instruction
+    /// reads it, see [`Keypad::tap`].
+    pub fn press_key(&mut self, key: u8) {
+        self.keypad.tap(key);
+    }
+
+    /// Address of the next instruction to execute.
+    pub fn pc(&self) -> u16 {
+        self.pc
+    }
+
+    pub fn set_pc(&mut self, pc: u16) {
+        self.pc = pc;
+    }
+
+    pub fn index(&self) -> u16 {
+        self.index
+    }
+
+    pub fn set_index(&mut self, index: u16) {
+        self.index = index;
+    }
+
+    /// The general purpose registers V0 to VF.
+    pub fn registers(&self) -> &[u8; 16] {
+        &self.reg
+    }
+
+    pub fn registers_mut(&mut self) -> &mut [u8; 16] {
+        &mut self.reg
+    }
+
+    /// The return addresses of the subroutines in progress, innermost last.
+    pub fn stack(&self) -> &[u16] {
+        &self.stack[..self.sp as usize]
+    }
+
+    /// The registers, for reporting a fault.
+    pub fn dump(&self) -> RegisterDump {
+        RegisterDump {
+            pc: self.pc,
+            index: self.index,
+            v: self.reg,
+            sp: self.sp,
+            dt: self.dt,
+            st: self.st,
+        }
+    }
+
+    /// The byte at `addr`, or a fault if it is past the end of memory.
+    ///
+    /// The fault blames the instruction just before the program counter,
+    /// which is the one running while an instruction executes.
+    pub fn read_byte(&self, addr: usize) -> core::result::Result<u8, Fault> {
+        self.mem
+            .get(addr)
+            .copied()
+            .ok_or_else(|| self.out_of_bounds(addr))
+    }
+
+    /// Stores `value` at `addr`; see [`read_byte`](Cpu::read_byte).
+    pub fn write_byte(&mut self, addr: usize, value: u8) -> core::result::Result<(), Fault> {
+        let fault = self.out_of_bounds(addr);
+        *self.mem.get_mut(addr).ok_or(fault)? = value;
+        Ok(())
+    }
+
+    /// The big-endian word at `addr`; see [`read_byte`](Cpu::read_byte).
+    pub fn read_word(&self, addr: usize) -> core::result::Result<u16, Fault> {
+        Ok(u16::from_be_bytes([
+            self.read_byte(addr)?,
+            self.read_byte(addr + 1)?,
+        ]))
+    }
+
+    /// The memory the last instruction executed read or wrote, if any.
+    /// Instruction fetches do not count, and nor do accesses that faulted.
+    pub fn last_access(&self) -> Option<Access> {
+        self.access
+    }
+
+    fn accessed(&mut self, addr: usize, len: usize, write: bool) {
+        self.access = Some(Access {
+            addr: addr as u16,
+            len: len as u16,
+            write,
+        });
+    }
+
+    fn out_of_bounds(&self, target: usize) -> Fault {
+        Fault::OutOfBounds {
+            addr: self.pc.wrapping_sub(2),
+            target,
+        }
+    }
+
+    /// Reads the instruction at the program counter and moves past it. An
+    /// instruction that runs off the end of memory is a fault, with the
+    /// program counter left where it was.
+    pub fn fetch_next(&mut self) -> core::result::Result<u16, Fault> {
+        let pc = self.pc as usize;
+        // an extension may run the instruction in place of execute
+        self.access = None;
+        match self.mem.get(pc..pc + 2) {
+            Some(&[high, low]) => {
+                self.pc += 2;
+                Ok(u16::from_be_bytes([high, low]))
+            }
+            _ => Err(Fault::OutOfBounds {
+                addr: self.pc,
+                target: pc.max(self.mem.len()),
+            }),
+        }
+    }
+
+    /// Decodes `raw`, fetched from `addr`, reusing the previous decode of
+    /// that address if memory has not changed.
+    pub fn decode(
+        &mut self,
+        addr: u16,
+        raw: u16,
+    ) -> core::result::Result<Instruction, DecodeError> {
+        if !self.decode_cache {
+            return Instruction::decode(raw);
+        }
+        self.decoded.get(addr, raw)
+    }
+
+    pub fn execute_instruction(&mut self, inst: u16) -> Chip8Message {
+        match Instruction::decode(inst) {
+            Ok(inst) => self.execute(inst),
+            Err(error) => self.reject(error),
+        }
+    }
+
+    /// Handles a word that did not decode, as [`on_unknown`](Cpu::on_unknown)
+    /// says.
+    pub fn reject(&mut self, error: DecodeError) -> Chip8Message {
+        self.access = None;
+        match self.on_unknown {
+            OnUnknown::Halt => self.fault(Fault::UnknownOpcode {
+                addr: self.pc.wrapping_sub(2),
+                raw: error.raw,
+            }),
+            OnUnknown::Skip => Chip8Message::None,
+        }
+    }
+
+    pub fn execute(&mut self, inst: Instruction) -> Chip8Message {
+        use Instruction as I;
+
+        self.access = None;
+        match inst {
+            I::Clear => {
+                self.disp.clear();
+                Chip8Message::ClearScreen
+            }
+            I::Jump { addr } => {
+                self.jump(addr);
+                Chip8Message::None
+            }
+            I::ReturnSub => self.return_sub(),
+            I::GotoSub { addr } => self.goto_sub(addr),
+            I::SkipEqual { x, byte } => {
+                self.skip_equal(x, byte);
+                Chip8Message::None
+            }
+            I::SkipNotEqual { x, byte } => {
+                self.skip_not_equal(x, byte);
+                Chip8Message::None
+            }
+            I::SkipVXEqualVY { x, y } => {
+                self.skip_vx_equal_vy(x, y);
+                Chip8Message::None
+            }
+            I::SkipVXNotEqualVY { x, y } => {
+                self.skip_vx_not_equal_vy(x, y);
+                Chip8Message::None
+            }
+            I::SkipIfKey { x } => {
+                self.skip_if_key(x);
+                Chip8Message::None
+            }
+            I::SkipIfNotKey { x } => {
+                self.skip_if_not_key(x);
+                Chip8Message::None
+            }
+            I::GetKey { x } => {
+                self.get_key(x);
+                Chip8Message::None
+            }
+            I::SetVX { x, byte } => {
+                self.set_vx(x, byte);
+                Chip8Message::None
+            }
+            I::AddVX { x, byte } => {
+                self.add_vx(x, byte);
+                Chip8Message::None
+            }
+            I::SetI { addr } => {
+                self.set_i(addr);
+                Chip8Message::None
+            }
+            I::AddI { x } => {
+                self.add_i(x);
+                Chip8Message::None
+            }
+            I::JumpWithOffset { addr } => {
+                self.jump_with_offset((addr >> 8) as u8, addr);
+                Chip8Message::None
+            }
+            I::Random { x, byte } => {
+                self.random(x, byte);
+                Chip8Message::None
+            }
+            I::FontCharacter { x } => {
+                self.font_character(x);
+                Chip8Message::None
+            }
+            I::Draw { .. } if self.quirks.display_wait && !self.vblank => {
+                // run it again until the frame begins
+                self.pc = self.pc.wrapping_sub(2);
+                Chip8Message::None
+            }
+            I::Draw { x, y, n } => {
+                self.vblank = false;
+                match self.draw(x, y, n) {
+                    Ok(rows) => Chip8Message::DrawScreen(rows),
+                    Err(fault) => self.fault(fault),
+                }
+            }
+            I::SetVXToVY { x, y } => {
+                self.set_vx_to_vy(x, y);
+                Chip8Message::None
+            }
+            I::BinaryOr { x, y } => {
+                self.binary_or(x, y);
+                Chip8Message::None
+            }
+            I::BinaryAnd { x, y } => {
+                self.binary_and(x, y);
+                Chip8Message::None
+            }
+            I::BinaryXor { x, y } => {
+                self.binary_xor(x, y);
+                Chip8Message::None
+            }
+            I::AddVYToVX { x, y } => {
+                self.add_vy_to_vx(x, y);
+                Chip8Message::None
+            }
+            I::SubVYFromVX { x, y } => {
+                self.sub_vy_from_vx(x, y);
+                Chip8Message::None
+            }
+            I::SubVXFromVY { x, y } => {
+                self.sub_vx_from_vy(x, y);
+                Chip8Message::None
+            }
+            I::ShiftRight { x, y } => {
+                self.shift_right(x, y);
+                Chip8Message::None
+            }
+            I::ShiftLeft { x, y } => {
+                self.shift_left(x, y);
+                Chip8Message::None
+            }
+            I::BinaryCodedDecimalConversion { x } => self.binary_coded_decimal_conversion(x),
+            I::SetVXToDT { x } => {
+                self.set_vx_to_dt(x);
+                Chip8Message::None
+            }
+            I::SetDTToVX { x } => {
+                self.set_dt_to_vx(x);
+                Chip8Message::None
+            }
+            I::SetSTToVX { x } => self.set_st_to_vx(x),
+            I::SaveRegisterToMemory { x } => self.save_register_to_memory(x),
+            I::LoadRegisterFromMemory { x } => self.load_register_from_memory(x),
+            I::SaveFlags { x } => {
+                let len = (x as usize + 1).min(FLAGS);
+                self.flags[..len].copy_from_slice(&self.reg[..len]);
+                Chip8Message::None
+            }
+            I::LoadFlags { x } => {
+                let len = (x as usize + 1).min(FLAGS);
+                self.reg[..len].copy_from_slice(&self.flags[..len]);
+                Chip8Message::None
+            }
+        }
+    }
+
+    fn jump(&mut self, nnn: u16) {
+        self.pc = nnn;
+    }
+
+    fn return_sub(&mut self) -> Chip8Message {
+        if self.sp == 0 {
+            return self.fault(Fault::StackUnderflow {
+                addr: self.pc.wrapping_sub(2),
+            });
+        }
+        self.sp -= 1;
+        self.pc = self.stack[self.sp as usize];
+        Chip8Message::None
+    }
+
+    fn goto_sub(&mut self, nnn: u16) -> Chip8Message {
+        if self.sp as usize == self.stack.len() {
+            return self.fault(Fault::StackOverflow {
+                addr: self.pc.wrapping_sub(2),
+            });
+        }
+        self.stack[self.sp as usize] = self.pc;
+        self.sp += 1;
+        self.pc = nnn;
+        Chip8Message::None
+    }
+
+    /// Leaves the program counter on the faulting instruction, so the machine
+    /// stays as it was before it ran.
+    fn fault(&mut self, fault: Fault) -> Chip8Message {
+        self.pc = self.pc.wrapping_sub(2);
+        Chip8Message::Halted(fault)
+    }
+
+    fn skip_equal(&mut self, x: u8, nn: u8) {
+        if self.reg[x as usize] == nn {
+            self.pc = self.pc.wrapping_add(2);
+        }
+    }
+
+    fn skip_not_equal(&mut self, x: u8, nn: u8) {
+        if self.reg[x as usize] != nn {
+            self.pc = self.pc.wrapping_add(2);
+        }
+    }
+
+    fn skip_vx_equal_vy(&mut self, x: u8, y: u8) {
+        if self.reg[x as usize] == self.reg[y as usize] {
+            self.pc = self.pc.wrapping_add(2);
+        }
+    }
+
+    fn skip_vx_not_equal_vy(&mut self, x: u8, y: u8) {
+        if self.reg[x as usize] != self.reg[y as usize] {
+            self.pc = self.pc.wrapping_add(2);
+        }
+    }
+
+    fn skip_if_key(&mut self, x: u8) {
+        if self.keypad.is_down(self.reg[x as usize]) {
+            self.pc = self.pc.wrapping_add(2);
+        }
+        self.keypad.read();
+    }
+
+    fn skip_if_not_key(&mut self, x: u8) {
+        if !self.keypad.is_down(self.reg[x as usize]) {
+            self.pc = self.pc.wrapping_add(2);
+        }
+        self.keypad.read();
+    }
+
+    fn get_key(&mut self, x: u8) {
+        if let Some(k) = self.keypad.take_press() {
+            self.reg[x as usize] = k;
+        } else {
+            self.pc = self.pc.wrapping_sub(2);
+        }
+        self.keypad.read();
+    }
+
+    fn set_vx(&mut self, x: u8, nn: u8) {
+        self.reg[x as usize] = nn;
+    }
+
+    fn add_vx(&mut self, x: u8, nn: u8) {
+        self.reg[x as usize] = self.reg[x as usize].wrapping_add(nn);
+    }
+
+    fn set_i(&mut self, nnn: u16) {
+        self.index = nnn;
+    }
+
+    fn add_i(&mut self, x: u8) {
+        self.index = self.index.wrapping_add(self.reg[x as usize] as u16);
+        if self.index >= 0x1000 {
+            self.reg[0xF] = 1;
+        }
+    }
+
+    fn jump_with_offset(&mut self, x: u8, nnn: u16) {
+        let offset = if self.quirks.jump_uses_vx {
+            self.reg[x as usize]
+        } else {
+            self.reg[0]
+        };
+        self.pc = nnn + offset as u16;
+    }
+
+    fn random(&mut self, x: u8, nn: u8) {
+        let r = self.rng.next_u8();
+        self.reg[x as usize] = r & nn;
+    }
+
+    fn font_character(&mut self, x: u8) {
+        let c = self.reg[x as usize];
+        self.index = c as u16 * 5 + 0x50;
+    }
+
+    /// Draws the sprite, returning the rows it touched.
+    ///
+    /// Each sprite row is shifted into place as a 64-bit mask and XORed into the
+    /// display row. Pixels past the right edge fall off and rows past the
+    /// bottom are skipped, unless the `draw_wraps` quirk rotates them around to
+    /// the other side. VF is set if any lit pixel was turned off. A sprite
+    /// that runs past the end of memory is a fault, and nothing is drawn.
+    fn draw(&mut self, x: u8, y: u8, n: u8) -> core::result::Result<DirtyRows, Fault> {
+        if n > 0 {
+            self.read_byte(self.index as usize + n as usize - 1)?;
+            self.accessed(self.index as usize, n as usize, false);
+        }
+        let x_coord = (self.reg[x as usize] as usize % Display::WIDTH) as u32;
+        let y_coord = self.reg[y as usize] as usize % Display::HEIGHT;
+        let wraps = self.quirks.draw_wraps;
+        let mut dirty = DirtyRows::NONE;
+        let mut collision = false;
+        for i in 0..n as usize {
+            let mut row = y_coord + i;
+            if row >= Display::HEIGHT {
+                if !wraps {
+                    break;
+                }
+                row %= Display::HEIGHT;
+            }
+            let sprite_data = self.mem[self.index as usize + i];
+            let sprite_row = u64::from(sprite_data) << 56;
+            let bits = if wraps {
+                sprite_row.rotate_right(x_coord)
+            } else {
+                sprite_row >> x_coord
+            };
+            if bits != 0 {
+                collision |= self.disp.xor_row(row, bits);
+                dirty.insert(row);
+            }
+        }
+        self.reg[0xF] = collision as u8;
+        Ok(dirty)
+    }
+
+    fn set_vx_to_vy(&mut self, x: u8, y: u8) {
+        self.reg[x as usize] = self.reg[y as usize];
+    }
+
+    fn binary_or(&mut self, x: u8, y: u8) {
+        self.reg[x as usize] |= self.reg[y as usize];
+        self.logic_flag();
+    }
+
+    fn binary_and(&mut self, x: u8, y: u8) {
+        self.reg[x as usize] &= self.reg[y as usize];
+        self.logic_flag();
+    }
+
+    fn binary_xor(&mut self, x: u8, y: u8) {
+        self.reg[x as usize] ^= self.reg[y as usize];
+        self.logic_flag();
+    }
+
+    fn logic_flag(&mut self) {
+        if self.quirks.logic_resets_vf {
+            self.reg[0xF] = 0;
+        }
+    }
+
+    /// The value 8XY6 and 8XYE shift.
+    fn shift_source(&self, x: u8, y: u8) -> u8 {
+        if self.quirks.shift_uses_vy {
+            self.reg[y as usize]
+        } else {
+            self.reg[x as usize]
+        }
+    }
+
+    // The arithmetic instructions write VF after the result, so the flag wins
+    // when VF is also the destination.
+
+    fn add_vy_to_vx(&mut self, x: u8, y: u8) {
+        let (sum, carry) = self.reg[x as usize].overflowing_add(self.reg[y as usize]);
+        self.reg[x as usize] = sum;
+        self.reg[0xF] = carry as u8;
+    }
+
+    fn sub_vy_from_vx(&mut self, x: u8, y: u8) {
+        let (diff, borrow) = self.reg[x as usize].overflowing_sub(self.reg[y as usize]);
+        self.reg[x as usize] = diff;
+        self.reg[0xF] = !borrow as u8;
+    }
+
+    fn sub_vx_from_vy(&mut self, x: u8, y: u8) {
+        let (diff, borrow) = self.reg[y as usize].overflowing_sub(self.reg[x as usize]);
+        self.reg[x as usize] = diff;
+        self.reg[0xF] = !borrow as u8;
+    }
+
+    fn shift_right(&mut self, x: u8, y: u8) {
+        let value = self.shift_source(x, y);
+        self.reg[x as usize] = value >> 1;
+        self.reg[0xF] = value & 0b0000_0001;
+    }
+
+    fn shift_left(&mut self, x: u8, y: u8) {
+        let value = self.shift_source(x, y);
+        self.reg[x as usize] = value << 1;
+        self.reg[0xF] = value >> 7;
+    }
+
+    fn binary_coded_decimal_conversion(&mut self, x: u8) -> Chip8Message {
+        let n = self.reg[x as usize];
+        match self.store(self.index as usize, &[n / 100, n / 10 % 10, n % 10]) {
+            Ok(()) => Chip8Message::None,
+            Err(fault) => self.fault(fault),
+        }
+    }
+
+    /// Copies `bytes` into memory at `addr`, or faults having copied nothing.
+    fn store(&mut self, addr: usize, bytes: &[u8]) -> core::result::Result<(), Fault> {
+        if let Some((last, _)) = bytes.split_last() {
+            self.write_byte(addr + bytes.len() - 1, *last)?;
+            self.mem[addr..addr + bytes.len()].copy_from_slice(bytes);
+            self.accessed(addr, bytes.len(), true);
+        }
+        Ok(())
+    }
+
+    fn set_vx_to_dt(&mut self, x: u8) {
+        self.reg[x as usize] = self.dt;
+    }
+
+    fn set_dt_to_vx(&mut self, x: u8) {
+        self.dt = self.reg[x as usize];
+    }
+
+    fn set_st_to_vx(&mut self, x: u8) -> Chip8Message {
+        let beeping = self.st > 0;
+        self.st = self.reg[x as usize];
+        match (beeping, self.st > 0) {
+            (false, true) => Chip8Message::Beep(true),
+            (true, false) => Chip8Message::Beep(false),
+            _ => Chip8Message::None,
+        }
+    }
+
+    fn save_register_to_memory(&mut self, x: u8) -> Chip8Message {
+        let reg = self.reg;
+        if let Err(fault) = self.store(self.index as usize, &reg[..=x as usize]) {
+            return self.fault(fault);
+        }
+        if self.quirks.load_store_increments_i {
+            self.index += u16::from(x) + 1;
+        }
+        Chip8Message::None
+    }
+
+    fn load_register_from_memory(&mut self, x: u8) -> Chip8Message {
+        let (start, len) = (self.index as usize, x as usize + 1);
+        if let Err(fault) = self.read_byte(start + len - 1) {
+            return self.fault(fault);
+        }
+        self.reg[..len].copy_from_slice(&self.mem[start..start + len]);
+        self.accessed(start, len, false);
+        if self.quirks.load_store_increments_i {
+            self.index += u16::from(x) + 1;
+        }
+        Chip8Message::None
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_jump() {
+        let mut cpu = Cpu::new();
+        cpu.execute_instruction(0x1234);
+        assert_eq!(cpu.pc, 0x234);
+    }
+
+    #[test]
+    fn test_return_sub() {
+        let mut cpu = Cpu::new();
+        cpu.stack[0] = 0x222;
+        cpu.sp = 1;
+        cpu.execute_instruction(0x00EE);
+        assert_eq!(cpu.pc, 0x222);
+        assert_eq!(cpu.stack(), &[] as &[u16]);
+    }
+
+    #[test]
+    fn test_goto_sub() {
+        let mut cpu = Cpu::new();
+        cpu.pc = 1;
+        cpu.execute_instruction(0x2123);
+        assert_eq!(cpu.pc, 0x123);
+        assert_eq!(cpu.stack(), &[1]);
+    }
+
+    #[test]
+    fn test_subroutines_at_address_zero() {
+        let mut cpu = Cpu::new();
+        // 2000 from 0x200, 2300 from 0x000, then return twice
+        cpu.mem[0x000..0x002].copy_from_slice(&[0x23, 0x00]);
+        cpu.mem[0x200..0x202].copy_from_slice(&[0x20, 0x00]);
+        cpu.mem[0x300..0x302].copy_from_slice(&[0x00, 0xEE]);
+        cpu.mem[0x002..0x004].copy_from_slice(&[0x00, 0xEE]);
+        for _ in 0..4 {
+            let inst = cpu.fetch_next().unwrap();
+            cpu.execute_instruction(inst);
+        }
+        assert_eq!(cpu.pc, 0x202);
+        assert_eq!(cpu.stack(), &[] as &[u16]);
+    }
+
+    #[test]
+    fn test_stack_faults() {
+        let mut cpu = Cpu::new();
+        cpu.mem[0x200..0x202].copy_from_slice(&[0x00, 0xEE]);
+        let inst = cpu.fetch_next().unwrap();
+        assert!(matches!(
+            cpu.execute_instruction(inst),
+            Chip8Message::Halted(Fault::StackUnderflow { addr: 0x200 })
+        ));
+        assert_eq!(cpu.pc, 0x200);
+
+        // 2200: call itself forever
+        cpu.mem[0x200..0x202].copy_from_slice(&[0x22, 0x00]);
+        for _ in 0..16 {
+            let inst = cpu.fetch_next().unwrap();
+            assert!(matches!(cpu.execute_instruction(inst), Chip8Message::None));
+        }
+        let inst = cpu.fetch_next().unwrap();
+        assert!(matches!(
+            cpu.execute_instruction(inst),
+            Chip8Message::Halted(Fault::StackOverflow { addr: 0x200 })
+        ));
+        assert_eq!(cpu.pc, 0x200);
+        assert_eq!(cpu.stack(), &[0x202; 16]);
+    }
+
+    #[test]
+    fn test_skip_equal() {
+        let mut cpu = Cpu::new();
+        cpu.reg[0] = 0x01;
+        cpu.execute_instruction(0x3001);
+        assert_eq!(cpu.pc, 0x202);
+    }
+
+    #[test]
+    fn test_skip_not_equal() {
+        let mut cpu = Cpu::new();
+        cpu.reg[0] = 0x02;
+        cpu.execute_instruction(0x4001);
+        assert_eq!(cpu.pc, 0x202);
+    }
+
+    #[test]
+    fn test_skip_vx_equal_vy() {
+        let mut cpu = Cpu::new();
+        cpu.reg[0] = 1;
+        cpu.reg[1] = 1;
+        cpu.execute_instruction(0x5010);
+        assert_eq!(cpu.pc, 0x202);
+    }
+
+    #[test]
+    fn test_set_vx() {
+        let mut cpu = Cpu::new();
+        cpu.execute_instruction(0x6012);
+        assert_eq!(cpu.reg[0], 0x12);
+    }
+
+    #[test]
+    fn test_add_without_carry() {
+        let mut cpu = Cpu::new();
+        cpu.reg[1] = 0x1;
+        cpu.execute_instruction(0x7112);
+        assert_eq!(cpu.reg[1], 0x13);
+        assert_eq!(cpu.reg[0xF], 0x0);
+    }
+
+    #[test]
+    fn test_set_vx_to_vy() {
+        let mut cpu = Cpu::new();
+        cpu.reg[0] = 1;
+        cpu.reg[1] = 2;
+        cpu.execute_instruction(0x8010);
+        assert_eq!(cpu.reg[0], 2);
+    }
+
+    #[test]
+    fn test_binary_or() {
+        let mut cpu = Cpu::new();
+        cpu.reg[0] = 0b010;
+        cpu.reg[1] = 0b110;
+        cpu.execute_instruction(0x8011);
+        assert_eq!(cpu.reg[0], 0b110);
+    }
+
+    #[test]
+    fn test_binary_and() {
+        let mut cpu = Cpu::new();
+        cpu.reg[0] = 0b011;
+        cpu.reg[1] = 0b110;
+        cpu.execute_instruction(0x8012);
+        assert_eq!(cpu.reg[0], 0b010);
+    }
+
+    #[test]
+    fn test_binary_xor() {
+        let mut cpu = Cpu::new();
+        cpu.reg[0] = 0b011;
+        cpu.reg[1] = 0b111;
+        cpu.execute_instruction(0x8013);
+        assert_eq!(cpu.reg[0], 0b100);
+    }
+
+    #[test]
+    fn test_add_with_carry() {
+        let mut cpu = Cpu::new();
+        cpu.reg[0] = 128;
+        cpu.reg[1] = 128;
+        cpu.execute_instruction(0x8014);
+        assert_eq!(cpu.reg[0], 0);
+        assert_eq!(cpu.reg[0xF], 1);
+
+        let mut cpu = Cpu::new();
+        cpu.reg[0] = 2;
+        cpu.reg[1] = 3;
+        cpu.execute_instruction(0x8014);
+        assert_eq!(cpu.reg[0], 5);
+        assert_eq!(cpu.reg[0xF], 0);
+    }
+
+    #[test]
+    fn test_sub_vy_from_vx() {
+        let mut cpu = Cpu::new();
+        cpu.reg[0] = 4;
+        cpu.reg[1] = 2;
+        cpu.execute_instruction(0x8015);
+        assert_eq!(cpu.reg[0], 2);
+        assert_eq!(cpu.reg[0xF], 1);
+
+        let mut cpu = Cpu::new();
+        cpu.reg[0] = 2;
+        cpu.reg[1] = 4;
+        cpu.execute_instruction(0x8015);
+        assert_eq!(cpu.reg[0], 254);
+        assert_eq!(cpu.reg[0xF], 0);
+    }
+
+    #[test]
+    fn test_shift_right() {
+        let mut cpu = Cpu::new();
+        cpu.reg[0] = 0xFF;
+        cpu.execute_instruction(0x8016);
+        assert_eq!(cpu.reg[0], 0x7F);
+        assert_eq!(cpu.reg[0xF], 1);
+    }
+
+    #[test]
+    fn test_shift_left() {
+        let mut cpu = Cpu::new();
+        cpu.reg[0] = 0xFF;
+        cpu.execute_instruction(0x801E);
+        assert_eq!(cpu.reg[0], 0xFE);
+        assert_eq!(cpu.reg[0xF], 1);
+    }
+
+    #[test]
+    fn test_skip_vx_not_equal_vy() {
+        let mut cpu = Cpu::new();
+        cpu.reg[0] = 1;
+        cpu.execute_instruction(0x9010);
+        assert_eq!(cpu.pc, 0x202);
+        cpu.reg[1] = 1;
+        cpu.execute_instruction(0x9010);
+        assert_eq!(cpu.pc, 0x202);
+    }
+
+    #[test]
+    fn test_set_index() {
+        let mut cpu = Cpu::new();
+        cpu.execute_instruction(0xA123);
+        assert_eq!(cpu.index, 0x123);
+    }
+
+    #[test]
+    fn test_jump_with_offset() {
+        let mut cpu = Cpu::new();
+        cpu.reg[0] = 1;
+        cpu.execute_instruction(0xB123);
+        assert_eq!(cpu.pc, 0x124)
+    }
+
+    #[test]
+    fn test_random_with_injected_rng() {
+        let mut a = Cpu::with_rng(Rng::new(1234));
+        let mut b = Cpu::with_rng(Rng::new(1234));
+        a.execute_instruction(0xC00F);
+        b.execute_instruction(0xC00F);
+        assert_eq!(a.reg[0], b.reg[0]);
+        assert_eq!(a.reg[0] & 0xF0, 0);
+    }
+
+    #[test]
+    fn test_draw() {
+        let mut cpu = Cpu::new();
+        cpu.mem[0x300] = 0b1010_0000;
+        cpu.index = 0x300;
+        cpu.reg[1] = 4;
+        let Chip8Message::DrawScreen(rows) = cpu.execute_instruction(0xD012) else {
+            panic!("expected a draw");
+        };
+        assert!(cpu.disp.pixel(0, 4));
+        assert!(!cpu.disp.pixel(1, 4));
+        assert!(cpu.disp.pixel(2, 4));
+        assert_eq!(cpu.reg[0xF], 0);
+        // the second sprite row is blank, so only the first changed
+        assert!(rows.iter().eq([4]));
+    }
+
+    #[test]
+    fn test_bcd() {
+        let mut cpu = Cpu::new();
+        cpu.reg[2] = 255;
+        cpu.index = 0x300;
+        cpu.execute_instruction(0xF233);
+        assert_eq!(&cpu.mem[0x300..0x303], &[2, 5, 5]);
+        cpu.reg[2] = 7;
+        cpu.execute_instruction(0xF233);
+        assert_eq!(&cpu.mem[0x300..0x303], &[0, 0, 7]);
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_serde_round_trip() {
+        let mut cpu = Cpu::new();
+        cpu.mem[..FONT_SET.len()].copy_from_slice(&FONT_SET);
+        cpu.mem[0xFFF] = 0xAB;
+        cpu.reg[3] = 7;
+        cpu.quirks = Quirks::COSMAC;
+        // A000: I = font "0", D005: draw it
+        cpu.execute_instruction(0xA000);
+        cpu.execute_instruction(0xD005);
+        let json = serde_json::to_string(&cpu).unwrap();
+        let back: Cpu = serde_json::from_str(&json).unwrap();
+        assert_eq!(back.mem, cpu.mem);
+        assert_eq!(back.disp, cpu.disp);
+        assert_eq!(back.dump(), cpu.dump());
+        assert_eq!(back.quirks, Quirks::COSMAC);
+        // memory must be whole
+        let short = json.replacen("[0,", "[", 1);
+        assert!(serde_json::from_str::<Cpu>(&short).is_err());
+    }
+
+    #[test]
+    fn test_rpl_flags() {
+        let mut cpu = Cpu::new();
+        cpu.reg = core::array::from_fn(|i| i as u8 + 1);
+        cpu.execute_instruction(0xF275);
+        assert_eq!(cpu.flags, [1, 2, 3, 0, 0, 0, 0, 0]);
+        // past V7 there are no more flags
+        cpu.execute_instruction(0xFF75);
+        assert_eq!(cpu.flags, [1, 2, 3, 4, 5, 6, 7, 8]);
+        cpu.reg = [0; 16];
+        cpu.execute_instruction(0xF185);
+        assert_eq!(cpu.reg[..3], [1, 2, 0]);
+        cpu.execute_instruction(0xFF85);
+        assert_eq!(cpu.reg[7..9], [8, 0]);
+        assert_eq!(cpu.last_access(), None);
+    }
+
+    #[test]
+    fn test_last_access() {
+        let mut cpu = Cpu::new();
+        cpu.index = 0x300;
+        let access = |addr, len, write| Some(Access { addr, len, write });
+        cpu.execute_instruction(0xF233);
+        assert_eq!(cpu.last_access(), access(0x300, 3, true));
+        cpu.execute_instruction(0xF165);
+        assert_eq!(cpu.last_access(), access(0x300, 2, false));
+        cpu.execute_instruction(0xD005);
+        assert_eq!(cpu.last_access(), access(0x300, 5, false));
+        assert!(cpu.last_access().unwrap().overlaps(0x304, 1));
+        assert!(!cpu.last_access().unwrap().overlaps(0x305, 4));
+        cpu.execute_instruction(0x6001);
+        assert_eq!(cpu.last_access(), None);
+        // a fault touches nothing
+        cpu.index = 0xFFF;
+        cpu.execute_instruction(0xF155);
+        assert_eq!(cpu.last_access(), None);
+    }
+
+    #[test]
+    fn test_unknown_opcode_faults() {
+        let mut cpu = Cpu::new();
+        cpu.mem[0x200..0x202].copy_from_slice(&[0xE1, 0xA2]);
+        let inst = cpu.fetch_next().unwrap();
+        assert!(matches!(
+            cpu.execute_instruction(inst),
+            Chip8Message::Halted(Fault::UnknownOpcode {
+                addr: 0x200,
+                raw: 0xE1A2
+            })
+        ));
+        assert_eq!(cpu.pc, 0x200);
+
+        cpu.on_unknown = OnUnknown::Skip;
+        let inst = cpu.fetch_next().unwrap();
+        assert!(matches!(cpu.execute_instruction(inst), Chip8Message::None));
+        assert_eq!(cpu.pc, 0x202);
+    }
+
+    #[test]
+    fn test_memory_faults() {
+        let mut cpu = Cpu::new();
+        cpu.pc = 0x302;
+        cpu.reg[2] = 123;
+        cpu.index = 0xFFE;
+        // FX33, FX55, FX65 and DXYN past the end fault with no effect
+        for inst in [0xF233, 0xF255, 0xF265, 0xD003] {
+            assert!(matches!(
+                cpu.execute_instruction(inst),
+                Chip8Message::Halted(Fault::OutOfBounds {
+                    addr: 0x300,
+                    target: 0x1000..
+                })
+            ));
+            cpu.pc = 0x302;
+        }
+        assert_eq!(&cpu.mem[0xFFE..], &[0, 0]);
+        assert_eq!(cpu.registers()[..3], [0, 0, 123]);
+        assert_eq!(cpu.index, 0xFFE);
+        // up to the last byte is fine
+        assert!(matches!(
+            cpu.execute_instruction(0xF155),
+            Chip8Message::None
+        ));
+        assert_eq!(cpu.read_word(0xFFE), Ok(0));
+        assert_eq!(
+            cpu.read_byte(0x1000),
+            Err(Fault::OutOfBounds {
+                addr: 0x300,
+                target: 0x1000
+            })
+        );
+
+        cpu.pc = 0xFFF;
+        assert_eq!(
+            cpu.fetch_next(),
+            Err(Fault::OutOfBounds {
+                addr: 0xFFF,
+                target: 0x1000
+            })
+        );
+        assert_eq!(cpu.pc(), 0xFFF);
+    }
+
+    #[test]
+    fn test_clear_screen() {
+        let mut cpu = Cpu::new();
+        cpu.disp.set_pixel(3, 3, true);
+        assert!(matches!(
+            cpu.execute_instruction(0x00E0),
+            Chip8Message::ClearScreen
+        ));
+        assert!(!cpu.disp.pixel(3, 3));
+    }
+
+    #[test]
+    fn test_draw_collision_and_clipping() {
+        let mut cpu = Cpu::new();
+        cpu.mem[0x300..0x303].copy_from_slice(&[0xFF, 0x81, 0xFF]);
+        cpu.index = 0x300;
+        // at (60, 30) the sprite is cut at the right edge and after two rows
+        cpu.reg[0] = 60;
+        cpu.reg[1] = 30;
+        cpu.execute_instruction(0xD013);
+        assert_eq!(cpu.disp.iter_set_pixels().count(), 4 + 1);
+        assert_eq!(cpu.reg[0xF], 0);
+        // redrawing erases it and reports the collision, even though a later
+        // row turns nothing off
+        cpu.execute_instruction(0xD013);
+        assert_eq!(cpu.disp.iter_set_pixels().count(), 0);
+        assert_eq!(cpu.reg[0xF], 1);
+        // coordinates wrap before drawing
+        cpu.reg[0] = 64 + 1;
+        cpu.reg[1] = 32;
+        cpu.execute_instruction(0xD011);
+        assert!(cpu.disp.pixel(1, 0) && cpu.disp.pixel(8, 0));
+        // the bottom row is drawn like any other
+        cpu.disp.clear();
+        cpu.reg[0] = 0;
+        cpu.reg[1] = 31;
+        let Chip8Message::DrawScreen(rows) = cpu.execute_instruction(0xD013) else {
+            panic!("expected a draw");
+        };
+        assert!(rows.iter().eq([31]));
+        assert_eq!(cpu.disp.iter_set_pixels().count(), 8);
+    }
+
+    #[test]
+    fn test_quirks() {
+        let mut cpu = Cpu::new();
+        cpu.quirks = Quirks::COSMAC;
+        cpu.reg[1] = 0b1000_0001;
+        cpu.execute_instruction(0x8016);
+        assert_eq!((cpu.reg[0], cpu.reg[0xF]), (0b0100_0000, 1));
+        cpu.reg[0xF] = 7;
+        cpu.execute_instruction(0x8011);
+        assert_eq!(cpu.reg[0xF], 0);
+        cpu.index = 0x300;
+        cpu.execute_instruction(0xF255);
+        assert_eq!(cpu.index, 0x303);
+
+        cpu.quirks = Quirks::CHIP48;
+        cpu.reg[2] = 4;
+        cpu.execute_instruction(0xB230);
+        assert_eq!(cpu.pc, 0x234);
+
+        cpu.quirks.draw_wraps = true;
+        cpu.mem[0x300..0x302].copy_from_slice(&[0xFF, 0xFF]);
+        cpu.index = 0x300;
+        cpu.reg[0] = 60;
+        cpu.reg[1] = 31;
+        cpu.disp.clear();
+        cpu.execute_instruction(0xD012);
+        assert!(cpu.disp.pixel(63, 31) && cpu.disp.pixel(3, 31));
+        assert!(cpu.disp.pixel(60, 0) && cpu.disp.pixel(0, 0));
+        assert_eq!(cpu.disp.iter_set_pixels().count(), 16);
+        // a collision only in the wrapped-around part still sets VF
+        cpu.disp.clear();
+        cpu.disp.set_pixel(1, 0, true);
+        cpu.execute_instruction(0xD012);
+        assert_eq!(cpu.reg[0xF], 1);
+        assert!(!cpu.disp.pixel(1, 0));
+    }
+
+    #[test]
+    fn test_decode_follows_memory() {
+        for cached in [true, false] {
+            let mut cpu = Cpu::new();
+            cpu.decode_cache = cached;
+            let set = Instruction::SetVX { x: 0xA, byte: 0x07 };
+            assert_eq!(cpu.decode(0x200, 0x6A07), Ok(set));
+            // rewritten in place, the next decode sees the new instruction
+            assert_eq!(
+                cpu.decode(0x200, 0x1200),
+                Ok(Instruction::Jump { addr: 0x200 })
+            );
+            assert_eq!(cpu.decode(0x200, 0x5AB1), Err(DecodeError { raw: 0x5AB1 }));
+        }
+    }
+
+    #[test]
+    fn test_unused_encodings_fault() {
+        for raw in [0x0000, 0x0123, 0x5AB1, 0x9ABF] {
+            let mut cpu = Cpu::new();
+            cpu.reg[0xA] = 1;
+            cpu.pc = 0x202;
+            assert!(matches!(
+                cpu.execute_instruction(raw),
+                Chip8Message::Halted(Fault::UnknownOpcode { addr: 0x200, raw: r }) if r == raw
+            ));
+        }
+    }
+
+    #[test]
+    fn test_key_press_is_consumed() {
+        let mut cpu = Cpu::new();
+        cpu.reg[0] = 0xA;
+        cpu.press_key(0xA);
+        cpu.execute_instruction(0xE09E);
+        assert_eq!(cpu.pc, 0x202);
+        cpu.execute_instruction(0xE09E);
+        assert_eq!(cpu.pc, 0x202);
+    }
+
+    #[test]
+    fn test_held_key_stays_down() {
+        let mut cpu = Cpu::new();
+        cpu.reg[0] = 0xA;
+        cpu.keypad.press(0xA);
+        cpu.execute_instruction(0xE0A1);
+        cpu.execute_instruction(0xE09E);
+        assert_eq!(cpu.pc, 0x202);
+        cpu.keypad.release(0xA);
+        cpu.execute_instruction(0xE0A1);
+        assert_eq!(cpu.pc, 0x204);
+    }
+
+    #[test]
+    fn test_get_key_waits() {
+        let mut cpu = Cpu::new();
+        cpu.pc = 0x202;
+        cpu.execute_instruction(0xF30A);
+        assert_eq!(cpu.pc, 0x200);
+        cpu.press_key(7);
+        cpu.execute_instruction(0xF30A);
+        assert_eq!(cpu.reg[3], 7);
+    }
+
+    #[test]
+    fn test_execute_every_word() {
+        let mut cpu = Cpu::with_rng(Rng::new(1));
+        for raw in 0..=u16::MAX {
+            if Instruction::decode(raw).is_err() {
+                continue;
+            }
+            // keep I, PC and the stack where every instruction stays in bounds
+            cpu.pc = START;
+            cpu.index = 0x300;
+            cpu.stack = [START; 16];
+            cpu.sp = 8;
+            cpu.reg = [0xFF; 16];
+            cpu.keypad.tap(raw as u8);
+            cpu.execute_instruction(raw);
+        }
+    }
+
+    fn alu(op: u16, a: u8, b: u8) -> Cpu {
+        alu_with(Quirks::default(), op, a, b)
+    }
+
+    fn alu_with(quirks: Quirks, op: u16, a: u8, b: u8) -> Cpu {
+        let mut cpu = Cpu::with_rng(Rng::new(1));
+        cpu.quirks = quirks;
+        cpu.reg[1] = a;
+        cpu.reg[2] = b;
+        cpu.execute_instruction(0x8120 | op);
+        cpu
+    }
+
+    /// Every combination of quirks.
+    fn quirks() -> impl proptest::strategy::Strategy<Value = Quirks> {
+        use proptest::strategy::Strategy;
+        proptest::prelude::any::<[bool; 6]>().prop_map(|q| Quirks {
+            shift_uses_vy: q[0],
+            jump_uses_vx: q[1],
+            load_store_increments_i: q[2],
+            logic_resets_vf: q[3],
+            draw_wraps: q[4],
+            display_wait: q[5],
+        })
+    }
+
+    proptest::proptest! {
+        /// No instruction panics, whatever the machine holds; the fuzz
+        /// targets in `fuzz/` search the same space harder.
+        #[test]
+        fn test_prop_nothing_panics(
+            insts in proptest::collection::vec(proptest::prelude::any::<u16>(), 1..32),
+            image in proptest::collection::vec(proptest::prelude::any::<u8>(), 0..64),
+            reg: [u8; 16],
+            pc: u16,
+            index: u16,
+            key in 0u8..16,
+            quirks in quirks(),
+            skip: bool,
+        ) {
+            let mut cpu = Cpu::with_rng(Rng::new(1));
+            cpu.quirks = quirks;
+            cpu.on_unknown = if skip { OnUnknown::Skip } else { OnUnknown::Halt };
+            let at = usize::from(index).min(cpu.mem.len() - image.len());
+            cpu.mem[at..at + image.len()].copy_from_slice(&image);
+            cpu.reg = reg;
+            cpu.pc = pc;
+            cpu.index = index;
+            cpu.keypad.tap(key);
+            for inst in insts {
+                cpu.execute_instruction(inst);
+            }
+        }
+
+        #[test]
+        fn test_prop_add_carries(a: u8, b: u8) {
+            let cpu = alu(0x4, a, b);
+            proptest::prop_assert_eq!(cpu.reg[1], a.wrapping_add(b));
+            proptest::prop_assert_eq!(cpu.reg[0xF], (a as u16 + b as u16 > 0xFF) as u8);
+        }
+
+        #[test]
+        fn test_prop_sub_borrows(a: u8, b: u8) {
+            // VF is 1 when there is no borrow, including when the operands are equal
+            let cpu = alu(0x5, a, b);
+            proptest::prop_assert_eq!(cpu.reg[1], a.wrapping_sub(b));
+            proptest::prop_assert_eq!(cpu.reg[0xF], (a >= b) as u8);
+            let cpu = alu(0x7, a, b);
+            proptest::prop_assert_eq!(cpu.reg[1], b.wrapping_sub(a));
+            proptest::prop_assert_eq!(cpu.reg[0xF], (b >= a) as u8);
+        }
+
+        #[test]
+        fn test_prop_shifts_set_shifted_out_bit(a: u8, b: u8, quirks in quirks()) {
+            let source = if quirks.shift_uses_vy { b } else { a };
+            let cpu = alu_with(quirks, 0x6, a, b);
+            proptest::prop_assert_eq!(cpu.reg[1], source >> 1);
+            proptest::prop_assert_eq!(cpu.reg[0xF], source & 1);
+            let cpu = alu_with(quirks, 0xE, a, b);
+            proptest::prop_assert_eq!(cpu.reg[1], source << 1);
+            proptest::prop_assert_eq!(cpu.reg[0xF], source >> 7);
+        }
+
+        #[test]
+        fn test_prop_logic_flag(a: u8, b: u8, vf: u8, quirks in quirks()) {
+            for (op, expected) in [(0x1, a | b), (0x2, a & b), (0x3, a ^ b)] {
+                let mut cpu = Cpu::with_rng(Rng::new(1));
+                cpu.quirks = quirks;
+                cpu.reg[1] = a;
+                cpu.reg[2] = b;
+                cpu.reg[0xF] = vf;
+                cpu.execute_instruction(0x8120 | op);
+                proptest::prop_assert_eq!(cpu.reg[1], expected);
+                proptest::prop_assert_eq!(cpu.reg[0xF], if quirks.logic_resets_vf { 0 } else { vf });
+            }
+        }
+
+        #[test]
+        fn test_prop_flag_wins_when_vf_is_the_target(a: u8, b: u8, op in proptest::sample::select(&[0x4u16, 0x5, 0x6, 0x7, 0xE][..])) {
+            let mut cpu = Cpu::with_rng(Rng::new(1));
+            cpu.reg[0xF] = a;
+            cpu.reg[2] = b;
+            cpu.execute_instruction(0x8F20 | op);
+            let expected = alu(op, a, b).reg[0xF];
+            proptest::prop_assert_eq!(cpu.reg[0xF], expected);
+        }
+
+        #[test]
+        fn test_prop_add_immediate_leaves_vf(a: u8, nn: u8, vf: u8) {
+            let mut cpu = Cpu::with_rng(Rng::new(1));
+            cpu.reg[1] = a;
+            cpu.reg[0xF] = vf;
+            cpu.execute_instruction(0x7100 | nn as u16);
+            proptest::prop_assert_eq!(cpu.reg[1], a.wrapping_add(nn));
+            proptest::prop_assert_eq!(cpu.reg[0xF], vf);
+        }
+
+        #[test]
+        fn test_prop_bcd(n: u8, index in 0x200u16..0xFFD) {
+            let mut cpu = Cpu::with_rng(Rng::new(1));
+            cpu.reg[3] = n;
+            cpu.index = index;
+            cpu.execute_instruction(0xF333);
+            let i = index as usize;
+            proptest::prop_assert_eq!(&cpu.mem[i..i + 3], &[n / 100, n / 10 % 10, n % 10]);
+        }
+
+        #[test]
+        fn test_prop_store_load_round_trip(reg: [u8; 16], x in 0u16..16, index in 0x200u16..0xFE0, quirks in quirks()) {
+            let mut cpu = Cpu::with_rng(Rng::new(1));
+            cpu.quirks = quirks;
+            let step = if quirks.load_store_increments_i { x + 1 } else { 0 };
+            cpu.reg = reg;
+            cpu.index = index;
+            cpu.execute_instruction(0xF055 | x << 8);
+            let i = index as usize;
+            proptest::prop_assert_eq!(&cpu.mem[i..=i + x as usize], &reg[..=x as usize]);
+            proptest::prop_assert_eq!(cpu.mem[i + x as usize + 1], 0);
+
+            proptest::prop_assert_eq!(cpu.index, index + step);
+
+            cpu.reg = [0; 16];
+            cpu.index = index;
+            cpu.execute_instruction(0xF065 | x << 8);
+            proptest::prop_assert_eq!(&cpu.reg[..=x as usize], &reg[..=x as usize]);
+            proptest::prop_assert!(cpu.reg[x as usize + 1..].iter().all(|r| *r == 0));
+            proptest::prop_assert_eq!(cpu.index, index + step);
+        }
+    }
+}
@@ -0,0 +1,378 @@
+//! Differential testing: one ROM run on two machines in lockstep, with the
+//! first frame where their states differ reported in full.
+//!
+//! Either side can be anything implementing [`Machine`]: this crate with some
+//! [`Engine`], the independent [`Reference`](crate::reference::Reference)
+//! interpreter behind the `reference` feature, or an adapter around another
+//! emulator core.
+//! [`run_stepwise`] compares after every instruction rather than every frame,
+//! to find the instruction where a misbehaving program first goes wrong.
+//!
+//! ```
+//! # #[cfg(feature = "reference")] {
+//! use chippers_core::chip::Interpreter;
+//! use chippers_core::differential::{run_differential, Lockstep};
+//! use chippers_core::reference::Reference;
+//!
+//! // 6007: V0 = 7, 1202: loop forever
+//! let rom = [0x60, 0x07, 0x12, 0x02];
+//! let mut reference = Reference::new();
+//! let mut core = Lockstep::new(Interpreter);
+//! assert_eq!(run_differential(&rom, &[], 60, 12, &mut reference, &mut core), None);
+//! # }
+//! ```
+
+use crate::chip::{Chip8, Chip8Message, Engine};
+use crate::cpu::Cpu;
+use crate::display::PackedFrame;
+use crate::oracle::KeyPress;
+use crate::rng::Rng;
+
+/// An emulator that can be driven frame by frame and inspected.
+pub trait Machine {
+    /// Loads `rom` at 0x200, with the font set, into a freshly reset machine.
+    fn load(&mut self, rom: &[u8]);
+    fn press_key(&mut self, key: u8);
+    /// Runs one instruction.
+    fn step(&mut self);
+    /// Ticks the delay and sound timers, ending a 60 Hz frame.
+    fn tick_timers(&mut self);
+    /// Runs `instructions` instructions, then ticks the timers once.
+    fn run_frame(&mut self, instructions: u32) {
+        for _ in 0..instructions {
+            self.step();
+        }
+        self.tick_timers();
+    }
+    fn snapshot(&self) -> Snapshot;
+}
+
+/// The state compared after every frame.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Snapshot {
+    pub pc: u16,
+    pub index: u16,
+    pub registers: [u8; 16],
+    pub dt: u8,
+    pub st: u8,
+    pub frame: PackedFrame,
+}
+
+impl Snapshot {
+    pub fn of(chip8: &Chip8) -> Self {
+        let cpu = &chip8.cpu;
+        Snapshot {
+            pc: cpu.pc(),
+            index: cpu.index(),
+            registers: *cpu.registers(),
+            dt: cpu.dt,
+            st: cpu.st,
+            frame: chip8.framebuffer(),
+        }
+    }
+
+    /// FNV-1a over every field, stable across runs and platforms.
+    pub fn hash(&self) -> u64 {
+        let mut hash = 0xCBF2_9CE4_8422_2325u64;
+        let fields = [&self.pc.to_be_bytes()[..], &self.index.to_be_bytes()];
+        let timers = [self.dt, self.st];
+        let bytes =
+            fields
+                .into_iter()
+                .chain([&self.registers[..], &timers[..], self.frame.as_1bpp()]);
+        for byte in bytes.flatten() {
+            hash ^= u64::from(*byte);
+            hash = hash.wrapping_mul(0x0000_0100_0000_01B3);
+        }
+        hash
+    }
+}
+
+/// The first frame after which two machines disagreed.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Divergence {
+    /// Frames both machines had run, counting the one that diverged.
+    pub frame: u64,
+    /// The instruction of that frame after which they diverged, counting
+    /// from 1, when compared by [`run_stepwise`]. `None` if they were
+    /// compared a frame at a time, or diverged as the timers ticked.
+    pub instruction: Option<u32>,
+    pub expected: Snapshot,
+    pub actual: Snapshot,
+}
+
+impl core::fmt::Display for Divergence {
+    /// Lists only what differs; the screen as text art, row by row.
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+        let (e, a) = (&self.expected, &self.actual);
+        match self.instruction {
+            Some(n) => writeln!(
+                f,
+                "machines diverged after instruction {} of frame {}:",
+                n, self.frame
+            )?,
+            None => writeln!(f, "machines diverged after frame {}:", self.frame)?,
+        }
+        if e.pc != a.pc {
+            writeln!(f, "  pc: expected {:#05x}, got {:#05x}", e.pc, a.pc)?;
+        }
+        if e.index != a.index {
+            writeln!(f, "  i: expected {:#05x}, got {:#05x}", e.index, a.index)?;
+        }
+        for (v, (e, a)) in e.registers.iter().zip(&a.registers).enumerate() {
+            if e != a {
+                writeln!(f, "  v{:x}: expected {:#04x}, got {:#04x}", v, e, a)?;
+            }
+        }
+        if (e.dt, e.st) != (a.dt, a.st) {
+            writeln!(
+                f,
+                "  dt/st: expected {}/{}, got {}/{}",
+                e.dt, e.st, a.dt, a.st
+            )?;
+        }
+        let width = e.frame.width / 8;
+        let rows = e.frame.as_1bpp().chunks(width);
+        for (y, (e, a)) in rows.zip(a.frame.as_1bpp().chunks(width)).enumerate() {
+            if e != a {
+                writeln!(f, "  row {:2}: expected {}", y, Art(e))?;
+                writeln!(f, "          got      {}", Art(a))?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// One packed row as `#` and `.`.
+struct Art<'a>(&'a [u8]);
+
+impl core::fmt::Display for Art<'_> {
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+        for byte in self.0 {
+            for bit in (0..8).rev() {
+                f.write_str(if byte >> bit & 1 == 1 { "#" } else { "." })?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// This crate's machine run by `engine`, as one side of a comparison.
+///
+/// The random number generator starts from [`Rng::DEFAULT_SEED`] on every
+/// load, like the runs in [`oracle`](crate::oracle).
+#[derive(Debug)]
+pub struct Lockstep<E> {
+    pub chip8: Chip8,
+    pub engine: E,
+}
+
+impl<E: Engine> Lockstep<E> {
+    pub fn new(engine: E) -> Self {
+        Lockstep {
+            chip8: Chip8::with_cpu(Cpu::with_rng(Rng::new(Rng::DEFAULT_SEED))),
+            engine,
+        }
+    }
+}
+
+impl<E: Engine> Machine for Lockstep<E> {
+    /// Panics if `rom` is too large to load.
+    fn load(&mut self, rom: &[u8]) {
+        self.chip8 = Chip8::with_cpu(Cpu::with_rng(Rng::new(Rng::DEFAULT_SEED)));
+        self.chip8.load_font_set();
+        if let Err(e) = self.chip8.load_rom(rom) {
+            panic!("{}", e);
+        }
+    }
+
+    fn press_key(&mut self, key: u8) {
+        self.chip8.cpu.press_key(key);
+    }
+
+    /// Engines that run several instructions per step run all of them.
+    fn step(&mut self) {
+        self.engine.step(&mut self.chip8);
+    }
+
+    fn tick_timers(&mut self) {
+        self.chip8.tick_timers();
+    }
+
+    /// Engines that run several instructions per step may overshoot the frame
+    /// by the rest of their last block. A program that faults stays where it
+    /// faulted for the rest of the frame.
+    fn run_frame(&mut self, instructions: u32) {
+        let end = self.chip8.instructions() + u64::from(instructions);
+        while self.chip8.instructions() < end {
+            if let Chip8Message::Halted(_) = self.engine.step(&mut self.chip8) {
+                break;
+            }
+        }
+        self.chip8.tick_timers();
+    }
+
+    fn snapshot(&self) -> Snapshot {
+        Snapshot::of(&self.chip8)
+    }
+}
+
+/// Runs `rom` on `expected` and `actual` for `frames` frames of
+/// `instructions_per_frame` instructions, pressing `keys` (sorted by frame) on
+/// both, and compares state hashes after every frame.
+///
+/// Returns the first divergence, or `None` if the machines agreed throughout.
+pub fn run_differential(
+    rom: &[u8],
+    keys: &[KeyPress],
+    frames: u64,
+    instructions_per_frame: u32,
+    expected: &mut impl Machine,
+    actual: &mut impl Machine,
+) -> Option<Divergence> {
+    expected.load(rom);
+    actual.load(rom);
+    let mut keys = keys.iter().peekable();
+    for frame in 0..frames {
+        while let Some(press) = keys.next_if(|press| press.frame <= frame) {
+            expected.press_key(press.key);
+            actual.press_key(press.key);
+        }
+        expected.run_frame(instructions_per_frame);
+        actual.run_frame(instructions_per_frame);
+        let (e, a) = (expected.snapshot(), actual.snapshot());
+        if e.hash() != a.hash() {
+            return Some(Divergence {
+                frame: frame + 1,
+                instruction: None,
+                expected: e,
+                actual: a,
+            });
+        }
+    }
+    None
+}
+
+/// Like [`run_differential`], but compares the machines after every
+/// instruction and after every tick of the timers, so the divergence names
+/// the instruction that caused it.
+///
+/// Slower by the cost of a snapshot per instruction, so best kept for
+/// narrowing down a divergence [`run_differential`] has found.
+pub fn run_stepwise(
+    rom: &[u8],
+    keys: &[KeyPress],
+    frames: u64,
+    instructions_per_frame: u32,
+    expected: &mut impl Machine,
+    actual: &mut impl Machine,
+) -> Option<Divergence> {
+    expected.load(rom);
+    actual.load(rom);
+    let mut keys = keys.iter().peekable();
+    for frame in 0..frames {
+        while let Some(press) = keys.next_if(|press| press.frame <= frame) {
+            expected.press_key(press.key);
+            actual.press_key(press.key);
+        }
+        for instruction in (1..=instructions_per_frame).map(Some).chain([None]) {
+            match instruction {
+                Some(_) => {
+                    expected.step();
+                    actual.step();
+                }
+                None => {
+                    expected.tick_timers();
+                    actual.tick_timers();
+                }
+            }
+            let (e, a) = (expected.snapshot(), actual.snapshot());
+            if e != a {
+                return Some(Divergence {
+                    frame: frame + 1,
+                    instruction,
+                    expected: e,
+                    actual: a,
+                });
+            }
+        }
+    }
+    None
+}
+
+#[cfg(all(test, feature = "std"))]
+mod test {
+    use super::*;
+    use crate::chip::Interpreter;
+    use std::string::ToString;
+
+    /// The interpreter, except that 7XNN does nothing from the seventh
+    /// instruction on.
+    struct Broken;
+
+    impl Engine for Broken {
+        fn step(&mut self, chip8: &mut Chip8) -> Chip8Message {
+            let pc = chip8.cpu.pc();
+            if chip8.instructions() >= 6 && chip8.cpu.mem[pc as usize] >> 4 == 0x7 {
+                chip8.cpu.set_pc(pc + 2);
+                chip8.retire(1);
+                return Chip8Message::None;
+            }
+            chip8.step()
+        }
+    }
+
+    #[test]
+    fn test_reports_first_divergence() {
+        // 7001: V0 += 1, D005: draw the byte at I, 1200: loop
+        let rom = [0x70, 0x01, 0xD0, 0x05, 0x12, 0x00];
+        let mut expected = Lockstep::new(Interpreter);
+        let mut actual = Lockstep::new(Broken);
+        let divergence = run_differential(&rom, &[], 10, 3, &mut expected, &mut actual).unwrap();
+        assert_eq!(divergence.frame, 3);
+        assert_eq!(divergence.expected.registers[0], 3);
+        assert_eq!(divergence.actual.registers[0], 2);
+        let report = divergence.to_string();
+        assert!(report.contains("v0: expected 0x03, got 0x02"), "{}", report);
+        assert!(!report.contains("pc:"));
+    }
+
+    #[test]
+    fn test_stepwise_names_the_instruction() {
+        let rom = [0x70, 0x01, 0xD0, 0x05, 0x12, 0x00];
+        let mut expected = Lockstep::new(Interpreter);
+        let mut actual = Lockstep::new(Broken);
+        let divergence = run_stepwise(&rom, &[], 10, 3, &mut expected, &mut actual).unwrap();
+        // the seventh instruction is the first of the third frame
+        assert_eq!((divergence.frame, divergence.instruction), (3, Some(1)));
+        let report = divergence.to_string();
+        assert!(
+            report.starts_with("machines diverged after instruction 1 of frame 3:\n"),
+            "{}",
+            report
+        );
+        let mut same = Lockstep::new(Interpreter);
+        assert_eq!(
+            run_stepwise(&rom, &[], 10, 3, &mut expected, &mut same),
+            None
+        );
+    }
+
+    #[test]
+    fn test_fault_ends_the_frame() {
+        // 1FFF: jump to the last byte, where no whole instruction fits
+        let mut machine = Lockstep::new(Interpreter);
+        machine.load(&[0x1F, 0xFF]);
+        machine.run_frame(10);
+        assert_eq!(machine.snapshot().pc, 0xFFF);
+        assert_eq!(machine.chip8.frames(), 1);
+    }
+
+    #[test]
+    fn test_hash_covers_the_screen() {
+        let mut chip8 = Chip8::new();
+        let before = Snapshot::of(&chip8).hash();
+        chip8.cpu.disp.set_pixel(63, 31, true);
+        assert_ne!(Snapshot::of(&chip8).hash(), before);
+    }
+}
@@ -0,0 +1,295 @@
+/// The 64x32 monochrome CHIP-8 display, the one framebuffer both the CPU
+/// draws into and the frontends read from.
+///
+/// Pixels are addressed as `(x, y)` with the origin in the top left corner.
+/// The storage layout is private; use the accessors rather than indexing, and
+/// [`width`](Display::width) and [`height`](Display::height) rather than the
+/// constants where the code should keep working at other resolutions.
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Display {
+    // one word per row, the most significant bit being x = 0
+    rows: [u64; Display::HEIGHT],
+}
+
+impl Default for Display {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Display {
+    pub const WIDTH: usize = 64;
+    pub const HEIGHT: usize = 32;
+
+    pub fn new() -> Self {
+        Display {
+            rows: [0; Self::HEIGHT],
+        }
+    }
+
+    /// Pixels across.
+    pub fn width(&self) -> usize {
+        Self::WIDTH
+    }
+
+    /// Pixels down.
+    pub fn height(&self) -> usize {
+        Self::HEIGHT
+    }
+
+    /// Whether the pixel at `(x, y)` is lit. Panics if out of range.
+    pub fn pixel(&self, x: usize, y: usize) -> bool {
+        assert!(x < Self::WIDTH, "x coordinate {} out of range", x);
+        self.rows[y] & Self::bit(x) != 0
+    }
+
+    pub fn set_pixel(&mut self, x: usize, y: usize, on: bool) {
+        assert!(x < Self::WIDTH, "x coordinate {} out of range", x);
+        if on {
+            self.rows[y] |= Self::bit(x);
+        } else {
+            self.rows[y] &= !Self::bit(x);
+        }
+    }
+
+    /// Flips the pixel at `(x, y)` the way a sprite draw does, returning
+    /// whether it was lit and so is now turned off.
+    pub fn xor_pixel(&mut self, x: usize, y: usize) -> bool {
+        assert!(x < Self::WIDTH, "x coordinate {} out of range", x);
+        self.xor_row(y, Self::bit(x))
+    }
+
+    /// XORs `bits` (bit 63 being x = 0) into row `y`, returning whether any lit
+    /// pixel was turned off.
+    pub(crate) fn xor_row(&mut self, y: usize, bits: u64) -> bool {
+        let collision = self.rows[y] & bits != 0;
+        self.rows[y] ^= bits;
+        collision
+    }
+
+    fn bit(x: usize) -> u64 {
+        1 << (Self::WIDTH - 1 - x)
+    }
+
+    /// Iterates the rows from top to bottom, each yielding its pixels left to right.
+    pub fn rows(&self) -> impl Iterator<Item = impl Iterator<Item = bool> + '_> + '_ {
+        (0..Self::HEIGHT).map(move |y| (0..Self::WIDTH).map(move |x| self.pixel(x, y)))
+    }
+
+    /// Iterates the `(x, y)` coordinates of every lit pixel, row by row.
+    pub fn iter_set_pixels(&self) -> impl Iterator<Item = (usize, usize)> + '_ {
+        (0..Self::HEIGHT)
+            .flat_map(|y| (0..Self::WIDTH).map(move |x| (x, y)))
+            .filter(|&(x, y)| self.pixel(x, y))
+    }
+
+    pub fn clear(&mut self) {
+        self.rows = [0; Self::HEIGHT];
+    }
+
+    /// The display as text, one line per row, `#` for lit pixels and `.` for
+    /// unlit ones; see [`text_art`](crate::golden::text_art).
+    #[cfg(feature = "std")]
+    pub fn to_ascii(&self) -> std::string::String {
+        crate::golden::text_art(&self.pack())
+    }
+
+    /// The display as a black and white PNG; see [`png`](crate::golden::png).
+    #[cfg(feature = "std")]
+    pub fn to_png(&self) -> std::vec::Vec<u8> {
+        crate::golden::png(&self.pack())
+    }
+
+    /// Packs the display into the stable [`PackedFrame`] layout.
+    pub fn pack(&self) -> PackedFrame {
+        let mut frame = PackedFrame {
+            width: Self::WIDTH,
+            height: Self::HEIGHT,
+            bits: [0; PackedFrame::CAPACITY],
+        };
+        for (dst, row) in frame.bits.chunks_exact_mut(8).zip(self.rows) {
+            dst.copy_from_slice(&row.to_be_bytes());
+        }
+        frame
+    }
+}
+
+/// A set of display rows, such as the rows a sprite draw touched, so frontends
+/// can redraw just those without the machine copying any pixels.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct DirtyRows(u64);
+
+impl DirtyRows {
+    pub const NONE: DirtyRows = DirtyRows(0);
+    pub const ALL: DirtyRows = DirtyRows(u64::MAX >> (64 - Display::HEIGHT));
+
+    pub fn insert(&mut self, y: usize) {
+        self.0 |= 1 << y;
+    }
+
+    pub fn contains(&self, y: usize) -> bool {
+        y < 64 && self.0 & (1 << y) != 0
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0 == 0
+    }
+
+    /// The rows in either set.
+    pub fn union(self, other: DirtyRows) -> DirtyRows {
+        DirtyRows(self.0 | other.0)
+    }
+
+    /// The rows in the set, top to bottom.
+    pub fn iter(&self) -> impl Iterator<Item = usize> {
+        let rows = *self;
+        (0..64).filter(move |y| rows.contains(*y))
+    }
+}
+
+/// A copy of the display in a documented layout that will not change when the
+/// internal representation does.
+///
+/// The 1bpp form is row-major with `width / 8` bytes per row, the most
+/// significant bit of each byte being the leftmost pixel. The 8bpp form is
+/// row-major with one byte per pixel, 0 for off and 1 for on.
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct PackedFrame {
+    pub width: usize,
+    pub height: usize,
+    #[cfg_attr(feature = "serde", serde(with = "crate::serde_array"))]
+    bits: [u8; PackedFrame::CAPACITY],
+}
+
+impl PackedFrame {
+    /// Enough room for a 128x64 display at 1bpp.
+    const CAPACITY: usize = 128 * 64 / 8;
+
+    /// A `width` by `height` frame from its 1bpp form, for frames drawn by
+    /// something other than a [`Display`], or `None` if `bits` is not
+    /// `width * height / 8` bytes long or the frame is larger than 128x64.
+    pub fn from_1bpp(width: usize, height: usize, bits: &[u8]) -> Option<Self> {
+        if !width.is_multiple_of(8)
+            || width * height / 8 != bits.len()
+            || bits.len() > Self::CAPACITY
+        {
+            return None;
+        }
+        let mut frame = PackedFrame {
+            width,
+            height,
+            bits: [0; Self::CAPACITY],
+        };
+        frame.bits[..bits.len()].copy_from_slice(bits);
+        Some(frame)
+    }
+
+    /// The frame at one bit per pixel, `width * height / 8` bytes long.
+    pub fn as_1bpp(&self) -> &[u8] {
+        &self.bits[..self.width * self.height / 8]
+    }
+
+    /// The frame at one byte per pixel, `width * height` bytes long.
+    pub fn iter_8bpp(&self) -> impl Iterator<Item = u8> + '_ {
+        self.as_1bpp()
+            .iter()
+            .flat_map(|byte| (0..8).map(move |i| (byte >> (7 - i)) & 1))
+    }
+
+    /// Writes the 8bpp form into `out`, returning the number of bytes written,
+    /// or `None` if `out` is shorter than `width * height`.
+    pub fn copy_8bpp(&self, out: &mut [u8]) -> Option<usize> {
+        let len = self.width * self.height;
+        let out = out.get_mut(..len)?;
+        for (dst, src) in out.iter_mut().zip(self.iter_8bpp()) {
+            *dst = src;
+        }
+        Some(len)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_iter_set_pixels() {
+        let mut disp = Display::new();
+        disp.set_pixel(5, 1, true);
+        disp.set_pixel(2, 3, true);
+        disp.set_pixel(7, 1, true);
+        let set: [(usize, usize); 3] = [(5, 1), (7, 1), (2, 3)];
+        assert!(disp.iter_set_pixels().eq(set));
+    }
+
+    #[test]
+    fn test_xor_pixel() {
+        let mut disp = Display::new();
+        assert_eq!((disp.width(), disp.height()), (64, 32));
+        assert!(!disp.xor_pixel(63, 31));
+        assert!(disp.pixel(63, 31));
+        assert!(disp.xor_pixel(63, 31));
+        assert_eq!(disp, Display::new());
+    }
+
+    #[test]
+    fn test_pack() {
+        let mut disp = Display::new();
+        disp.set_pixel(0, 0, true);
+        disp.set_pixel(9, 1, true);
+        let frame = disp.pack();
+        assert_eq!((frame.width, frame.height), (64, 32));
+        assert_eq!(frame.as_1bpp().len(), 256);
+        assert_eq!(frame.as_1bpp()[0], 0x80);
+        assert_eq!(frame.as_1bpp()[9], 0x40);
+        let mut bytes = [0u8; 2048];
+        assert_eq!(frame.copy_8bpp(&mut bytes), Some(2048));
+        assert_eq!(bytes.iter().filter(|b| **b == 1).count(), 2);
+        assert_eq!(bytes[64 + 9], 1);
+        assert_eq!(frame.copy_8bpp(&mut [0u8; 100]), None);
+        assert_eq!(PackedFrame::from_1bpp(64, 32, frame.as_1bpp()), Some(frame));
+        assert_eq!(PackedFrame::from_1bpp(64, 32, &[0; 255]), None);
+        assert_eq!(PackedFrame::from_1bpp(256, 64, &[0; 2048]), None);
+    }
+
+    #[test]
+    fn test_rows() {
+        let mut disp = Display::new();
+        disp.set_pixel(63, 31, true);
+        assert_eq!(disp.rows().count(), Display::HEIGHT);
+        let last: [bool; Display::WIDTH] = {
+            let mut row = [false; Display::WIDTH];
+            row[63] = true;
+            row
+        };
+        assert!(disp.rows().last().unwrap().eq(last));
+        assert!(disp.rows().next().unwrap().all(|p| !p));
+    }
+
+    #[test]
+    fn test_xor_row() {
+        let mut disp = Display::new();
+        assert!(!disp.xor_row(2, 0xC000_0000_0000_0001));
+        assert!(disp.pixel(0, 2) && disp.pixel(1, 2) && disp.pixel(63, 2));
+        assert!(disp.xor_row(2, 0x4000_0000_0000_0000));
+        assert!(disp.pixel(0, 2) && !disp.pixel(1, 2));
+        disp.set_pixel(0, 2, false);
+        assert!(disp.iter_set_pixels().eq([(63, 2)]));
+    }
+
+    #[test]
+    fn test_dirty_rows() {
+        let mut rows = DirtyRows::NONE;
+        assert!(rows.is_empty());
+        rows.insert(3);
+        rows.insert(31);
+        assert!(rows.iter().eq([3, 31]));
+        assert!(rows.contains(31) && !rows.contains(30) && !rows.contains(99));
+        assert_eq!(DirtyRows::ALL.iter().count(), Display::HEIGHT);
+        let mut other = DirtyRows::NONE;
+        other.insert(5);
+        assert!(rows.union(other).iter().eq([3, 5, 31]));
+    }
+}
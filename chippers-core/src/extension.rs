@@ -0,0 +1,74 @@
+//! Handlers for opcodes the interpreter does not implement, so embedders can
+//! add experimental peripherals (serial output, extra storage, ...) without
+//! patching the decoder.
+//!
+//! Handlers are boxed closures and so need `std`; like [`Hooks`](crate::hooks::Hooks)
+//! they must be `Send` and `Sync` to keep the machine `Send` and `Sync`.
+
+use crate::chip::Chip8Message;
+use crate::cpu::Cpu;
+use std::boxed::Box;
+use std::vec::Vec;
+
+type Handler = Box<dyn FnMut(u16, &mut Cpu) -> Chip8Message + Send + Sync>;
+
+struct Entry {
+    mask: u16,
+    pattern: u16,
+    handler: Handler,
+}
+
+/// The opcode handlers registered on a machine.
+///
+/// An instruction `inst` is claimed by the first handler, in registration
+/// order, for which `inst & mask == pattern`. Handlers are consulted before
+/// the built-in decoder, so they can take over 0NNN or the reserved EX and FX
+/// ranges; claiming a standard opcode replaces it.
+#[derive(Default)]
+pub struct Extensions {
+    entries: Vec<Entry>,
+}
+
+impl core::fmt::Debug for Extensions {
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+        f.debug_struct("Extensions")
+            .field("handlers", &self.entries.len())
+            .finish()
+    }
+}
+
+impl Extensions {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Whether no handlers are registered.
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Runs `handler` for every instruction matching `pattern` under `mask`.
+    ///
+    /// The handler gets the instruction and the CPU, with the program counter
+    /// already past the instruction, and returns what the frontend should do.
+    pub fn register(
+        &mut self,
+        mask: u16,
+        pattern: u16,
+        handler: impl FnMut(u16, &mut Cpu) -> Chip8Message + Send + Sync + 'static,
+    ) {
+        self.entries.push(Entry {
+            mask,
+            pattern: pattern & mask,
+            handler: Box::new(handler),
+        });
+    }
+
+    /// Runs the handler claiming `inst`, if there is one.
+    pub(crate) fn dispatch(&mut self, inst: u16, cpu: &mut Cpu) -> Option<Chip8Message> {
+        self.entries
+            .iter_mut()
+            .find(|e| inst & e.mask == e.pattern)
+            .map(|e| (e.handler)(inst, cpu))
+    }
+}
@@ -0,0 +1,118 @@
+//! Golden-frame snapshots: a frame compared against a checked-in text render.
+//!
+//! Renders are one line per row, `#` for lit pixels and `.` for unlit ones, so
+//! they read well in a diff. To accept new output, run the tests with
+//! `CHIPPERS_BLESS=1` set; every snapshot is then written instead of checked.
+//!
+//! ```no_run
+//! use chippers_core::golden::assert_golden;
+//! use chippers_core::{run_rom_until, Limits};
+//!
+//! let rom = std::fs::read("IBM Logo.ch8").unwrap();
+//! let run = run_rom_until(&rom, |_| false, Limits::frames(60)).unwrap();
+//! assert_golden("tests/golden/ibm_logo.txt", &run.framebuffer());
+//! ```
+
+use crate::backend::Palette;
+use crate::display::PackedFrame;
+use std::path::Path;
+use std::string::String;
+use std::vec::Vec;
+
+/// Set to anything but `0` to rewrite snapshots rather than check them.
+pub const BLESS_VAR: &str = "CHIPPERS_BLESS";
+
+/// Renders `frame` as text art.
+pub fn text_art(frame: &PackedFrame) -> String {
+    let mut art = String::with_capacity((frame.width + 1) * frame.height);
+    for (i, pixel) in frame.iter_8bpp().enumerate() {
+        art.push(if pixel == 1 { '#' } else { '.' });
+        if (i + 1) % frame.width == 0 {
+            art.push('\n');
+        }
+    }
+    art
+}
+
+/// Encodes `frame` as a black and white PNG, one image pixel per display
+/// pixel, see [`image::png`](crate::image::png).
+pub fn png(frame: &PackedFrame) -> Vec<u8> {
+    crate::image::png(frame, 1, Palette::MONOCHROME)
+}
+
+fn blessing() -> bool {
+    std::env::var_os(BLESS_VAR).is_some_and(|v| v != "0")
+}
+
+/// Panics unless `frame` matches the snapshot at `path`, or writes the
+/// snapshot when blessing.
+pub fn assert_golden(path: impl AsRef<Path>, frame: &PackedFrame) {
+    let path = path.as_ref();
+    let actual = text_art(frame);
+    if blessing() {
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir).unwrap();
+        }
+        std::fs::write(path, &actual)
+            .unwrap_or_else(|e| panic!("could not write {}: {}", path.display(), e));
+        return;
+    }
+    let expected = match std::fs::read_to_string(path) {
+        Ok(expected) => expected,
+        Err(e) => panic!(
+            "could not read {}: {}\nrun with {}=1 to create it",
+            path.display(),
+            e,
+            BLESS_VAR
+        ),
+    };
+    // snapshots edited on Windows may have picked up carriage returns
+    if expected.replace("\r\n", "\n") != actual {
+        panic!(
+            "frame does not match {}\n\nexpected:\n{}\nactual:\n{}\nrun with {}=1 if the change is intended",
+            path.display(),
+            expected,
+            actual,
+            BLESS_VAR
+        );
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::display::Display;
+    use crate::image::crc32;
+
+    #[test]
+    fn test_text_art() {
+        let mut disp = Display::new();
+        disp.set_pixel(1, 0, true);
+        disp.set_pixel(63, 31, true);
+        let art = text_art(&disp.pack());
+        let lines: std::vec::Vec<&str> = art.lines().collect();
+        assert_eq!(lines.len(), Display::HEIGHT);
+        assert!(lines[0].starts_with(".#."));
+        assert!(lines[31].ends_with(".#"));
+        assert_eq!(art.matches('#').count(), 2);
+    }
+
+    #[test]
+    fn test_png() {
+        let mut disp = Display::new();
+        disp.set_pixel(0, 0, true);
+        let png = png(&disp.pack());
+        assert_eq!(&png[..8], b"\x89PNG\r\n\x1a\n");
+        // IHDR: 64x32, 1-bit grayscale
+        assert_eq!(&png[12..16], b"IHDR");
+        assert_eq!(&png[16..25], &[0, 0, 0, 64, 0, 0, 0, 32, 1]);
+        // the checksum of an IHDR chunk for that header
+        assert_eq!(&png[29..33], &crc32(&png[12..29]).to_be_bytes());
+        assert_eq!(crc32(b"123456789"), 0xCBF4_3926);
+        // the stored first scanline: no filter, then the top-left pixel lit
+        let idat = 33 + 8;
+        assert_eq!(&png[idat - 4..idat], b"IDAT");
+        assert_eq!(&png[idat + 2 + 5..idat + 2 + 5 + 3], &[0, 0x80, 0]);
+        assert!(png.ends_with(&[0, 0, 0, 0, b'I', b'E', b'N', b'D', 0xAE, 0x42, 0x60, 0x82]));
+    }
+}
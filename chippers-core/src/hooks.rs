@@ -0,0 +1,202 @@
+//! Callbacks invoked by [`Chip8`](crate::chip::Chip8) as it runs, so tracers,
+//! profilers and the like can observe execution without their own dispatch loop.
+//!
+//! Hooks are boxed closures and so need `std`. They must be `Send` and `Sync`
+//! to keep the machine `Send` and `Sync`; share state through an `Arc` and a
+//! lock or atomics.
+//!
+//! A tool that wants several of the callbacks at once, such as a coverage
+//! counter or a renderer of its own, can implement [`Observer`] instead and
+//! be registered with [`Hooks::observe`]. Wrapped in an `Arc<Mutex<_>>`, it
+//! can be kept and read once the run is over:
+//!
+//! ```
+//! use chippers_core::chip::Chip8;
+//! use chippers_core::cpu::Cpu;
+//! use chippers_core::hooks::Observer;
+//! use std::sync::{Arc, Mutex};
+//!
+//! #[derive(Default)]
+//! struct Jumps(u32);
+//!
+//! impl Observer for Jumps {
+//!     fn on_instruction(&mut self, _addr: u16, opcode: u16, _cpu: &Cpu) {
+//!         self.0 += u32::from(opcode >> 12 == 1);
+//!     }
+//! }
+//!
+//! let jumps = Arc::new(Mutex::new(Jumps::default()));
+//! let mut chip8 = Chip8::new();
+//! chip8.load_rom(&[0x12, 0x00]).unwrap();
+//! chip8.hooks.observe(jumps.clone());
+//! for _ in 0..3 {
+//!     chip8.step();
+//! }
+//! assert_eq!(jumps.lock().unwrap().0, 3);
+//! ```
+
+use crate::cpu::{Cpu, Fault};
+use crate::display::Display;
+use std::boxed::Box;
+use std::sync::{Arc, Mutex};
+use std::vec::Vec;
+
+type FetchHook = Box<dyn FnMut(u16) + Send + Sync>;
+type InstructionHook = Box<dyn FnMut(u16, u16, &Cpu) + Send + Sync>;
+type DrawHook = Box<dyn FnMut(&Display) + Send + Sync>;
+type KeyReadHook = Box<dyn FnMut(Option<u8>) + Send + Sync>;
+type TimerTickHook = Box<dyn FnMut(u8, u8) + Send + Sync>;
+type FaultHook = Box<dyn FnMut(Fault, &Cpu) + Send + Sync>;
+
+/// Watches a machine run through the callbacks it cares about, each of which
+/// does nothing by default.
+pub trait Observer: Send + Sync {
+    /// Called after each instruction with its address, its opcode and the
+    /// resulting CPU state.
+    fn on_instruction(&mut self, _addr: u16, _opcode: u16, _cpu: &Cpu) {}
+    /// Called after each 60 Hz timer tick with the number of frames so far.
+    fn on_frame(&mut self, _frame: u64, _cpu: &Cpu) {}
+    /// Called when the program faults, with the CPU as the fault left it.
+    fn on_fault(&mut self, _fault: Fault, _cpu: &Cpu) {}
+}
+
+impl<O: Observer> Observer for Arc<Mutex<O>> {
+    fn on_instruction(&mut self, addr: u16, opcode: u16, cpu: &Cpu) {
+        let mut observer = self.lock().unwrap_or_else(|e| e.into_inner());
+        observer.on_instruction(addr, opcode, cpu);
+    }
+    fn on_frame(&mut self, frame: u64, cpu: &Cpu) {
+        let mut observer = self.lock().unwrap_or_else(|e| e.into_inner());
+        observer.on_frame(frame, cpu);
+    }
+    fn on_fault(&mut self, fault: Fault, cpu: &Cpu) {
+        let mut observer = self.lock().unwrap_or_else(|e| e.into_inner());
+        observer.on_fault(fault, cpu);
+    }
+}
+
+/// The hooks registered on a machine, run in the order they were added, and
+/// then the observers, in theirs.
+#[derive(Default)]
+pub struct Hooks {
+    fetch: Vec<FetchHook>,
+    instruction: Vec<InstructionHook>,
+    draw: Vec<DrawHook>,
+    key_read: Vec<KeyReadHook>,
+    timer_tick: Vec<TimerTickHook>,
+    fault: Vec<FaultHook>,
+    observers: Vec<Box<dyn Observer>>,
+}
+
+impl core::fmt::Debug for Hooks {
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+        f.debug_struct("Hooks")
+            .field("fetch", &self.fetch.len())
+            .field("instruction", &self.instruction.len())
+            .field("draw", &self.draw.len())
+            .field("key_read", &self.key_read.len())
+            .field("timer_tick", &self.timer_tick.len())
+            .field("fault", &self.fault.len())
+            .field("observers", &self.observers.len())
+            .finish()
+    }
+}
+
+impl Hooks {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Whether no hooks are registered.
+    pub fn is_empty(&self) -> bool {
+        self.fetch.is_empty()
+            && self.instruction.is_empty()
+            && self.draw.is_empty()
+            && self.key_read.is_empty()
+            && self.timer_tick.is_empty()
+            && self.fault.is_empty()
+            && self.observers.is_empty()
+    }
+
+    /// Called before each instruction is fetched, with its address.
+    pub fn on_fetch(&mut self, hook: impl FnMut(u16) + Send + Sync + 'static) {
+        self.fetch.push(Box::new(hook));
+    }
+
+    /// Called after each instruction with its address, its opcode and the
+    /// resulting CPU state.
+    pub fn on_instruction(&mut self, hook: impl FnMut(u16, u16, &Cpu) + Send + Sync + 'static) {
+        self.instruction.push(Box::new(hook));
+    }
+
+    /// Called with the display whenever the program draws a sprite.
+    pub fn on_draw(&mut self, hook: impl FnMut(&Display) + Send + Sync + 'static) {
+        self.draw.push(Box::new(hook));
+    }
+
+    /// Called when a key instruction (EX9E, EXA1, FX0A) reads the keypad, with
+    /// the key it saw, if any.
+    pub fn on_key_read(&mut self, hook: impl FnMut(Option<u8>) + Send + Sync + 'static) {
+        self.key_read.push(Box::new(hook));
+    }
+
+    /// Called after each timer tick with the new delay and sound timer values.
+    pub fn on_timer_tick(&mut self, hook: impl FnMut(u8, u8) + Send + Sync + 'static) {
+        self.timer_tick.push(Box::new(hook));
+    }
+
+    /// Called when the program faults, with the CPU as the fault left it.
+    pub fn on_fault(&mut self, hook: impl FnMut(Fault, &Cpu) + Send + Sync + 'static) {
+        self.fault.push(Box::new(hook));
+    }
+
+    /// Registers `observer` for all of its callbacks.
+    pub fn observe(&mut self, observer: impl Observer + 'static) {
+        self.observers.push(Box::new(observer));
+    }
+
+    pub(crate) fn fetch(&mut self, addr: u16) {
+        for hook in &mut self.fetch {
+            hook(addr);
+        }
+    }
+
+    pub(crate) fn instruction(&mut self, addr: u16, inst: u16, cpu: &Cpu) {
+        for hook in &mut self.instruction {
+            hook(addr, inst, cpu);
+        }
+        for observer in &mut self.observers {
+            observer.on_instruction(addr, inst, cpu);
+        }
+    }
+
+    pub(crate) fn draw(&mut self, display: &Display) {
+        for hook in &mut self.draw {
+            hook(display);
+        }
+    }
+
+    pub(crate) fn key_read(&mut self, key: Option<u8>) {
+        for hook in &mut self.key_read {
+            hook(key);
+        }
+    }
+
+    pub(crate) fn timer_tick(&mut self, frame: u64, cpu: &Cpu) {
+        for hook in &mut self.timer_tick {
+            hook(cpu.dt, cpu.st);
+        }
+        for observer in &mut self.observers {
+            observer.on_frame(frame, cpu);
+        }
+    }
+
+    pub(crate) fn fault(&mut self, fault: Fault, cpu: &Cpu) {
+        for hook in &mut self.fault {
+            hook(fault, cpu);
+        }
+        for observer in &mut self.observers {
+            observer.on_fault(fault, cpu);
+        }
+    }
+}
@@ -0,0 +1,290 @@
+//! Frames as images: palette indices scaled up for encoders, PNG files, and
+//! screenshots of a running machine.
+//!
+//! [`ControlMessage::Screenshot`] saves the display as a PNG named for the
+//! time it was taken, `chippers-20260101-120000.png`, in the directory and at
+//! the scale and colors set in [`Chip8::screenshots`].
+//!
+//! [`ControlMessage::Screenshot`]: crate::backend::ControlMessage::Screenshot
+
+use crate::backend::Palette;
+use crate::chip::Chip8;
+use crate::display::PackedFrame;
+use std::fs::OpenOptions;
+use std::io::Write;
+use std::path::PathBuf;
+use std::string::{String, ToString};
+use std::vec::Vec;
+
+/// Image pixels per display pixel in a screenshot, unless set otherwise.
+pub const SCREENSHOT_SCALE: usize = 4;
+
+/// The palette index of every image pixel in `frame` stretched to `width` by
+/// `height`, row by row: 0 for unlit, 1 for lit.
+///
+/// The image should be a whole multiple of the frame's size each way, so a
+/// low resolution frame fills an image sized for a high resolution one.
+pub fn indices(frame: &PackedFrame, width: usize, height: usize) -> Vec<u8> {
+    let pixels: Vec<u8> = frame.iter_8bpp().collect();
+    let across = (width / frame.width).max(1);
+    let down = (height / frame.height).max(1);
+    let mut out = Vec::with_capacity(width * height);
+    for y in 0..height {
+        let row = &pixels[y / down * frame.width..][..frame.width];
+        out.extend((0..width).map(|x| row[x / across]));
+    }
+    out
+}
+
+/// Encodes `frame` as a PNG, each display pixel `scale` image pixels across,
+/// lit pixels in `palette.on` and unlit ones in `palette.off`.
+///
+/// The image is 1-bit, grayscale for [`Palette::MONOCHROME`] and indexed
+/// otherwise, with the pixel data stored uncompressed: a CHIP-8 frame is at
+/// most a few kilobytes, and this keeps the core free of a compression
+/// dependency.
+pub fn png(frame: &PackedFrame, scale: usize, palette: Palette) -> Vec<u8> {
+    let scale = scale.max(1);
+    let (width, height) = (frame.width * scale, frame.height * scale);
+    // each scanline starts with its filter type, 0 for none
+    let mut raw = Vec::with_capacity((width / 8 + 1) * height);
+    for row in indices(frame, width, height).chunks(width) {
+        raw.push(0);
+        raw.extend(
+            row.chunks(8)
+                .map(|bits| bits.iter().fold(0, |byte, bit| byte << 1 | bit)),
+        );
+    }
+
+    let gray = palette == Palette::MONOCHROME;
+    let mut ihdr = Vec::with_capacity(13);
+    ihdr.extend_from_slice(&(width as u32).to_be_bytes());
+    ihdr.extend_from_slice(&(height as u32).to_be_bytes());
+    // bit depth 1, grayscale or indexed, deflate, adaptive filtering, not
+    // interlaced
+    ihdr.extend_from_slice(&[1, if gray { 0 } else { 3 }, 0, 0, 0]);
+    let (off, on) = (palette.off, palette.on);
+    let plte = [off.r, off.g, off.b, on.r, on.g, on.b];
+
+    // a zlib stream of stored deflate blocks
+    let mut idat = vec![0x78, 0x01];
+    let mut blocks = raw.chunks(0xFFFF).peekable();
+    while let Some(block) = blocks.next() {
+        idat.push(u8::from(blocks.peek().is_none()));
+        let len = block.len() as u16;
+        idat.extend_from_slice(&len.to_le_bytes());
+        idat.extend_from_slice(&(!len).to_le_bytes());
+        idat.extend_from_slice(block);
+    }
+    idat.extend_from_slice(&adler32(&raw).to_be_bytes());
+
+    let mut out = b"\x89PNG\r\n\x1a\n".to_vec();
+    let mut chunks = vec![(b"IHDR", &ihdr[..])];
+    if !gray {
+        chunks.push((b"PLTE", &plte));
+    }
+    chunks.extend([(b"IDAT", &idat[..]), (b"IEND", &[])]);
+    for (kind, data) in chunks {
+        out.extend_from_slice(&(data.len() as u32).to_be_bytes());
+        let start = out.len();
+        out.extend_from_slice(kind);
+        out.extend_from_slice(data);
+        let crc = crc32(&out[start..]);
+        out.extend_from_slice(&crc.to_be_bytes());
+    }
+    out
+}
+
+pub(crate) fn crc32(bytes: &[u8]) -> u32 {
+    let mut crc = !0u32;
+    for byte in bytes {
+        crc ^= u32::from(*byte);
+        for _ in 0..8 {
+            crc = (crc >> 1) ^ (0xEDB8_8320 & (crc & 1).wrapping_neg());
+        }
+    }
+    !crc
+}
+
+fn adler32(bytes: &[u8]) -> u32 {
+    let (mut a, mut b) = (1u32, 0u32);
+    for byte in bytes {
+        a = (a + u32::from(*byte)) % 65521;
+        b = (b + a) % 65521;
+    }
+    b << 16 | a
+}
+
+/// Where and how [`Chip8::screenshot`] saves the display.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Screenshots {
+    pub dir: PathBuf,
+    /// Image pixels per display pixel.
+    pub scale: usize,
+    pub palette: Palette,
+}
+
+impl Default for Screenshots {
+    /// The working directory, at [`SCREENSHOT_SCALE`], in black and white.
+    fn default() -> Self {
+        Screenshots {
+            dir: PathBuf::from("."),
+            scale: SCREENSHOT_SCALE,
+            palette: Palette::MONOCHROME,
+        }
+    }
+}
+
+/// Why [`Chip8::screenshot`] could not save the display.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ScreenshotError {
+    pub path: PathBuf,
+    pub message: String,
+}
+
+impl core::fmt::Display for ScreenshotError {
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+        writeln!(
+            f,
+            "error: cannot save screenshot {}: {}",
+            self.path.display(),
+            self.message
+        )
+    }
+}
+
+impl std::error::Error for ScreenshotError {}
+
+impl Chip8 {
+    /// Saves the display as a PNG in the [`screenshots`](Chip8::screenshots)
+    /// directory, named for the current time, and returns its path. Never
+    /// overwrites a file: a second screenshot in the same second gets a
+    /// number on the end.
+    pub fn screenshot(&self) -> core::result::Result<PathBuf, ScreenshotError> {
+        let settings = &self.screenshots;
+        let png = png(&self.framebuffer(), settings.scale, settings.palette);
+        let stamp = timestamp(std::time::SystemTime::now());
+        let mut n = 1;
+        loop {
+            let name = match n {
+                1 => format!("chippers-{}.png", stamp),
+                n => format!("chippers-{}-{}.png", stamp, n),
+            };
+            let path = settings.dir.join(name);
+            let error = |e: std::io::Error| ScreenshotError {
+                path: path.clone(),
+                message: e.to_string(),
+            };
+            match OpenOptions::new().write(true).create_new(true).open(&path) {
+                Ok(mut file) => {
+                    file.write_all(&png).map_err(error)?;
+                    return Ok(path);
+                }
+                Err(e) if e.kind() == std::io::ErrorKind::AlreadyExists => n += 1,
+                Err(e) => return Err(error(e)),
+            }
+        }
+    }
+}
+
+/// `time` in UTC as `YYYYMMDD-HHMMSS`.
+pub fn timestamp(time: std::time::SystemTime) -> String {
+    let secs = time
+        .duration_since(std::time::UNIX_EPOCH)
+        .map_or(0, |since| since.as_secs());
+    let (days, secs) = ((secs / 86400) as i64, secs % 86400);
+    // days since the epoch to a date, after Howard Hinnant's civil_from_days
+    let z = days + 719_468;
+    let (era, day_of_era) = (z.div_euclid(146_097), z.rem_euclid(146_097));
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let mp = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = year_of_era + era * 400 + i64::from(month <= 2);
+    format!(
+        "{:04}{:02}{:02}-{:02}{:02}{:02}",
+        year,
+        month,
+        day,
+        secs / 3600,
+        secs / 60 % 60,
+        secs % 60
+    )
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::display::Display;
+    use std::time::{Duration, UNIX_EPOCH};
+
+    #[test]
+    fn test_indices() {
+        let mut disp = Display::new();
+        disp.set_pixel(1, 0, true);
+        let frame = disp.pack();
+        let pixels = indices(&frame, 128, 64);
+        assert_eq!(pixels.len(), 128 * 64);
+        assert_eq!(&pixels[..6], &[0, 0, 1, 1, 0, 0]);
+        assert_eq!(&pixels[128..134], &[0, 0, 1, 1, 0, 0]);
+        assert_eq!(pixels.iter().filter(|p| **p == 1).count(), 4);
+    }
+
+    #[test]
+    fn test_png() {
+        let mut disp = Display::new();
+        disp.set_pixel(0, 0, true);
+        let png = png(&disp.pack(), 2, Palette::AMBER);
+        assert_eq!(&png[..8], b"\x89PNG\r\n\x1a\n");
+        // 128x64, 1-bit indexed
+        assert_eq!(&png[16..25], &[0, 0, 0, 128, 0, 0, 0, 64, 1]);
+        assert_eq!(png[25], 3);
+        assert_eq!(&png[33..37], &6u32.to_be_bytes());
+        assert_eq!(&png[37..41], b"PLTE");
+        assert_eq!(&png[41..47], &[0x1A, 0x10, 0x00, 0xFF, 0xB0, 0x00]);
+        // the first two scanlines start with two lit pixels
+        let idat = png.windows(4).position(|w| w == b"IDAT").unwrap() + 4;
+        let raw = &png[idat + 2 + 5..];
+        assert_eq!(&raw[..3], &[0, 0xC0, 0]);
+        assert_eq!(&raw[17..20], &[0, 0xC0, 0]);
+        assert_eq!(&raw[34..37], &[0, 0, 0]);
+    }
+
+    #[test]
+    fn test_timestamp() {
+        assert_eq!(timestamp(UNIX_EPOCH), "19700101-000000");
+        let leap_day = UNIX_EPOCH + Duration::from_secs(951_782_400 + 13 * 3600 + 5 * 60 + 9);
+        assert_eq!(timestamp(leap_day), "20000229-130509");
+        let new_year = UNIX_EPOCH + Duration::from_secs(1_798_761_599);
+        assert_eq!(timestamp(new_year), "20261231-235959");
+    }
+
+    #[test]
+    fn test_screenshot() {
+        let dir = std::env::temp_dir().join(format!("chippers-screenshot-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let mut chip8 = Chip8::new();
+        chip8.screenshots.dir = dir.clone();
+        let first = chip8.screenshot().unwrap();
+        let second = chip8.screenshot().unwrap();
+        assert_ne!(first, second);
+        let name = first.file_name().unwrap().to_str().unwrap();
+        assert!(
+            name.starts_with("chippers-") && name.ends_with(".png"),
+            "{}",
+            name
+        );
+        assert_eq!(
+            std::fs::read(&second).unwrap(),
+            png(&chip8.framebuffer(), 4, Palette::MONOCHROME)
+        );
+        std::fs::remove_dir_all(&dir).unwrap();
+        chip8.screenshots.dir = dir.join("missing");
+        let error = chip8.screenshot().unwrap_err();
+        assert!(error
+            .to_string()
+            .starts_with("error: cannot save screenshot "));
+    }
+}
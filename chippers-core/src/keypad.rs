@@ -0,0 +1,196 @@
+//! The state of the 16-key hexadecimal keypad, and the keyboard keys that
+//! press it.
+
+/// Which keys are down, plus the last press the program has not yet read.
+///
+/// Frontends that see key releases call [`press`](Keypad::press) and
+/// [`release`](Keypad::release) as keys go down and up. Frontends that only
+/// see presses, like a terminal, call [`tap`](Keypad::tap) instead: a tapped
+/// key stays down until a key instruction has read the keypad.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Keypad {
+    down: u16,
+    tapped: u16,
+    last_press: Option<u8>,
+}
+
+impl Keypad {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn press(&mut self, key: u8) {
+        let key = key & 0xF;
+        self.down |= 1 << key;
+        self.last_press = Some(key);
+    }
+
+    pub fn release(&mut self, key: u8) {
+        let key = key & 0xF;
+        self.down &= !(1 << key);
+        self.tapped &= !(1 << key);
+    }
+
+    /// Presses `key` until the program next reads the keypad.
+    pub fn tap(&mut self, key: u8) {
+        self.press(key);
+        self.tapped |= 1 << (key & 0xF);
+    }
+
+    pub fn is_down(&self, key: u8) -> bool {
+        self.down & 1 << (key & 0xF) != 0
+    }
+
+    /// The last key pressed that FX0A has not yet taken.
+    pub fn last_press(&self) -> Option<u8> {
+        self.last_press
+    }
+
+    /// Takes the last press, for FX0A.
+    pub(crate) fn take_press(&mut self) -> Option<u8> {
+        self.last_press.take()
+    }
+
+    /// Lets go of tapped keys once an instruction has seen them.
+    pub(crate) fn read(&mut self) {
+        self.down &= !self.tapped;
+        if self.tapped != 0 {
+            self.last_press = None;
+        }
+        self.tapped = 0;
+    }
+}
+
+/// The keypad's keys row by row, as they are laid over the keyboard.
+const KEYPAD_ORDER: [u8; 16] = [1, 2, 3, 0xC, 4, 5, 6, 0xD, 7, 8, 9, 0xE, 0xA, 0, 0xB, 0xF];
+
+/// Which keyboard keys press which keypad keys, by the character a keyboard
+/// key types.
+///
+/// The keypad is laid over four rows of four keys on the left of the
+/// keyboard; on a QWERTY keyboard:
+///
+/// ```text
+/// 1 2 3 C        1 2 3 4
+/// 4 5 6 D   <-   q w e r
+/// 7 8 9 E        a s d f
+/// A 0 B F        z x c v
+/// ```
+///
+/// Every frontend looks keys up here. Those that only see where a key is on
+/// the keyboard rather than what it types name it by what it types on QWERTY.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct KeyMap {
+    // the character pressing each keypad key, by keypad key
+    chars: [Option<char>; 16],
+}
+
+impl Default for KeyMap {
+    fn default() -> Self {
+        Self::layout("qwerty").unwrap()
+    }
+}
+
+impl KeyMap {
+    /// Names accepted by [`layout`](KeyMap::layout).
+    pub const LAYOUTS: [&'static str; 4] = ["qwerty", "azerty", "qwertz", "dvorak"];
+
+    /// The same four rows of keys on a keyboard with this layout, `qwerty`
+    /// being the default.
+    pub fn layout(name: &str) -> Option<Self> {
+        let rows = match name {
+            "qwerty" => "1234qwerasdfzxcv",
+            "azerty" => "&é\"'azerqsdfwxcv",
+            "qwertz" => "1234qwerasdfyxcv",
+            "dvorak" => "1234',.paoeu;qjk",
+            _ => return None,
+        };
+        Self::from_rows(rows)
+    }
+
+    /// The keys typing `rows`, the keypad's rows one after the other, or
+    /// `None` unless they are 16 different characters.
+    pub fn from_rows(rows: &str) -> Option<Self> {
+        let mut map = KeyMap { chars: [None; 16] };
+        let mut chars = rows.chars().map(|c| c.to_ascii_lowercase());
+        for key in KEYPAD_ORDER {
+            let c = chars.next()?;
+            if map.key(c).is_some() {
+                return None;
+            }
+            map.chars[usize::from(key)] = Some(c);
+        }
+        chars.next().is_none().then_some(map)
+    }
+
+    /// The keypad key the keyboard key typing `c` presses, if any.
+    pub fn key(&self, c: char) -> Option<u8> {
+        let c = c.to_ascii_lowercase();
+        (0..16).find(|key| self.chars[usize::from(*key)] == Some(c))
+    }
+
+    /// The character typed by the keyboard key that presses keypad `key`.
+    pub fn char(&self, key: u8) -> Option<char> {
+        self.chars[usize::from(key & 0xF)]
+    }
+
+    /// Makes the key typing `c` press keypad `key` instead of the key it
+    /// pressed before, if any. The keyboard key that pressed `key` no longer
+    /// does.
+    pub fn bind(&mut self, c: char, key: u8) {
+        let c = c.to_ascii_lowercase();
+        if let Some(old) = self.key(c) {
+            self.chars[usize::from(old)] = None;
+        }
+        self.chars[usize::from(key & 0xF)] = Some(c);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_held_and_tapped_keys() {
+        let mut keypad = Keypad::new();
+        keypad.press(3);
+        keypad.tap(0x1C);
+        assert!(keypad.is_down(3) && keypad.is_down(0xC));
+        assert_eq!(keypad.last_press(), Some(0xC));
+        keypad.read();
+        assert!(keypad.is_down(3) && !keypad.is_down(0xC));
+        assert_eq!(keypad.last_press(), None);
+        keypad.read();
+        assert!(keypad.is_down(3));
+        keypad.release(3);
+        assert!(!keypad.is_down(3));
+    }
+
+    #[test]
+    fn test_key_maps() {
+        let qwerty = KeyMap::default();
+        assert_eq!(qwerty.key('1'), Some(1));
+        assert_eq!(qwerty.key('4'), Some(0xC));
+        assert_eq!(qwerty.key('F'), Some(0xE));
+        assert_eq!(qwerty.key('v'), Some(0xF));
+        assert_eq!(qwerty.key('x'), Some(0));
+        assert_eq!(qwerty.key('p'), None);
+        for name in KeyMap::LAYOUTS {
+            let map = KeyMap::layout(name).unwrap();
+            assert!((0..16).all(|key| map.key(map.char(key).unwrap()) == Some(key)));
+        }
+        let azerty = KeyMap::layout("azerty").unwrap();
+        assert_eq!(azerty.key('é'), Some(2));
+        assert_eq!(azerty.key('w'), Some(0xA));
+        assert_eq!(KeyMap::from_rows("1234qwerasdfzxc"), None);
+        assert_eq!(KeyMap::from_rows("1234qwerasdfzxcc"), None);
+
+        let mut map = qwerty;
+        map.bind('p', 0xE);
+        assert_eq!(map.key('p'), Some(0xE));
+        assert_eq!(map.key('f'), None);
+        map.bind('p', 0);
+        assert_eq!((map.key('p'), map.char(0xE)), (Some(0), None));
+    }
+}
@@ -0,0 +1,49 @@
+#![cfg_attr(not(feature = "std"), no_std)]
+
+pub mod backend;
+pub mod chip;
+pub mod cpu;
+pub mod differential;
+pub mod display;
+#[cfg(feature = "std")]
+pub mod extension;
+#[cfg(feature = "std")]
+pub mod golden;
+#[cfg(feature = "std")]
+pub mod hooks;
+#[cfg(feature = "std")]
+pub mod image;
+pub mod keypad;
+#[cfg(feature = "std")]
+pub mod megachip;
+pub mod opcode;
+pub mod oracle;
+pub mod plugin;
+pub mod quirks;
+#[cfg(feature = "reference")]
+pub mod reference;
+#[cfg(feature = "std")]
+pub mod render;
+#[cfg(feature = "std")]
+pub mod replay;
+pub mod rng;
+pub mod romdb;
+#[cfg(feature = "serde")]
+mod serde_array;
+#[cfg(feature = "std")]
+pub mod state;
+pub mod timing;
+
+pub use backend::{AudioBackend, DisplayBackend, Frontend, InputBackend};
+#[cfg(feature = "std")]
+pub use chip::RunError;
+pub use chip::{Chip8, Chip8Message, Engine, FrameReport, Interpreter, LoadError, Pace, RomInfo};
+pub use cpu::{Access, Cpu, Fault};
+pub use display::{Display, PackedFrame};
+#[cfg(feature = "std")]
+pub use hooks::Observer;
+pub use keypad::{KeyMap, Keypad};
+pub use opcode::{Instruction, Opcode};
+pub use oracle::{run_rom_scripted, run_rom_until, KeyPress, Limits, Run, Stop};
+pub use quirks::Quirks;
+pub use timing::Timing;
@@ -0,0 +1,383 @@
+//! Experimental, partial support for MegaChip programs, which draw indexed
+//! color sprites on a 256x192 screen.
+//!
+//! A MegaChip program starts out as a plain CHIP-8 one and turns the extra
+//! mode on with 0011. From then on sprites are drawn into a back buffer, a
+//! byte of color index per pixel, and 00E0 shows the back buffer and clears
+//! it. The program reaches data past the 4 KiB the CPU can address with
+//! 01NN NNNN, which points I anywhere in a ROM of up to 16 MiB.
+//!
+//! | Opcode      | Effect                                              |
+//! |-------------|-----------------------------------------------------|
+//! | `0010`      | MegaChip mode off                                   |
+//! | `0011`      | MegaChip mode on, clearing the screen               |
+//! | `00BN`      | scroll the back buffer up N lines                   |
+//! | `00E0`      | show the back buffer, then clear it                 |
+//! | `01NN NNNN` | I = NNNNNN, a 24-bit address                        |
+//! | `02NN`      | load NN colors from I, ARGB, into indices 1 to NN   |
+//! | `03NN`      | sprite width, 0 meaning 256                         |
+//! | `04NN`      | sprite height, 0 meaning 256                        |
+//! | `080N`      | blend: normal, 25%, 50%, 75%, add or multiply       |
+//! | `09NN`      | the index a sprite collides with, setting VF        |
+//! | `DXYN`      | draw a sprite of indices from I; index 0 is clear   |
+//!
+//! Alpha (05NN) and digitized sound (060N, 0700) are accepted and ignored,
+//! as are font sprites in MegaChip mode, and none of this is saved with the
+//! machine's state.
+
+use crate::backend::{Resolution, Rgb};
+use crate::chip::Chip8Message;
+use crate::cpu::Cpu;
+use crate::display::DirtyRows;
+use std::vec;
+use std::vec::Vec;
+
+/// Width of the MegaChip screen in pixels.
+pub const WIDTH: usize = Resolution::MEGACHIP.width as usize;
+/// Height of the MegaChip screen in pixels.
+pub const HEIGHT: usize = Resolution::MEGACHIP.height as usize;
+
+/// Largest MegaChip ROM, as much as a 24-bit I reaches.
+pub const MAX_ROM_SIZE: usize = 1 << 24;
+
+/// How a sprite's colors combine with the pixels under them.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Blend {
+    #[default]
+    Normal,
+    /// The sprite a quarter opaque.
+    Quarter,
+    Half,
+    ThreeQuarters,
+    Add,
+    Multiply,
+}
+
+impl Blend {
+    fn apply(self, under: Rgb, over: Rgb) -> Rgb {
+        let channel = |under: u8, over: u8| -> u8 {
+            let (under, over) = (u16::from(under), u16::from(over));
+            let mixed = match self {
+                Blend::Normal => over,
+                Blend::Quarter => (under * 3 + over) / 4,
+                Blend::Half => (under + over) / 2,
+                Blend::ThreeQuarters => (under + over * 3) / 4,
+                Blend::Add => (under + over).min(0xFF),
+                Blend::Multiply => under * over / 0xFF,
+            };
+            mixed as u8
+        };
+        Rgb::new(
+            channel(under.r, over.r),
+            channel(under.g, over.g),
+            channel(under.b, over.b),
+        )
+    }
+}
+
+/// The MegaChip state of a machine, see the [module](self) documentation.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct MegaChip {
+    on: bool,
+    // the whole ROM and where it was loaded, for addresses past 4 KiB
+    rom: Vec<u8>,
+    rom_addr: u32,
+    // the address 01NN set and what it left in the CPU's I; once the
+    // program changes I itself, I is used again
+    long_index: Option<(u32, u16)>,
+    palette: Vec<Rgb>,
+    sprite_width: usize,
+    sprite_height: usize,
+    blend: Blend,
+    collision: u8,
+    // color index per pixel of the back buffer, for collisions
+    indices: Vec<u8>,
+    back: Vec<Rgb>,
+    front: Vec<Rgb>,
+}
+
+impl MegaChip {
+    /// MegaChip support for `rom`, loaded at `addr`, with the mode off.
+    pub fn new(rom: &[u8], addr: u16) -> Self {
+        let mut palette = vec![Rgb::BLACK; 256];
+        palette[1] = Rgb::WHITE;
+        MegaChip {
+            on: false,
+            rom: rom.to_vec(),
+            rom_addr: u32::from(addr),
+            long_index: None,
+            palette,
+            sprite_width: 8,
+            sprite_height: 8,
+            blend: Blend::Normal,
+            collision: 0,
+            indices: vec![0; WIDTH * HEIGHT],
+            back: vec![Rgb::BLACK; WIDTH * HEIGHT],
+            front: vec![Rgb::BLACK; WIDTH * HEIGHT],
+        }
+    }
+
+    /// Whether the program has turned MegaChip mode on.
+    pub fn is_on(&self) -> bool {
+        self.on
+    }
+
+    /// The picture last shown by 00E0, row by row, [`WIDTH`] by [`HEIGHT`].
+    pub fn frame(&self) -> &[Rgb] {
+        &self.front
+    }
+
+    /// Turns the mode off and forgets everything the program set, as when
+    /// the ROM was loaded.
+    pub fn reset(&mut self) {
+        let rom = core::mem::take(&mut self.rom);
+        *self = MegaChip::new(&rom, self.rom_addr as u16);
+    }
+
+    /// Runs `inst` if it is a MegaChip instruction, with the program counter
+    /// already past it, returning what the frontend should do; other
+    /// instructions are left to the CPU.
+    pub fn execute(&mut self, inst: u16, cpu: &mut Cpu) -> Option<Chip8Message> {
+        let nn = (inst & 0xFF) as u8;
+        if inst == 0x0011 {
+            self.on = true;
+            self.clear();
+            self.front.fill(Rgb::BLACK);
+            return Some(Chip8Message::DrawScreen(DirtyRows::ALL));
+        }
+        if !self.on {
+            return None;
+        }
+        let msg = match inst & 0xF000 {
+            0x0000 => match inst {
+                0x0010 => {
+                    self.on = false;
+                    Chip8Message::DrawScreen(DirtyRows::ALL)
+                }
+                0x00E0 => {
+                    core::mem::swap(&mut self.front, &mut self.back);
+                    self.clear();
+                    Chip8Message::DrawScreen(DirtyRows::ALL)
+                }
+                _ if inst & 0xFFF0 == 0x00B0 => {
+                    self.scroll_up(usize::from(inst & 0xF));
+                    Chip8Message::None
+                }
+                _ => match inst & 0xFF00 {
+                    0x0100 => {
+                        let low = cpu.read_word(usize::from(cpu.pc())).unwrap_or(0);
+                        cpu.set_pc(cpu.pc().wrapping_add(2));
+                        let long = u32::from(nn) << 16 | u32::from(low);
+                        cpu.set_index(low);
+                        self.long_index = Some((long, cpu.index()));
+                        Chip8Message::None
+                    }
+                    0x0200 => {
+                        let from = self.index(cpu);
+                        for n in 0..u32::from(nn) {
+                            let [_alpha, r, g, b] =
+                                [0, 1, 2, 3].map(|i| self.byte(cpu, from + n * 4 + i));
+                            self.palette[n as usize + 1] = Rgb::new(r, g, b);
+                        }
+                        Chip8Message::None
+                    }
+                    0x0300 => {
+                        self.sprite_width = if nn == 0 { 256 } else { usize::from(nn) };
+                        Chip8Message::None
+                    }
+                    0x0400 => {
+                        self.sprite_height = if nn == 0 { 256 } else { usize::from(nn) };
+                        Chip8Message::None
+                    }
+                    0x0500 | 0x0600 | 0x0700 => Chip8Message::None,
+                    0x0800 => {
+                        self.blend = match inst & 0xF {
+                            1 => Blend::Quarter,
+                            2 => Blend::Half,
+                            3 => Blend::ThreeQuarters,
+                            4 => Blend::Add,
+                            5 => Blend::Multiply,
+                            _ => Blend::Normal,
+                        };
+                        Chip8Message::None
+                    }
+                    0x0900 => {
+                        self.collision = nn;
+                        Chip8Message::None
+                    }
+                    _ => return None,
+                },
+            },
+            0xD000 => {
+                let reg = cpu.registers();
+                let x = usize::from(reg[usize::from(inst >> 8 & 0xF)]);
+                let y = usize::from(reg[usize::from(inst >> 4 & 0xF)]);
+                let hit = self.draw(cpu, x, y);
+                cpu.registers_mut()[0xF] = u8::from(hit);
+                Chip8Message::None
+            }
+            _ => return None,
+        };
+        Some(msg)
+    }
+
+    /// Where I points, with the 24 bits 01NN gave it if the program has not
+    /// set I since.
+    fn index(&self, cpu: &Cpu) -> u32 {
+        match self.long_index {
+            Some((long, short)) if short == cpu.index() => long,
+            _ => u32::from(cpu.index()),
+        }
+    }
+
+    /// The byte at `addr`: in memory below 4 KiB, else in the ROM.
+    fn byte(&self, cpu: &Cpu, addr: u32) -> u8 {
+        match cpu.mem.get(addr as usize) {
+            Some(&byte) => byte,
+            None => addr
+                .checked_sub(self.rom_addr)
+                .and_then(|offset| self.rom.get(offset as usize))
+                .copied()
+                .unwrap_or(0),
+        }
+    }
+
+    /// Draws the sprite at I into the back buffer with its top left at
+    /// (`x`, `y`), clipped at the edges, returning whether it covered a
+    /// pixel of the collision index.
+    fn draw(&mut self, cpu: &Cpu, x: usize, y: usize) -> bool {
+        let from = self.index(cpu);
+        let mut hit = false;
+        for row in 0..self.sprite_height {
+            for col in 0..self.sprite_width {
+                let offset = (row * self.sprite_width + col) as u32;
+                let index = self.byte(cpu, from.wrapping_add(offset));
+                let (px, py) = (x + col, y + row);
+                if index == 0 || px >= WIDTH || py >= HEIGHT {
+                    continue;
+                }
+                let at = py * WIDTH + px;
+                hit |= self.indices[at] == self.collision;
+                self.indices[at] = index;
+                self.back[at] = self
+                    .blend
+                    .apply(self.back[at], self.palette[usize::from(index)]);
+            }
+        }
+        hit
+    }
+
+    fn clear(&mut self) {
+        self.indices.fill(0);
+        self.back.fill(Rgb::BLACK);
+    }
+
+    fn scroll_up(&mut self, lines: usize) {
+        let shift = lines.min(HEIGHT) * WIDTH;
+        self.indices.copy_within(shift.., 0);
+        self.back.copy_within(shift.., 0);
+        let end = WIDTH * HEIGHT - shift;
+        self.indices[end..].fill(0);
+        self.back[end..].fill(Rgb::BLACK);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn run(mega: &mut MegaChip, cpu: &mut Cpu, inst: u16) -> Option<Chip8Message> {
+        cpu.set_pc(cpu.pc() + 2);
+        mega.execute(inst, cpu)
+    }
+
+    #[test]
+    fn test_mode() {
+        let mut mega = MegaChip::new(&[0; 2], 0x200);
+        let mut cpu = Cpu::new();
+        // nothing but 0011 is claimed until the mode is on
+        assert!(run(&mut mega, &mut cpu, 0x00E0).is_none());
+        assert!(run(&mut mega, &mut cpu, 0xD015).is_none());
+        assert!(run(&mut mega, &mut cpu, 0x0011).is_some());
+        assert!(mega.is_on());
+        assert!(run(&mut mega, &mut cpu, 0x00E0).is_some());
+        assert!(run(&mut mega, &mut cpu, 0x1234).is_none());
+        assert!(run(&mut mega, &mut cpu, 0x0010).is_some());
+        assert!(!mega.is_on());
+    }
+
+    #[test]
+    fn test_palette_and_sprites() {
+        // a color at 0x1000, past what the CPU can reach, and a 2x2 sprite
+        let mut rom = vec![0; 0x1000 - 0x200];
+        rom.extend([0xFF, 0x10, 0x20, 0x30, 0x01, 0x00, 0x00, 0x01]);
+        let mut mega = MegaChip::new(&rom, 0x200);
+        let mut cpu = Cpu::new();
+        // the second word of each 01NN follows it
+        cpu.mem[0x204..0x206].copy_from_slice(&[0x10, 0x00]);
+        cpu.mem[0x20A..0x20C].copy_from_slice(&[0x10, 0x04]);
+        run(&mut mega, &mut cpu, 0x0011);
+        run(&mut mega, &mut cpu, 0x0100);
+        assert_eq!(mega.index(&cpu), 0x1000);
+        run(&mut mega, &mut cpu, 0x0201);
+        assert_eq!(mega.palette[1], Rgb::new(0x10, 0x20, 0x30));
+
+        cpu.set_index(0x100);
+        assert_eq!(
+            mega.index(&cpu),
+            0x100,
+            "setting I forgets the long address"
+        );
+        run(&mut mega, &mut cpu, 0x0100);
+        assert_eq!(mega.index(&cpu), 0x1004);
+        run(&mut mega, &mut cpu, 0x0302);
+        run(&mut mega, &mut cpu, 0x0402);
+        cpu.registers_mut()[0] = 3;
+        cpu.registers_mut()[1] = 5;
+        run(&mut mega, &mut cpu, 0xD010);
+        // drawn to the back buffer, not yet shown
+        assert_eq!(mega.frame()[5 * WIDTH + 3], Rgb::BLACK);
+        run(&mut mega, &mut cpu, 0x00E0);
+        let color = Rgb::new(0x10, 0x20, 0x30);
+        assert_eq!(mega.frame()[5 * WIDTH + 3], color);
+        assert_eq!(mega.frame()[5 * WIDTH + 4], Rgb::BLACK);
+        assert_eq!(mega.frame()[6 * WIDTH + 4], color);
+        assert_eq!(mega.back, vec![Rgb::BLACK; WIDTH * HEIGHT]);
+    }
+
+    #[test]
+    fn test_collision_and_blend() {
+        let mut mega = MegaChip::new(&[0; 2], 0x200);
+        let mut cpu = Cpu::new();
+        cpu.mem[0x300] = 1;
+        cpu.set_index(0x300);
+        run(&mut mega, &mut cpu, 0x0011);
+        run(&mut mega, &mut cpu, 0x0301);
+        run(&mut mega, &mut cpu, 0x0401);
+        run(&mut mega, &mut cpu, 0x0901);
+        run(&mut mega, &mut cpu, 0xD000);
+        assert_eq!(cpu.registers()[0xF], 0);
+        run(&mut mega, &mut cpu, 0x0802);
+        run(&mut mega, &mut cpu, 0xD000);
+        assert_eq!(cpu.registers()[0xF], 1);
+        // white over white at half is still white
+        assert_eq!(mega.back[0], Rgb::WHITE);
+        assert_eq!(
+            Blend::Half.apply(Rgb::BLACK, Rgb::WHITE),
+            Rgb::new(0x7F, 0x7F, 0x7F)
+        );
+        assert_eq!(
+            Blend::Multiply.apply(Rgb::WHITE, Rgb::new(1, 2, 3)),
+            Rgb::new(1, 2, 3)
+        );
+
+        // off the edge is clipped
+        cpu.registers_mut()[0] = 0xFF;
+        cpu.registers_mut()[1] = 0xFF;
+        run(&mut mega, &mut cpu, 0xD010);
+        assert_eq!(cpu.registers()[0xF], 0);
+
+        run(&mut mega, &mut cpu, 0x00B1);
+        assert_eq!(mega.back[0], Rgb::BLACK);
+    }
+}
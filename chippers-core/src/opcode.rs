@@ -0,0 +1,855 @@
+#[derive(Debug)]
+pub struct RawOpcode {
+    op: u16,
+    x: u16,
+    y: u16,
+    n: u16,
+    kk: u16,
+}
+
+impl RawOpcode {
+    pub fn new(op: u16, x: u16, y: u16, n: u16, kk: u16) -> Self {
+        RawOpcode { op, x, y, n, kk }
+    }
+}
+
+impl core::fmt::Display for RawOpcode {
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+        writeln!(f, "{:x}{:x}{:x}{:x}", self.op, self.x, self.y, self.n)?;
+        Ok(())
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Opcode {
+    Clear,                        // 00E0
+    Jump,                         // 1NNN
+    ReturnSub,                    // 00EE
+    GotoSub,                      // 2NNN
+    SkipEqual,                    // 3XNN
+    SkipNotEqual,                 // 4XNN
+    SkipVXEqualVY,                // 5XY0
+    SkipVXNotEqualVY,             // 9XY0
+    SkipIfKey,                    // EX9E
+    SkipIfNotKey,                 // EXA1
+    GetKey,                       // FX0A
+    SetVX,                        // 6XNN
+    AddVX,                        // 7XNN, does not effect carry flag
+    SetI,                         // ANNN
+    AddI,                         // FX1E
+    JumpWithOffset, // BNNN, should add configuration for BXNN behavior for CHIP-48 programs
+    Random,         // CXNN, generate random, AND with NN, put in VX
+    Draw,           // DXYN
+    FontCharacter,  // FX29
+    SetVXToVY,      // 8XY0
+    BinaryOr,       // 8XY1
+    BinaryAnd,      // 8XY2
+    BinaryXor,      // 8XY3
+    AddVYToVX,      // 8XY4, does effect carry flag
+    SubVYFromVX,    // 8XY5, put result in VX
+    SubVXFromVY,    // 8XY7, put result in VX
+    ShiftRight,     // 8XY6, ignore VY in modern implementation
+    ShiftLeft,      // 8XYE, ignore VY in modern implementation,
+    BinaryCodedDecimalConversion, // FX33
+    SetVXToDT,      // FX07
+    SetDTToVX,      // FX15
+    SetSTToVX,      // FX18
+    SaveRegisterToMemory, // FX55
+    LoadRegisterFromMemory, // FX65
+    SaveFlags,      // FX75, SUPER-CHIP
+    LoadFlags,      // FX85, SUPER-CHIP
+}
+
+/// Every instruction as `(mask, bits, opcode)`: a word is that instruction
+/// when `word & mask == bits`. Grouped by the high nibble, which every mask
+/// covers; a word matching no entry of its group is not an instruction.
+const ENCODINGS: [&[(u16, u16, Opcode)]; 16] = [
+    &[
+        (0xFFFF, 0x00E0, Opcode::Clear),
+        (0xFFFF, 0x00EE, Opcode::ReturnSub),
+    ],
+    &[(0xF000, 0x1000, Opcode::Jump)],
+    &[(0xF000, 0x2000, Opcode::GotoSub)],
+    &[(0xF000, 0x3000, Opcode::SkipEqual)],
+    &[(0xF000, 0x4000, Opcode::SkipNotEqual)],
+    &[(0xF00F, 0x5000, Opcode::SkipVXEqualVY)],
+    &[(0xF000, 0x6000, Opcode::SetVX)],
+    &[(0xF000, 0x7000, Opcode::AddVX)],
+    &[
+        (0xF00F, 0x8000, Opcode::SetVXToVY),
+        (0xF00F, 0x8001, Opcode::BinaryOr),
+        (0xF00F, 0x8002, Opcode::BinaryAnd),
+        (0xF00F, 0x8003, Opcode::BinaryXor),
+        (0xF00F, 0x8004, Opcode::AddVYToVX),
+        (0xF00F, 0x8005, Opcode::SubVYFromVX),
+        (0xF00F, 0x8006, Opcode::ShiftRight),
+        (0xF00F, 0x8007, Opcode::SubVXFromVY),
+        (0xF00F, 0x800E, Opcode::ShiftLeft),
+    ],
+    &[(0xF00F, 0x9000, Opcode::SkipVXNotEqualVY)],
+    &[(0xF000, 0xA000, Opcode::SetI)],
+    &[(0xF000, 0xB000, Opcode::JumpWithOffset)],
+    &[(0xF000, 0xC000, Opcode::Random)],
+    &[(0xF000, 0xD000, Opcode::Draw)],
+    &[
+        (0xF0FF, 0xE09E, Opcode::SkipIfKey),
+        (0xF0FF, 0xE0A1, Opcode::SkipIfNotKey),
+    ],
+    &[
+        (0xF0FF, 0xF007, Opcode::SetVXToDT),
+        (0xF0FF, 0xF00A, Opcode::GetKey),
+        (0xF0FF, 0xF015, Opcode::SetDTToVX),
+        (0xF0FF, 0xF018, Opcode::SetSTToVX),
+        (0xF0FF, 0xF01E, Opcode::AddI),
+        (0xF0FF, 0xF029, Opcode::FontCharacter),
+        (0xF0FF, 0xF033, Opcode::BinaryCodedDecimalConversion),
+        (0xF0FF, 0xF055, Opcode::SaveRegisterToMemory),
+        (0xF0FF, 0xF065, Opcode::LoadRegisterFromMemory),
+        (0xF0FF, 0xF075, Opcode::SaveFlags),
+        (0xF0FF, 0xF085, Opcode::LoadFlags),
+    ],
+];
+
+impl Opcode {
+    /// Looks `raw` up in the table of encodings.
+    pub fn decode(raw: u16) -> Result<Opcode, DecodeError> {
+        ENCODINGS[usize::from(raw >> 12)]
+            .iter()
+            .find(|&&(mask, bits, _)| raw & mask == bits)
+            .map(|&(_, _, opcode)| opcode)
+            .ok_or(DecodeError { raw })
+    }
+
+    /// The bits of the instruction word that select this opcode, with every
+    /// operand field zero.
+    pub fn bits(self) -> u16 {
+        ENCODINGS
+            .iter()
+            .flat_map(|group| group.iter())
+            .find(|&&(_, _, opcode)| opcode == self)
+            .map(|&(_, bits, _)| bits)
+            .expect("every opcode has an encoding")
+    }
+}
+
+impl core::convert::TryFrom<&RawOpcode> for Instruction {
+    type Error = DecodeError;
+
+    fn try_from(raw_op: &RawOpcode) -> Result<Instruction, DecodeError> {
+        Instruction::decode(raw_op.op << 12 | raw_op.x << 8 | raw_op.kk)
+    }
+}
+
+impl core::convert::TryFrom<u16> for Instruction {
+    type Error = DecodeError;
+
+    fn try_from(raw: u16) -> Result<Instruction, DecodeError> {
+        Instruction::decode(raw)
+    }
+}
+
+/// A word that is not an instruction this crate knows.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct DecodeError {
+    pub raw: u16,
+}
+
+impl core::fmt::Display for DecodeError {
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+        write!(f, "unknown opcode {:04x}", self.raw)
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for DecodeError {}
+
+/// A decoded instruction with the operands it takes, each at the width it
+/// has in the instruction word: `x` and `y` are registers, `n` a nibble,
+/// `byte` the low byte and `addr` the low twelve bits.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Instruction {
+    Clear,
+    ReturnSub,
+    Jump {
+        addr: u16,
+    },
+    GotoSub {
+        addr: u16,
+    },
+    SkipEqual {
+        x: u8,
+        byte: u8,
+    },
+    SkipNotEqual {
+        x: u8,
+        byte: u8,
+    },
+    SkipVXEqualVY {
+        x: u8,
+        y: u8,
+    },
+    SkipVXNotEqualVY {
+        x: u8,
+        y: u8,
+    },
+    SkipIfKey {
+        x: u8,
+    },
+    SkipIfNotKey {
+        x: u8,
+    },
+    GetKey {
+        x: u8,
+    },
+    SetVX {
+        x: u8,
+        byte: u8,
+    },
+    AddVX {
+        x: u8,
+        byte: u8,
+    },
+    SetI {
+        addr: u16,
+    },
+    AddI {
+        x: u8,
+    },
+    /// BNNN; the high nibble of `addr` picks VX under the jump quirk.
+    JumpWithOffset {
+        addr: u16,
+    },
+    Random {
+        x: u8,
+        byte: u8,
+    },
+    Draw {
+        x: u8,
+        y: u8,
+        n: u8,
+    },
+    FontCharacter {
+        x: u8,
+    },
+    SetVXToVY {
+        x: u8,
+        y: u8,
+    },
+    BinaryOr {
+        x: u8,
+        y: u8,
+    },
+    BinaryAnd {
+        x: u8,
+        y: u8,
+    },
+    BinaryXor {
+        x: u8,
+        y: u8,
+    },
+    AddVYToVX {
+        x: u8,
+        y: u8,
+    },
+    SubVYFromVX {
+        x: u8,
+        y: u8,
+    },
+    SubVXFromVY {
+        x: u8,
+        y: u8,
+    },
+    ShiftRight {
+        x: u8,
+        y: u8,
+    },
+    ShiftLeft {
+        x: u8,
+        y: u8,
+    },
+    BinaryCodedDecimalConversion {
+        x: u8,
+    },
+    SetVXToDT {
+        x: u8,
+    },
+    SetDTToVX {
+        x: u8,
+    },
+    SetSTToVX {
+        x: u8,
+    },
+    SaveRegisterToMemory {
+        x: u8,
+    },
+    LoadRegisterFromMemory {
+        x: u8,
+    },
+    SaveFlags {
+        x: u8,
+    },
+    LoadFlags {
+        x: u8,
+    },
+}
+
+impl Instruction {
+    /// Decodes `raw`, or fails if it is not an instruction.
+    pub fn decode(raw: u16) -> Result<Self, DecodeError> {
+        use Instruction as I;
+
+        let [high, byte] = raw.to_be_bytes();
+        let (x, y, n, addr) = (high & 0xF, byte >> 4, byte & 0xF, raw & 0xFFF);
+        Ok(match Opcode::decode(raw)? {
+            Opcode::Clear => I::Clear,
+            Opcode::ReturnSub => I::ReturnSub,
+            Opcode::Jump => I::Jump { addr },
+            Opcode::GotoSub => I::GotoSub { addr },
+            Opcode::SkipEqual => I::SkipEqual { x, byte },
+            Opcode::SkipNotEqual => I::SkipNotEqual { x, byte },
+            Opcode::SkipVXEqualVY => I::SkipVXEqualVY { x, y },
+            Opcode::SkipVXNotEqualVY => I::SkipVXNotEqualVY { x, y },
+            Opcode::SkipIfKey => I::SkipIfKey { x },
+            Opcode::SkipIfNotKey => I::SkipIfNotKey { x },
+            Opcode::GetKey => I::GetKey { x },
+            Opcode::SetVX => I::SetVX { x, byte },
+            Opcode::AddVX => I::AddVX { x, byte },
+            Opcode::SetI => I::SetI { addr },
+            Opcode::AddI => I::AddI { x },
+            Opcode::JumpWithOffset => I::JumpWithOffset { addr },
+            Opcode::Random => I::Random { x, byte },
+            Opcode::Draw => I::Draw { x, y, n },
+            Opcode::FontCharacter => I::FontCharacter { x },
+            Opcode::SetVXToVY => I::SetVXToVY { x, y },
+            Opcode::BinaryOr => I::BinaryOr { x, y },
+            Opcode::BinaryAnd => I::BinaryAnd { x, y },
+            Opcode::BinaryXor => I::BinaryXor { x, y },
+            Opcode::AddVYToVX => I::AddVYToVX { x, y },
+            Opcode::SubVYFromVX => I::SubVYFromVX { x, y },
+            Opcode::SubVXFromVY => I::SubVXFromVY { x, y },
+            Opcode::ShiftRight => I::ShiftRight { x, y },
+            Opcode::ShiftLeft => I::ShiftLeft { x, y },
+            Opcode::BinaryCodedDecimalConversion => I::BinaryCodedDecimalConversion { x },
+            Opcode::SetVXToDT => I::SetVXToDT { x },
+            Opcode::SetDTToVX => I::SetDTToVX { x },
+            Opcode::SetSTToVX => I::SetSTToVX { x },
+            Opcode::SaveRegisterToMemory => I::SaveRegisterToMemory { x },
+            Opcode::LoadRegisterFromMemory => I::LoadRegisterFromMemory { x },
+            Opcode::SaveFlags => I::SaveFlags { x },
+            Opcode::LoadFlags => I::LoadFlags { x },
+        })
+    }
+
+    pub fn opcode(self) -> Opcode {
+        use Instruction as I;
+
+        match self {
+            I::Clear => Opcode::Clear,
+            I::ReturnSub => Opcode::ReturnSub,
+            I::Jump { .. } => Opcode::Jump,
+            I::GotoSub { .. } => Opcode::GotoSub,
+            I::SkipEqual { .. } => Opcode::SkipEqual,
+            I::SkipNotEqual { .. } => Opcode::SkipNotEqual,
+            I::SkipVXEqualVY { .. } => Opcode::SkipVXEqualVY,
+            I::SkipVXNotEqualVY { .. } => Opcode::SkipVXNotEqualVY,
+            I::SkipIfKey { .. } => Opcode::SkipIfKey,
+            I::SkipIfNotKey { .. } => Opcode::SkipIfNotKey,
+            I::GetKey { .. } => Opcode::GetKey,
+            I::SetVX { .. } => Opcode::SetVX,
+            I::AddVX { .. } => Opcode::AddVX,
+            I::SetI { .. } => Opcode::SetI,
+            I::AddI { .. } => Opcode::AddI,
+            I::JumpWithOffset { .. } => Opcode::JumpWithOffset,
+            I::Random { .. } => Opcode::Random,
+            I::Draw { .. } => Opcode::Draw,
+            I::FontCharacter { .. } => Opcode::FontCharacter,
+            I::SetVXToVY { .. } => Opcode::SetVXToVY,
+            I::BinaryOr { .. } => Opcode::BinaryOr,
+            I::BinaryAnd { .. } => Opcode::BinaryAnd,
+            I::BinaryXor { .. } => Opcode::BinaryXor,
+            I::AddVYToVX { .. } => Opcode::AddVYToVX,
+            I::SubVYFromVX { .. } => Opcode::SubVYFromVX,
+            I::SubVXFromVY { .. } => Opcode::SubVXFromVY,
+            I::ShiftRight { .. } => Opcode::ShiftRight,
+            I::ShiftLeft { .. } => Opcode::ShiftLeft,
+            I::BinaryCodedDecimalConversion { .. } => Opcode::BinaryCodedDecimalConversion,
+            I::SetVXToDT { .. } => Opcode::SetVXToDT,
+            I::SetDTToVX { .. } => Opcode::SetDTToVX,
+            I::SetSTToVX { .. } => Opcode::SetSTToVX,
+            I::SaveRegisterToMemory { .. } => Opcode::SaveRegisterToMemory,
+            I::LoadRegisterFromMemory { .. } => Opcode::LoadRegisterFromMemory,
+            I::SaveFlags { .. } => Opcode::SaveFlags,
+            I::LoadFlags { .. } => Opcode::LoadFlags,
+        }
+    }
+
+    /// The instruction word, the inverse of [`decode`](Instruction::decode).
+    /// Operands wider than their field are cut to it.
+    pub fn encode(self) -> u16 {
+        use Instruction as I;
+
+        let x = |x: u8| u16::from(x & 0xF) << 8;
+        let y = |y: u8| u16::from(y & 0xF) << 4;
+        let operands = match self {
+            I::Clear | I::ReturnSub => 0,
+            I::Jump { addr } | I::GotoSub { addr } | I::SetI { addr } => addr & 0xFFF,
+            I::JumpWithOffset { addr } => addr & 0xFFF,
+            I::SkipEqual { x: vx, byte }
+            | I::SkipNotEqual { x: vx, byte }
+            | I::SetVX { x: vx, byte }
+            | I::AddVX { x: vx, byte }
+            | I::Random { x: vx, byte } => x(vx) | u16::from(byte),
+            I::SkipVXEqualVY { x: vx, y: vy }
+            | I::SkipVXNotEqualVY { x: vx, y: vy }
+            | I::SetVXToVY { x: vx, y: vy }
+            | I::BinaryOr { x: vx, y: vy }
+            | I::BinaryAnd { x: vx, y: vy }
+            | I::BinaryXor { x: vx, y: vy }
+            | I::AddVYToVX { x: vx, y: vy }
+            | I::SubVYFromVX { x: vx, y: vy }
+            | I::SubVXFromVY { x: vx, y: vy }
+            | I::ShiftRight { x: vx, y: vy }
+            | I::ShiftLeft { x: vx, y: vy } => x(vx) | y(vy),
+            I::Draw { x: vx, y: vy, n } => x(vx) | y(vy) | u16::from(n & 0xF),
+            I::SkipIfKey { x: vx }
+            | I::SkipIfNotKey { x: vx }
+            | I::GetKey { x: vx }
+            | I::AddI { x: vx }
+            | I::FontCharacter { x: vx }
+            | I::BinaryCodedDecimalConversion { x: vx }
+            | I::SetVXToDT { x: vx }
+            | I::SetDTToVX { x: vx }
+            | I::SetSTToVX { x: vx }
+            | I::SaveRegisterToMemory { x: vx }
+            | I::LoadRegisterFromMemory { x: vx }
+            | I::SaveFlags { x: vx }
+            | I::LoadFlags { x: vx } => x(vx),
+        };
+        self.opcode().bits() | operands
+    }
+}
+
+/// Writes the instruction in the usual assembler syntax, such as
+/// `LD V3, #0A` or `DRW V1, V2, 5`.
+impl core::fmt::Display for Instruction {
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+        use Instruction as I;
+
+        match *self {
+            I::Clear => write!(f, "CLS"),
+            I::ReturnSub => write!(f, "RET"),
+            I::Jump { addr } => write!(f, "JP #{:03X}", addr),
+            I::GotoSub { addr } => write!(f, "CALL #{:03X}", addr),
+            I::SkipEqual { x, byte } => write!(f, "SE V{:X}, #{:02X}", x, byte),
+            I::SkipNotEqual { x, byte } => write!(f, "SNE V{:X}, #{:02X}", x, byte),
+            I::SkipVXEqualVY { x, y } => write!(f, "SE V{:X}, V{:X}", x, y),
+            I::SkipVXNotEqualVY { x, y } => write!(f, "SNE V{:X}, V{:X}", x, y),
+            I::SetVX { x, byte } => write!(f, "LD V{:X}, #{:02X}", x, byte),
+            I::AddVX { x, byte } => write!(f, "ADD V{:X}, #{:02X}", x, byte),
+            I::SetVXToVY { x, y } => write!(f, "LD V{:X}, V{:X}", x, y),
+            I::BinaryOr { x, y } => write!(f, "OR V{:X}, V{:X}", x, y),
+            I::BinaryAnd { x, y } => write!(f, "AND V{:X}, V{:X}", x, y),
+            I::BinaryXor { x, y } => write!(f, "XOR V{:X}, V{:X}", x, y),
+            I::AddVYToVX { x, y } => write!(f, "ADD V{:X}, V{:X}", x, y),
+            I::SubVYFromVX { x, y } => write!(f, "SUB V{:X}, V{:X}", x, y),
+            I::ShiftRight { x, y } => write!(f, "SHR V{:X}, V{:X}", x, y),
+            I::SubVXFromVY { x, y } => write!(f, "SUBN V{:X}, V{:X}", x, y),
+            I::ShiftLeft { x, y } => write!(f, "SHL V{:X}, V{:X}", x, y),
+            I::SetI { addr } => write!(f, "LD I, #{:03X}", addr),
+            I::JumpWithOffset { addr } => write!(f, "JP V0, #{:03X}", addr),
+            I::Random { x, byte } => write!(f, "RND V{:X}, #{:02X}", x, byte),
+            I::Draw { x, y, n } => write!(f, "DRW V{:X}, V{:X}, {}", x, y, n),
+            I::SkipIfKey { x } => write!(f, "SKP V{:X}", x),
+            I::SkipIfNotKey { x } => write!(f, "SKNP V{:X}", x),
+            I::SetVXToDT { x } => write!(f, "LD V{:X}, DT", x),
+            I::GetKey { x } => write!(f, "LD V{:X}, K", x),
+            I::SetDTToVX { x } => write!(f, "LD DT, V{:X}", x),
+            I::SetSTToVX { x } => write!(f, "LD ST, V{:X}", x),
+            I::AddI { x } => write!(f, "ADD I, V{:X}", x),
+            I::FontCharacter { x } => write!(f, "LD F, V{:X}", x),
+            I::BinaryCodedDecimalConversion { x } => write!(f, "LD B, V{:X}", x),
+            I::SaveRegisterToMemory { x } => write!(f, "LD [I], V{:X}", x),
+            I::LoadRegisterFromMemory { x } => write!(f, "LD V{:X}, [I]", x),
+            I::SaveFlags { x } => write!(f, "LD R, V{:X}", x),
+            I::LoadFlags { x } => write!(f, "LD V{:X}, R", x),
+        }
+    }
+}
+
+/// Any word as a disassembler lists it: the instruction it decodes to, or
+/// else as data, `DW #E1A2`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct Disassembly(pub u16);
+
+impl core::fmt::Display for Disassembly {
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+        match Instruction::decode(self.0) {
+            Ok(inst) => write!(f, "{}", inst),
+            Err(DecodeError { raw }) => write!(f, "DW #{:04X}", raw),
+        }
+    }
+}
+
+/// Why text did not parse as an [`Instruction`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum ParseInstructionError {
+    /// The first word is not a mnemonic of any instruction.
+    Mnemonic,
+    /// The mnemonic takes other operands, or one is out of range.
+    Operands,
+}
+
+impl core::fmt::Display for ParseInstructionError {
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+        match self {
+            ParseInstructionError::Mnemonic => write!(f, "unknown mnemonic"),
+            ParseInstructionError::Operands => write!(f, "wrong operands for the mnemonic"),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for ParseInstructionError {}
+
+/// One operand in assembler syntax.
+#[derive(Clone, Copy)]
+enum Operand {
+    V(u8),
+    Number(u16),
+    I,
+    AtI,
+    Dt,
+    St,
+    K,
+    F,
+    B,
+    R,
+}
+
+impl Operand {
+    /// Reads `V3`, `I`, `[I]`, `DT`, `ST`, `K`, `F`, `B` or `R`, or a number
+    /// in hex as `#0A` or `0x0A`, or else in decimal.
+    fn parse(text: &str) -> Option<Self> {
+        let named = [
+            ("I", Operand::I),
+            ("[I]", Operand::AtI),
+            ("DT", Operand::Dt),
+            ("ST", Operand::St),
+            ("K", Operand::K),
+            ("F", Operand::F),
+            ("B", Operand::B),
+            ("R", Operand::R),
+        ];
+        if let Some((_, operand)) = named
+            .iter()
+            .find(|(name, _)| name.eq_ignore_ascii_case(text))
+        {
+            return Some(*operand);
+        }
+        let hex = text
+            .strip_prefix('#')
+            .or_else(|| text.strip_prefix("0x"))
+            .or_else(|| text.strip_prefix("0X"));
+        match (text.as_bytes(), hex) {
+            ([b'V' | b'v', digit], _) => (*digit as char).to_digit(16).map(|x| Operand::V(x as u8)),
+            (_, Some(hex)) => u16::from_str_radix(hex, 16).ok().map(Operand::Number),
+            _ => text.parse().ok().map(Operand::Number),
+        }
+    }
+}
+
+/// Reads the assembler syntax [`Display`](core::fmt::Display) writes, such as
+/// `LD V3, #0A` or `DRW V1, V2, 5`, in either case. `DW` gives the
+/// instruction the word it is followed by decodes to.
+impl core::str::FromStr for Instruction {
+    type Err = ParseInstructionError;
+
+    fn from_str(text: &str) -> Result<Self, ParseInstructionError> {
+        use Operand::*;
+        use ParseInstructionError::Operands;
+
+        let text = text.trim();
+        let (name, rest) = text.split_once(char::is_whitespace).unwrap_or((text, ""));
+        let mut upper = [0u8; 4];
+        let name = upper
+            .get_mut(..name.len())
+            .map(|upper| {
+                upper.copy_from_slice(name.as_bytes());
+                upper.make_ascii_uppercase();
+                &*upper
+            })
+            .and_then(|name| core::str::from_utf8(name).ok())
+            .ok_or(ParseInstructionError::Mnemonic)?;
+        let mut ops = [None; 3];
+        if !rest.trim().is_empty() {
+            let mut slots = ops.iter_mut();
+            for op in rest.split(',') {
+                let slot = slots.next().ok_or(Operands)?;
+                *slot = Some(Operand::parse(op.trim()).ok_or(Operands)?);
+            }
+        }
+
+        let addr = |n: u16| if n <= 0xFFF { Ok(n) } else { Err(Operands) };
+        let byte = |n: u16| u8::try_from(n).map(u16::from).map_err(|_| Operands);
+        let vx = |x: u8| u16::from(x) << 8;
+        let vxy = |x: u8, y: u8| u16::from(x) << 8 | u16::from(y) << 4;
+        let raw = match (name, ops) {
+            ("CLS", [None, None, None]) => 0x00E0,
+            ("RET", [None, None, None]) => 0x00EE,
+            ("JP", [Some(Number(n)), None, None]) => 0x1000 | addr(n)?,
+            ("JP", [Some(V(0)), Some(Number(n)), None]) => 0xB000 | addr(n)?,
+            ("CALL", [Some(Number(n)), None, None]) => 0x2000 | addr(n)?,
+            ("SE", [Some(V(x)), Some(Number(n)), None]) => 0x3000 | vx(x) | byte(n)?,
+            ("SNE", [Some(V(x)), Some(Number(n)), None]) => 0x4000 | vx(x) | byte(n)?,
+            ("SE", [Some(V(x)), Some(V(y)), None]) => 0x5000 | vxy(x, y),
+            ("LD", [Some(V(x)), Some(Number(n)), None]) => 0x6000 | vx(x) | byte(n)?,
+            ("ADD", [Some(V(x)), Some(Number(n)), None]) => 0x7000 | vx(x) | byte(n)?,
+            ("LD", [Some(V(x)), Some(V(y)), None]) => 0x8000 | vxy(x, y),
+            ("OR", [Some(V(x)), Some(V(y)), None]) => 0x8001 | vxy(x, y),
+            ("AND", [Some(V(x)), Some(V(y)), None]) => 0x8002 | vxy(x, y),
+            ("XOR", [Some(V(x)), Some(V(y)), None]) => 0x8003 | vxy(x, y),
+            ("ADD", [Some(V(x)), Some(V(y)), None]) => 0x8004 | vxy(x, y),
+            ("SUB", [Some(V(x)), Some(V(y)), None]) => 0x8005 | vxy(x, y),
+            ("SHR", [Some(V(x)), y, None]) => match y {
+                Some(V(y)) => 0x8006 | vxy(x, y),
+                None => 0x8006 | vx(x),
+                _ => return Err(Operands),
+            },
+            ("SUBN", [Some(V(x)), Some(V(y)), None]) => 0x8007 | vxy(x, y),
+            ("SHL", [Some(V(x)), y, None]) => match y {
+                Some(V(y)) => 0x800E | vxy(x, y),
+                None => 0x800E | vx(x),
+                _ => return Err(Operands),
+            },
+            ("SNE", [Some(V(x)), Some(V(y)), None]) => 0x9000 | vxy(x, y),
+            ("LD", [Some(I), Some(Number(n)), None]) => 0xA000 | addr(n)?,
+            ("RND", [Some(V(x)), Some(Number(n)), None]) => 0xC000 | vx(x) | byte(n)?,
+            ("DRW", [Some(V(x)), Some(V(y)), Some(Number(n))]) if n <= 0xF => {
+                0xD000 | vxy(x, y) | n
+            }
+            ("SKP", [Some(V(x)), None, None]) => 0xE09E | vx(x),
+            ("SKNP", [Some(V(x)), None, None]) => 0xE0A1 | vx(x),
+            ("LD", [Some(V(x)), Some(Dt), None]) => 0xF007 | vx(x),
+            ("LD", [Some(V(x)), Some(K), None]) => 0xF00A | vx(x),
+            ("LD", [Some(Dt), Some(V(x)), None]) => 0xF015 | vx(x),
+            ("LD", [Some(St), Some(V(x)), None]) => 0xF018 | vx(x),
+            ("ADD", [Some(I), Some(V(x)), None]) => 0xF01E | vx(x),
+            ("LD", [Some(F), Some(V(x)), None]) => 0xF029 | vx(x),
+            ("LD", [Some(B), Some(V(x)), None]) => 0xF033 | vx(x),
+            ("LD", [Some(AtI), Some(V(x)), None]) => 0xF055 | vx(x),
+            ("LD", [Some(V(x)), Some(AtI), None]) => 0xF065 | vx(x),
+            ("LD", [Some(R), Some(V(x)), None]) => 0xF075 | vx(x),
+            ("LD", [Some(V(x)), Some(R), None]) => 0xF085 | vx(x),
+            ("DW", [Some(Number(n)), None, None]) => n,
+            (
+                "CLS" | "RET" | "JP" | "CALL" | "SE" | "SNE" | "LD" | "ADD" | "OR" | "AND" | "XOR"
+                | "SUB" | "SHR" | "SUBN" | "SHL" | "RND" | "DRW" | "SKP" | "SKNP" | "DW",
+                _,
+            ) => return Err(Operands),
+            _ => return Err(ParseInstructionError::Mnemonic),
+        };
+        Instruction::decode(raw).map_err(|_| Operands)
+    }
+}
+
+/// Instructions decoded on first use, one slot per aligned memory word.
+///
+/// Each slot remembers the word it was decoded from and is redecoded whenever
+/// memory holds something else, so self-modifying code and ROM reloads never
+/// run stale instructions and nothing has to report writes to memory.
+#[derive(Clone)]
+pub struct DecodeCache {
+    slots: [(u16, Result<Instruction, DecodeError>); DecodeCache::SLOTS],
+}
+
+impl core::fmt::Debug for DecodeCache {
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+        f.debug_struct("DecodeCache").finish_non_exhaustive()
+    }
+}
+
+impl Default for DecodeCache {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl DecodeCache {
+    const SLOTS: usize = 4096 / 2;
+
+    pub fn new() -> Self {
+        DecodeCache {
+            slots: [(0, Instruction::decode(0)); Self::SLOTS],
+        }
+    }
+
+    /// Returns the decoded form of `raw`, fetched from `addr`.
+    pub fn get(&mut self, addr: u16, raw: u16) -> Result<Instruction, DecodeError> {
+        let slot = &mut self.slots[(addr as usize / 2) % Self::SLOTS];
+        if slot.0 != raw {
+            *slot = (raw, Instruction::decode(raw));
+        }
+        slot.1
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_decode_cache_follows_memory() {
+        let mut cache = DecodeCache::new();
+        assert_eq!(cache.get(0x200, 0x00E0), Ok(Instruction::Clear));
+        assert_eq!(
+            cache.get(0x200, 0x1234),
+            Ok(Instruction::Jump { addr: 0x234 })
+        );
+        // an odd address shares its slot but decodes by content
+        let set = Instruction::SetVX { x: 0xA, byte: 0x07 };
+        assert_eq!(cache.get(0x201, 0x6A07), Ok(set));
+        assert_eq!(cache.get(0x200, 0x0000), Err(DecodeError { raw: 0x0000 }));
+        assert_eq!(cache.get(0x200, 0x1234), Instruction::decode(0x1234));
+    }
+
+    #[test]
+    fn test_mnemonics() {
+        let cases = [
+            (0x00E0, "CLS"),
+            (0x6A0A, "LD VA, #0A"),
+            (0xD125, "DRW V1, V2, 5"),
+            (0xA2F0, "LD I, #2F0"),
+            (0xF355, "LD [I], V3"),
+            (0xF775, "LD R, V7"),
+            (0xE1A2, "DW #E1A2"),
+            (0x0123, "DW #0123"),
+            (0x5121, "DW #5121"),
+        ];
+        for (raw, text) in cases {
+            assert_eq!(Disassembly(raw).to_string(), text);
+        }
+    }
+
+    #[test]
+    fn test_parse_mnemonics() {
+        let cases = [
+            ("CLS", 0x00E0),
+            ("ld va, #0a", 0x6A0A),
+            ("  DRW V1 , V2, 5 ", 0xD125),
+            ("LD I, 0x2F0", 0xA2F0),
+            ("JP V0, #300", 0xB300),
+            ("ADD V4, 255", 0x74FF),
+            ("SHR V3", 0x8306),
+            ("LD [I], V3", 0xF355),
+            ("LD V7, R", 0xF785),
+            ("DW #00E0", 0x00E0),
+        ];
+        for (text, raw) in cases {
+            assert_eq!(text.parse().ok(), Instruction::decode(raw).ok(), "{}", text);
+        }
+        let errors = [
+            ("NOP", ParseInstructionError::Mnemonic),
+            ("", ParseInstructionError::Mnemonic),
+            ("SYS #123", ParseInstructionError::Mnemonic),
+            ("LD V3", ParseInstructionError::Operands),
+            ("LD V3, #100", ParseInstructionError::Operands),
+            ("JP #1000", ParseInstructionError::Operands),
+            ("JP V1, #300", ParseInstructionError::Operands),
+            ("DRW V1, V2, 16", ParseInstructionError::Operands),
+            ("SE VG, 1", ParseInstructionError::Operands),
+            ("ADD V1, V2, V3, V4", ParseInstructionError::Operands),
+            ("DW #E1A2", ParseInstructionError::Operands),
+        ];
+        for (text, error) in errors {
+            assert_eq!(text.parse::<Instruction>(), Err(error), "{}", text);
+        }
+    }
+
+    #[test]
+    fn test_mnemonics_read_back() {
+        for raw in 0..=u16::MAX {
+            if let Ok(inst) = Instruction::decode(raw) {
+                assert_eq!(inst.to_string().parse(), Ok(inst), "{:04x}", raw);
+            }
+        }
+    }
+
+    #[test]
+    fn test_decode() {
+        let cases = [
+            (0x00E0, Ok(Opcode::Clear)),
+            (0x00EE, Ok(Opcode::ReturnSub)),
+            (0x0123, Err(DecodeError { raw: 0x0123 })),
+            (0x10E0, Ok(Opcode::Jump)),
+            (0x5AB0, Ok(Opcode::SkipVXEqualVY)),
+            (0x5AB1, Err(DecodeError { raw: 0x5AB1 })),
+            (0x8AB4, Ok(Opcode::AddVYToVX)),
+            (0x8ABE, Ok(Opcode::ShiftLeft)),
+            (0x8AB8, Err(DecodeError { raw: 0x8AB8 })),
+            (0x9AB0, Ok(Opcode::SkipVXNotEqualVY)),
+            (0x9ABF, Err(DecodeError { raw: 0x9ABF })),
+            (0xE19E, Ok(Opcode::SkipIfKey)),
+            (0xE1A1, Ok(Opcode::SkipIfNotKey)),
+            (0xE1A2, Err(DecodeError { raw: 0xE1A2 })),
+            (0xF00A, Ok(Opcode::GetKey)),
+            (0xF265, Ok(Opcode::LoadRegisterFromMemory)),
+            (0xF375, Ok(Opcode::SaveFlags)),
+            (0xF385, Ok(Opcode::LoadFlags)),
+            (0xF2FF, Err(DecodeError { raw: 0xF2FF })),
+        ];
+        for (raw, opcode) in cases {
+            assert_eq!(Opcode::decode(raw), opcode, "{:04x}", raw);
+            let raw_op = RawOpcode::new(
+                raw >> 12,
+                raw >> 8 & 0xF,
+                raw >> 4 & 0xF,
+                raw & 0xF,
+                raw & 0xFF,
+            );
+            let decoded = Instruction::try_from(&raw_op).map(Instruction::opcode);
+            assert_eq!(decoded, opcode, "{:04x}", raw);
+        }
+    }
+
+    #[test]
+    fn test_typed_operands() {
+        let draw = Instruction::decode(0xD12F);
+        assert_eq!(draw, Ok(Instruction::Draw { x: 1, y: 2, n: 15 }));
+        let call = Instruction::try_from(0x2ABC);
+        assert_eq!(call, Ok(Instruction::GotoSub { addr: 0xABC }));
+        let add = Instruction::decode(0x7AFE);
+        assert_eq!(add, Ok(Instruction::AddVX { x: 0xA, byte: 0xFE }));
+        let jump = Instruction::decode(0xB2F0);
+        assert_eq!(jump, Ok(Instruction::JumpWithOffset { addr: 0x2F0 }));
+        let error = Instruction::decode(0xE1A2).unwrap_err();
+        assert_eq!(error.to_string(), "unknown opcode e1a2");
+    }
+
+    #[test]
+    fn test_decode_every_word() {
+        let mut cache = DecodeCache::new();
+        for raw in 0..=u16::MAX {
+            let decoded = Instruction::decode(raw);
+            assert_eq!(cache.get(raw & 0xFFE, raw), decoded);
+            let matches = ENCODINGS
+                .iter()
+                .flat_map(|group| group.iter())
+                .filter(|&&(mask, bits, _)| raw & mask == bits)
+                .count();
+            match decoded {
+                Ok(inst) => {
+                    // the table has no overlaps, and decoding loses nothing
+                    assert_eq!(matches, 1, "{:04x}", raw);
+                    assert_eq!(inst.encode(), raw, "{:04x}", raw);
+                    assert_eq!(Opcode::decode(raw), Ok(inst.opcode()));
+                }
+                Err(error) => {
+                    assert_eq!((matches, error), (0, DecodeError { raw }));
+                    // only the groups selected by their low bits have gaps
+                    let group = raw >> 12;
+                    assert!(matches!(group, 0x0 | 0x5 | 0x8 | 0x9 | 0xE | 0xF));
+                }
+            }
+        }
+    }
+}
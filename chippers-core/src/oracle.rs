@@ -0,0 +1,201 @@
+//! Headless runs for integration tests of CHIP-8 programs.
+//!
+//! ```
+//! use chippers_core::{run_rom_until, Limits, Stop};
+//!
+//! // 6007: V0 = 7, 1202: loop forever
+//! let rom = [0x60, 0x07, 0x12, 0x02];
+//! let run = run_rom_until(&rom, |chip8| chip8.cpu.registers()[0] == 7, Limits::default())?;
+//! assert_eq!(run.stop, Stop::Condition);
+//! assert_eq!(run.instructions, 1);
+//! # Ok::<(), chippers_core::chip::LoadError>(())
+//! ```
+
+pub use crate::chip::MAX_ROM_SIZE;
+
+use crate::chip::{Chip8, Chip8Message, LoadError};
+use crate::cpu::{Cpu, Fault};
+use crate::display::PackedFrame;
+use crate::rng::Rng;
+
+/// Bounds on a headless run.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Limits {
+    /// The run stops after this many instructions if the condition never holds.
+    pub max_instructions: u64,
+    /// Instructions per 60 Hz frame; the timers tick once per frame.
+    pub instructions_per_frame: u32,
+}
+
+impl Limits {
+    /// Exactly `frames` frames at the default speed.
+    pub fn frames(frames: u64) -> Self {
+        let per_frame = Self::default().instructions_per_frame;
+        Limits {
+            max_instructions: frames * u64::from(per_frame),
+            instructions_per_frame: per_frame,
+        }
+    }
+}
+
+impl Default for Limits {
+    /// One million instructions at about 700 instructions per second.
+    fn default() -> Self {
+        Limits {
+            max_instructions: 1_000_000,
+            instructions_per_frame: 12,
+        }
+    }
+}
+
+/// A key press fed to the machine at the start of a frame.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct KeyPress {
+    pub frame: u64,
+    pub key: u8,
+}
+
+/// Why a headless run ended.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Stop {
+    /// The condition returned true.
+    Condition,
+    /// `Limits::max_instructions` instructions ran first.
+    InstructionLimit,
+    /// The program faulted; the machine is left on the faulting instruction.
+    Fault(Fault),
+}
+
+/// The machine as it was when a headless run ended.
+#[derive(Debug)]
+pub struct Run {
+    pub chip8: Chip8,
+    pub stop: Stop,
+    pub instructions: u64,
+    pub frames: u64,
+}
+
+impl Run {
+    pub fn framebuffer(&self) -> PackedFrame {
+        self.chip8.framebuffer()
+    }
+}
+
+/// Loads `rom` at 0x200 with the font set and runs it until `condition`
+/// holds, checking it after every instruction.
+///
+/// The random number generator starts from [`Rng::DEFAULT_SEED`], so the same
+/// ROM and limits always give the same run.
+///
+/// Fails without running if `rom` is empty or longer than [`MAX_ROM_SIZE`].
+pub fn run_rom_until(
+    rom: &[u8],
+    condition: impl FnMut(&Chip8) -> bool,
+    limits: Limits,
+) -> Result<Run, LoadError> {
+    run_rom_scripted(rom, &[], condition, limits)
+}
+
+/// Like [`run_rom_until`], pressing each key in `keys` at the start of its frame.
+///
+/// `keys` must be sorted by frame; a press latches until a key instruction
+/// reads it or the next press replaces it.
+pub fn run_rom_scripted(
+    rom: &[u8],
+    keys: &[KeyPress],
+    mut condition: impl FnMut(&Chip8) -> bool,
+    limits: Limits,
+) -> Result<Run, LoadError> {
+    let mut chip8 = Chip8::with_cpu(Cpu::with_rng(Rng::new(Rng::DEFAULT_SEED)));
+    chip8.load_font_set();
+    chip8.load_rom(rom)?;
+    let per_frame = u64::from(limits.instructions_per_frame.max(1));
+    let mut keys = keys.iter().peekable();
+    let mut instructions = 0;
+    let stop = loop {
+        if instructions >= limits.max_instructions {
+            break Stop::InstructionLimit;
+        }
+        let frame = instructions / per_frame;
+        if instructions % per_frame == 0 {
+            while let Some(press) = keys.next_if(|press| press.frame <= frame) {
+                chip8.cpu.press_key(press.key);
+            }
+        }
+        if let Chip8Message::Halted(fault) = chip8.step() {
+            break Stop::Fault(fault);
+        }
+        instructions += 1;
+        if instructions % per_frame == 0 {
+            chip8.tick_timers();
+        }
+        if condition(&chip8) {
+            break Stop::Condition;
+        }
+    };
+    Ok(Run {
+        chip8,
+        stop,
+        instructions,
+        frames: instructions / per_frame,
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_instruction_limit() {
+        let run = run_rom_until(&[0x12, 0x00], |_| false, Limits::default()).unwrap();
+        assert_eq!(run.stop, Stop::InstructionLimit);
+        assert_eq!(run.instructions, 1_000_000);
+        assert_eq!(run.frames, 1_000_000 / 12);
+    }
+
+    #[test]
+    fn test_runs_are_reproducible() {
+        // C0FF: V0 = random, 7101: V1 += 1, 1200: loop
+        let rom = [0xC0, 0xFF, 0x71, 0x01, 0x12, 0x00];
+        let a = run_rom_until(&rom, |_| false, Limits::frames(10)).unwrap();
+        let b = run_rom_until(&rom, |_| false, Limits::frames(10)).unwrap();
+        assert_eq!(a.frames, 10);
+        assert_eq!(a.chip8.cpu.registers(), b.chip8.cpu.registers());
+    }
+
+    #[test]
+    fn test_fault_stops_the_run() {
+        // 6001: V0 = 1, 00EE: return from nowhere
+        let run = run_rom_until(&[0x60, 0x01, 0x00, 0xEE], |_| false, Limits::default()).unwrap();
+        assert_eq!(run.stop, Stop::Fault(Fault::StackUnderflow { addr: 0x202 }));
+        assert_eq!(run.instructions, 1);
+        assert_eq!(run.chip8.cpu.pc(), 0x202);
+    }
+
+    #[test]
+    fn test_scripted_keys() {
+        // F00A: wait for a key into V0, A050: I = font, D115: draw it at (V1, V1), 1206: halt
+        let rom = [0xF0, 0x0A, 0xA0, 0x50, 0xD1, 0x15, 0x12, 0x06];
+        let keys = [KeyPress { frame: 3, key: 9 }];
+        let run =
+            run_rom_scripted(&rom, &keys, |c| c.cpu.pc() == 0x206, Limits::default()).unwrap();
+        assert_eq!(run.stop, Stop::Condition);
+        assert_eq!(run.chip8.cpu.registers()[0], 9);
+        assert_eq!(run.frames, 3);
+        // the top row of the "0" glyph
+        assert_eq!(run.framebuffer().as_1bpp()[0], 0xF0);
+    }
+
+    #[test]
+    fn test_unloadable_roms_are_errors() {
+        let run = |rom: &[u8]| run_rom_until(rom, |_| false, Limits::default()).err();
+        assert_eq!(run(&[]), Some(LoadError::Empty));
+        assert_eq!(
+            run(&[0; MAX_ROM_SIZE + 1]),
+            Some(LoadError::TooLarge {
+                size: MAX_ROM_SIZE + 1,
+                max: MAX_ROM_SIZE
+            })
+        );
+    }
+}
@@ -0,0 +1,47 @@
+//! Stable C ABI for frontends loaded from shared libraries at runtime.
+//!
+//! A plugin is a `cdylib` exporting a function named [`ENTRY_POINT`] with the
+//! signature `extern "C" fn() -> *const PluginVTable`. Only `#[repr(C)]` types
+//! cross the boundary, so plugins can be written in any language and built with
+//! any compiler version, as long as `abi_version` matches [`ABI_VERSION`].
+
+use core::ffi::{c_char, c_void};
+
+/// Bumped whenever `PluginVTable` changes shape.
+pub const ABI_VERSION: u32 = 1;
+
+/// Symbol name the host looks up in a plugin library.
+pub const ENTRY_POINT: &[u8] = b"chippers_plugin_entry\0";
+
+/// Returned by `poll_key` when no key is pending.
+pub const NO_KEY: i32 = -1;
+
+/// Returned by fallible vtable functions on success; anything else is an error.
+pub const PLUGIN_OK: i32 = 0;
+
+/// Signature of the exported entry point.
+pub type PluginEntry = unsafe extern "C" fn() -> *const PluginVTable;
+
+/// Function table describing a frontend plugin.
+///
+/// `create` returns an opaque state pointer that is passed back to every other
+/// function and finally to `destroy`.
+#[repr(C)]
+pub struct PluginVTable {
+    pub abi_version: u32,
+    /// NUL-terminated human readable name.
+    pub name: *const c_char,
+    pub create: unsafe extern "C" fn() -> *mut c_void,
+    pub destroy: unsafe extern "C" fn(state: *mut c_void),
+    pub clear_screen: unsafe extern "C" fn(state: *mut c_void) -> i32,
+    /// `pixels` holds `width * height` bytes, row-major, 0 = off and 1 = on.
+    pub draw_screen:
+        unsafe extern "C" fn(state: *mut c_void, pixels: *const u8, width: u16, height: u16) -> i32,
+    /// Returns a keypad value 0x0-0xF, [`NO_KEY`], or another negative value on error.
+    pub poll_key: unsafe extern "C" fn(state: *mut c_void) -> i32,
+    pub beep: unsafe extern "C" fn(state: *mut c_void, on: bool) -> i32,
+}
+
+// The table is immutable data describing functions, so sharing it is fine even
+// though it holds a raw pointer; this lets plugins put it in a `static`.
+unsafe impl Sync for PluginVTable {}
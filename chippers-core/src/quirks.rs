@@ -0,0 +1,137 @@
+//! Behaviors that differ between CHIP-8 implementations.
+//!
+//! Programs are written against one interpreter's quirks and may misbehave on
+//! another's. The presets match the common targets; [`Quirks::default`] is what
+//! this crate has always done.
+
+/// Which variant of each ambiguous instruction the CPU runs.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Quirks {
+    /// 8XY6 and 8XYE shift VY into VX, instead of shifting VX in place.
+    pub shift_uses_vy: bool,
+    /// BXNN jumps to XNN + VX, instead of BNNN jumping to NNN + V0.
+    pub jump_uses_vx: bool,
+    /// FX55 and FX65 leave I just past the last register they touched.
+    pub load_store_increments_i: bool,
+    /// 8XY1, 8XY2 and 8XY3 reset VF to 0.
+    pub logic_resets_vf: bool,
+    /// Sprites wrap around the edges of the screen instead of being clipped.
+    pub draw_wraps: bool,
+    /// DXYN waits for the next 60 Hz frame to begin before drawing, as the
+    /// COSMAC VIP waited for vertical blank, so at most one sprite is drawn a
+    /// frame. Games written for it may run too fast without.
+    pub display_wait: bool,
+}
+
+impl Quirks {
+    /// The original COSMAC VIP interpreter.
+    pub const COSMAC: Quirks = Quirks {
+        shift_uses_vy: true,
+        jump_uses_vx: false,
+        load_store_increments_i: true,
+        logic_resets_vf: true,
+        draw_wraps: false,
+        display_wait: true,
+    };
+
+    /// CHIP-48 on the HP-48 calculators.
+    pub const CHIP48: Quirks = Quirks {
+        shift_uses_vy: false,
+        jump_uses_vx: true,
+        load_store_increments_i: false,
+        logic_resets_vf: false,
+        draw_wraps: false,
+        display_wait: false,
+    };
+
+    /// SUPER-CHIP 1.1. As CHIP-48, except that in low resolution it waits for
+    /// vertical blank before drawing.
+    pub const SCHIP: Quirks = Quirks {
+        display_wait: true,
+        ..Quirks::CHIP48
+    };
+
+    /// Names accepted by [`preset`](Quirks::preset).
+    pub const PRESETS: [&'static str; 4] = ["chippers", "cosmac", "chip48", "schip"];
+
+    /// Names accepted by [`set`](Quirks::set).
+    pub const NAMES: [&'static str; 6] = [
+        "shift-uses-vy",
+        "jump-uses-vx",
+        "load-store-increments-i",
+        "logic-resets-vf",
+        "draw-wraps",
+        "display-wait",
+    ];
+
+    /// Looks up a preset by name, `chippers` being the default.
+    pub fn preset(name: &str) -> Option<Quirks> {
+        match name {
+            "chippers" => Some(Quirks::default()),
+            "cosmac" => Some(Quirks::COSMAC),
+            "chip48" => Some(Quirks::CHIP48),
+            "schip" => Some(Quirks::SCHIP),
+            _ => None,
+        }
+    }
+
+    /// Turns the quirk called `name` on or off, returning false if there is no
+    /// such quirk.
+    pub fn set(&mut self, name: &str, on: bool) -> bool {
+        let quirk = match name {
+            "shift-uses-vy" => &mut self.shift_uses_vy,
+            "jump-uses-vx" => &mut self.jump_uses_vx,
+            "load-store-increments-i" => &mut self.load_store_increments_i,
+            "logic-resets-vf" => &mut self.logic_resets_vf,
+            "draw-wraps" => &mut self.draw_wraps,
+            "display-wait" => &mut self.display_wait,
+            _ => return false,
+        };
+        *quirk = on;
+        true
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_presets_and_names() {
+        for name in Quirks::PRESETS {
+            assert!(Quirks::preset(name).is_some(), "{}", name);
+        }
+        assert_eq!(Quirks::preset("vip"), None);
+        let mut quirks = Quirks::default();
+        for name in Quirks::NAMES {
+            assert!(quirks.set(name, true), "{}", name);
+        }
+        assert!(!quirks.set("fast", true));
+        assert_eq!(
+            quirks,
+            Quirks {
+                shift_uses_vy: true,
+                jump_uses_vx: true,
+                load_store_increments_i: true,
+                logic_resets_vf: true,
+                draw_wraps: true,
+                display_wait: true,
+            }
+        );
+    }
+
+    #[test]
+    fn test_schip_is_not_chip48() {
+        // the same draw, before the first frame begins
+        let pc_after_draw = |name| {
+            let mut cpu = crate::cpu::Cpu::new();
+            cpu.quirks = Quirks::preset(name).unwrap();
+            cpu.set_pc(0x202);
+            cpu.execute_instruction(0xD015);
+            cpu.pc()
+        };
+        assert_eq!(pc_after_draw("chip48"), 0x202);
+        assert_eq!(pc_after_draw("schip"), 0x200);
+    }
+}
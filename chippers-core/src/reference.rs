@@ -0,0 +1,274 @@
+//! A second CHIP-8 interpreter, to check this crate's against with
+//! [`differential`](crate::differential).
+//!
+//! [`Reference`] is written as plainly as possible from the usual description
+//! of the instruction set and shares no code with the [`Cpu`](crate::cpu::Cpu)
+//! but the random number generator, so that both draw the same CXNN values,
+//! and the [`Snapshot`] it reports. Behind the `reference` feature, as only
+//! tests want it.
+//!
+//! ```
+//! use chippers_core::chip::Interpreter;
+//! use chippers_core::differential::{run_differential, Lockstep};
+//! use chippers_core::reference::Reference;
+//!
+//! // 6007: V0 = 7, 8016: V0 >>= 1, 1204: loop forever
+//! let rom = [0x60, 0x07, 0x80, 0x16, 0x12, 0x04];
+//! let mut reference = Reference::new();
+//! let mut core = Lockstep::new(Interpreter);
+//! assert_eq!(run_differential(&rom, &[], 60, 12, &mut reference, &mut core), None);
+//! ```
+
+use crate::differential::{Machine, Snapshot};
+use crate::display::PackedFrame;
+use crate::rng::Rng;
+
+const WIDTH: usize = 64;
+const HEIGHT: usize = 32;
+
+const FONT: [u8; 80] = [
+    0xF0, 0x90, 0x90, 0x90, 0xF0, // 0
+    0x20, 0x60, 0x20, 0x20, 0x70, // 1
+    0xF0, 0x10, 0xF0, 0x80, 0xF0, // 2
+    0xF0, 0x10, 0xF0, 0x10, 0xF0, // 3
+    0x90, 0x90, 0xF0, 0x10, 0x10, // 4
+    0xF0, 0x80, 0xF0, 0x10, 0xF0, // 5
+    0xF0, 0x80, 0xF0, 0x90, 0xF0, // 6
+    0xF0, 0x10, 0x20, 0x40, 0x40, // 7
+    0xF0, 0x90, 0xF0, 0x90, 0xF0, // 8
+    0xF0, 0x90, 0xF0, 0x10, 0xF0, // 9
+    0xF0, 0x90, 0xF0, 0x90, 0x90, // A
+    0xE0, 0x90, 0xE0, 0x90, 0xE0, // B
+    0xF0, 0x80, 0x80, 0x80, 0xF0, // C
+    0xE0, 0x90, 0x90, 0x90, 0xE0, // D
+    0xF0, 0x80, 0xF0, 0x80, 0xF0, // E
+    0xF0, 0x80, 0xF0, 0x80, 0x80, // F
+];
+const FONT_ADDR: usize = 0x50;
+
+/// CHIP-8 with the core's default quirks: shifts and BNNN use VX and V0,
+/// FX55 and FX65 leave I alone, sprites clip at the edges and draw at once.
+/// A fault leaves the program counter on the instruction, which then runs
+/// again forever, as the core's does.
+#[derive(Clone, Debug)]
+pub struct Reference {
+    mem: [u8; 4096],
+    v: [u8; 16],
+    i: u16,
+    pc: u16,
+    stack: [u16; 16],
+    sp: usize,
+    dt: u8,
+    st: u8,
+    screen: [[bool; WIDTH]; HEIGHT],
+    // keys pressed since a key instruction last read the keypad, one bit each
+    down: u16,
+    // the last of them, for FX0A
+    last: Option<u8>,
+    rng: Rng,
+}
+
+impl Default for Reference {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Reference {
+    pub fn new() -> Self {
+        Reference {
+            mem: [0; 4096],
+            v: [0; 16],
+            i: 0,
+            pc: 0x200,
+            stack: [0; 16],
+            sp: 0,
+            dt: 0,
+            st: 0,
+            screen: [[false; WIDTH]; HEIGHT],
+            down: 0,
+            last: None,
+            rng: Rng::new(Rng::DEFAULT_SEED),
+        }
+    }
+
+    /// Runs `op`, returning whether it faulted.
+    fn execute(&mut self, op: u16) -> bool {
+        let x = usize::from(op >> 8 & 0xF);
+        let y = usize::from(op >> 4 & 0xF);
+        let n = usize::from(op & 0xF);
+        let nn = (op & 0xFF) as u8;
+        let nnn = op & 0xFFF;
+        let i = usize::from(self.i);
+        match op >> 12 {
+            0x0 if op == 0x00E0 => self.screen = [[false; WIDTH]; HEIGHT],
+            0x0 if op == 0x00EE => {
+                if self.sp == 0 {
+                    return true;
+                }
+                self.sp -= 1;
+                self.pc = self.stack[self.sp];
+            }
+            0x1 => self.pc = nnn,
+            0x2 => {
+                if self.sp == self.stack.len() {
+                    return true;
+                }
+                self.stack[self.sp] = self.pc;
+                self.sp += 1;
+                self.pc = nnn;
+            }
+            0x3 if self.v[x] == nn => self.pc += 2,
+            0x4 if self.v[x] != nn => self.pc += 2,
+            0x5 | 0x9 if n != 0 => return true,
+            0x5 if self.v[x] == self.v[y] => self.pc += 2,
+            0x9 if self.v[x] != self.v[y] => self.pc += 2,
+            0x3 | 0x4 | 0x5 | 0x9 => {}
+            0x6 => self.v[x] = nn,
+            0x7 => self.v[x] = self.v[x].wrapping_add(nn),
+            0x8 => {
+                let (vx, vy) = (self.v[x], self.v[y]);
+                let (result, flag) = match n {
+                    0x0 => (vy, None),
+                    0x1 => (vx | vy, None),
+                    0x2 => (vx & vy, None),
+                    0x3 => (vx ^ vy, None),
+                    0x4 => (
+                        vx.wrapping_add(vy),
+                        Some(u8::from(vx as u16 + vy as u16 > 0xFF)),
+                    ),
+                    0x5 => (vx.wrapping_sub(vy), Some(u8::from(vx >= vy))),
+                    0x6 => (vx >> 1, Some(vx & 1)),
+                    0x7 => (vy.wrapping_sub(vx), Some(u8::from(vy >= vx))),
+                    0xE => (vx << 1, Some(vx >> 7)),
+                    _ => return true,
+                };
+                self.v[x] = result;
+                if let Some(flag) = flag {
+                    self.v[0xF] = flag;
+                }
+            }
+            0xA => self.i = nnn,
+            0xB => self.pc = nnn + u16::from(self.v[0]),
+            0xC => self.v[x] = self.rng.next_u8() & nn,
+            0xD => {
+                if n > 0 && i + n > self.mem.len() {
+                    return true;
+                }
+                let (left, top) = (
+                    usize::from(self.v[x]) % WIDTH,
+                    usize::from(self.v[y]) % HEIGHT,
+                );
+                let mut erased = false;
+                for row in 0..n {
+                    for col in 0..8 {
+                        let (px, py) = (left + col, top + row);
+                        if px >= WIDTH || py >= HEIGHT || self.mem[i + row] & 0x80 >> col == 0 {
+                            continue;
+                        }
+                        erased |= self.screen[py][px];
+                        self.screen[py][px] ^= true;
+                    }
+                }
+                self.v[0xF] = u8::from(erased);
+            }
+            0xE if nn == 0x9E || nn == 0xA1 => {
+                let down = self.down & 1 << (self.v[x] & 0xF) != 0;
+                if down == (nn == 0x9E) {
+                    self.pc += 2;
+                }
+                self.release_keys();
+            }
+            0xF => match nn {
+                0x07 => self.v[x] = self.dt,
+                0x0A => {
+                    match self.last.take() {
+                        Some(key) => self.v[x] = key,
+                        None => self.pc -= 2,
+                    }
+                    self.release_keys();
+                }
+                0x15 => self.dt = self.v[x],
+                0x18 => self.st = self.v[x],
+                0x1E => {
+                    self.i += u16::from(self.v[x]);
+                    if self.i >= 0x1000 {
+                        self.v[0xF] = 1;
+                    }
+                }
+                0x29 => self.i = (FONT_ADDR + usize::from(self.v[x]) * 5) as u16,
+                0x33 => {
+                    if i + 3 > self.mem.len() {
+                        return true;
+                    }
+                    let value = self.v[x];
+                    self.mem[i..i + 3].copy_from_slice(&[value / 100, value / 10 % 10, value % 10]);
+                }
+                0x55 | 0x65 if i + x + 1 > self.mem.len() => return true,
+                0x55 => self.mem[i..=i + x].copy_from_slice(&self.v[..=x]),
+                0x65 => self.v[..=x].copy_from_slice(&self.mem[i..=i + x]),
+                _ => return true,
+            },
+            _ => return true,
+        }
+        false
+    }
+
+    /// Presses last only until a key instruction has seen them.
+    fn release_keys(&mut self) {
+        if self.down != 0 {
+            self.last = None;
+        }
+        self.down = 0;
+    }
+}
+
+impl Machine for Reference {
+    /// Panics if `rom` does not fit in memory from 0x200.
+    fn load(&mut self, rom: &[u8]) {
+        *self = Reference::new();
+        self.mem[FONT_ADDR..FONT_ADDR + FONT.len()].copy_from_slice(&FONT);
+        self.mem[0x200..0x200 + rom.len()].copy_from_slice(rom);
+    }
+
+    fn press_key(&mut self, key: u8) {
+        self.down |= 1 << (key & 0xF);
+        self.last = Some(key & 0xF);
+    }
+
+    fn step(&mut self) {
+        let pc = usize::from(self.pc);
+        if pc + 1 >= self.mem.len() {
+            return;
+        }
+        let op = u16::from_be_bytes([self.mem[pc], self.mem[pc + 1]]);
+        self.pc += 2;
+        if self.execute(op) {
+            self.pc -= 2;
+        }
+    }
+
+    fn tick_timers(&mut self) {
+        self.dt = self.dt.saturating_sub(1);
+        self.st = self.st.saturating_sub(1);
+    }
+
+    fn snapshot(&self) -> Snapshot {
+        let mut bits = [0u8; WIDTH * HEIGHT / 8];
+        for (y, row) in self.screen.iter().enumerate() {
+            for (x, lit) in row.iter().enumerate() {
+                if *lit {
+                    bits[(y * WIDTH + x) / 8] |= 0x80 >> (x % 8);
+                }
+            }
+        }
+        Snapshot {
+            pc: self.pc,
+            index: self.i,
+            registers: self.v,
+            dt: self.dt,
+            st: self.st,
+            frame: PackedFrame::from_1bpp(WIDTH, HEIGHT, &bits).unwrap(),
+        }
+    }
+}
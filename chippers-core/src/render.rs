@@ -0,0 +1,369 @@
+//! Drawing on a thread of its own, so a slow display never holds up the CPU.
+//!
+//! [`RenderThread`] wraps any [`DisplayBackend`] and hands frames to a render
+//! thread through a single slot rather than a queue. When the backend falls
+//! behind, a new frame replaces the one still waiting: only the most recent
+//! frame is drawn, with the dirty rows of every frame it replaced, and the
+//! dropped frames are counted.
+
+use crate::backend::{Capabilities, DisplayBackend, Palette, Resolution, Rgb};
+use crate::display::{DirtyRows, Display};
+use std::collections::VecDeque;
+use std::string::String;
+use std::sync::{Arc, Condvar, Mutex, MutexGuard};
+use std::thread::JoinHandle;
+use std::time::Duration;
+use std::vec::Vec;
+
+/// Calls that are not frames, run in order before the next frame is drawn.
+#[derive(Debug)]
+enum Command {
+    Resolution(Resolution),
+    Palette(Palette),
+    Beep(bool),
+    Title(String),
+    Invalidate,
+    Color(Resolution, Vec<Rgb>),
+}
+
+struct State<E> {
+    frame: Option<(Display, DirtyRows)>,
+    commands: VecDeque<Command>,
+    // the render thread is drawing something it has taken out of the slot
+    busy: bool,
+    closing: bool,
+    error: Option<E>,
+    drawn: u64,
+    skipped: u64,
+}
+
+impl<E> State<E> {
+    fn idle(&self) -> bool {
+        self.frame.is_none() && self.commands.is_empty() && !self.busy
+    }
+}
+
+struct Shared<E> {
+    state: Mutex<State<E>>,
+    wake: Condvar,
+}
+
+impl<E> Shared<E> {
+    fn lock(&self) -> MutexGuard<'_, State<E>> {
+        // a panic in the backend already ended the render thread; the state
+        // itself is always consistent
+        self.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+/// A [`DisplayBackend`] that draws through `D` on a separate thread.
+///
+/// Drawing only copies the display into the slot and returns. An error from
+/// the backend stops the render thread and is returned by the next call.
+pub struct RenderThread<D: DisplayBackend> {
+    shared: Arc<Shared<D::Error>>,
+    thread: Option<JoinHandle<D>>,
+}
+
+impl<D: DisplayBackend> core::fmt::Debug for RenderThread<D> {
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+        let state = self.shared.lock();
+        f.debug_struct("RenderThread")
+            .field("drawn", &state.drawn)
+            .field("skipped", &state.skipped)
+            .finish()
+    }
+}
+
+impl<D> RenderThread<D>
+where
+    D: DisplayBackend + Send + 'static,
+    D::Error: Send + 'static,
+{
+    /// Moves `backend` onto a new render thread.
+    pub fn spawn(backend: D) -> Self {
+        let shared = Arc::new(Shared {
+            state: Mutex::new(State {
+                frame: None,
+                commands: VecDeque::new(),
+                busy: false,
+                closing: false,
+                error: None,
+                drawn: 0,
+                skipped: 0,
+            }),
+            wake: Condvar::new(),
+        });
+        let thread = {
+            let shared = Arc::clone(&shared);
+            std::thread::spawn(move || render(backend, &shared))
+        };
+        RenderThread {
+            shared,
+            thread: Some(thread),
+        }
+    }
+}
+
+impl<D: DisplayBackend> RenderThread<D> {
+    /// Frames the render thread has drawn.
+    pub fn drawn_frames(&self) -> u64 {
+        self.shared.lock().drawn
+    }
+
+    /// Frames replaced by a newer one before the render thread got to them.
+    pub fn skipped_frames(&self) -> u64 {
+        self.shared.lock().skipped
+    }
+
+    /// Waits until everything sent so far has been drawn.
+    pub fn sync(&mut self) -> core::result::Result<(), D::Error> {
+        let mut state = self.shared.lock();
+        while !state.idle() && state.error.is_none() && !self.finished() {
+            // a panicking backend ends the thread without waking us
+            state = self
+                .shared
+                .wake
+                .wait_timeout(state, Duration::from_millis(10))
+                .unwrap_or_else(|e| e.into_inner())
+                .0;
+        }
+        state.error.take().map_or(Ok(()), Err)
+    }
+
+    /// Stops the render thread once it has drawn everything sent so far and
+    /// gives the backend back, or `None` if the backend panicked.
+    pub fn into_inner(mut self) -> Option<D> {
+        self.stop()
+    }
+
+    fn finished(&self) -> bool {
+        self.thread.as_ref().is_none_or(|t| t.is_finished())
+    }
+
+    fn stop(&mut self) -> Option<D> {
+        self.shared.lock().closing = true;
+        self.shared.wake.notify_all();
+        self.thread.take()?.join().ok()
+    }
+
+    fn send(
+        &mut self,
+        update: impl FnOnce(&mut State<D::Error>),
+    ) -> core::result::Result<(), D::Error> {
+        let mut state = self.shared.lock();
+        if let Some(err) = state.error.take() {
+            return Err(err);
+        }
+        update(&mut state);
+        drop(state);
+        self.shared.wake.notify_all();
+        Ok(())
+    }
+
+    fn send_frame(
+        &mut self,
+        display: &Display,
+        rows: DirtyRows,
+    ) -> core::result::Result<(), D::Error> {
+        self.send(|state| {
+            let rows = match state.frame.take() {
+                Some((_, pending)) => {
+                    state.skipped += 1;
+                    rows.union(pending)
+                }
+                None => rows,
+            };
+            state.frame = Some((display.clone(), rows));
+        })
+    }
+}
+
+impl<D: DisplayBackend> Drop for RenderThread<D> {
+    fn drop(&mut self) {
+        self.stop();
+    }
+}
+
+fn render<D: DisplayBackend>(mut backend: D, shared: &Shared<D::Error>) -> D {
+    let mut state = shared.lock();
+    loop {
+        while state.idle() && !state.closing {
+            state = shared.wake.wait(state).unwrap_or_else(|e| e.into_inner());
+        }
+        if state.idle() {
+            return backend;
+        }
+        let commands = core::mem::take(&mut state.commands);
+        let frame = state.frame.take();
+        state.busy = true;
+        drop(state);
+
+        let mut result = Ok(());
+        for command in commands {
+            result = result.and_then(|()| match command {
+                Command::Resolution(resolution) => backend.set_resolution(resolution),
+                Command::Palette(palette) => backend.set_palette(palette),
+                Command::Beep(on) => backend.beep(on),
+                Command::Title(title) => backend.set_title(&title),
+                Command::Invalidate => backend.invalidate(),
+                Command::Color(resolution, pixels) => backend.draw_color(resolution, &pixels),
+            });
+        }
+        let drew = frame.is_some();
+        if let Some((display, rows)) = frame {
+            result = result.and_then(|()| backend.draw_rows(&display, rows));
+        }
+
+        state = shared.lock();
+        state.busy = false;
+        state.drawn += u64::from(drew);
+        let failed = result.is_err();
+        state.error = result.err();
+        shared.wake.notify_all();
+        if failed {
+            return backend;
+        }
+    }
+}
+
+impl<D: DisplayBackend> DisplayBackend for RenderThread<D> {
+    type Error = D::Error;
+    const CAPABILITIES: Capabilities = D::CAPABILITIES;
+
+    /// Sent as a blank frame, so it replaces any frame still waiting.
+    fn clear_screen(&mut self) -> core::result::Result<(), Self::Error> {
+        self.send_frame(&Display::new(), DirtyRows::ALL)
+    }
+
+    fn draw_screen(&mut self, display: &Display) -> core::result::Result<(), Self::Error> {
+        self.send_frame(display, DirtyRows::ALL)
+    }
+
+    fn draw_rows(
+        &mut self,
+        display: &Display,
+        rows: DirtyRows,
+    ) -> core::result::Result<(), Self::Error> {
+        self.send_frame(display, rows)
+    }
+
+    fn set_resolution(&mut self, resolution: Resolution) -> core::result::Result<(), Self::Error> {
+        self.send(|state| state.commands.push_back(Command::Resolution(resolution)))
+    }
+
+    fn set_palette(&mut self, palette: Palette) -> core::result::Result<(), Self::Error> {
+        self.send(|state| state.commands.push_back(Command::Palette(palette)))
+    }
+
+    fn beep(&mut self, on: bool) -> core::result::Result<(), Self::Error> {
+        self.send(|state| state.commands.push_back(Command::Beep(on)))
+    }
+
+    fn set_title(&mut self, title: &str) -> core::result::Result<(), Self::Error> {
+        self.send(|state| state.commands.push_back(Command::Title(title.into())))
+    }
+
+    fn invalidate(&mut self) -> core::result::Result<(), Self::Error> {
+        self.send(|state| state.commands.push_back(Command::Invalidate))
+    }
+
+    /// Replaces any frame or picture still waiting, as the picture covers
+    /// the whole screen.
+    fn draw_color(
+        &mut self,
+        resolution: Resolution,
+        pixels: &[Rgb],
+    ) -> core::result::Result<(), Self::Error> {
+        self.send(|state| {
+            let waiting = state.commands.len();
+            state
+                .commands
+                .retain(|command| !matches!(command, Command::Color(..)));
+            let replaced = waiting - state.commands.len() + usize::from(state.frame.is_some());
+            state.skipped += replaced as u64;
+            state.frame = None;
+            state
+                .commands
+                .push_back(Command::Color(resolution, pixels.to_vec()));
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::vec::Vec;
+
+    /// Takes a while over every frame and remembers what it drew.
+    struct Slow {
+        frames: Vec<(Display, DirtyRows)>,
+        fail: bool,
+    }
+
+    impl DisplayBackend for Slow {
+        type Error = &'static str;
+
+        fn clear_screen(&mut self) -> core::result::Result<(), Self::Error> {
+            self.draw_screen(&Display::new())
+        }
+
+        fn draw_screen(&mut self, display: &Display) -> core::result::Result<(), Self::Error> {
+            self.draw_rows(display, DirtyRows::ALL)
+        }
+
+        fn draw_rows(
+            &mut self,
+            display: &Display,
+            rows: DirtyRows,
+        ) -> core::result::Result<(), Self::Error> {
+            if self.fail {
+                return Err("broken");
+            }
+            std::thread::sleep(Duration::from_millis(20));
+            self.frames.push((display.clone(), rows));
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_slow_backend_draws_latest_frame() {
+        let mut render = RenderThread::spawn(Slow {
+            frames: Vec::new(),
+            fail: false,
+        });
+        let mut display = Display::new();
+        for y in 0..Display::HEIGHT {
+            display.set_pixel(0, y, true);
+            let mut rows = DirtyRows::NONE;
+            rows.insert(y);
+            render.draw_rows(&display, rows).unwrap();
+        }
+        render.sync().unwrap();
+        assert!(render.skipped_frames() > 0);
+        assert_eq!(
+            render.drawn_frames() + render.skipped_frames(),
+            Display::HEIGHT as u64
+        );
+
+        let backend = render.into_inner().unwrap();
+        let (last, _) = backend.frames.last().unwrap();
+        assert_eq!(last, &display);
+        // no row was lost with the frames that were dropped
+        let rows = backend
+            .frames
+            .iter()
+            .fold(DirtyRows::NONE, |all, (_, rows)| all.union(*rows));
+        assert_eq!(rows, DirtyRows::ALL);
+    }
+
+    #[test]
+    fn test_backend_error_is_returned() {
+        let mut render = RenderThread::spawn(Slow {
+            frames: Vec::new(),
+            fail: true,
+        });
+        render.clear_screen().unwrap();
+        assert_eq!(render.sync(), Err("broken"));
+    }
+}
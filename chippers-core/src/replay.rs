@@ -0,0 +1,192 @@
+//! Recorded input, to play a session back exactly as it went.
+//!
+//! A program only sees the outside world through the random number generator,
+//! the keys and the timers. A [`Replay`] holds the seed the generator started
+//! from and every key press, timer tick and reset, each stamped with the
+//! number of instructions run before it. [`Chip8::record`] fills one in as
+//! [`Chip8::run_with`] runs, and [`Chip8::play`] feeds one back in place of the
+//! frontend's keys and the wall clock.
+//!
+//! Replays are written as text, an event a line:
+//!
+//! ```text
+//! chippers replay 1
+//! seed 2545f491
+//! 812 tick
+//! 1630 key 5
+//! 1630 tick
+//! 2100 reset
+//! ```
+//!
+//! Loading a saved state while recording is not recorded, so a replay of such
+//! a session goes its own way from there.
+//!
+//! [`Chip8::record`]: crate::chip::Chip8::record
+//! [`Chip8::play`]: crate::chip::Chip8::play
+//! [`Chip8::run_with`]: crate::chip::Chip8::run_with
+
+use std::string::{String, ToString};
+use std::vec::Vec;
+
+/// The format version [`Replay`]'s `Display` writes.
+pub const REPLAY_VERSION: u32 = 1;
+
+/// Something from outside the program that a replay repeats.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Event {
+    /// The frontend pressed a keypad key.
+    Key(u8),
+    /// The delay and sound timers ticked.
+    Tick,
+    /// The machine started over, see [`Chip8::reset`](crate::chip::Chip8::reset).
+    Reset,
+}
+
+/// A seed and the events that followed it, in order.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Replay {
+    pub seed: u32,
+    /// Each event with the instructions run, since the start or the last
+    /// reset, before it.
+    pub events: Vec<(u64, Event)>,
+}
+
+impl Replay {
+    pub fn new(seed: u32) -> Self {
+        Replay {
+            seed,
+            events: Vec::new(),
+        }
+    }
+
+    /// Reads a replay written by its `Display`.
+    pub fn parse(text: &str) -> core::result::Result<Self, ReplayError> {
+        let mut lines = text
+            .lines()
+            .enumerate()
+            .map(|(i, line)| (i + 1, line.trim()));
+        match lines.next() {
+            Some((_, header)) if header.starts_with("chippers replay ") => {
+                let version = &header["chippers replay ".len()..];
+                if version.parse() != Ok(REPLAY_VERSION) {
+                    return Err(ReplayError::Version(version.to_string()));
+                }
+            }
+            _ => return Err(ReplayError::NotAReplay),
+        }
+        let seed = match lines.next() {
+            Some((n, line)) => line
+                .strip_prefix("seed ")
+                .and_then(|seed| u32::from_str_radix(seed, 16).ok())
+                .ok_or(ReplayError::Line(n))?,
+            None => return Err(ReplayError::Line(2)),
+        };
+        let mut replay = Replay::new(seed);
+        for (n, line) in lines.filter(|(_, line)| !line.is_empty()) {
+            let mut words = line.split_whitespace();
+            let at = words.next().and_then(|at| at.parse().ok());
+            let event = match (words.next(), words.next()) {
+                (Some("tick"), None) => Some(Event::Tick),
+                (Some("reset"), None) => Some(Event::Reset),
+                (Some("key"), Some(key)) => u8::from_str_radix(key, 16)
+                    .ok()
+                    .filter(|key| *key < 16)
+                    .map(Event::Key),
+                _ => None,
+            };
+            match (at, event, words.next()) {
+                (Some(at), Some(event), None) => replay.events.push((at, event)),
+                _ => return Err(ReplayError::Line(n)),
+            }
+        }
+        Ok(replay)
+    }
+}
+
+impl core::fmt::Display for Replay {
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+        writeln!(f, "chippers replay {}", REPLAY_VERSION)?;
+        writeln!(f, "seed {:08x}", self.seed)?;
+        for (at, event) in &self.events {
+            match event {
+                Event::Key(key) => writeln!(f, "{} key {:x}", at, key)?,
+                Event::Tick => writeln!(f, "{} tick", at)?,
+                Event::Reset => writeln!(f, "{} reset", at)?,
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Why [`Replay::parse`] refused a replay.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ReplayError {
+    /// The text does not start like a replay.
+    NotAReplay,
+    /// Written in a format version this build does not read.
+    Version(String),
+    /// The line with this number is not an event.
+    Line(usize),
+}
+
+impl core::fmt::Display for ReplayError {
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+        match self {
+            ReplayError::NotAReplay => writeln!(f, "error: not a chippers replay"),
+            ReplayError::Version(version) => writeln!(
+                f,
+                "error: replay is version {}, only version {} can be played",
+                version, REPLAY_VERSION
+            ),
+            ReplayError::Line(n) => writeln!(f, "error: replay line {} is not an event", n),
+        }
+    }
+}
+
+impl std::error::Error for ReplayError {}
+
+/// What [`Chip8::run_with`](crate::chip::Chip8::run_with) does with input.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub(crate) enum Session {
+    /// Takes keys from the frontend and ticks by the wall clock.
+    #[default]
+    Live,
+    /// Runs live, noting down the input.
+    Recording(Replay),
+    /// Takes keys and ticks from a replay, `next` being the next event to
+    /// play, then runs live once it is over.
+    Playing { replay: Replay, next: usize },
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_replay_text() {
+        let replay = Replay {
+            seed: 0x2545_F491,
+            events: vec![
+                (812, Event::Tick),
+                (1630, Event::Key(0xA)),
+                (1630, Event::Tick),
+                (2100, Event::Reset),
+            ],
+        };
+        let text = replay.to_string();
+        assert_eq!(
+            text,
+            "chippers replay 1\nseed 2545f491\n812 tick\n1630 key a\n1630 tick\n2100 reset\n"
+        );
+        assert_eq!(Replay::parse(&text), Ok(replay));
+        assert_eq!(Replay::parse("hello"), Err(ReplayError::NotAReplay));
+        assert_eq!(
+            Replay::parse("chippers replay 2\nseed 1\n"),
+            Err(ReplayError::Version("2".to_string()))
+        );
+        assert_eq!(
+            Replay::parse("chippers replay 1\nseed 1\n5 key 10\n"),
+            Err(ReplayError::Line(3))
+        );
+    }
+}
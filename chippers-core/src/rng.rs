@@ -0,0 +1,82 @@
+/// Small xorshift PRNG backing the CXNN instruction.
+///
+/// Keeping this inside the core means the interpreter needs no OS entropy
+/// source; hosts with one can seed it through [`Rng::new`].
+#[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Rng {
+    state: u32,
+}
+
+impl Rng {
+    /// Seed used when no other entropy is injected.
+    pub const DEFAULT_SEED: u32 = 0x2545_F491;
+
+    pub fn new(seed: u32) -> Self {
+        // xorshift gets stuck on an all-zero state
+        let state = if seed == 0 { Self::DEFAULT_SEED } else { seed };
+        Rng { state }
+    }
+
+    /// Seeds from the host's entropy source.
+    #[cfg(feature = "rand")]
+    pub fn from_entropy() -> Self {
+        Self::new(rand::random())
+    }
+
+    /// Seeds from the sub-second part of the system clock.
+    #[cfg(feature = "std")]
+    pub fn from_clock() -> Self {
+        let nanos = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map(|d| d.subsec_nanos())
+            .unwrap_or(0);
+        Self::new(nanos)
+    }
+
+    /// A seed for [`Rng::new`] from the host's entropy source, or from the
+    /// clock without the `rand` feature.
+    #[cfg(feature = "std")]
+    pub fn fresh_seed() -> u32 {
+        #[cfg(feature = "rand")]
+        return rand::random();
+        #[cfg(not(feature = "rand"))]
+        return Self::from_clock().state;
+    }
+
+    /// The current state, which [`Rng::new`] resumes from.
+    #[cfg(feature = "std")]
+    pub(crate) fn state(&self) -> u32 {
+        self.state
+    }
+
+    pub fn next_u8(&mut self) -> u8 {
+        let mut x = self.state;
+        x ^= x << 13;
+        x ^= x >> 17;
+        x ^= x << 5;
+        self.state = x;
+        (x >> 24) as u8
+    }
+}
+
+#[cfg(feature = "rand")]
+impl Default for Rng {
+    fn default() -> Self {
+        Self::from_entropy()
+    }
+}
+
+#[cfg(all(feature = "std", not(feature = "rand")))]
+impl Default for Rng {
+    fn default() -> Self {
+        Self::from_clock()
+    }
+}
+
+#[cfg(not(feature = "std"))]
+impl Default for Rng {
+    fn default() -> Self {
+        Self::new(Self::DEFAULT_SEED)
+    }
+}
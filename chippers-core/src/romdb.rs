@@ -0,0 +1,253 @@
+//! What is known about particular ROMs, looked up by the SHA-1 of their
+//! bytes so a renamed file is still recognised.
+//!
+//! The `chippers` binary runs a ROM it finds here with the quirks and speed
+//! the ROM was written for, unless told otherwise.
+//!
+//! ```
+//! use chippers_core::romdb::{self, Sha1};
+//!
+//! let rom = [0x00, 0xE0, 0x12, 0x02];
+//! assert_eq!(Sha1::of(&rom).to_string().len(), 40);
+//! assert!(romdb::lookup(&rom).is_none());
+//! ```
+
+/// A SHA-1 digest, shown in lowercase hex.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct Sha1(pub [u8; 20]);
+
+impl Sha1 {
+    /// The digest of `data`.
+    pub fn of(data: &[u8]) -> Self {
+        let mut state: [u32; 5] = [
+            0x6745_2301,
+            0xEFCD_AB89,
+            0x98BA_DCFE,
+            0x1032_5476,
+            0xC3D2_E1F0,
+        ];
+        // the data, a one bit, zeros and the length in bits, in 64-byte blocks
+        let blocks = (data.len() + 8) / 64 + 1;
+        for n in 0..blocks {
+            let mut block = [0u8; 64];
+            for (i, byte) in block.iter_mut().enumerate() {
+                let at = n * 64 + i;
+                *byte = match at.cmp(&data.len()) {
+                    core::cmp::Ordering::Less => data[at],
+                    core::cmp::Ordering::Equal => 0x80,
+                    core::cmp::Ordering::Greater => 0,
+                };
+            }
+            if n == blocks - 1 {
+                let bits = (data.len() as u64).wrapping_mul(8);
+                block[56..].copy_from_slice(&bits.to_be_bytes());
+            }
+            compress(&mut state, &block);
+        }
+        let mut digest = [0; 20];
+        for (bytes, word) in digest.chunks_exact_mut(4).zip(state) {
+            bytes.copy_from_slice(&word.to_be_bytes());
+        }
+        Sha1(digest)
+    }
+
+    /// Reads 40 hex digits, for digests written into the source.
+    ///
+    /// # Panics
+    ///
+    /// If `hex` is not 40 hex digits; in a constant, that fails the build.
+    pub const fn from_hex(hex: &str) -> Self {
+        let hex = hex.as_bytes();
+        assert!(hex.len() == 40, "a SHA-1 is 40 hex digits");
+        let mut digest = [0; 20];
+        let mut i = 0;
+        while i < 20 {
+            digest[i] = nibble(hex[i * 2]) << 4 | nibble(hex[i * 2 + 1]);
+            i += 1;
+        }
+        Sha1(digest)
+    }
+}
+
+const fn nibble(digit: u8) -> u8 {
+    match digit {
+        b'0'..=b'9' => digit - b'0',
+        b'a'..=b'f' => digit - b'a' + 10,
+        b'A'..=b'F' => digit - b'A' + 10,
+        _ => panic!("not a hex digit"),
+    }
+}
+
+fn compress(state: &mut [u32; 5], block: &[u8; 64]) {
+    let mut w = [0u32; 80];
+    for (word, bytes) in w.iter_mut().zip(block.chunks_exact(4)) {
+        *word = u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]);
+    }
+    for i in 16..80 {
+        w[i] = (w[i - 3] ^ w[i - 8] ^ w[i - 14] ^ w[i - 16]).rotate_left(1);
+    }
+    let [mut a, mut b, mut c, mut d, mut e] = *state;
+    for (i, word) in w.iter().enumerate() {
+        let (f, k) = match i {
+            0..=19 => ((b & c) | (!b & d), 0x5A82_7999),
+            20..=39 => (b ^ c ^ d, 0x6ED9_EBA1),
+            40..=59 => ((b & c) | (b & d) | (c & d), 0x8F1B_BCDC),
+            _ => (b ^ c ^ d, 0xCA62_C1D6),
+        };
+        let temp = a
+            .rotate_left(5)
+            .wrapping_add(f)
+            .wrapping_add(e)
+            .wrapping_add(k)
+            .wrapping_add(*word);
+        (e, d, c, b, a) = (d, c, b.rotate_left(30), a, temp);
+    }
+    for (word, add) in state.iter_mut().zip([a, b, c, d, e]) {
+        *word = word.wrapping_add(add);
+    }
+}
+
+impl core::fmt::Display for Sha1 {
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+        for byte in self.0 {
+            write!(f, "{:02x}", byte)?;
+        }
+        Ok(())
+    }
+}
+
+/// A ROM in the database.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct KnownRom {
+    pub sha1: Sha1,
+    pub title: &'static str,
+    pub author: Option<&'static str>,
+    pub year: Option<u16>,
+    /// The [quirks preset](crate::quirks::Quirks::preset) the ROM was
+    /// written for, if it needs one.
+    pub quirks: Option<&'static str>,
+    /// Instructions a second the ROM plays best at, if not the default.
+    pub speed: Option<u32>,
+}
+
+impl core::fmt::Display for KnownRom {
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+        write!(f, "{}", self.title)?;
+        if let Some(author) = self.author {
+            write!(f, " by {}", author)?;
+        }
+        if let Some(year) = self.year {
+            write!(f, ", {}", year)?;
+        }
+        Ok(())
+    }
+}
+
+/// The ROMs this build knows.
+pub const KNOWN_ROMS: [KnownRom; 7] = [
+    KnownRom {
+        sha1: Sha1::from_hex("1ba58656810b67fd131eb9af3e3987863bf26c90"),
+        title: "IBM Logo",
+        author: None,
+        year: None,
+        quirks: None,
+        speed: None,
+    },
+    KnownRom {
+        sha1: Sha1::from_hex("8b70080adbac44513ec60005734a816372b845ec"),
+        title: "Maze (alt)",
+        author: Some("David Winter"),
+        year: None,
+        quirks: None,
+        speed: None,
+    },
+    KnownRom {
+        sha1: Sha1::from_hex("a0073e944d5ae9ca14324543fdf818907de80449"),
+        title: "Sierpinski",
+        author: Some("Sergey Naydenov"),
+        year: Some(2010),
+        quirks: None,
+        speed: None,
+    },
+    KnownRom {
+        sha1: Sha1::from_hex("f13766c14aeb02ad8d4d103cb5eadd282d20cddc"),
+        title: "Brix",
+        author: Some("Andreas Gustafsson"),
+        year: Some(1990),
+        quirks: Some("chip48"),
+        speed: None,
+    },
+    KnownRom {
+        sha1: Sha1::from_hex("91442577a6bbf8c3267f2df95fdfc50baebe176d"),
+        title: "Brick (Brix hack)",
+        author: None,
+        year: Some(1990),
+        quirks: Some("chip48"),
+        speed: None,
+    },
+    KnownRom {
+        sha1: Sha1::from_hex("f1cfcffe1937ed6dd6eeed1a7f85dfc777bda700"),
+        title: "Opcode test",
+        author: Some("corax89"),
+        year: None,
+        quirks: None,
+        speed: None,
+    },
+    KnownRom {
+        sha1: Sha1::from_hex("ba603bde1d8596c575e81096fff3cea40173d7e3"),
+        title: "Delay Timer Test",
+        author: Some("Matthew Mikolay"),
+        year: Some(2010),
+        quirks: None,
+        speed: None,
+    },
+];
+
+/// The ROM with this digest, if it is known.
+pub fn find(sha1: Sha1) -> Option<&'static KnownRom> {
+    KNOWN_ROMS.iter().find(|known| known.sha1 == sha1)
+}
+
+/// What is known about `rom`, if anything.
+pub fn lookup(rom: &[u8]) -> Option<&'static KnownRom> {
+    find(Sha1::of(rom))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::string::ToString;
+
+    #[test]
+    fn test_sha1() {
+        let hex = |data: &[u8]| Sha1::of(data).to_string();
+        assert_eq!(hex(b""), "da39a3ee5e6b4b0d3255bfef95601890afd80709");
+        assert_eq!(hex(b"abc"), "a9993e364706816aba3e25717850c26c9cd0d89d");
+        // 56 bytes, so the length needs a block of its own
+        assert_eq!(
+            hex(b"abcdbcdecdefdefgefghfghighijhijkijkljklmklmnlmnomnopnopq"),
+            "84983e441c3bd26ebaae4aa1f95129e5e54670f1"
+        );
+        let digest = Sha1::of(b"abc");
+        assert_eq!(Sha1::from_hex(&digest.to_string()), digest);
+    }
+
+    #[test]
+    fn test_lookup() {
+        let ibm = lookup(include_bytes!("../../IBM Logo.ch8")).unwrap();
+        assert_eq!(ibm.title, "IBM Logo");
+        let brix = lookup(include_bytes!("../../Brix [Andreas Gustafsson, 1990].ch8")).unwrap();
+        assert_eq!(brix.quirks, Some("chip48"));
+        assert_eq!(brix.to_string(), "Brix by Andreas Gustafsson, 1990");
+        for known in KNOWN_ROMS {
+            if let Some(preset) = known.quirks {
+                assert!(
+                    crate::quirks::Quirks::preset(preset).is_some(),
+                    "{}",
+                    preset
+                );
+            }
+        }
+        assert_eq!(lookup(&[0x12, 0x00]), None);
+    }
+}
@@ -0,0 +1,369 @@
+//! Snapshots of a whole machine, to resume a program where it left off.
+//!
+//! A state is a flat, versioned byte string, every multi-byte field
+//! big-endian:
+//!
+//! | Bytes | Field                                              |
+//! |-------|----------------------------------------------------|
+//! | 8     | `CHIP8SAV`                                         |
+//! | 1     | format version, currently 2                        |
+//! | 4096  | memory                                             |
+//! | 16    | V0 to VF                                           |
+//! | 2     | I                                                  |
+//! | 2     | PC                                                 |
+//! | 1     | stack depth                                        |
+//! | 32    | the 16 stack entries, unused ones included         |
+//! | 1     | delay timer                                        |
+//! | 1     | sound timer                                        |
+//! | 4     | random number generator state                      |
+//! | 1     | quirks, one bit each in [`Quirks::NAMES`] order    |
+//! | 8     | instructions executed                              |
+//! | 256   | display, 8 bytes per row, leftmost pixel in the MSB |
+//! | 8     | the RPL user flags of FX75 and FX85, from version 2 |
+//!
+//! The keypad is not saved: keys belong to the host, not the program.
+//! Version 1 states still load, leaving the flags as they were.
+//!
+//! [`Quirks::NAMES`]: crate::quirks::Quirks::NAMES
+
+use crate::chip::Chip8;
+use crate::cpu::FLAGS;
+use crate::display::Display;
+use crate::quirks::Quirks;
+use crate::rng::Rng;
+use std::path::PathBuf;
+use std::string::{String, ToString};
+use std::vec::Vec;
+
+const MAGIC: &[u8; 8] = b"CHIP8SAV";
+
+/// The format version [`Chip8::save_state`] writes.
+pub const STATE_VERSION: u8 = 2;
+
+/// Length in bytes of a version 1 state, which has no flags.
+const STATE_SIZE_V1: usize = 8 + 1 + 4096 + 16 + 2 + 2 + 1 + 32 + 1 + 1 + 4 + 1 + 8 + 256;
+
+/// Length in bytes of a version 2 state.
+pub const STATE_SIZE: usize = STATE_SIZE_V1 + FLAGS;
+
+/// Why [`Chip8::load_state`] refused a state.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum StateError {
+    /// The bytes do not start like a saved state.
+    NotAState,
+    /// Written by a newer version of the format.
+    Version { found: u8 },
+    /// Shorter or longer than a state of its version.
+    Size { size: usize, expected: usize },
+    /// A field holds a value no running machine could have.
+    Corrupt(&'static str),
+    /// The state file could not be read or written.
+    Io(String),
+}
+
+impl core::fmt::Display for StateError {
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+        match self {
+            StateError::NotAState => writeln!(f, "error: not a chippers save state")?,
+            StateError::Version { found } => writeln!(
+                f,
+                "error: save state version {} is newer than this build supports ({})",
+                found, STATE_VERSION
+            )?,
+            StateError::Size { size, expected } => writeln!(
+                f,
+                "error: save state is {} bytes, expected {}",
+                size, expected
+            )?,
+            StateError::Corrupt(field) => writeln!(f, "error: save state has a bad {}", field)?,
+            StateError::Io(s) => writeln!(f, "error: could not access save state: {}", s)?,
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for StateError {}
+
+impl From<std::io::Error> for StateError {
+    fn from(err: std::io::Error) -> StateError {
+        StateError::Io(err.to_string())
+    }
+}
+
+/// Where the run loop keeps the state saved by
+/// [`ControlMessage::SaveState`](crate::backend::ControlMessage::SaveState).
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub enum StateSlot {
+    /// In memory, lost when the process ends.
+    #[default]
+    Memory,
+    /// In a file, so a later run can pick it up.
+    File(PathBuf),
+}
+
+impl Chip8 {
+    /// The whole machine in the format described in [`state`](crate::state).
+    pub fn save_state(&self) -> Vec<u8> {
+        let cpu = &self.cpu;
+        let mut out = Vec::with_capacity(STATE_SIZE);
+        out.extend_from_slice(MAGIC);
+        out.push(STATE_VERSION);
+        out.extend_from_slice(&cpu.mem);
+        out.extend_from_slice(cpu.registers());
+        out.extend_from_slice(&cpu.index().to_be_bytes());
+        out.extend_from_slice(&cpu.pc().to_be_bytes());
+        out.push(cpu.sp);
+        for addr in cpu.stack {
+            out.extend_from_slice(&addr.to_be_bytes());
+        }
+        out.push(cpu.dt);
+        out.push(cpu.st);
+        out.extend_from_slice(&cpu.rng.state().to_be_bytes());
+        out.push(quirk_bits(cpu.quirks));
+        out.extend_from_slice(&self.instructions().to_be_bytes());
+        out.extend_from_slice(self.framebuffer().as_1bpp());
+        out.extend_from_slice(&cpu.flags);
+        out
+    }
+
+    /// Restores a machine saved by [`save_state`](Chip8::save_state). On error
+    /// the machine is left as it was.
+    pub fn load_state(&mut self, state: &[u8]) -> core::result::Result<(), StateError> {
+        let Some(rest) = state.strip_prefix(MAGIC) else {
+            return Err(StateError::NotAState);
+        };
+        let expected = match rest.first() {
+            Some(1) => STATE_SIZE_V1,
+            Some(&STATE_VERSION) => STATE_SIZE,
+            Some(&found) => return Err(StateError::Version { found }),
+            None => {
+                return Err(StateError::Size {
+                    size: state.len(),
+                    expected: STATE_SIZE,
+                })
+            }
+        };
+        if state.len() != expected {
+            return Err(StateError::Size {
+                size: state.len(),
+                expected,
+            });
+        }
+        let mut r = Reader(&rest[1..]);
+        let mem = r.take(4096);
+        let reg = r.take(16);
+        let index = r.u16();
+        let pc = r.u16();
+        let sp = r.take(1)[0];
+        let stack = r.take(32);
+        let (dt, st) = (r.take(1)[0], r.take(1)[0]);
+        let rng = r.u32();
+        let quirks = r.take(1)[0];
+        let instructions = u64::from_be_bytes(r.take(8).try_into().unwrap());
+        let display = r.take(Display::WIDTH * Display::HEIGHT / 8);
+        let flags = (expected == STATE_SIZE).then(|| r.take(FLAGS));
+
+        if pc >= 0x1000 {
+            return Err(StateError::Corrupt("program counter"));
+        }
+        if usize::from(sp) > 16 {
+            return Err(StateError::Corrupt("stack depth"));
+        }
+        if rng == 0 {
+            return Err(StateError::Corrupt("random number generator state"));
+        }
+        if quirks >> Quirks::NAMES.len() != 0 {
+            return Err(StateError::Corrupt("quirks"));
+        }
+
+        let cpu = &mut self.cpu;
+        cpu.mem.copy_from_slice(mem);
+        cpu.registers_mut().copy_from_slice(reg);
+        cpu.set_index(index);
+        cpu.set_pc(pc);
+        cpu.sp = sp;
+        for (entry, bytes) in cpu.stack.iter_mut().zip(stack.chunks(2)) {
+            *entry = u16::from_be_bytes([bytes[0], bytes[1]]);
+        }
+        cpu.dt = dt;
+        cpu.st = st;
+        cpu.rng = Rng::new(rng);
+        for (i, name) in Quirks::NAMES.iter().enumerate() {
+            cpu.quirks.set(name, quirks >> i & 1 == 1);
+        }
+        cpu.disp.clear();
+        for (y, row) in display.chunks(8).enumerate() {
+            cpu.disp
+                .xor_row(y, u64::from_be_bytes(row.try_into().unwrap()));
+        }
+        if let Some(flags) = flags {
+            self.cpu.flags.copy_from_slice(flags);
+        }
+        self.instructions = instructions;
+        Ok(())
+    }
+
+    /// Saves the machine into [`state_slot`](Chip8::state_slot).
+    pub fn quick_save(&mut self) -> core::result::Result<(), StateError> {
+        let state = self.save_state();
+        match &self.state_slot {
+            StateSlot::Memory => self.saved_state = Some(state),
+            StateSlot::File(path) => std::fs::write(path, state)?,
+        }
+        Ok(())
+    }
+
+    /// Restores the machine from [`state_slot`](Chip8::state_slot), returning
+    /// false if nothing has been saved there yet.
+    pub fn quick_load(&mut self) -> core::result::Result<bool, StateError> {
+        let state = match &self.state_slot {
+            StateSlot::Memory => match self.saved_state.take() {
+                Some(state) => state,
+                None => return Ok(false),
+            },
+            StateSlot::File(path) => match std::fs::read(path) {
+                Ok(state) => state,
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(false),
+                Err(e) => return Err(e.into()),
+            },
+        };
+        let loaded = self.load_state(&state);
+        if self.state_slot == StateSlot::Memory {
+            self.saved_state = Some(state);
+        }
+        loaded.map(|()| true)
+    }
+}
+
+fn quirk_bits(quirks: Quirks) -> u8 {
+    let on = [
+        quirks.shift_uses_vy,
+        quirks.jump_uses_vx,
+        quirks.load_store_increments_i,
+        quirks.logic_resets_vf,
+        quirks.draw_wraps,
+        quirks.display_wait,
+    ];
+    on.iter()
+        .enumerate()
+        .fold(0, |bits, (i, on)| bits | u8::from(*on) << i)
+}
+
+/// Reads fields off the front of a state whose length has been checked.
+struct Reader<'a>(&'a [u8]);
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, n: usize) -> &'a [u8] {
+        let (field, rest) = self.0.split_at(n);
+        self.0 = rest;
+        field
+    }
+
+    fn u16(&mut self) -> u16 {
+        let bytes = self.take(2);
+        u16::from_be_bytes([bytes[0], bytes[1]])
+    }
+
+    fn u32(&mut self) -> u32 {
+        u32::from_be_bytes(self.take(4).try_into().unwrap())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_round_trip() {
+        let mut chip8 = Chip8::new();
+        chip8.load_font_set();
+        chip8.cpu.quirks = Quirks::COSMAC;
+        // 6A07: VA = 7, FA18: ST = VA, 2208: call 0x208, 1206: halt, A050: I = font, D005: draw
+        chip8
+            .load_rom(&[
+                0x6A, 0x07, 0xFA, 0x18, 0x22, 0x08, 0x12, 0x06, 0xA0, 0x50, 0xD0, 0x05,
+            ])
+            .unwrap();
+        for _ in 0..5 {
+            chip8.step();
+        }
+        chip8.cpu.flags = [1, 2, 3, 4, 5, 6, 7, 8];
+        let state = chip8.save_state();
+        assert_eq!(state.len(), STATE_SIZE);
+
+        let mut restored = Chip8::new();
+        restored.load_state(&state).unwrap();
+        assert_eq!(restored.save_state(), state);
+        assert_eq!(restored.cpu.stack(), &[0x206]);
+        assert_eq!(restored.cpu.quirks, Quirks::COSMAC);
+        assert_eq!(restored.framebuffer(), chip8.framebuffer());
+        assert_eq!(restored.instructions(), 5);
+        assert_eq!(restored.cpu.flags, [1, 2, 3, 4, 5, 6, 7, 8]);
+        // a version 1 state, from before the flags were saved, leaves them be
+        let mut v1 = state[..STATE_SIZE - FLAGS].to_vec();
+        v1[8] = 1;
+        let mut old = Chip8::new();
+        old.cpu.flags = [9; FLAGS];
+        old.load_state(&v1).unwrap();
+        assert_eq!(old.cpu.flags, [9; FLAGS]);
+        assert_eq!(old.cpu.stack(), &[0x206]);
+        // both carry on identically, random numbers included
+        chip8.cpu.mem[0x20C..0x20E].copy_from_slice(&[0xC0, 0xFF]);
+        restored.cpu.mem[0x20C..0x20E].copy_from_slice(&[0xC0, 0xFF]);
+        chip8.step();
+        restored.step();
+        assert_eq!(restored.save_state(), chip8.save_state());
+    }
+
+    #[test]
+    fn test_quick_save_slots() {
+        let path = std::env::temp_dir().join(format!("chippers-state-{}", std::process::id()));
+        let mut chip8 = Chip8::new();
+        assert_eq!(chip8.quick_load(), Ok(false));
+        for slot in [StateSlot::Memory, StateSlot::File(path.clone())] {
+            chip8.state_slot = slot;
+            chip8.cpu.registers_mut()[0] = 1;
+            chip8.quick_save().unwrap();
+            chip8.cpu.registers_mut()[0] = 2;
+            assert_eq!(chip8.quick_load(), Ok(true));
+            assert_eq!(chip8.cpu.registers()[0], 1);
+            // a slot can be loaded again and again
+            assert_eq!(chip8.quick_load(), Ok(true));
+        }
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn test_rejects_bad_states() {
+        let mut chip8 = Chip8::new();
+        let mut state = chip8.save_state();
+        assert_eq!(chip8.load_state(b"hello"), Err(StateError::NotAState));
+        assert_eq!(
+            chip8.load_state(&state[..100]),
+            Err(StateError::Size {
+                size: 100,
+                expected: STATE_SIZE
+            })
+        );
+        state[8] = 3;
+        assert_eq!(
+            chip8.load_state(&state),
+            Err(StateError::Version { found: 3 })
+        );
+        state[8] = 1;
+        assert_eq!(
+            chip8.load_state(&state),
+            Err(StateError::Size {
+                size: STATE_SIZE,
+                expected: STATE_SIZE - FLAGS
+            })
+        );
+        state[8] = STATE_VERSION;
+        // the stack depth follows memory, registers, I and PC
+        state[9 + 4096 + 16 + 4] = 17;
+        assert_eq!(
+            chip8.load_state(&state),
+            Err(StateError::Corrupt("stack depth"))
+        );
+    }
+}
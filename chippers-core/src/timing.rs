@@ -0,0 +1,108 @@
+//! How long instructions take.
+//!
+//! By default every instruction takes the same time, a [`speed`]th of a
+//! second. The COSMAC VIP's interpreter was nothing like so even: a jump
+//! took about a tenth of a millisecond, a BCD conversion nearly one and a
+//! sprite more than a whole frame, as it waited for the display interrupt
+//! before drawing. [`Timing::Cosmac`] charges each instruction its rough
+//! cost on the VIP instead, so games written for it keep their original
+//! pace, slowing down when they draw a lot as they did then.
+//!
+//! [`speed`]: crate::chip::Chip8::speed
+
+use crate::opcode::Opcode;
+
+/// Microseconds in a 60 Hz frame.
+pub const FRAME_MICROS: u32 = 16_667;
+
+/// How [`step_frame`](crate::chip::Chip8::step_frame) and
+/// [`run_with`](crate::chip::Chip8::run_with) count the time instructions
+/// take.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub enum Timing {
+    /// Every instruction takes a [`speed`](crate::chip::Chip8::speed)th of a
+    /// second.
+    #[default]
+    Uniform,
+    /// Each instruction takes as long as it did on the COSMAC VIP, see
+    /// [`cosmac_micros`]; the speed is ignored.
+    Cosmac,
+}
+
+impl Timing {
+    /// Names accepted by [`from_name`](Timing::from_name).
+    pub const NAMES: [&'static str; 2] = ["uniform", "cosmac"];
+
+    pub fn from_name(name: &str) -> Option<Timing> {
+        match name {
+            "uniform" => Some(Timing::Uniform),
+            "cosmac" => Some(Timing::Cosmac),
+            _ => None,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Timing::Uniform => "uniform",
+            Timing::Cosmac => "cosmac",
+        }
+    }
+}
+
+/// What [`cosmac_micros`] charges a word that does not decode, as for the
+/// instructions the VIP never ran.
+pub const UNKNOWN_MICROS: u32 = 100;
+
+/// Roughly how many microseconds the COSMAC VIP's interpreter spends on
+/// `opcode`, averaged over its operands. A draw includes the wait for the
+/// display interrupt, and so runs past the end of the frame it starts in.
+pub fn cosmac_micros(opcode: Opcode) -> u32 {
+    match opcode {
+        Opcode::SetVX => 27,
+        Opcode::AddVX | Opcode::SetVXToDT | Opcode::SetDTToVX | Opcode::SetSTToVX => 45,
+        Opcode::GetKey => 45,
+        Opcode::SkipEqual | Opcode::SkipNotEqual | Opcode::SetI => 55,
+        Opcode::SkipVXEqualVY
+        | Opcode::SkipVXNotEqualVY
+        | Opcode::SkipIfKey
+        | Opcode::SkipIfNotKey => 73,
+        Opcode::AddI => 86,
+        Opcode::FontCharacter => 91,
+        Opcode::Jump | Opcode::GotoSub | Opcode::ReturnSub | Opcode::JumpWithOffset => 105,
+        Opcode::Clear => 109,
+        Opcode::Random => 164,
+        Opcode::SetVXToVY
+        | Opcode::BinaryOr
+        | Opcode::BinaryAnd
+        | Opcode::BinaryXor
+        | Opcode::AddVYToVX
+        | Opcode::SubVYFromVX
+        | Opcode::SubVXFromVY
+        | Opcode::ShiftRight
+        | Opcode::ShiftLeft => 200,
+        Opcode::SaveRegisterToMemory | Opcode::LoadRegisterFromMemory => 605,
+        Opcode::BinaryCodedDecimalConversion => 927,
+        Opcode::Draw => 22_734,
+        Opcode::SaveFlags | Opcode::LoadFlags => UNKNOWN_MICROS,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_names() {
+        for name in Timing::NAMES {
+            assert_eq!(Timing::from_name(name).unwrap().name(), name);
+        }
+        assert_eq!(Timing::from_name("fast"), None);
+    }
+
+    #[test]
+    fn test_cosmac_costs() {
+        // arithmetic is cheap, and a draw dearer than a frame
+        assert!(cosmac_micros(Opcode::SetVX) < cosmac_micros(Opcode::AddVYToVX));
+        assert!(cosmac_micros(Opcode::Draw) > FRAME_MICROS);
+    }
+}
@@ -0,0 +1,134 @@
+#![no_std]
+
+//! Draws the CHIP-8 display onto any `embedded-graphics` draw target, so the
+//! `no_std` core can drive small SPI/I2C panels such as the SSD1306 or ILI9341.
+
+use chippers_core::backend::DisplayBackend;
+use chippers_core::display::Display;
+use embedded_graphics_core::{pixelcolor::PixelColor, prelude::*, primitives::Rectangle};
+
+/// Adapts an `embedded-graphics` [`DrawTarget`] into a CHIP-8 display backend.
+///
+/// Each CHIP-8 pixel is drawn as a `scale`x`scale` square starting at `origin`;
+/// a 128x64 SSD1306 fits the whole screen at scale 2.
+#[derive(Debug)]
+pub struct EmbeddedDisplay<D, C> {
+    target: D,
+    on: C,
+    off: C,
+    origin: Point,
+    scale: u32,
+}
+
+impl<D, C> EmbeddedDisplay<D, C>
+where
+    D: DrawTarget<Color = C>,
+    C: PixelColor,
+{
+    pub fn new(target: D, on: C, off: C) -> Self {
+        EmbeddedDisplay {
+            target,
+            on,
+            off,
+            origin: Point::zero(),
+            scale: 1,
+        }
+    }
+
+    /// Draws every CHIP-8 pixel as a square of this many device pixels.
+    pub fn with_scale(mut self, scale: u32) -> Self {
+        self.scale = scale.max(1);
+        self
+    }
+
+    /// Offsets the CHIP-8 screen on the device, e.g. to center it.
+    pub fn with_origin(mut self, origin: Point) -> Self {
+        self.origin = origin;
+        self
+    }
+
+    pub fn target(&mut self) -> &mut D {
+        &mut self.target
+    }
+
+    pub fn release(self) -> D {
+        self.target
+    }
+
+    fn pixel_area(&self, x: usize, y: usize) -> Rectangle {
+        let top_left = self.origin + Point::new(x as i32, y as i32) * self.scale as i32;
+        Rectangle::new(top_left, Size::new(self.scale, self.scale))
+    }
+}
+
+impl<D, C> DisplayBackend for EmbeddedDisplay<D, C>
+where
+    D: DrawTarget<Color = C>,
+    C: PixelColor,
+{
+    type Error = D::Error;
+
+    fn clear_screen(&mut self) -> core::result::Result<(), Self::Error> {
+        let area = Rectangle::new(self.origin, Size::new(64, 32) * self.scale);
+        self.target.fill_solid(&area, self.off)
+    }
+
+    fn draw_screen(&mut self, disp: &Display) -> core::result::Result<(), Self::Error> {
+        for (y, row) in disp.rows().enumerate() {
+            for (x, pix) in row.enumerate() {
+                let color = if pix { self.on } else { self.off };
+                let area = self.pixel_area(x, y);
+                self.target.fill_solid(&area, color)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use embedded_graphics_core::pixelcolor::BinaryColor;
+
+    struct Panel {
+        pixels: [[bool; 64]; 128],
+    }
+
+    impl OriginDimensions for Panel {
+        fn size(&self) -> Size {
+            Size::new(128, 64)
+        }
+    }
+
+    impl DrawTarget for Panel {
+        type Color = BinaryColor;
+        type Error = core::convert::Infallible;
+
+        fn draw_iter<I>(&mut self, pixels: I) -> core::result::Result<(), Self::Error>
+        where
+            I: IntoIterator<Item = Pixel<Self::Color>>,
+        {
+            for Pixel(point, color) in pixels {
+                if self.bounding_box().contains(point) {
+                    self.pixels[point.x as usize][point.y as usize] = color.is_on();
+                }
+            }
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_draw_scaled() {
+        let panel = Panel {
+            pixels: [[false; 64]; 128],
+        };
+        let mut display =
+            EmbeddedDisplay::new(panel, BinaryColor::On, BinaryColor::Off).with_scale(2);
+        let mut disp = Display::new();
+        disp.set_pixel(3, 1, true);
+        display.draw_screen(&disp).unwrap();
+        let panel = display.release();
+        assert!(panel.pixels[6][2] && panel.pixels[7][3]);
+        assert!(!panel.pixels[5][2] && !panel.pixels[8][2]);
+    }
+}
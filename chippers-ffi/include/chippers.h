@@ -0,0 +1,148 @@
+#ifndef CHIPPERS_H
+#define CHIPPERS_H
+
+/* Generated by cbindgen from chippers-ffi; do not edit by hand. */
+
+#include <stdarg.h>
+#include <stdbool.h>
+#include <stddef.h>
+#include <stdint.h>
+#include <stdlib.h>
+
+/**
+ * Width of the CHIP-8 display in pixels.
+ */
+#define CHIPPERS_WIDTH 64
+
+/**
+ * Height of the CHIP-8 display in pixels.
+ */
+#define CHIPPERS_HEIGHT 32
+
+/**
+ * Largest ROM that fits between 0x200 and the end of memory.
+ */
+#define CHIPPERS_MAX_ROM_SIZE (4096 - 512)
+
+/**
+ * Result codes returned by the fallible functions.
+ */
+typedef enum ChippersStatus {
+  CHIPPERS_STATUS_OK = 0,
+  CHIPPERS_STATUS_NULL_POINTER = 1,
+  CHIPPERS_STATUS_ROM_TOO_LARGE = 2,
+  CHIPPERS_STATUS_BUFFER_TOO_SMALL = 3,
+  CHIPPERS_STATUS_ROM_EMPTY = 4,
+} ChippersStatus;
+
+/**
+ * Why a machine stopped, as reported by `chippers_fault`.
+ */
+typedef enum ChippersFault {
+  CHIPPERS_FAULT_NONE = 0,
+  CHIPPERS_FAULT_STACK_OVERFLOW = 1,
+  CHIPPERS_FAULT_STACK_UNDERFLOW = 2,
+  CHIPPERS_FAULT_UNKNOWN_OPCODE = 3,
+  CHIPPERS_FAULT_OUT_OF_BOUNDS = 4,
+} ChippersFault;
+
+/**
+ * Opaque handle to one emulated machine.
+ */
+typedef struct ChippersMachine ChippersMachine;
+
+#ifdef __cplusplus
+extern "C" {
+#endif // __cplusplus
+
+/**
+ * Creates a machine with the font set loaded and no ROM.
+ *
+ * Free it with `chippers_free`.
+ */
+struct ChippersMachine *chippers_new(void);
+
+/**
+ * Destroys a machine created by `chippers_new`. Passing NULL is a no-op.
+ *
+ * # Safety
+ *
+ * `machine` must be NULL or a pointer returned by `chippers_new` that has not
+ * already been freed.
+ */
+void chippers_free(struct ChippersMachine *machine);
+
+/**
+ * Resets the machine and copies `len` bytes of ROM to 0x200.
+ *
+ * # Safety
+ *
+ * `machine` must be a live machine and `rom` must point to `len` readable bytes.
+ */
+enum ChippersStatus chippers_load_rom(struct ChippersMachine *machine,
+                                      const uint8_t *rom,
+                                      size_t len);
+
+/**
+ * Runs `instructions` instructions followed by one 60 Hz timer tick.
+ *
+ * Returns true if the display changed during the frame. A program that
+ * faults, for example by returning from outside any subroutine, stays on the
+ * faulting instruction and the rest of the frame is skipped; check
+ * `chippers_fault` to tell a frozen program from a quiet one.
+ *
+ * # Safety
+ *
+ * `machine` must be NULL or a live machine.
+ */
+bool chippers_step_frame(struct ChippersMachine *machine, uint32_t instructions);
+
+/**
+ * Returns the fault that stopped the program, or `CHIPPERS_FAULT_NONE` while
+ * it is still running. Loading a ROM clears it.
+ *
+ * # Safety
+ *
+ * `machine` must be NULL or a live machine.
+ */
+enum ChippersFault chippers_fault(const struct ChippersMachine *machine);
+
+/**
+ * Copies the display into `out` as `CHIPPERS_WIDTH * CHIPPERS_HEIGHT` bytes,
+ * row-major, one byte per pixel (0 = off, 1 = on).
+ *
+ * # Safety
+ *
+ * `machine` must be a live machine and `out` must point to `len` writable bytes.
+ */
+enum ChippersStatus chippers_framebuffer(const struct ChippersMachine *machine,
+                                         uint8_t *out,
+                                         size_t len);
+
+/**
+ * Copies the display into `out` as `CHIPPERS_WIDTH * CHIPPERS_HEIGHT / 8`
+ * bytes, row-major, one bit per pixel with the leftmost pixel in the most
+ * significant bit.
+ *
+ * # Safety
+ *
+ * `machine` must be a live machine and `out` must point to `len` writable bytes.
+ */
+enum ChippersStatus chippers_framebuffer_1bpp(const struct ChippersMachine *machine,
+                                              uint8_t *out,
+                                              size_t len);
+
+/**
+ * Reports a press of keypad key `key` (0x0-0xF).
+ *
+ * # Safety
+ *
+ * `machine` must be NULL or a live machine.
+ */
+void chippers_key_press(struct ChippersMachine *machine, uint8_t key);
+
+#ifdef __cplusplus
+}  // extern "C"
+#endif  // __cplusplus
+
+#endif  /* CHIPPERS_H */
@@ -0,0 +1,262 @@
+//! C ABI for embedding the chippers core in non-Rust applications.
+//!
+//! The header in `include/chippers.h` is generated from this file with
+//! `cbindgen --config cbindgen.toml --output include/chippers.h`.
+
+use chippers_core::chip::{Chip8, LoadError};
+use chippers_core::cpu::Fault;
+
+/// Width of the CHIP-8 display in pixels.
+pub const CHIPPERS_WIDTH: usize = 64;
+/// Height of the CHIP-8 display in pixels.
+pub const CHIPPERS_HEIGHT: usize = 32;
+/// Largest ROM that fits between 0x200 and the end of memory.
+pub const CHIPPERS_MAX_ROM_SIZE: usize = 4096 - 0x200;
+
+/// Opaque handle to one emulated machine.
+pub struct ChippersMachine {
+    chip8: Chip8,
+    fault: Option<Fault>,
+}
+
+/// Result codes returned by the fallible functions.
+#[repr(C)]
+#[derive(Debug, PartialEq, Eq)]
+pub enum ChippersStatus {
+    Ok = 0,
+    NullPointer = 1,
+    RomTooLarge = 2,
+    BufferTooSmall = 3,
+    RomEmpty = 4,
+}
+
+/// Why a machine stopped, as reported by `chippers_fault`.
+#[repr(C)]
+#[derive(Debug, PartialEq, Eq)]
+pub enum ChippersFault {
+    None = 0,
+    StackOverflow = 1,
+    StackUnderflow = 2,
+    UnknownOpcode = 3,
+    OutOfBounds = 4,
+}
+
+impl From<Option<Fault>> for ChippersFault {
+    fn from(fault: Option<Fault>) -> Self {
+        match fault {
+            None => ChippersFault::None,
+            Some(Fault::StackOverflow { .. }) => ChippersFault::StackOverflow,
+            Some(Fault::StackUnderflow { .. }) => ChippersFault::StackUnderflow,
+            Some(Fault::UnknownOpcode { .. }) => ChippersFault::UnknownOpcode,
+            Some(Fault::OutOfBounds { .. }) => ChippersFault::OutOfBounds,
+        }
+    }
+}
+
+/// Creates a machine with the font set loaded and no ROM.
+///
+/// Free it with `chippers_free`.
+#[no_mangle]
+pub extern "C" fn chippers_new() -> *mut ChippersMachine {
+    let mut chip8 = Chip8::new();
+    chip8.load_font_set();
+    Box::into_raw(Box::new(ChippersMachine { chip8, fault: None }))
+}
+
+/// Destroys a machine created by `chippers_new`. Passing NULL is a no-op.
+///
+/// # Safety
+///
+/// `machine` must be NULL or a pointer returned by `chippers_new` that has not
+/// already been freed.
+#[no_mangle]
+pub unsafe extern "C" fn chippers_free(machine: *mut ChippersMachine) {
+    if !machine.is_null() {
+        drop(Box::from_raw(machine));
+    }
+}
+
+/// Resets the machine and copies `len` bytes of ROM to 0x200.
+///
+/// # Safety
+///
+/// `machine` must be a live machine and `rom` must point to `len` readable bytes.
+#[no_mangle]
+pub unsafe extern "C" fn chippers_load_rom(
+    machine: *mut ChippersMachine,
+    rom: *const u8,
+    len: usize,
+) -> ChippersStatus {
+    let (Some(machine), false) = (machine.as_mut(), rom.is_null()) else {
+        return ChippersStatus::NullPointer;
+    };
+    let rom = std::slice::from_raw_parts(rom, len);
+    let mut chip8 = Chip8::new();
+    chip8.load_font_set();
+    match chip8.load_rom(rom) {
+        Ok(_) => {}
+        Err(LoadError::Empty) => return ChippersStatus::RomEmpty,
+        Err(LoadError::TooLarge { .. }) => return ChippersStatus::RomTooLarge,
+    }
+    machine.chip8 = chip8;
+    machine.fault = None;
+    ChippersStatus::Ok
+}
+
+/// Runs `instructions` instructions followed by one 60 Hz timer tick.
+///
+/// Returns true if the display changed during the frame. A program that
+/// faults, for example by returning from outside any subroutine, stays on the
+/// faulting instruction and the rest of the frame is skipped; check
+/// `chippers_fault` to tell a frozen program from a quiet one.
+///
+/// # Safety
+///
+/// `machine` must be NULL or a live machine.
+#[no_mangle]
+pub unsafe extern "C" fn chippers_step_frame(
+    machine: *mut ChippersMachine,
+    instructions: u32,
+) -> bool {
+    let Some(machine) = machine.as_mut() else {
+        return false;
+    };
+    let report = machine.chip8.run_frame(instructions);
+    if report.fault.is_some() {
+        machine.fault = report.fault;
+    }
+    report.drawn
+}
+
+/// Returns the fault that stopped the program, or `CHIPPERS_FAULT_NONE` while
+/// it is still running. Loading a ROM clears it.
+///
+/// # Safety
+///
+/// `machine` must be NULL or a live machine.
+#[no_mangle]
+pub unsafe extern "C" fn chippers_fault(machine: *const ChippersMachine) -> ChippersFault {
+    match machine.as_ref() {
+        Some(machine) => machine.fault.into(),
+        None => ChippersFault::None,
+    }
+}
+
+/// Copies the display into `out` as `CHIPPERS_WIDTH * CHIPPERS_HEIGHT` bytes,
+/// row-major, one byte per pixel (0 = off, 1 = on).
+///
+/// # Safety
+///
+/// `machine` must be a live machine and `out` must point to `len` writable bytes.
+#[no_mangle]
+pub unsafe extern "C" fn chippers_framebuffer(
+    machine: *const ChippersMachine,
+    out: *mut u8,
+    len: usize,
+) -> ChippersStatus {
+    let (Some(machine), false) = (machine.as_ref(), out.is_null()) else {
+        return ChippersStatus::NullPointer;
+    };
+    let out = std::slice::from_raw_parts_mut(out, len);
+    match machine.chip8.framebuffer().copy_8bpp(out) {
+        Some(_) => ChippersStatus::Ok,
+        None => ChippersStatus::BufferTooSmall,
+    }
+}
+
+/// Copies the display into `out` as `CHIPPERS_WIDTH * CHIPPERS_HEIGHT / 8`
+/// bytes, row-major, one bit per pixel with the leftmost pixel in the most
+/// significant bit.
+///
+/// # Safety
+///
+/// `machine` must be a live machine and `out` must point to `len` writable bytes.
+#[no_mangle]
+pub unsafe extern "C" fn chippers_framebuffer_1bpp(
+    machine: *const ChippersMachine,
+    out: *mut u8,
+    len: usize,
+) -> ChippersStatus {
+    let (Some(machine), false) = (machine.as_ref(), out.is_null()) else {
+        return ChippersStatus::NullPointer;
+    };
+    let frame = machine.chip8.framebuffer();
+    let bits = frame.as_1bpp();
+    if len < bits.len() {
+        return ChippersStatus::BufferTooSmall;
+    }
+    std::slice::from_raw_parts_mut(out, bits.len()).copy_from_slice(bits);
+    ChippersStatus::Ok
+}
+
+/// Reports a press of keypad key `key` (0x0-0xF).
+///
+/// # Safety
+///
+/// `machine` must be NULL or a live machine.
+#[no_mangle]
+pub unsafe extern "C" fn chippers_key_press(machine: *mut ChippersMachine, key: u8) {
+    if let Some(machine) = machine.as_mut() {
+        machine.chip8.cpu.press_key(key);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_run_rom_through_c_abi() {
+        // 6005: V0 = 5, F029: I = font(V0), D015: draw it at (V0, V0)
+        let rom = [0x60, 0x05, 0xF0, 0x29, 0xD0, 0x05];
+        let mut frame = [0u8; CHIPPERS_WIDTH * CHIPPERS_HEIGHT];
+        unsafe {
+            let machine = chippers_new();
+            let status = chippers_load_rom(machine, rom.as_ptr(), rom.len());
+            assert_eq!(status, ChippersStatus::Ok);
+            assert!(chippers_step_frame(machine, 3));
+            let status = chippers_framebuffer(machine, frame.as_mut_ptr(), frame.len());
+            assert_eq!(status, ChippersStatus::Ok);
+            chippers_free(machine);
+        }
+        // top row of the "5" glyph is 0xF0
+        assert_eq!(
+            &frame[5 * CHIPPERS_WIDTH + 5..5 * CHIPPERS_WIDTH + 10],
+            &[1, 1, 1, 1, 0]
+        );
+    }
+
+    #[test]
+    fn test_reports_faults() {
+        // 6001: V0 = 1, 00EE: return outside any subroutine
+        let rom = [0x60, 0x01, 0x00, 0xEE];
+        unsafe {
+            let machine = chippers_new();
+            assert_eq!(chippers_fault(machine), ChippersFault::None);
+            chippers_load_rom(machine, rom.as_ptr(), rom.len());
+            chippers_step_frame(machine, 1);
+            assert_eq!(chippers_fault(machine), ChippersFault::None);
+            chippers_step_frame(machine, 1);
+            assert_eq!(chippers_fault(machine), ChippersFault::StackUnderflow);
+            chippers_load_rom(machine, rom.as_ptr(), rom.len());
+            assert_eq!(chippers_fault(machine), ChippersFault::None);
+            chippers_free(machine);
+            assert_eq!(chippers_fault(std::ptr::null()), ChippersFault::None);
+        }
+    }
+
+    #[test]
+    fn test_rejects_bad_arguments() {
+        let rom = [0u8; CHIPPERS_MAX_ROM_SIZE + 1];
+        unsafe {
+            let machine = chippers_new();
+            let status = chippers_load_rom(machine, rom.as_ptr(), rom.len());
+            assert_eq!(status, ChippersStatus::RomTooLarge);
+            let status = chippers_load_rom(machine, rom.as_ptr(), 0);
+            assert_eq!(status, ChippersStatus::RomEmpty);
+            let status = chippers_load_rom(machine, std::ptr::null(), 0);
+            assert_eq!(status, ChippersStatus::NullPointer);
+            chippers_free(machine);
+        }
+    }
+}
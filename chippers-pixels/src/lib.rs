@@ -0,0 +1,327 @@
+//! A CHIP-8 machine in a window, drawn as a texture by `pixels`.
+//!
+//! winit owns the event loop, so rather than implementing
+//! [`Frontend`](chippers_core::Frontend) this runs the machine a frame at a
+//! time from inside the loop, like the Bevy plugin. Frames are paced by the
+//! wall clock at 60 Hz and presented with vsync. Holding Tab runs fast
+//! forward, and M, unless it is on the keypad, switches slow motion on and
+//! off. Escape or closing the window ends the run.
+
+use chippers_core::backend::{Palette, Resolution, Rgb};
+use chippers_core::chip::{Chip8, Pace};
+use chippers_core::cpu::Fault;
+use chippers_core::display::Display;
+use chippers_core::keypad::KeyMap;
+use pixels::{Pixels, PixelsBuilder, SurfaceTexture};
+use std::time::{Duration, Instant};
+use winit::dpi::LogicalSize;
+use winit::event::{ElementState, Event, KeyboardInput, VirtualKeyCode, WindowEvent};
+use winit::event_loop::EventLoop;
+use winit::platform::run_return::EventLoopExtRunReturn;
+use winit::window::WindowBuilder;
+
+/// Window pixels per CHIP-8 pixel when the window opens.
+pub const DEFAULT_SCALE: u32 = 10;
+
+/// More frames than this behind, say after the window was dragged, and the
+/// machine skips ahead rather than trying to catch up. Fast forward counts
+/// the frames it runs in the time of these.
+const MAX_CATCH_UP: u64 = 4;
+
+#[derive(Debug)]
+pub enum PixelsError {
+    Window(winit::error::OsError),
+    Pixels(pixels::Error),
+    Resize(pixels::TextureError),
+    Fault(Fault),
+}
+
+impl std::fmt::Display for PixelsError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            PixelsError::Window(e) => writeln!(f, "error: could not open window: {}", e)?,
+            PixelsError::Pixels(e) => writeln!(f, "error: rendering failed: {}", e)?,
+            PixelsError::Resize(e) => writeln!(f, "error: could not resize window: {}", e)?,
+            PixelsError::Fault(fault) => fault.fmt(f)?,
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for PixelsError {}
+
+impl From<pixels::Error> for PixelsError {
+    fn from(e: pixels::Error) -> Self {
+        PixelsError::Pixels(e)
+    }
+}
+
+impl From<pixels::TextureError> for PixelsError {
+    fn from(e: pixels::TextureError) -> Self {
+        PixelsError::Resize(e)
+    }
+}
+
+/// How to run the machine.
+#[derive(Clone, Debug)]
+pub struct Options {
+    pub title: String,
+    pub scale: u32,
+    pub instructions_per_frame: u32,
+    pub palette: Palette,
+    pub keys: KeyMap,
+}
+
+impl Default for Options {
+    fn default() -> Self {
+        Options {
+            title: "chippers".to_string(),
+            scale: DEFAULT_SCALE,
+            instructions_per_frame: 12,
+            palette: Palette::default(),
+            keys: KeyMap::default(),
+        }
+    }
+}
+
+/// The character a key types, for the keys a [`KeyMap`] can name.
+pub fn virtual_key_char(key: VirtualKeyCode) -> Option<char> {
+    let c = match key {
+        VirtualKeyCode::Key0 => '0',
+        VirtualKeyCode::Key1 => '1',
+        VirtualKeyCode::Key2 => '2',
+        VirtualKeyCode::Key3 => '3',
+        VirtualKeyCode::Key4 => '4',
+        VirtualKeyCode::Key5 => '5',
+        VirtualKeyCode::Key6 => '6',
+        VirtualKeyCode::Key7 => '7',
+        VirtualKeyCode::Key8 => '8',
+        VirtualKeyCode::Key9 => '9',
+        VirtualKeyCode::A => 'a',
+        VirtualKeyCode::B => 'b',
+        VirtualKeyCode::C => 'c',
+        VirtualKeyCode::D => 'd',
+        VirtualKeyCode::E => 'e',
+        VirtualKeyCode::F => 'f',
+        VirtualKeyCode::G => 'g',
+        VirtualKeyCode::H => 'h',
+        VirtualKeyCode::I => 'i',
+        VirtualKeyCode::J => 'j',
+        VirtualKeyCode::K => 'k',
+        VirtualKeyCode::L => 'l',
+        VirtualKeyCode::M => 'm',
+        VirtualKeyCode::N => 'n',
+        VirtualKeyCode::O => 'o',
+        VirtualKeyCode::P => 'p',
+        VirtualKeyCode::Q => 'q',
+        VirtualKeyCode::R => 'r',
+        VirtualKeyCode::S => 's',
+        VirtualKeyCode::T => 't',
+        VirtualKeyCode::U => 'u',
+        VirtualKeyCode::V => 'v',
+        VirtualKeyCode::W => 'w',
+        VirtualKeyCode::X => 'x',
+        VirtualKeyCode::Y => 'y',
+        VirtualKeyCode::Z => 'z',
+        VirtualKeyCode::Comma => ',',
+        VirtualKeyCode::Period => '.',
+        VirtualKeyCode::Semicolon => ';',
+        VirtualKeyCode::Apostrophe => '\'',
+        VirtualKeyCode::Slash => '/',
+        VirtualKeyCode::Minus => '-',
+        _ => return None,
+    };
+    Some(c)
+}
+
+/// Copies `display` into an RGBA `frame` of the same size.
+pub fn draw(display: &Display, palette: Palette, frame: &mut [u8]) {
+    let pixels = display.rows().flatten();
+    for (rgba, on) in frame.chunks_exact_mut(4).zip(pixels) {
+        let color = if on { palette.on } else { palette.off };
+        rgba.copy_from_slice(&[color.r, color.g, color.b, 0xFF]);
+    }
+}
+
+/// Copies a MegaChip picture into an RGBA `frame` of the same size.
+pub fn draw_color(pixels: &[Rgb], frame: &mut [u8]) {
+    for (rgba, color) in frame.chunks_exact_mut(4).zip(pixels) {
+        rgba.copy_from_slice(&[color.r, color.g, color.b, 0xFF]);
+    }
+}
+
+/// Opens a window and runs `chip8` in it until the window is closed.
+pub fn run(chip8: &mut Chip8, options: &Options) -> std::result::Result<(), PixelsError> {
+    let mut event_loop = EventLoop::new();
+    let (width, height) = (Display::WIDTH as u32, Display::HEIGHT as u32);
+    let window = WindowBuilder::new()
+        .with_title(&options.title)
+        .with_inner_size(LogicalSize::new(
+            width * options.scale,
+            height * options.scale,
+        ))
+        .with_min_inner_size(LogicalSize::new(width, height))
+        .build(&event_loop)
+        .map_err(PixelsError::Window)?;
+    let size = window.inner_size();
+    let surface = SurfaceTexture::new(size.width, size.height, &window);
+    let mut pixels: Pixels = PixelsBuilder::new(width, height, surface)
+        .enable_vsync(true)
+        .build()?;
+
+    let mut start = Instant::now();
+    let frame = Duration::from_secs_f64(1. / 60.);
+    let mut frames = 0u64;
+    let (mut fast_forward, mut slow_motion) = (false, false);
+    let mut pace = Pace::Normal;
+    // the size of the texture, which grows while a MegaChip picture is shown
+    let mut buffer = Resolution::LORES;
+    let mut result = Ok(());
+    event_loop.run_return(|event, _, control_flow| {
+        let step = match event {
+            Event::WindowEvent { event, .. } => match event {
+                WindowEvent::CloseRequested
+                | WindowEvent::KeyboardInput {
+                    input:
+                        KeyboardInput {
+                            virtual_keycode: Some(VirtualKeyCode::Escape),
+                            state: ElementState::Pressed,
+                            ..
+                        },
+                    ..
+                } => {
+                    control_flow.set_exit();
+                    Ok(())
+                }
+                WindowEvent::KeyboardInput {
+                    input:
+                        KeyboardInput {
+                            virtual_keycode: Some(VirtualKeyCode::Tab),
+                            state,
+                            ..
+                        },
+                    ..
+                } => {
+                    fast_forward = state == ElementState::Pressed;
+                    Ok(())
+                }
+                WindowEvent::KeyboardInput {
+                    input:
+                        KeyboardInput {
+                            virtual_keycode: Some(VirtualKeyCode::M),
+                            state: ElementState::Pressed,
+                            ..
+                        },
+                    ..
+                } if options.keys.key('m').is_none() => {
+                    slow_motion = !slow_motion;
+                    Ok(())
+                }
+                WindowEvent::Resized(size) => pixels
+                    .resize_surface(size.width, size.height)
+                    .map_err(PixelsError::from),
+                WindowEvent::KeyboardInput {
+                    input:
+                        KeyboardInput {
+                            virtual_keycode: Some(key),
+                            state,
+                            ..
+                        },
+                    ..
+                } => {
+                    press(chip8, options.keys, key, state);
+                    Ok(())
+                }
+                _ => Ok(()),
+            },
+            Event::MainEventsCleared => {
+                if Pace::of(fast_forward, slow_motion) != pace {
+                    // frames are counted afresh at the new pace
+                    pace = Pace::of(fast_forward, slow_motion);
+                    (start, frames) = (Instant::now(), 0);
+                }
+                let paced = pace.wall_time(frame);
+                let due = (start.elapsed().as_nanos() / paced.as_nanos()) as u64;
+                // as many frames as fit in the catch-up time at this pace
+                let max_behind = MAX_CATCH_UP * (frame.as_nanos() / paced.as_nanos()).max(1) as u64;
+                if due.saturating_sub(frames) > max_behind {
+                    frames = due - 1;
+                }
+                let mut ran = Ok(());
+                while frames < due && ran.is_ok() {
+                    ran = run_frame(chip8, options.instructions_per_frame);
+                    frames += 1;
+                }
+                window.request_redraw();
+                ran
+            }
+            Event::RedrawRequested(_) => {
+                let mega = chip8.megachip.as_ref().filter(|mega| mega.is_on());
+                let wanted = match mega {
+                    Some(_) => Resolution::MEGACHIP,
+                    None => Resolution::LORES,
+                };
+                let resized = if buffer == wanted {
+                    Ok(())
+                } else {
+                    buffer = wanted;
+                    pixels.resize_buffer(u32::from(wanted.width), u32::from(wanted.height))
+                };
+                match mega {
+                    Some(mega) => draw_color(mega.frame(), pixels.frame_mut()),
+                    None => draw(&chip8.cpu.disp, options.palette, pixels.frame_mut()),
+                }
+                resized
+                    .map_err(PixelsError::from)
+                    .and_then(|()| pixels.render().map_err(PixelsError::from))
+            }
+            _ => Ok(()),
+        };
+        if let Err(e) = step {
+            result = Err(e);
+            control_flow.set_exit();
+        }
+    });
+    result
+}
+
+fn press(chip8: &mut Chip8, keys: KeyMap, key: VirtualKeyCode, state: ElementState) {
+    match (key, state) {
+        // a missing or unreadable state, or a failed screenshot, is no reason
+        // to stop the game
+        (VirtualKeyCode::F5, ElementState::Pressed) => drop(chip8.quick_save()),
+        (VirtualKeyCode::F9, ElementState::Pressed) => drop(chip8.quick_load()),
+        (VirtualKeyCode::F12, ElementState::Pressed) => drop(chip8.screenshot()),
+        (key, ElementState::Pressed) => {
+            if let Some(key) = virtual_key_char(key).and_then(|c| keys.key(c)) {
+                chip8.cpu.keypad.press(key);
+            }
+        }
+        (key, ElementState::Released) => {
+            if let Some(key) = virtual_key_char(key).and_then(|c| keys.key(c)) {
+                chip8.cpu.keypad.release(key);
+            }
+        }
+    }
+}
+
+fn run_frame(chip8: &mut Chip8, instructions: u32) -> std::result::Result<(), PixelsError> {
+    match chip8.run_frame(instructions).fault {
+        Some(fault) => Err(PixelsError::Fault(fault)),
+        None => Ok(()),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_draw() {
+        let mut display = Display::new();
+        display.set_pixel(1, 0, true);
+        let mut frame = vec![0; Display::WIDTH * Display::HEIGHT * 4];
+        draw(&display, Palette::MONOCHROME, &mut frame);
+        assert_eq!(&frame[..8], &[0, 0, 0, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF]);
+    }
+}
@@ -0,0 +1,18 @@
+use chippers_core::chip::Chip8;
+use chippers_pixels::Options;
+
+fn main() -> std::result::Result<(), Box<dyn std::error::Error>> {
+    let Some(path) = std::env::args_os().nth(1) else {
+        return Err("usage: chippers-pixels <FILE>".into());
+    };
+    let rom = std::fs::read(&path)?;
+    let mut chip8 = Chip8::new();
+    chip8.load_font_set();
+    chip8.load_rom(&rom)?;
+    let options = Options {
+        title: format!("chippers - {}", path.to_string_lossy()),
+        ..Options::default()
+    };
+    chippers_pixels::run(&mut chip8, &options)?;
+    Ok(())
+}
@@ -0,0 +1,195 @@
+//! JavaScript bindings for the chippers core.
+//!
+//! Build an npm package with `wasm-pack build chippers-wasm --target bundler`;
+//! wasm-bindgen emits the TypeScript declarations alongside it.
+//!
+//! ```js
+//! import { Chip8 } from "chippers-wasm";
+//!
+//! const chip8 = new Chip8();
+//! chip8.loadRom(new Uint8Array(await rom.arrayBuffer()));
+//! const KEYS = Object.fromEntries([..."x123qweasdzc4rfv"].map((k, i) => [k, i]));
+//! addEventListener("keydown", (e) => KEYS[e.key] !== undefined && chip8.keyDown(KEYS[e.key]));
+//! addEventListener("keyup", (e) => KEYS[e.key] !== undefined && chip8.keyUp(KEYS[e.key]));
+//! requestAnimationFrame(function frame() {
+//!     if (chip8.runFrame()) draw(chip8.display(), chip8.width, chip8.height);
+//!     requestAnimationFrame(frame);
+//! });
+//! ```
+//!
+//! Only the `no_std` core is compiled in, so nothing here needs threads, a
+//! clock or a terminal: the page's animation frame is the only timer.
+
+use chippers_core::chip::{self, Chip8Message};
+use chippers_core::cpu::Cpu;
+use chippers_core::rng::Rng;
+use wasm_bindgen::prelude::*;
+
+const WIDTH: usize = 64;
+const HEIGHT: usize = 32;
+
+/// A CHIP-8 machine driven one 60 Hz frame at a time from JavaScript.
+#[wasm_bindgen]
+pub struct Chip8 {
+    chip8: chip::Chip8,
+    instructions_per_frame: u32,
+}
+
+#[wasm_bindgen]
+impl Chip8 {
+    #[wasm_bindgen(constructor)]
+    pub fn new() -> Chip8 {
+        Chip8 {
+            chip8: fresh_machine(),
+            instructions_per_frame: 10,
+        }
+    }
+
+    /// Resets the machine and loads `rom` at 0x200.
+    #[wasm_bindgen(js_name = loadRom)]
+    pub fn load_rom(&mut self, rom: &[u8]) -> Result<(), JsError> {
+        let mut chip8 = fresh_machine();
+        chip8
+            .load_rom(rom)
+            .map_err(|e| JsError::new(e.to_string().trim_end()))?;
+        self.chip8 = chip8;
+        Ok(())
+    }
+
+    /// Runs one frame of instructions and ticks the timers.
+    ///
+    /// Returns true if the display changed. A program that faults stays on the
+    /// faulting instruction and the rest of the frame is skipped.
+    #[wasm_bindgen(js_name = runFrame)]
+    pub fn run_frame(&mut self) -> bool {
+        self.chip8.run_frame(self.instructions_per_frame).drawn
+    }
+
+    /// Runs a single instruction without ticking the timers, for stepping
+    /// through a program. Returns true if the display changed.
+    pub fn tick(&mut self) -> bool {
+        matches!(
+            self.chip8.step(),
+            Chip8Message::ClearScreen | Chip8Message::DrawScreen(_)
+        )
+    }
+
+    /// The display as `width * height` bytes, row-major, 0 = off and 1 = on.
+    pub fn display(&self) -> Vec<u8> {
+        self.chip8.framebuffer().iter_8bpp().collect()
+    }
+
+    /// The display packed one bit per pixel, `width / 8` bytes per row with
+    /// the leftmost pixel in the most significant bit.
+    pub fn framebuffer(&self) -> Vec<u8> {
+        self.chip8.framebuffer().as_1bpp().to_vec()
+    }
+
+    /// Reports a press of keypad key `key` (0x0-0xF) from a source that
+    /// never says when the key is let go; it is held until the program reads
+    /// it.
+    #[wasm_bindgen(js_name = keyPress)]
+    pub fn key_press(&mut self, key: u8) {
+        self.chip8.cpu.press_key(key);
+    }
+
+    /// Holds keypad key `key` (0x0-0xF) down until [`key_up`](Chip8::key_up).
+    #[wasm_bindgen(js_name = keyDown)]
+    pub fn key_down(&mut self, key: u8) {
+        self.chip8.cpu.keypad.press(key);
+    }
+
+    #[wasm_bindgen(js_name = keyUp)]
+    pub fn key_up(&mut self, key: u8) {
+        self.chip8.cpu.keypad.release(key);
+    }
+
+    /// True while the sound timer is running and a tone should play.
+    #[wasm_bindgen(getter)]
+    pub fn beeping(&self) -> bool {
+        self.chip8.cpu.st > 0
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn width(&self) -> usize {
+        WIDTH
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn height(&self) -> usize {
+        HEIGHT
+    }
+
+    #[wasm_bindgen(getter = instructionsPerFrame)]
+    pub fn instructions_per_frame(&self) -> u32 {
+        self.instructions_per_frame
+    }
+
+    #[wasm_bindgen(setter = instructionsPerFrame)]
+    pub fn set_instructions_per_frame(&mut self, n: u32) {
+        self.instructions_per_frame = n;
+    }
+}
+
+impl Default for Chip8 {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+fn fresh_machine() -> chip::Chip8 {
+    let mut chip8 = chip::Chip8::with_cpu(Cpu::with_rng(Rng::new(seed())));
+    chip8.load_font_set();
+    chip8
+}
+
+#[cfg(target_arch = "wasm32")]
+fn seed() -> u32 {
+    (js_sys::Math::random() * u32::MAX as f64) as u32
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+fn seed() -> u32 {
+    Rng::DEFAULT_SEED
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_run_frame() {
+        let mut chip8 = Chip8::new();
+        // 6005: V0 = 5, F029: I = font(V0), D015: draw it at (V0, V0)
+        chip8
+            .load_rom(&[0x60, 0x05, 0xF0, 0x29, 0xD0, 0x05])
+            .unwrap();
+        chip8.set_instructions_per_frame(3);
+        assert!(chip8.run_frame());
+        let display = chip8.display();
+        assert_eq!(&display[5 * WIDTH + 5..5 * WIDTH + 10], &[1, 1, 1, 1, 0]);
+        // the same row packed: pixels 5 to 8 are bits 2 to 0 of byte 0 and 7 of byte 1
+        let framebuffer = chip8.framebuffer();
+        assert_eq!(&framebuffer[5 * 8..5 * 8 + 2], &[0b0000_0111, 0b1000_0000]);
+    }
+
+    #[test]
+    fn test_keys_and_tick() {
+        let mut chip8 = Chip8::new();
+        // E09E: skip if key V0 = 0 is down, 1200: loop, 1204: done
+        chip8
+            .load_rom(&[0xE0, 0x9E, 0x12, 0x00, 0x12, 0x04])
+            .unwrap();
+        chip8.key_down(0);
+        chip8.tick();
+        chip8.key_up(0);
+        assert_eq!(chip8.chip8.cpu.pc(), 0x204);
+        chip8
+            .load_rom(&[0xE0, 0x9E, 0x12, 0x00, 0x12, 0x04])
+            .unwrap();
+        chip8.key_down(0);
+        chip8.key_up(0);
+        assert!(!chip8.tick());
+        assert_eq!(chip8.chip8.cpu.pc(), 0x202);
+    }
+}
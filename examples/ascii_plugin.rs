@@ -0,0 +1,60 @@
+//! A minimal frontend plugin that prints each frame to stderr as text.
+//!
+//! Build with `cargo build --example ascii_plugin` and run the emulator with
+//! `chippers --plugin target/debug/examples/libascii_plugin.so <ROM>`.
+
+use chippers::plugin::*;
+use std::ffi::c_void;
+use std::io::Write;
+
+unsafe extern "C" fn create() -> *mut c_void {
+    std::ptr::null_mut()
+}
+
+unsafe extern "C" fn destroy(_state: *mut c_void) {}
+
+unsafe extern "C" fn clear_screen(_state: *mut c_void) -> i32 {
+    PLUGIN_OK
+}
+
+unsafe extern "C" fn draw_screen(
+    _state: *mut c_void,
+    pixels: *const u8,
+    width: u16,
+    height: u16,
+) -> i32 {
+    let pixels = std::slice::from_raw_parts(pixels, width as usize * height as usize);
+    let mut frame = String::from("\x1b[H");
+    for row in pixels.chunks(width as usize) {
+        frame.extend(row.iter().map(|p| if *p == 1 { '#' } else { ' ' }));
+        frame.push('\n');
+    }
+    match std::io::stderr().write_all(frame.as_bytes()) {
+        Ok(()) => PLUGIN_OK,
+        Err(_) => 1,
+    }
+}
+
+unsafe extern "C" fn poll_key(_state: *mut c_void) -> i32 {
+    NO_KEY
+}
+
+unsafe extern "C" fn beep(_state: *mut c_void, _on: bool) -> i32 {
+    PLUGIN_OK
+}
+
+static VTABLE: PluginVTable = PluginVTable {
+    abi_version: ABI_VERSION,
+    name: c"ascii".as_ptr(),
+    create,
+    destroy,
+    clear_screen,
+    draw_screen,
+    poll_key,
+    beep,
+};
+
+#[no_mangle]
+pub extern "C" fn chippers_plugin_entry() -> *const PluginVTable {
+    &VTABLE
+}
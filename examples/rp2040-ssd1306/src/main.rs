@@ -0,0 +1,89 @@
+//! Runs a CHIP-8 ROM on an RP2040 with an SSD1306 OLED over I2C.
+
+#![no_std]
+#![no_main]
+
+use chippers_core::backend::DisplayBackend;
+use chippers_core::chip::{Chip8, Chip8Message};
+use chippers_embedded::EmbeddedDisplay;
+use embedded_graphics::pixelcolor::BinaryColor;
+use fugit::RateExtU32;
+use panic_halt as _;
+use rp2040_hal::{self as hal, pac};
+use ssd1306::{prelude::*, I2CDisplayInterface, Ssd1306};
+
+#[link_section = ".boot2"]
+#[used]
+pub static BOOT2: [u8; 256] = rp2040_boot2::BOOT_LOADER_GENERIC_03H;
+
+const XTAL_FREQ_HZ: u32 = 12_000_000;
+const ROM: &[u8] = include_bytes!("../../../IBM Logo.ch8");
+/// Roughly 600 instructions per second at 60 frames per second.
+const INSTRUCTIONS_PER_FRAME: u32 = 10;
+
+#[hal::entry]
+fn main() -> ! {
+    let mut pac = pac::Peripherals::take().unwrap();
+    let mut watchdog = hal::Watchdog::new(pac.WATCHDOG);
+    let clocks = hal::clocks::init_clocks_and_plls(
+        XTAL_FREQ_HZ,
+        pac.XOSC,
+        pac.CLOCKS,
+        pac.PLL_SYS,
+        pac.PLL_USB,
+        &mut pac.RESETS,
+        &mut watchdog,
+    )
+    .ok()
+    .unwrap();
+    let sio = hal::Sio::new(pac.SIO);
+    let pins = hal::gpio::Pins::new(
+        pac.IO_BANK0,
+        pac.PADS_BANK0,
+        sio.gpio_bank0,
+        &mut pac.RESETS,
+    );
+    let timer = hal::Timer::new(pac.TIMER, &mut pac.RESETS, &clocks);
+
+    let sda: hal::gpio::Pin<_, hal::gpio::FunctionI2C, hal::gpio::PullUp> =
+        pins.gpio4.reconfigure();
+    let scl: hal::gpio::Pin<_, hal::gpio::FunctionI2C, hal::gpio::PullUp> =
+        pins.gpio5.reconfigure();
+    let i2c = hal::I2C::i2c0(
+        pac.I2C0,
+        sda,
+        scl,
+        400.kHz(),
+        &mut pac.RESETS,
+        &clocks.system_clock,
+    );
+    let mut oled = Ssd1306::new(
+        I2CDisplayInterface::new(i2c),
+        DisplaySize128x64,
+        DisplayRotation::Rotate0,
+    )
+    .into_buffered_graphics_mode();
+    oled.init().unwrap();
+
+    // 64x32 at scale 2 fills the 128x64 panel exactly
+    let mut display = EmbeddedDisplay::new(oled, BinaryColor::On, BinaryColor::Off).with_scale(2);
+    let mut chip8 = Chip8::new();
+    chip8.load_font_set();
+    chip8.load_rom(ROM).unwrap();
+
+    display.clear_screen().unwrap();
+    loop {
+        let frame_start = timer.get_counter();
+        for _ in 0..INSTRUCTIONS_PER_FRAME {
+            match chip8.step() {
+                Chip8Message::ClearScreen => display.clear_screen().unwrap(),
+                Chip8Message::DrawScreen(rows) => display.draw_rows(&chip8.cpu.disp, rows).unwrap(),
+                Chip8Message::Halted(_) => break,
+                _ => {}
+            }
+        }
+        display.target().flush().unwrap();
+        chip8.tick_timers();
+        while (timer.get_counter() - frame_start).to_millis() < 16 {}
+    }
+}
@@ -0,0 +1,169 @@
+//! The buzzer, played as a square wave on the default output device.
+//!
+//! Output streams cannot move between threads on every platform, so the stream
+//! lives on a thread of its own and [`Beeper`] only flips a flag it reads.
+
+use chippers_core::backend::AudioBackend;
+use cpal::traits::{DeviceTrait, HostTrait, StreamTrait};
+use cpal::{FromSample, SampleFormat, SizedSample, StreamConfig};
+use std::marker::PhantomData;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{mpsc, Arc};
+
+/// Pitch of the tone in Hz.
+pub const TONE: f32 = 440.;
+/// Peak amplitude, well short of full scale.
+const VOLUME: f32 = 0.1;
+
+/// An [`AudioBackend`] that sounds a square wave while the tone is on.
+///
+/// The error type is only there to match a frontend's: once open, setting the
+/// tone cannot fail. Dropping the beeper closes the stream.
+#[derive(Debug)]
+pub struct Beeper<E = AudioError> {
+    on: Arc<AtomicBool>,
+    // dropping the sender ends the audio thread, and the stream with it
+    _stream: Option<mpsc::Sender<()>>,
+    _error: PhantomData<fn() -> E>,
+}
+
+impl<E> Beeper<E> {
+    /// Starts a stream on the default output device.
+    pub fn open() -> std::result::Result<Self, AudioError> {
+        let on = Arc::new(AtomicBool::new(false));
+        let (opened, result) = mpsc::channel();
+        let (stop, stopped) = mpsc::channel::<()>();
+        let tone = Arc::clone(&on);
+        std::thread::spawn(move || match play(tone) {
+            Ok(_stream) => {
+                let _ = opened.send(Ok(()));
+                let _ = stopped.recv();
+            }
+            Err(e) => {
+                let _ = opened.send(Err(e));
+            }
+        });
+        result.recv().map_err(|_| AudioError::NoDevice)??;
+        Ok(Beeper {
+            on,
+            _stream: Some(stop),
+            _error: PhantomData,
+        })
+    }
+
+    /// A beeper with no device behind it, for when [`open`](Beeper::open)
+    /// fails but the program should run anyway.
+    pub fn silent() -> Self {
+        Beeper {
+            on: Arc::new(AtomicBool::new(false)),
+            _stream: None,
+            _error: PhantomData,
+        }
+    }
+
+    pub fn is_on(&self) -> bool {
+        self.on.load(Ordering::Relaxed)
+    }
+}
+
+impl<E> AudioBackend for Beeper<E> {
+    type Error = E;
+    fn set_tone(&mut self, on: bool) -> std::result::Result<(), Self::Error> {
+        self.on.store(on, Ordering::Relaxed);
+        Ok(())
+    }
+}
+
+fn play(on: Arc<AtomicBool>) -> std::result::Result<cpal::Stream, AudioError> {
+    let device = cpal::default_host()
+        .default_output_device()
+        .ok_or(AudioError::NoDevice)?;
+    let supported = device.default_output_config()?;
+    let format = supported.sample_format();
+    let config = supported.config();
+    let stream = match format {
+        SampleFormat::I16 => build::<i16>(&device, &config, on)?,
+        SampleFormat::U16 => build::<u16>(&device, &config, on)?,
+        SampleFormat::F32 => build::<f32>(&device, &config, on)?,
+        format => return Err(AudioError::Format(format)),
+    };
+    stream.play()?;
+    Ok(stream)
+}
+
+fn build<T>(
+    device: &cpal::Device,
+    config: &StreamConfig,
+    on: Arc<AtomicBool>,
+) -> std::result::Result<cpal::Stream, cpal::BuildStreamError>
+where
+    T: SizedSample + FromSample<f32>,
+{
+    let channels = usize::from(config.channels);
+    let period = config.sample_rate.0 as f32 / TONE;
+    let mut phase = 0f32;
+    device.build_output_stream(
+        config,
+        move |data: &mut [T], _| {
+            let on = on.load(Ordering::Relaxed);
+            for frame in data.chunks_mut(channels) {
+                let level = match (on, phase < period / 2.) {
+                    (false, _) => 0.,
+                    (true, true) => VOLUME,
+                    (true, false) => -VOLUME,
+                };
+                frame.fill(T::from_sample(level));
+                phase = (phase + 1.) % period;
+            }
+        },
+        // an underrun is a click, not a reason to stop the program
+        |_| {},
+        None,
+    )
+}
+
+#[derive(Debug)]
+pub enum AudioError {
+    /// There is no output device, or the audio thread could not start.
+    NoDevice,
+    Config(cpal::DefaultStreamConfigError),
+    /// The device only takes samples in a format the beeper does not produce.
+    Format(SampleFormat),
+    Build(cpal::BuildStreamError),
+    Play(cpal::PlayStreamError),
+}
+
+impl std::fmt::Display for AudioError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            AudioError::NoDevice => writeln!(f, "error: no audio output device")?,
+            AudioError::Config(e) => writeln!(f, "error: audio device config: {}", e)?,
+            AudioError::Format(format) => {
+                writeln!(f, "error: unsupported audio sample format {}", format)?
+            }
+            AudioError::Build(e) => writeln!(f, "error: could not open audio stream: {}", e)?,
+            AudioError::Play(e) => writeln!(f, "error: could not start audio stream: {}", e)?,
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for AudioError {}
+
+impl From<cpal::DefaultStreamConfigError> for AudioError {
+    fn from(e: cpal::DefaultStreamConfigError) -> Self {
+        AudioError::Config(e)
+    }
+}
+
+impl From<cpal::BuildStreamError> for AudioError {
+    fn from(e: cpal::BuildStreamError) -> Self {
+        AudioError::Build(e)
+    }
+}
+
+impl From<cpal::PlayStreamError> for AudioError {
+    fn from(e: cpal::PlayStreamError) -> Self {
+        AudioError::Play(e)
+    }
+}
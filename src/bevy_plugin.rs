@@ -0,0 +1,247 @@
+//! Embeds a CHIP-8 machine in a Bevy app.
+//!
+//! [`ChippersPlugin`] runs the machine once per `Update`, exposes its display as
+//! an [`Image`] asset through the [`Chip8Screen`] resource, and mirrors the
+//! keyboard onto the keypad through a [`KeyMap`], `1234`/`QWER`/`ASDF`/`ZXCV` by
+//! default, keys staying down for as long as they are held.
+//! Put the image on a sprite, a UI node or a material to show the screen.
+
+use bevy::app::{App, Plugin, Startup, Update};
+use bevy::asset::{Assets, Handle, RenderAssetUsages};
+use bevy::ecs::prelude::*;
+use bevy::image::Image;
+use bevy::input::keyboard::KeyCode;
+use bevy::input::ButtonInput;
+use chippers_core::backend::{Palette, Resolution, Rgb};
+use chippers_core::chip::{Chip8, LoadError};
+use chippers_core::display::Display;
+use chippers_core::keypad::KeyMap;
+use wgpu_types::{Extent3d, TextureDimension, TextureFormat};
+
+/// Adds a machine running a ROM to the app.
+#[derive(Clone, Debug)]
+pub struct ChippersPlugin {
+    rom: Vec<u8>,
+    /// Instructions run per `Update`; the timers tick once per `Update`.
+    pub instructions_per_frame: u32,
+    pub palette: Palette,
+    pub keys: KeyMap,
+}
+
+impl ChippersPlugin {
+    /// Fails if `rom` cannot be loaded, so that adding the plugin cannot.
+    pub fn new(rom: impl Into<Vec<u8>>) -> Result<Self, LoadError> {
+        let rom = rom.into();
+        Chip8::new().load_rom(&rom)?;
+        Ok(ChippersPlugin {
+            rom,
+            instructions_per_frame: 12,
+            palette: Palette::default(),
+            keys: KeyMap::default(),
+        })
+    }
+
+    pub fn rom(&self) -> &[u8] {
+        &self.rom
+    }
+}
+
+/// The running machine.
+#[derive(Resource, Debug)]
+pub struct Chip8Machine {
+    pub chip8: Chip8,
+    pub instructions_per_frame: u32,
+    pub palette: Palette,
+    pub keys: KeyMap,
+}
+
+/// The display texture, updated after every frame the program draws in.
+#[derive(Resource, Clone, Debug)]
+pub struct Chip8Screen(pub Handle<Image>);
+
+impl Plugin for ChippersPlugin {
+    fn build(&self, app: &mut App) {
+        let mut chip8 = Chip8::new();
+        chip8.load_font_set();
+        // new loaded it already; were it refused, the empty machine would
+        // halt on its first instruction
+        let _ = chip8.load_rom(&self.rom);
+        app.insert_resource(Chip8Machine {
+            chip8,
+            instructions_per_frame: self.instructions_per_frame,
+            palette: self.palette,
+            keys: self.keys,
+        })
+        .add_systems(Startup, create_screen)
+        .add_systems(Update, (read_keypad, run_frame).chain());
+    }
+}
+
+fn rgba(rgb: Rgb) -> [u8; 4] {
+    [rgb.r, rgb.g, rgb.b, 0xFF]
+}
+
+fn create_screen(
+    mut commands: Commands,
+    machine: Res<Chip8Machine>,
+    mut images: ResMut<Assets<Image>>,
+) {
+    let image = Image::new_fill(
+        Extent3d {
+            width: Display::WIDTH as u32,
+            height: Display::HEIGHT as u32,
+            depth_or_array_layers: 1,
+        },
+        TextureDimension::D2,
+        &rgba(machine.palette.off),
+        TextureFormat::Rgba8UnormSrgb,
+        RenderAssetUsages::default(),
+    );
+    commands.insert_resource(Chip8Screen(images.add(image)));
+}
+
+/// The character a key types on a QWERTY keyboard. Bevy names keys by where
+/// they are rather than what they type.
+pub fn key_code_char(code: KeyCode) -> Option<char> {
+    let c = match code {
+        KeyCode::Digit0 => '0',
+        KeyCode::Digit1 => '1',
+        KeyCode::Digit2 => '2',
+        KeyCode::Digit3 => '3',
+        KeyCode::Digit4 => '4',
+        KeyCode::Digit5 => '5',
+        KeyCode::Digit6 => '6',
+        KeyCode::Digit7 => '7',
+        KeyCode::Digit8 => '8',
+        KeyCode::Digit9 => '9',
+        KeyCode::KeyA => 'a',
+        KeyCode::KeyB => 'b',
+        KeyCode::KeyC => 'c',
+        KeyCode::KeyD => 'd',
+        KeyCode::KeyE => 'e',
+        KeyCode::KeyF => 'f',
+        KeyCode::KeyG => 'g',
+        KeyCode::KeyH => 'h',
+        KeyCode::KeyI => 'i',
+        KeyCode::KeyJ => 'j',
+        KeyCode::KeyK => 'k',
+        KeyCode::KeyL => 'l',
+        KeyCode::KeyM => 'm',
+        KeyCode::KeyN => 'n',
+        KeyCode::KeyO => 'o',
+        KeyCode::KeyP => 'p',
+        KeyCode::KeyQ => 'q',
+        KeyCode::KeyR => 'r',
+        KeyCode::KeyS => 's',
+        KeyCode::KeyT => 't',
+        KeyCode::KeyU => 'u',
+        KeyCode::KeyV => 'v',
+        KeyCode::KeyW => 'w',
+        KeyCode::KeyX => 'x',
+        KeyCode::KeyY => 'y',
+        KeyCode::KeyZ => 'z',
+        KeyCode::Comma => ',',
+        KeyCode::Period => '.',
+        KeyCode::Semicolon => ';',
+        KeyCode::Quote => '\'',
+        KeyCode::Slash => '/',
+        KeyCode::Minus => '-',
+        _ => return None,
+    };
+    Some(c)
+}
+
+fn read_keypad(keys: Res<ButtonInput<KeyCode>>, mut machine: ResMut<Chip8Machine>) {
+    let machine = &mut *machine;
+    let map = |code: &KeyCode| key_code_char(*code).and_then(|c| machine.keys.key(c));
+    let keypad = &mut machine.chip8.cpu.keypad;
+    for key in keys.get_just_pressed().filter_map(map) {
+        keypad.press(key);
+    }
+    for key in keys.get_just_released().filter_map(map) {
+        keypad.release(key);
+    }
+}
+
+fn run_frame(
+    mut machine: ResMut<Chip8Machine>,
+    screen: Option<Res<Chip8Screen>>,
+    mut images: ResMut<Assets<Image>>,
+) {
+    let machine = &mut *machine;
+    let frame = machine.chip8.run_frame(machine.instructions_per_frame);
+    let Some(screen) = screen else { return };
+    if !frame.drawn {
+        return;
+    }
+    let Some(image) = images.get_mut(&screen.0) else {
+        return;
+    };
+    // the image grows to show a MegaChip picture, and shrinks back after
+    let mega = machine.chip8.megachip.as_ref().filter(|mega| mega.is_on());
+    let size = match mega {
+        Some(_) => Resolution::MEGACHIP,
+        None => Resolution::LORES,
+    };
+    if image.width() != u32::from(size.width) {
+        image.resize(Extent3d {
+            width: u32::from(size.width),
+            height: u32::from(size.height),
+            depth_or_array_layers: 1,
+        });
+    }
+    let Some(data) = image.data.as_mut() else {
+        return;
+    };
+    if let Some(mega) = mega {
+        for (texel, color) in data.chunks_exact_mut(4).zip(mega.frame()) {
+            texel.copy_from_slice(&rgba(*color));
+        }
+        return;
+    }
+    let (on, off) = (rgba(machine.palette.on), rgba(machine.palette.off));
+    for (texel, pixel) in data
+        .chunks_exact_mut(4)
+        .zip(machine.chip8.framebuffer().iter_8bpp())
+    {
+        texel.copy_from_slice(if pixel == 1 { &on } else { &off });
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use bevy::asset::AssetApp;
+
+    #[test]
+    fn test_plugin_updates_screen() {
+        let mut app = App::new();
+        app.add_plugins(bevy::asset::AssetPlugin::default())
+            .init_asset::<Image>()
+            .init_resource::<ButtonInput<KeyCode>>()
+            // A050: I = font "0", D005: draw it at (V0, V0), 1204: halt
+            .add_plugins(ChippersPlugin::new([0xA0, 0x50, 0xD0, 0x05, 0x12, 0x04]).unwrap());
+        app.update();
+        let handle = app.world().resource::<Chip8Screen>().0.clone();
+        let images = app.world().resource::<Assets<Image>>();
+        let data = images.get(&handle).unwrap().data.as_ref().unwrap();
+        assert_eq!(data.len(), 64 * 32 * 4);
+        // the top row of the glyph is 0xF0: four lit pixels, then unlit ones
+        assert!(data[..16].iter().all(|b| *b == 0xFF));
+        assert_eq!(&data[16..20], &[0, 0, 0, 0xFF]);
+    }
+
+    #[test]
+    fn test_rom_is_checked_up_front() {
+        assert_eq!(
+            ChippersPlugin::new(Vec::new()).unwrap_err(),
+            LoadError::Empty
+        );
+        let error = ChippersPlugin::new(vec![0; 4096]).unwrap_err();
+        assert!(matches!(error, LoadError::TooLarge { size: 4096, .. }));
+        assert_eq!(
+            ChippersPlugin::new([0x12, 0x00]).unwrap().rom(),
+            [0x12, 0x00]
+        );
+    }
+}
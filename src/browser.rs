@@ -0,0 +1,271 @@
+//! A picker over a directory of ROMs in the terminal, for `--romdir`.
+//!
+//! Each ROM is listed with its size and SHA-1, under its title when the
+//! [ROM database](chippers_core::romdb) knows it, with the author and the
+//! quirks it was written for shown below the list. The arrow keys, Page Up,
+//! Page Down, Home and End move; Enter runs the ROM and Escape or `q` leaves.
+
+use crate::terminal::{RawScreen, TerminalError};
+use chippers_core::romdb::{self, KnownRom, Sha1};
+use crossterm::event::{self, Event, KeyCode, KeyEventKind};
+use crossterm::{cursor, queue, style, terminal};
+use std::io::{stdout, Write};
+use std::path::{Path, PathBuf};
+
+/// File extensions taken for ROMs when scanning a directory.
+pub const ROM_EXTENSIONS: [&str; 4] = ["ch8", "c8", "sc8", "xo8"];
+
+/// A ROM found by [`scan`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Entry {
+    pub path: PathBuf,
+    pub size: usize,
+    pub sha1: Sha1,
+    pub known: Option<&'static KnownRom>,
+}
+
+impl Entry {
+    /// The ROM's title if it is known, else its file name.
+    pub fn name(&self) -> String {
+        match self.known {
+            Some(known) => known.title.to_string(),
+            None => self
+                .path
+                .file_stem()
+                .unwrap_or(self.path.as_os_str())
+                .to_string_lossy()
+                .into_owned(),
+        }
+    }
+}
+
+/// The ROMs directly in `dir`, by name.
+pub fn scan(dir: &Path) -> std::io::Result<Vec<Entry>> {
+    let mut entries = Vec::new();
+    for file in std::fs::read_dir(dir)? {
+        let path = file?.path();
+        let is_rom = path
+            .extension()
+            .and_then(|ext| ext.to_str())
+            .is_some_and(|ext| ROM_EXTENSIONS.contains(&ext.to_ascii_lowercase().as_str()));
+        if !is_rom || !path.is_file() {
+            continue;
+        }
+        let rom = std::fs::read(&path)?;
+        let sha1 = Sha1::of(&rom);
+        entries.push(Entry {
+            path,
+            size: rom.len(),
+            sha1,
+            known: romdb::find(sha1),
+        });
+    }
+    entries.sort_by(|a, b| a.path.cmp(&b.path));
+    Ok(entries)
+}
+
+/// The list and where the user is in it.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Picker {
+    entries: Vec<Entry>,
+    selected: usize,
+    // the first entry on screen
+    top: usize,
+}
+
+/// What a key press asks of the picker.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Choice {
+    Run(PathBuf),
+    Quit,
+}
+
+impl Picker {
+    pub fn new(entries: Vec<Entry>) -> Self {
+        Picker {
+            entries,
+            selected: 0,
+            top: 0,
+        }
+    }
+
+    /// Shows the list until the user picks a ROM or leaves, returning the
+    /// ROM picked.
+    pub fn run(&mut self) -> std::result::Result<Option<PathBuf>, TerminalError> {
+        let screen = RawScreen::enter()?;
+        let choice = loop {
+            let (width, height) = terminal::size()?;
+            self.draw(usize::from(width), usize::from(height))?;
+            let Event::Key(key) = event::read()? else {
+                continue;
+            };
+            if key.kind == KeyEventKind::Release {
+                continue;
+            }
+            let page = usize::from(height).saturating_sub(FOOTER + 1).max(1);
+            match self.press(key.code, page) {
+                Some(Choice::Run(path)) => break Some(path),
+                Some(Choice::Quit) => break None,
+                None => {}
+            }
+        };
+        screen.leave()?;
+        Ok(choice)
+    }
+
+    /// Moves the selection for `key`, `page` entries being on screen at once.
+    pub fn press(&mut self, key: KeyCode, page: usize) -> Option<Choice> {
+        let last = self.entries.len().saturating_sub(1);
+        self.selected = match key {
+            KeyCode::Enter => {
+                return self
+                    .entries
+                    .get(self.selected)
+                    .map(|entry| Choice::Run(entry.path.clone()))
+            }
+            KeyCode::Esc | KeyCode::Char('q') => return Some(Choice::Quit),
+            KeyCode::Up | KeyCode::Char('k') => self.selected.saturating_sub(1),
+            KeyCode::Down | KeyCode::Char('j') => (self.selected + 1).min(last),
+            KeyCode::PageUp => self.selected.saturating_sub(page),
+            KeyCode::PageDown => (self.selected + page).min(last),
+            KeyCode::Home => 0,
+            KeyCode::End => last,
+            _ => self.selected,
+        };
+        // keep the selection on screen
+        self.top = self
+            .top
+            .min(self.selected)
+            .max((self.selected + 1).saturating_sub(page));
+        None
+    }
+
+    /// The screen as lines of at most `width` characters, `height` of them.
+    pub fn lines(&self, width: usize, height: usize) -> Vec<String> {
+        let rows = height.saturating_sub(FOOTER);
+        let mut lines: Vec<String> = self
+            .entries
+            .iter()
+            .enumerate()
+            .skip(self.top)
+            .take(rows)
+            .map(|(i, entry)| {
+                let marker = if i == self.selected { '>' } else { ' ' };
+                format!(
+                    "{} {:<32.32} {:>6}  {}",
+                    marker,
+                    entry.name(),
+                    entry.size,
+                    entry.sha1
+                )
+            })
+            .collect();
+        if self.entries.is_empty() {
+            lines.push(String::from("  no ROMs here"));
+        }
+        lines.resize(rows, String::new());
+        lines.push(String::new());
+        lines.push(match self.entries.get(self.selected) {
+            Some(entry) => details(entry),
+            None => String::new(),
+        });
+        lines.push(String::from(
+            "↑↓ PgUp PgDn Home End move  Enter run  Esc quit",
+        ));
+        lines
+            .into_iter()
+            .map(|line| line.chars().take(width).collect())
+            .collect()
+    }
+
+    fn draw(&self, width: usize, height: usize) -> std::result::Result<(), TerminalError> {
+        let mut out = stdout().lock();
+        queue!(out, terminal::Clear(terminal::ClearType::All))?;
+        for (row, line) in self.lines(width, height).iter().enumerate() {
+            queue!(out, cursor::MoveTo(0, row as u16), style::Print(line))?;
+        }
+        out.flush()?;
+        Ok(())
+    }
+}
+
+/// Lines below the list: a gap, the details and the keys.
+const FOOTER: usize = 3;
+
+/// The file and what the database says about it.
+fn details(entry: &Entry) -> String {
+    let mut text = entry.path.display().to_string();
+    if let Some(known) = entry.known {
+        if let Some(author) = known.author {
+            text += &format!(", by {}", author);
+        }
+        if let Some(year) = known.year {
+            text += &format!(", {}", year);
+        }
+        if let Some(quirks) = known.quirks {
+            text += &format!(", for {} quirks", quirks);
+        }
+        if let Some(speed) = known.speed {
+            text += &format!(", at {} instructions a second", speed);
+        }
+    }
+    text
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    /// A picker over a directory of three ROMs, made afresh for each `test`.
+    fn picker(test: &str) -> Picker {
+        let name = format!("chippers-romdir-{}-{}", test, std::process::id());
+        let dir = std::env::temp_dir().join(name);
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("b.ch8"), include_bytes!("../IBM Logo.ch8")).unwrap();
+        std::fs::write(dir.join("a.CH8"), [0x12, 0x00]).unwrap();
+        std::fs::write(dir.join("c.sc8"), [0x00, 0xE0]).unwrap();
+        std::fs::write(dir.join("notes.txt"), "not a rom").unwrap();
+        let entries = scan(&dir).unwrap();
+        std::fs::remove_dir_all(&dir).unwrap();
+        Picker::new(entries)
+    }
+
+    #[test]
+    fn test_scan() {
+        let picker = picker("scan");
+        let names: Vec<String> = picker.entries.iter().map(Entry::name).collect();
+        assert_eq!(names, ["a", "IBM Logo", "c"]);
+        assert_eq!(picker.entries[1].size, 132);
+        let lines = picker.lines(100, 8);
+        assert_eq!(lines.len(), 8);
+        assert_eq!(
+            lines[1].trim_end(),
+            format!(
+                "  {:<32} {:>6}  {}",
+                "IBM Logo", 132, picker.entries[1].sha1
+            )
+        );
+        assert!(lines[0].starts_with("> a "));
+        assert!(lines[6].ends_with("a.CH8"), "{}", lines[6]);
+    }
+
+    #[test]
+    fn test_keys() {
+        let mut picker = picker("keys");
+        assert_eq!(picker.press(KeyCode::Down, 2), None);
+        assert_eq!(picker.press(KeyCode::Down, 2), None);
+        assert_eq!(picker.press(KeyCode::Down, 2), None);
+        assert_eq!(picker.selected, 2);
+        // the list scrolled to keep the selection in view
+        assert_eq!(picker.top, 1);
+        assert_eq!(picker.press(KeyCode::Home, 2), None);
+        assert_eq!((picker.selected, picker.top), (0, 0));
+        picker.press(KeyCode::PageDown, 2);
+        match picker.press(KeyCode::Enter, 2) {
+            Some(Choice::Run(path)) => assert!(path.ends_with("c.sc8")),
+            other => panic!("{:?}", other),
+        }
+        assert_eq!(picker.press(KeyCode::Esc, 2), Some(Choice::Quit));
+        assert_eq!(Picker::new(Vec::new()).press(KeyCode::Enter, 2), None);
+    }
+}
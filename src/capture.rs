@@ -0,0 +1,368 @@
+//! Screen recordings, saved as an animated GIF or APNG.
+//!
+//! A [`Capture`] hooks into a machine and keeps the display as it stood at
+//! each timer tick, whenever it changed since the last one: that is what a
+//! 60 Hz screen would have shown, and timing frames by ticks rather than the
+//! wall clock keeps them right when running headless or from a replay.
+//!
+//! ```no_run
+//! use chippers::capture::{Capture, Format};
+//! use chippers::backend::Palette;
+//! use chippers::chip::Chip8;
+//!
+//! let mut chip8 = Chip8::new();
+//! let capture = Capture::new();
+//! capture.attach(&mut chip8);
+//! // ... run it ...
+//! let gif = capture.encode(Format::Gif, 4, Palette::AMBER).unwrap();
+//! std::fs::write("run.gif", gif).unwrap();
+//! ```
+
+use chippers_core::backend::Palette;
+use chippers_core::chip::Chip8;
+use chippers_core::display::PackedFrame;
+use chippers_core::image::indices;
+use std::borrow::Cow;
+use std::path::Path;
+use std::sync::{Arc, Mutex, MutexGuard};
+
+/// Timer ticks a second, the rate frames are timed in.
+const TICKS_PER_SECOND: u64 = 60;
+
+/// How long the last frame stays up at least before the animation loops.
+const LAST_FRAME_TICKS: u64 = TICKS_PER_SECOND;
+
+/// Image formats a capture can be saved as.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Format {
+    Gif,
+    Apng,
+}
+
+impl Format {
+    /// The format to save to `path` in, by its extension: `.gif`, or `.png`
+    /// or `.apng`.
+    pub fn from_path(path: &Path) -> Option<Self> {
+        let extension = path.extension()?.to_str()?.to_ascii_lowercase();
+        match extension.as_str() {
+            "gif" => Some(Format::Gif),
+            "png" | "apng" => Some(Format::Apng),
+            _ => None,
+        }
+    }
+
+    /// The shortest time a frame can be shown for, in ticks. GIF delays are
+    /// in hundredths of a second, and viewers slow down anything under two
+    /// of them, so a GIF drops frames that only lasted a tick.
+    fn shortest_frame(self) -> u64 {
+        match self {
+            Format::Gif => 2,
+            Format::Apng => 1,
+        }
+    }
+}
+
+/// Keeps the frames a machine shows, to encode as an animation.
+#[derive(Clone, Default)]
+pub struct Capture(Arc<Mutex<Film>>);
+
+#[derive(Default)]
+struct Film {
+    ticks: u64,
+    // drawn since the last tick
+    latest: Option<PackedFrame>,
+    // each frame with the tick it went up on
+    frames: Vec<(u64, PackedFrame)>,
+}
+
+impl Film {
+    fn show(&mut self, frame: PackedFrame) {
+        if self.frames.last().map(|(_, last)| last) != Some(&frame) {
+            self.frames.push((self.ticks, frame));
+        }
+    }
+}
+
+impl std::fmt::Debug for Capture {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        f.debug_struct("Capture")
+            .field("frames", &self.frames())
+            .finish_non_exhaustive()
+    }
+}
+
+impl Capture {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Starts keeping the frames `chip8` shows, from the display as it is now.
+    pub fn attach(&self, chip8: &mut Chip8) {
+        self.lock().show(chip8.cpu.disp.pack());
+        let film = self.0.clone();
+        chip8.hooks.on_instruction(move |_, raw, cpu| {
+            // 00E0 clears, 00CN, 00DN and 00FB to 00FF scroll or switch
+            // resolution, DXYN draws
+            if raw & 0xFF00 == 0x0000 || raw & 0xF000 == 0xD000 {
+                let mut film = film.lock().unwrap_or_else(|e| e.into_inner());
+                film.latest = Some(cpu.disp.pack());
+            }
+        });
+        let film = self.0.clone();
+        chip8.hooks.on_timer_tick(move |_, _| {
+            let mut film = film.lock().unwrap_or_else(|e| e.into_inner());
+            film.ticks += 1;
+            if let Some(frame) = film.latest.take() {
+                film.show(frame);
+            }
+        });
+    }
+
+    /// Distinct frames kept so far.
+    pub fn frames(&self) -> usize {
+        let film = self.lock();
+        film.frames.len() + usize::from(film.latest.is_some())
+    }
+
+    /// Encodes the frames kept so far as an endlessly looping animation, each
+    /// display pixel `scale` image pixels across, in `palette`'s colors.
+    ///
+    /// Frames at a lower resolution than the highest one shown are scaled up
+    /// to fill the same image.
+    pub fn encode(
+        &self,
+        format: Format,
+        scale: usize,
+        palette: Palette,
+    ) -> std::result::Result<Vec<u8>, CaptureError> {
+        let mut film = self.lock();
+        if let Some(frame) = film.latest.take() {
+            film.show(frame);
+        }
+        let frames = timeline(&film.frames, film.ticks, format.shortest_frame());
+        let scale = scale.max(1);
+        let width = frames
+            .iter()
+            .map(|(frame, _)| frame.width)
+            .max()
+            .unwrap_or(64)
+            * scale;
+        let height = frames
+            .iter()
+            .map(|(frame, _)| frame.height)
+            .max()
+            .unwrap_or(32)
+            * scale;
+        let colors = [
+            palette.off.r,
+            palette.off.g,
+            palette.off.b,
+            palette.on.r,
+            palette.on.g,
+            palette.on.b,
+        ];
+        let mut out = Vec::new();
+        match format {
+            Format::Gif => {
+                let mut encoder =
+                    gif::Encoder::new(&mut out, width as u16, height as u16, &colors)?;
+                encoder.set_repeat(gif::Repeat::Infinite)?;
+                let mut shown = 0;
+                for (frame, ticks) in frames {
+                    // round each frame's end rather than its length, so the
+                    // delays add up to the run's length
+                    let start = shown * 100 / TICKS_PER_SECOND;
+                    shown += ticks;
+                    let delay = shown * 100 / TICKS_PER_SECOND - start;
+                    encoder.write_frame(&gif::Frame {
+                        width: width as u16,
+                        height: height as u16,
+                        delay: delay.min(u64::from(u16::MAX)) as u16,
+                        buffer: Cow::Owned(indices(frame, width, height)),
+                        ..gif::Frame::default()
+                    })?;
+                }
+            }
+            Format::Apng => {
+                let mut encoder = png::Encoder::new(&mut out, width as u32, height as u32);
+                encoder.set_color(png::ColorType::Indexed);
+                encoder.set_depth(png::BitDepth::Eight);
+                encoder.set_palette(colors.to_vec());
+                encoder.set_animated(frames.len() as u32, 0)?;
+                let mut writer = encoder.write_header()?;
+                for (frame, ticks) in frames {
+                    writer.set_frame_delay(
+                        ticks.min(u64::from(u16::MAX)) as u16,
+                        TICKS_PER_SECOND as u16,
+                    )?;
+                    writer.write_image_data(&indices(frame, width, height))?;
+                }
+                writer.finish()?;
+            }
+        }
+        Ok(out)
+    }
+
+    /// Encodes the frames kept so far in the format `path`'s extension names
+    /// and writes them there, returning how many there were.
+    pub fn save(
+        &self,
+        path: &Path,
+        scale: usize,
+        palette: Palette,
+    ) -> std::result::Result<usize, CaptureError> {
+        let format =
+            Format::from_path(path).ok_or_else(|| CaptureError::Format(path.to_owned()))?;
+        let frames = self.frames();
+        std::fs::write(path, self.encode(format, scale, palette)?)?;
+        Ok(frames)
+    }
+
+    fn lock(&self) -> MutexGuard<'_, Film> {
+        self.0.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+/// Each frame with how many ticks it stays up, leaving out those up for fewer
+/// than `shortest` ticks, though never the last.
+fn timeline(frames: &[(u64, PackedFrame)], end: u64, shortest: u64) -> Vec<(&PackedFrame, u64)> {
+    let mut timeline: Vec<(&PackedFrame, u64)> = Vec::with_capacity(frames.len());
+    let mut pending = None;
+    for (i, (at, frame)) in frames.iter().enumerate() {
+        // a dropped frame's time goes to the one after it
+        let start = pending.take().unwrap_or(*at);
+        let until = frames
+            .get(i + 1)
+            .map_or(end.max(at + LAST_FRAME_TICKS), |(next, _)| *next);
+        if until - start < shortest && i + 1 < frames.len() {
+            pending = Some(start);
+        } else {
+            timeline.push((frame, until - start));
+        }
+    }
+    timeline
+}
+
+/// Why a capture could not be saved.
+#[derive(Debug)]
+pub enum CaptureError {
+    /// The file name does not end in `.gif`, `.png` or `.apng`.
+    Format(std::path::PathBuf),
+    Io(std::io::Error),
+    Gif(gif::EncodingError),
+    Png(png::EncodingError),
+}
+
+impl From<std::io::Error> for CaptureError {
+    fn from(e: std::io::Error) -> Self {
+        CaptureError::Io(e)
+    }
+}
+
+impl From<gif::EncodingError> for CaptureError {
+    fn from(e: gif::EncodingError) -> Self {
+        CaptureError::Gif(e)
+    }
+}
+
+impl From<png::EncodingError> for CaptureError {
+    fn from(e: png::EncodingError) -> Self {
+        CaptureError::Png(e)
+    }
+}
+
+impl std::fmt::Display for CaptureError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            CaptureError::Format(path) => writeln!(
+                f,
+                "error: cannot tell the format to save {} in, expected .gif, .png or .apng",
+                path.display()
+            ),
+            CaptureError::Io(e) => writeln!(f, "error: cannot write capture: {}", e),
+            CaptureError::Gif(e) => writeln!(f, "error: cannot encode gif: {}", e),
+            CaptureError::Png(e) => writeln!(f, "error: cannot encode png: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for CaptureError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            CaptureError::Format(_) => None,
+            CaptureError::Io(e) => Some(e),
+            CaptureError::Gif(e) => Some(e),
+            CaptureError::Png(e) => Some(e),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    // 00E0: clear, A050: I = font "0", D005: draw it, 00E0: clear, 1208: stay
+    const ROM: [u8; 10] = [0x00, 0xE0, 0xA0, 0x50, 0xD0, 0x05, 0x00, 0xE0, 0x12, 0x08];
+
+    #[test]
+    fn test_capture() {
+        let mut chip8 = Chip8::new();
+        chip8.load_font_set();
+        chip8.load_rom(&ROM).unwrap();
+        let capture = Capture::new();
+        capture.attach(&mut chip8);
+        assert_eq!(capture.frames(), 1);
+        // clear and draw within a tick: only the drawing is shown
+        for _ in 0..3 {
+            chip8.step();
+        }
+        chip8.tick_timers();
+        assert_eq!(capture.frames(), 2);
+        // nothing changes for three ticks, then the screen clears
+        for _ in 0..3 {
+            chip8.tick_timers();
+        }
+        chip8.step();
+        chip8.tick_timers();
+        assert_eq!(capture.frames(), 3);
+
+        let gif = capture.encode(Format::Gif, 2, Palette::AMBER).unwrap();
+        assert_eq!(&gif[..6], b"GIF89a");
+        assert_eq!(&gif[6..10], &[128, 0, 64, 0]);
+        // the global palette: unlit, then lit
+        assert_eq!(&gif[13..19], &[0x1A, 0x10, 0x00, 0xFF, 0xB0, 0x00]);
+
+        let apng = capture.encode(Format::Apng, 1, Palette::AMBER).unwrap();
+        assert_eq!(&apng[..8], b"\x89PNG\r\n\x1a\n");
+        let actl = apng.windows(4).position(|w| w == b"acTL").unwrap();
+        assert_eq!(&apng[actl + 4..actl + 8], &3u32.to_be_bytes());
+    }
+
+    #[test]
+    fn test_timeline() {
+        let frame = |on| {
+            let mut disp = chippers_core::display::Display::new();
+            disp.set_pixel(0, 0, on);
+            disp.pack()
+        };
+        let frames = [(0, frame(false)), (1, frame(true)), (2, frame(false))];
+        let lengths = |shortest| -> Vec<u64> {
+            timeline(&frames, 10, shortest)
+                .into_iter()
+                .map(|(_, ticks)| ticks)
+                .collect()
+        };
+        assert_eq!(lengths(1), [1, 1, 60]);
+        // the first frame, up for a tick, folds into the next
+        assert_eq!(lengths(2), [2, 60]);
+    }
+
+    #[test]
+    fn test_format_from_path() {
+        assert_eq!(Format::from_path(Path::new("run.gif")), Some(Format::Gif));
+        assert_eq!(Format::from_path(Path::new("run.PNG")), Some(Format::Apng));
+        assert_eq!(Format::from_path(Path::new("run.apng")), Some(Format::Apng));
+        assert_eq!(Format::from_path(Path::new("run.txt")), None);
+        assert_eq!(Format::from_path(Path::new("run")), None);
+    }
+}
@@ -0,0 +1,288 @@
+//! Conditions on the machine's state, such as `v3 == 0x1f && i > 0x300`, for
+//! the debugger's conditional breakpoints.
+//!
+//! A condition compares values with `==`, `!=`, `<`, `<=`, `>` and `>=`, and
+//! combines the comparisons with `&&`, `||`, `!` and parentheses. The values
+//! are the registers `v0` to `vf`, `i`, `pc`, `sp`, `dt` and `st`, and
+//! numbers, decimal or hexadecimal with `0x`. Names are not case sensitive.
+//! A value on its own holds when it is not zero.
+
+use chippers_core::cpu::Cpu;
+
+/// A parsed condition, shown as it was written.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Condition {
+    source: String,
+    expr: Expr,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+enum Expr {
+    Value(Value),
+    Compare(Box<Expr>, Comparison, Box<Expr>),
+    And(Box<Expr>, Box<Expr>),
+    Or(Box<Expr>, Box<Expr>),
+    Not(Box<Expr>),
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Value {
+    Register(u8),
+    Index,
+    Pc,
+    Sp,
+    Dt,
+    St,
+    Number(u16),
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Comparison {
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+}
+
+impl Condition {
+    /// Whether the condition holds for the machine as `cpu` leaves it.
+    pub fn holds(&self, cpu: &Cpu) -> bool {
+        self.expr.eval(cpu) != 0
+    }
+}
+
+impl std::fmt::Display for Condition {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{}", self.source)
+    }
+}
+
+impl std::str::FromStr for Condition {
+    type Err = String;
+
+    fn from_str(source: &str) -> std::result::Result<Self, Self::Err> {
+        let tokens = tokenize(source)?;
+        let mut parser = Parser { tokens, next: 0 };
+        let expr = parser.or()?;
+        match parser.peek() {
+            None => Ok(Condition {
+                source: source.trim().to_string(),
+                expr,
+            }),
+            Some(token) => Err(format!("unexpected {:?} in condition", token)),
+        }
+    }
+}
+
+impl Expr {
+    fn eval(&self, cpu: &Cpu) -> u16 {
+        match self {
+            Expr::Value(value) => value.eval(cpu),
+            Expr::Compare(a, comparison, b) => {
+                let (a, b) = (a.eval(cpu), b.eval(cpu));
+                u16::from(match comparison {
+                    Comparison::Eq => a == b,
+                    Comparison::Ne => a != b,
+                    Comparison::Lt => a < b,
+                    Comparison::Le => a <= b,
+                    Comparison::Gt => a > b,
+                    Comparison::Ge => a >= b,
+                })
+            }
+            Expr::And(a, b) => u16::from(a.eval(cpu) != 0 && b.eval(cpu) != 0),
+            Expr::Or(a, b) => u16::from(a.eval(cpu) != 0 || b.eval(cpu) != 0),
+            Expr::Not(a) => u16::from(a.eval(cpu) == 0),
+        }
+    }
+}
+
+impl Value {
+    fn eval(self, cpu: &Cpu) -> u16 {
+        match self {
+            Value::Register(x) => u16::from(cpu.registers()[usize::from(x)]),
+            Value::Index => cpu.index(),
+            Value::Pc => cpu.pc(),
+            Value::Sp => cpu.stack().len() as u16,
+            Value::Dt => u16::from(cpu.dt),
+            Value::St => u16::from(cpu.st),
+            Value::Number(n) => n,
+        }
+    }
+
+    fn parse(word: &str) -> std::result::Result<Self, String> {
+        let lower = word.to_ascii_lowercase();
+        let value = match lower.as_str() {
+            "i" => Value::Index,
+            "pc" => Value::Pc,
+            "sp" => Value::Sp,
+            "dt" => Value::Dt,
+            "st" => Value::St,
+            _ => match (lower.strip_prefix('v'), lower.strip_prefix("0x")) {
+                (Some(x), _) if x.len() == 1 => Value::Register(
+                    u8::from_str_radix(x, 16).map_err(|_| format!("{} is not a register", word))?,
+                ),
+                (_, Some(digits)) => Value::Number(
+                    u16::from_str_radix(digits, 16)
+                        .map_err(|_| format!("{} is not a number", word))?,
+                ),
+                _ => Value::Number(
+                    lower
+                        .parse()
+                        .map_err(|_| format!("{} is not a value", word))?,
+                ),
+            },
+        };
+        Ok(value)
+    }
+}
+
+/// Splits a condition into words and operators.
+fn tokenize(source: &str) -> std::result::Result<Vec<String>, String> {
+    const OPERATORS: [&str; 12] = [
+        "==", "!=", "<=", ">=", "&&", "||", "<", ">", "!", "(", ")", "=",
+    ];
+    let mut tokens = Vec::new();
+    let mut rest = source.trim_start();
+    while let Some(c) = rest.chars().next() {
+        if c.is_ascii_alphanumeric() {
+            let end = rest
+                .find(|c: char| !c.is_ascii_alphanumeric())
+                .unwrap_or(rest.len());
+            tokens.push(rest[..end].to_string());
+            rest = &rest[end..];
+        } else {
+            let op = OPERATORS
+                .iter()
+                .find(|op| rest.starts_with(**op))
+                .ok_or_else(|| format!("unexpected {:?} in condition", c))?;
+            if *op == "=" {
+                return Err(String::from("use == to compare"));
+            }
+            tokens.push(op.to_string());
+            rest = &rest[op.len()..];
+        }
+        rest = rest.trim_start();
+    }
+    Ok(tokens)
+}
+
+/// A recursive descent parser, loosest binding first: `||`, `&&`, the
+/// comparisons, then `!`, parentheses and values.
+struct Parser {
+    tokens: Vec<String>,
+    next: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&str> {
+        self.tokens.get(self.next).map(String::as_str)
+    }
+
+    fn take(&mut self) -> std::result::Result<&str, String> {
+        let token = self
+            .tokens
+            .get(self.next)
+            .ok_or("condition ends too soon")?;
+        self.next += 1;
+        Ok(token)
+    }
+
+    fn or(&mut self) -> std::result::Result<Expr, String> {
+        let mut expr = self.and()?;
+        while self.peek() == Some("||") {
+            self.next += 1;
+            expr = Expr::Or(Box::new(expr), Box::new(self.and()?));
+        }
+        Ok(expr)
+    }
+
+    fn and(&mut self) -> std::result::Result<Expr, String> {
+        let mut expr = self.compare()?;
+        while self.peek() == Some("&&") {
+            self.next += 1;
+            expr = Expr::And(Box::new(expr), Box::new(self.compare()?));
+        }
+        Ok(expr)
+    }
+
+    fn compare(&mut self) -> std::result::Result<Expr, String> {
+        let expr = self.unary()?;
+        let comparison = match self.peek() {
+            Some("==") => Comparison::Eq,
+            Some("!=") => Comparison::Ne,
+            Some("<") => Comparison::Lt,
+            Some("<=") => Comparison::Le,
+            Some(">") => Comparison::Gt,
+            Some(">=") => Comparison::Ge,
+            _ => return Ok(expr),
+        };
+        self.next += 1;
+        let other = self.unary()?;
+        Ok(Expr::Compare(Box::new(expr), comparison, Box::new(other)))
+    }
+
+    fn unary(&mut self) -> std::result::Result<Expr, String> {
+        match self.take()? {
+            "!" => Ok(Expr::Not(Box::new(self.unary()?))),
+            "(" => {
+                let expr = self.or()?;
+                match self.take()? {
+                    ")" => Ok(expr),
+                    token => Err(format!("expected ) but found {:?}", token)),
+                }
+            }
+            token => Value::parse(token).map(Expr::Value),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn holds(condition: &str, cpu: &Cpu) -> bool {
+        condition.parse::<Condition>().unwrap().holds(cpu)
+    }
+
+    #[test]
+    fn test_conditions() {
+        let mut cpu = Cpu::new();
+        cpu.registers_mut()[3] = 0x1F;
+        cpu.set_index(0x301);
+        assert!(holds("V3 == 0x1F && I > 0x300", &cpu));
+        assert!(holds("v3 == 31", &cpu));
+        assert!(!holds("v3 != 31 || i <= 0x300", &cpu));
+        assert!(holds("!(v3 < 31) && v3 >= 31", &cpu));
+        assert!(holds("pc == 0x200 && sp == 0 && !dt && !st", &cpu));
+        assert!(holds("v3", &cpu) && !holds("v4", &cpu));
+        // && binds tighter than ||
+        assert!(holds("1 || 0 && 0", &cpu));
+        assert!(!holds("(1 || 0) && 0", &cpu));
+        assert_eq!(
+            "v3  ==0x1f".parse::<Condition>().unwrap().to_string(),
+            "v3  ==0x1f"
+        );
+    }
+
+    #[test]
+    fn test_bad_conditions() {
+        for condition in [
+            "",
+            "v3 ==",
+            "vg == 1",
+            "v3 = 1",
+            "(v3",
+            "v3 == 1 v4",
+            "x > 1",
+            "v3 + 1",
+        ] {
+            assert!(
+                condition.parse::<Condition>().is_err(),
+                "{:?} parsed",
+                condition
+            );
+        }
+    }
+}
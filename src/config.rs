@@ -0,0 +1,211 @@
+//! Settings kept between runs, read from a TOML file.
+//!
+//! The `chippers` binary reads `$XDG_CONFIG_HOME/chippers/config.toml`, or
+//! `~/.config/chippers/config.toml`, or the file named with `--config`. Every
+//! setting is optional, and a flag given on the command line wins over it.
+//!
+//! ```toml
+//! backend = "terminal"
+//! speed = 700
+//! quirks = "cosmac"
+//! theme = "amber"
+//! fg = "#ffb000"
+//! render = "halfblock"
+//! layout = "azerty"
+//!
+//! [quirk]
+//! logic-resets-vf = false
+//!
+//! # keyboard character = keypad key
+//! [keys]
+//! f = 0xE
+//!
+//! # settings for one ROM, by its SHA-1 as `--romdir` shows it
+//! [rom.0123456789abcdef0123456789abcdef01234567]
+//! persist = ["3F0-3FF"]
+//! ```
+
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+/// The settings in a config file, `None` or empty where it says nothing.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    /// Where to show the display and read keys, as `--backend`.
+    pub backend: Option<String>,
+    /// How to execute instructions, as `--engine`.
+    pub engine: Option<String>,
+    /// Instructions a second, as `--speed`.
+    pub speed: Option<u32>,
+    /// How long instructions take, as `--timing`.
+    pub timing: Option<String>,
+    /// Quirks preset, as `--quirks`.
+    pub quirks: Option<String>,
+    /// Quirks turned on or off over the preset, as `--quirk`.
+    pub quirk: BTreeMap<String, bool>,
+    /// Color theme, as `--theme`.
+    pub theme: Option<String>,
+    /// Color of lit pixels, as `--fg`.
+    pub fg: Option<String>,
+    /// Color of unlit pixels, as `--bg`.
+    pub bg: Option<String>,
+    /// How the terminal draws pixels, as `--render`.
+    pub render: Option<String>,
+    /// Terminal cells per pixel, as `--scale`.
+    pub scale: Option<String>,
+    /// Keyboard layout to lay the keypad over, as `--keys`.
+    pub layout: Option<String>,
+    /// Keypad keys pressed by keyboard characters, over the layout, as `--key`.
+    pub keys: BTreeMap<char, u8>,
+    /// Settings for single ROMs, by the SHA-1 of the ROM in lowercase hex.
+    pub rom: BTreeMap<String, RomConfig>,
+}
+
+/// The settings for one ROM.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct RomConfig {
+    /// Memory ranges to keep between runs, as `--persist`.
+    pub persist: Vec<String>,
+}
+
+impl Config {
+    /// Where the config file lives when `--config` does not say.
+    pub fn default_path() -> Option<PathBuf> {
+        let dir = match std::env::var_os("XDG_CONFIG_HOME").filter(|dir| !dir.is_empty()) {
+            Some(dir) => PathBuf::from(dir),
+            None => PathBuf::from(std::env::var_os("HOME")?).join(".config"),
+        };
+        Some(dir.join("chippers").join("config.toml"))
+    }
+
+    /// Reads the config file at `path`.
+    pub fn load(path: &Path) -> std::result::Result<Self, ConfigError> {
+        let text = std::fs::read_to_string(path).map_err(|source| ConfigError::Io {
+            path: path.to_owned(),
+            source,
+        })?;
+        Self::parse(&text).map_err(|e| e.at(path))
+    }
+
+    /// Reads the config file at [`default_path`](Config::default_path), with
+    /// nothing set if there is none.
+    pub fn load_default() -> std::result::Result<Self, ConfigError> {
+        match Self::default_path() {
+            Some(path) if path.exists() => Self::load(&path),
+            _ => Ok(Self::default()),
+        }
+    }
+
+    /// Reads settings from the text of a config file.
+    pub fn parse(text: &str) -> std::result::Result<Self, ConfigError> {
+        let config: Config = toml::from_str(text).map_err(|e| ConfigError::Invalid {
+            path: None,
+            message: e.message().to_string(),
+        })?;
+        if let Some((c, key)) = config.keys.iter().find(|(_, key)| **key > 0xF) {
+            return Err(ConfigError::Invalid {
+                path: None,
+                message: format!("key {:?} is bound to {}, past the last key 0xF", c, key),
+            });
+        }
+        Ok(config)
+    }
+}
+
+/// Why a config file could not be read.
+#[derive(Debug)]
+pub enum ConfigError {
+    /// The file could not be read.
+    Io {
+        path: PathBuf,
+        source: std::io::Error,
+    },
+    /// The file is not TOML or has a setting of the wrong kind.
+    Invalid {
+        path: Option<PathBuf>,
+        message: String,
+    },
+}
+
+impl ConfigError {
+    fn at(self, path: &Path) -> Self {
+        match self {
+            ConfigError::Invalid { message, .. } => ConfigError::Invalid {
+                path: Some(path.to_owned()),
+                message,
+            },
+            e => e,
+        }
+    }
+}
+
+impl std::fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            ConfigError::Io { path, source } => {
+                writeln!(f, "error: cannot read {}: {}", path.display(), source)
+            }
+            ConfigError::Invalid {
+                path: Some(path),
+                message,
+            } => writeln!(f, "error: in {}: {}", path.display(), message),
+            ConfigError::Invalid {
+                path: None,
+                message,
+            } => writeln!(f, "error: in config: {}", message),
+        }
+    }
+}
+
+impl std::error::Error for ConfigError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            ConfigError::Io { source, .. } => Some(source),
+            ConfigError::Invalid { .. } => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_parse() {
+        let config = Config::parse(
+            "speed = 700\nquirks = \"cosmac\"\n\n[quirk]\nlogic-resets-vf = false\n\n[keys]\nf = 0xE\n",
+        )
+        .unwrap();
+        assert_eq!(config.speed, Some(700));
+        assert_eq!(config.quirks.as_deref(), Some("cosmac"));
+        assert_eq!(config.quirk.get("logic-resets-vf"), Some(&false));
+        assert_eq!(config.keys.get(&'f'), Some(&0xE));
+        assert_eq!(config.theme, None);
+        assert_eq!(Config::parse("").unwrap(), Config::default());
+        let config = Config::parse("[rom.abc]\npersist = [\"300-30F\"]\n").unwrap();
+        assert_eq!(config.rom["abc"].persist, ["300-30F"]);
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        let error = Config::parse("speed = \"fast\"").unwrap_err();
+        assert!(
+            error.to_string().starts_with("error: in config: "),
+            "{}",
+            error
+        );
+        assert!(Config::parse("colour = \"red\"").is_err());
+        let error = Config::parse("[keys]\nf = 16").unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "error: in config: key 'f' is bound to 16, past the last key 0xF\n"
+        );
+        let error = Config::parse("speed = 1.5")
+            .unwrap_err()
+            .at(Path::new("chippers.toml"));
+        assert!(error.to_string().starts_with("error: in chippers.toml: "));
+    }
+}
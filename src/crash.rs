@@ -0,0 +1,145 @@
+//! Crash dumps, for attaching to a bug report when a program faults.
+//!
+//! A [`CrashDump`] hooks into a machine and keeps the last [`HISTORY`]
+//! instructions it ran. If the program faults, it writes the fault, the
+//! registers, the stack, those instructions, all of memory and the screen
+//! to a file named for the time, `chippers-crash-YYYYMMDD-HHMMSS.txt`.
+
+use crate::debugger::hex_dump;
+use chippers_core::chip::Chip8;
+use chippers_core::cpu::{Cpu, Fault};
+use chippers_core::golden::text_art;
+use chippers_core::image::timestamp;
+use chippers_core::opcode::Disassembly;
+use std::collections::VecDeque;
+use std::io::Write;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex, MutexGuard};
+
+/// How many of the instructions before a fault a dump shows.
+pub const HISTORY: usize = 64;
+
+/// Writes a dump into a directory if the machine it is attached to faults.
+///
+/// Hooks cannot return errors, so whether the dump was written is reported
+/// by [`take`](CrashDump::take).
+#[derive(Clone, Debug)]
+pub struct CrashDump(Arc<Mutex<Recorder>>);
+
+#[derive(Debug)]
+struct Recorder {
+    dir: PathBuf,
+    // addresses and words of the instructions run, oldest first
+    history: VecDeque<(u16, u16)>,
+    written: Option<std::io::Result<PathBuf>>,
+}
+
+impl CrashDump {
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        CrashDump(Arc::new(Mutex::new(Recorder {
+            dir: dir.into(),
+            history: VecDeque::with_capacity(HISTORY),
+            written: None,
+        })))
+    }
+
+    /// Watches `chip8` from the instruction it runs next.
+    pub fn attach(&self, chip8: &mut Chip8) {
+        let recorder = self.0.clone();
+        chip8.hooks.on_instruction(move |addr, raw, _| {
+            let mut recorder = recorder.lock().unwrap_or_else(|e| e.into_inner());
+            if recorder.history.len() == HISTORY {
+                recorder.history.pop_front();
+            }
+            recorder.history.push_back((addr, raw));
+        });
+        let recorder = self.0.clone();
+        chip8.hooks.on_fault(move |fault, cpu| {
+            let mut recorder = recorder.lock().unwrap_or_else(|e| e.into_inner());
+            let name = format!(
+                "chippers-crash-{}.txt",
+                timestamp(std::time::SystemTime::now())
+            );
+            let path = recorder.dir.join(name);
+            let history: Vec<_> = recorder.history.iter().copied().collect();
+            let written = std::fs::File::create(&path)
+                .and_then(|mut file| write_dump(&mut file, fault, cpu, &history));
+            recorder.written = Some(written.map(|()| path));
+        });
+    }
+
+    /// Where the dump went if the program faulted, or why it could not be
+    /// written.
+    pub fn take(&self) -> Option<std::io::Result<PathBuf>> {
+        self.lock().written.take()
+    }
+
+    fn lock(&self) -> MutexGuard<'_, Recorder> {
+        self.0.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+/// Writes the dump of `cpu` as `fault` left it, after running the
+/// instructions in `history`, addresses and words, oldest first and ending
+/// with the one that faulted.
+pub fn write_dump(
+    out: &mut impl Write,
+    fault: Fault,
+    cpu: &Cpu,
+    history: &[(u16, u16)],
+) -> std::io::Result<()> {
+    write!(out, "{}", fault)?;
+    writeln!(out, "\nregisters")?;
+    write!(out, "{}", cpu.dump())?;
+    writeln!(out, "\nstack, innermost last")?;
+    for addr in cpu.stack() {
+        writeln!(out, "{:03x}", addr)?;
+    }
+    writeln!(out, "\nlast {} instructions", history.len())?;
+    for (addr, raw) in history {
+        writeln!(out, "{:03x}  {:04x}  {}", addr, raw, Disassembly(*raw))?;
+    }
+    writeln!(out, "\nmemory")?;
+    hex_dump(out, &cpu.mem, 0..cpu.mem.len(), None)?;
+    writeln!(out, "\nscreen")?;
+    write!(out, "{}", text_art(&cpu.disp.pack()))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_dump_on_fault() {
+        let dir = std::env::temp_dir().join(format!("chippers-crash-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let crash = CrashDump::new(&dir);
+        let mut chip8 = Chip8::new();
+        // 6A05: VA = 5, 2206: call 206, 00E0: never reached, E1A2: unknown
+        chip8
+            .load_rom(&[0x6A, 0x05, 0x22, 0x06, 0x00, 0xE0, 0xE1, 0xA2])
+            .unwrap();
+        crash.attach(&mut chip8);
+        for _ in 0..3 {
+            chip8.step();
+        }
+        let path = crash.take().unwrap().unwrap();
+        assert!(crash.take().is_none());
+        let dump = std::fs::read_to_string(&path).unwrap();
+        std::fs::remove_dir_all(&dir).unwrap();
+        assert!(
+            dump.starts_with("error: unknown opcode e1a2 at 0x206\n"),
+            "{}",
+            dump
+        );
+        assert!(dump.contains("\nstack, innermost last\n204\n"), "{}", dump);
+        assert!(
+            dump.contains("\nlast 3 instructions\n200  6a05  LD VA, #05\n202  2206  CALL #206\n206  e1a2  DW #E1A2\n"),
+            "{}",
+            dump
+        );
+        assert!(dump.contains("\n200: 6a 05 22 06 00 e0 e1 a2"), "{}", dump);
+        assert!(dump.contains("\nff0: "), "{}", dump);
+        assert!(dump.ends_with(&format!("{}\n", ".".repeat(64))), "{}", dump);
+    }
+}
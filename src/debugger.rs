@@ -0,0 +1,827 @@
+//! A line-oriented debugger: breakpoints, single-stepping and state dumps.
+//!
+//! The machine runs headless under the debugger, its timers ticking once every
+//! `instructions_per_frame` instructions, and only between commands.
+//!
+//! | Command              | Effect                                            |
+//! |----------------------|---------------------------------------------------|
+//! | `step [n]`, `s`      | runs one instruction, or `n`                      |
+//! | `continue`, `c`      | runs until a breakpoint, watchpoint or fault      |
+//! | `break <addr>`, `b`  | stops before the instruction at `<addr>` runs     |
+//! | `b <addr> if <c>`    | the same, when the [`Condition`] `<c>` holds      |
+//! | `delete <addr>`, `d` | removes the breakpoint at `<addr>`                |
+//! | `watch <vx\|i>`, `w` | stops after an instruction changes VX or I        |
+//! | `watch <addr> [n]`   | stops after an instruction writes those `n` bytes |
+//! | `rwatch <addr> [n]`  | the same, for reads as well as writes             |
+//! | `unwatch <target>`   | removes the watchpoints on a register or address  |
+//! | `regs`, `r`          | V0 to VF, I, PC, timers and the stack             |
+//! | `backtrace`, `bt`    | the next instruction, then each call still active |
+//! | `mem <addr> [n]`     | `n` bytes of memory from `<addr>`, 16 by default  |
+//! | `view <addr> [n]`    | the same after every stop, until a bare `view`    |
+//! | `sprite [addr] [n]`  | `n` rows from `<addr>`, or I, drawn as a sprite   |
+//! | `screen`             | the display as text art                           |
+//! | `key <hex>`          | presses keypad key `<hex>` (`0` to `f`)           |
+//! | `profile`, `p`       | starts profiling, then reports what has run since |
+//! | `quit`, `q`          | ends the session                                  |
+//!
+//! Addresses and keys are hexadecimal, with or without `0x`; counts are
+//! decimal, and a memory watchpoint covers one byte by default. An empty line
+//! repeats the last command.
+//!
+//! Memory is shown as a [`hex_dump`], with the bytes the last `step` or
+//! `continue` wrote marked underneath.
+
+use crate::condition::Condition;
+use crate::profile::Profiler;
+use chippers_core::chip::{Chip8, Chip8Message};
+use chippers_core::cpu::Access;
+use chippers_core::golden::text_art;
+use chippers_core::opcode::Disassembly;
+use std::collections::{BTreeMap, BTreeSet};
+use std::io::{BufRead, Write};
+use std::ops::Range;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Command {
+    Step(u32),
+    Continue,
+    Break(u16, Option<Condition>),
+    Delete(u16),
+    Watch(Watch),
+    Unwatch(Watch),
+    Registers,
+    Backtrace,
+    Memory {
+        addr: u16,
+        len: u16,
+    },
+    /// Shows `len` bytes from `addr` after every stop, or nothing with `None`.
+    View(Option<(u16, u16)>),
+    /// Draws `rows` bytes as a sprite from `addr`, or from I with `None`.
+    Sprite {
+        addr: Option<u16>,
+        rows: u16,
+    },
+    Screen,
+    Key(u8),
+    Profile,
+    Quit,
+}
+
+impl std::str::FromStr for Command {
+    type Err = String;
+
+    fn from_str(line: &str) -> std::result::Result<Self, Self::Err> {
+        let mut words = line.split_whitespace();
+        let name = words.next().unwrap_or_default();
+        let arg = words.next();
+        let command = match name {
+            "step" | "s" => Command::Step(arg.map_or(Ok(1), count)?),
+            "continue" | "c" => Command::Continue,
+            "break" | "b" => {
+                let addr = hex(arg)?;
+                match words.next() {
+                    None => Command::Break(addr, None),
+                    Some("if") => {
+                        let condition = words.collect::<Vec<_>>().join(" ");
+                        Command::Break(addr, Some(condition.parse()?))
+                    }
+                    Some(word) => return Err(format!("expected if but found {:?}", word)),
+                }
+            }
+            "delete" | "d" => Command::Delete(hex(arg)?),
+            "watch" | "w" => Command::Watch(watch(arg, words.next(), false)?),
+            "rwatch" => match watch(arg, words.next(), true)? {
+                watch @ Watch::Memory { .. } => Command::Watch(watch),
+                _ => return Err(String::from("only memory can be watched for reads")),
+            },
+            "unwatch" => Command::Unwatch(watch(arg, None, false)?),
+            "regs" | "r" => Command::Registers,
+            "backtrace" | "bt" => Command::Backtrace,
+            "mem" | "m" => Command::Memory {
+                addr: hex(arg)?,
+                len: words.next().map_or(Ok(16), count)? as u16,
+            },
+            "view" | "v" => match arg {
+                Some(_) => Command::View(Some((
+                    hex(arg)?,
+                    words.next().map_or(Ok(16), count)? as u16,
+                ))),
+                None => Command::View(None),
+            },
+            "sprite" | "sp" => Command::Sprite {
+                addr: match arg {
+                    None | Some("i" | "I") => None,
+                    Some(_) => Some(hex(arg)?),
+                },
+                rows: words.next().map_or(Ok(15), count)? as u16,
+            },
+            "screen" => Command::Screen,
+            "key" | "k" => Command::Key(hex(arg)?.min(0xF) as u8),
+            "profile" | "p" => Command::Profile,
+            "quit" | "q" => Command::Quit,
+            _ => return Err(format!("unknown command {:?}", name)),
+        };
+        Ok(command)
+    }
+}
+
+fn hex(word: Option<&str>) -> std::result::Result<u16, String> {
+    let word = word.ok_or("missing address")?;
+    let digits = word.trim_start_matches("0x");
+    u16::from_str_radix(digits, 16)
+        .ok()
+        .filter(|addr| *addr < 0x1000)
+        .ok_or_else(|| format!("{} is not an address", word))
+}
+
+fn count(word: &str) -> std::result::Result<u32, String> {
+    word.parse().map_err(|_| format!("{} is not a count", word))
+}
+
+/// A register, `v0` to `vf` or `i`, or an address and a byte count.
+fn watch(
+    target: Option<&str>,
+    len: Option<&str>,
+    reads: bool,
+) -> std::result::Result<Watch, String> {
+    let register = target.and_then(|word| word.strip_prefix(['v', 'V']));
+    match (target, register) {
+        (Some("i" | "I"), _) => Ok(Watch::Index),
+        (Some(word), Some(x)) => u8::from_str_radix(x, 16)
+            .ok()
+            .filter(|x| *x < 16)
+            .map(Watch::Register)
+            .ok_or_else(|| format!("{} is not a register", word)),
+        _ => Ok(Watch::Memory {
+            addr: hex(target)?,
+            len: len.map_or(Ok(1), count)?.max(1) as u16,
+            reads,
+        }),
+    }
+}
+
+/// What a watchpoint stops on.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Watch {
+    /// VX changing value.
+    Register(u8),
+    /// I changing value.
+    Index,
+    /// An instruction writing any of `len` bytes from `addr`, or reading
+    /// them too if `reads` is set.
+    Memory { addr: u16, len: u16, reads: bool },
+}
+
+impl Watch {
+    /// Whether `self` and `other` watch the same register or address.
+    fn same_target(&self, other: &Watch) -> bool {
+        match (self, other) {
+            (Watch::Memory { addr: a, .. }, Watch::Memory { addr: b, .. }) => a == b,
+            _ => self == other,
+        }
+    }
+}
+
+impl std::fmt::Display for Watch {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Watch::Register(x) => write!(f, "v{:x}", x),
+            Watch::Index => write!(f, "i"),
+            Watch::Memory { addr, len: 1, .. } => write!(f, "{:#05x}", addr),
+            Watch::Memory { addr, len, .. } => {
+                write!(f, "{:#05x}-{:#05x}", addr, addr + len - 1)
+            }
+        }
+    }
+}
+
+/// Why execution stopped and handed control back to the prompt.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Pause {
+    /// The requested number of instructions ran.
+    Stepped,
+    /// The next instruction has a breakpoint.
+    Breakpoint(u16),
+    /// The instruction at `at` changed a watched register, which held `was`.
+    Changed { at: u16, watch: Watch, was: u16 },
+    /// The instruction at `at` touched watched memory.
+    Accessed { at: u16, access: Access },
+    /// The instruction at the program counter cannot run.
+    Fault,
+}
+
+/// Breakpoints and the stepped execution they control.
+#[derive(Clone, Debug)]
+pub struct Debugger {
+    // each with the condition it stops on, if it has one
+    breakpoints: BTreeMap<u16, Option<Condition>>,
+    watches: BTreeSet<Watch>,
+    instructions_per_frame: u32,
+    // instructions run since the timers last ticked
+    frame_instructions: u32,
+    profiler: Option<Profiler>,
+    // memory as it was before the last step or continue
+    before: Vec<u8>,
+    // the range `view` shows after every stop
+    view: Option<Range<usize>>,
+}
+
+impl Default for Debugger {
+    fn default() -> Self {
+        Self::new(12)
+    }
+}
+
+impl Debugger {
+    pub fn new(instructions_per_frame: u32) -> Self {
+        Debugger {
+            breakpoints: BTreeMap::new(),
+            watches: BTreeSet::new(),
+            instructions_per_frame: instructions_per_frame.max(1),
+            frame_instructions: 0,
+            profiler: None,
+            before: Vec::new(),
+            view: None,
+        }
+    }
+
+    /// Reports from `profiler`, already attached to the machine, when asked
+    /// to `profile`.
+    pub fn with_profiler(mut self, profiler: Profiler) -> Self {
+        self.profiler = Some(profiler);
+        self
+    }
+
+    pub fn breakpoints(&self) -> impl Iterator<Item = u16> + '_ {
+        self.breakpoints.keys().copied()
+    }
+
+    pub fn watches(&self) -> impl Iterator<Item = Watch> + '_ {
+        self.watches.iter().copied()
+    }
+
+    /// Runs up to `max` instructions, stopping early before one with a
+    /// breakpoint whose condition holds, or after one that sets off a watchpoint. The instruction at
+    /// the program counter always runs, so continuing from a breakpoint moves
+    /// past it.
+    pub fn run(&mut self, chip8: &mut Chip8, max: Option<u64>) -> Pause {
+        let mut executed = 0;
+        loop {
+            if max.is_some_and(|max| executed >= max) {
+                return Pause::Stepped;
+            }
+            let pc = chip8.cpu.pc();
+            let stop = self.breakpoints.get(&pc).is_some_and(|condition| {
+                condition
+                    .as_ref()
+                    .is_none_or(|condition| condition.holds(&chip8.cpu))
+            });
+            if executed > 0 && stop {
+                return Pause::Breakpoint(pc);
+            }
+            let (reg, index) = (*chip8.cpu.registers(), chip8.cpu.index());
+            if let Chip8Message::Halted(_) = chip8.step() {
+                return Pause::Fault;
+            }
+            executed += 1;
+            if let Some(pause) = self.watched(chip8, pc, reg, index) {
+                return pause;
+            }
+            self.frame_instructions += 1;
+            if self.frame_instructions == self.instructions_per_frame {
+                self.frame_instructions = 0;
+                chip8.tick_timers();
+            }
+        }
+    }
+
+    /// The first watchpoint the instruction at `at` set off, given the
+    /// registers and I as they were before it ran.
+    fn watched(&self, chip8: &Chip8, at: u16, reg: [u8; 16], index: u16) -> Option<Pause> {
+        let access = chip8.cpu.last_access();
+        self.watches.iter().find_map(|&watch| match watch {
+            Watch::Register(x) => {
+                let was = reg[usize::from(x)];
+                (chip8.cpu.registers()[usize::from(x)] != was).then_some(Pause::Changed {
+                    at,
+                    watch,
+                    was: u16::from(was),
+                })
+            }
+            Watch::Index => (chip8.cpu.index() != index).then_some(Pause::Changed {
+                at,
+                watch,
+                was: index,
+            }),
+            Watch::Memory { addr, len, reads } => access
+                .filter(|access| (access.write || reads) && access.overlaps(addr, len))
+                .map(|access| Pause::Accessed { at, access }),
+        })
+    }
+
+    /// Reads commands from `input` until `quit` or the end of input, writing
+    /// what they show to `out`.
+    pub fn session(
+        &mut self,
+        chip8: &mut Chip8,
+        input: impl BufRead,
+        mut out: impl Write,
+    ) -> std::io::Result<()> {
+        self.show_next(chip8, &mut out)?;
+        write!(out, "(chippers) ")?;
+        out.flush()?;
+        let mut last = None;
+        for line in input.lines() {
+            let line = line?;
+            let command = match line.trim() {
+                "" => last
+                    .clone()
+                    .ok_or_else(|| String::from("no command to repeat")),
+                line => line.parse(),
+            };
+            match command {
+                Ok(Command::Quit) => return Ok(()),
+                Ok(command) => {
+                    self.execute(command.clone(), chip8, &mut out)?;
+                    last = Some(command);
+                }
+                Err(e) => writeln!(out, "{}", e)?,
+            }
+            write!(out, "(chippers) ")?;
+            out.flush()?;
+        }
+        writeln!(out)
+    }
+
+    fn execute(
+        &mut self,
+        command: Command,
+        chip8: &mut Chip8,
+        out: &mut impl Write,
+    ) -> std::io::Result<()> {
+        match command {
+            Command::Step(n) => self.resume(chip8, Some(u64::from(n)), out)?,
+            Command::Continue => self.resume(chip8, None, out)?,
+            Command::Break(addr, condition) => {
+                match &condition {
+                    Some(condition) => {
+                        writeln!(out, "breakpoint at {:#05x} if {}", addr, condition)?
+                    }
+                    None => writeln!(out, "breakpoint at {:#05x}", addr)?,
+                }
+                self.breakpoints.insert(addr, condition);
+            }
+            Command::Delete(addr) => {
+                if self.breakpoints.remove(&addr).is_none() {
+                    writeln!(out, "no breakpoint at {:#05x}", addr)?;
+                }
+            }
+            Command::Watch(watch) => {
+                self.watches.retain(|w| !w.same_target(&watch));
+                self.watches.insert(watch);
+                writeln!(out, "watchpoint on {}", watch)?;
+            }
+            Command::Unwatch(watch) => {
+                let before = self.watches.len();
+                self.watches.retain(|w| !w.same_target(&watch));
+                if self.watches.len() == before {
+                    writeln!(out, "no watchpoint on {}", watch)?;
+                }
+            }
+            Command::Registers => show_registers(chip8, out)?,
+            Command::Backtrace => {
+                write!(out, "#0 ")?;
+                show_instruction(chip8, chip8.cpu.pc(), out)?;
+                // the 2NNN just before each return address made its frame
+                for (frame, ret) in chip8.cpu.stack().iter().rev().enumerate() {
+                    write!(out, "#{} ", frame + 1)?;
+                    show_instruction(chip8, ret.wrapping_sub(2), out)?;
+                }
+            }
+            Command::Memory { addr, len } => {
+                let range = usize::from(addr)..usize::from(addr) + usize::from(len);
+                hex_dump(out, &chip8.cpu.mem, range, Some(&self.before))?;
+            }
+            Command::View(range) => {
+                self.view = range
+                    .map(|(addr, len)| usize::from(addr)..usize::from(addr) + usize::from(len));
+                if let Some(range) = self.view.clone() {
+                    hex_dump(out, &chip8.cpu.mem, range, Some(&self.before))?;
+                }
+            }
+            Command::Sprite { addr, rows } => {
+                let addr = usize::from(addr.unwrap_or(chip8.cpu.index()));
+                sprite_art(out, &chip8.cpu.mem, addr..addr + usize::from(rows))?;
+            }
+            Command::Screen => write!(out, "{}", text_art(&chip8.framebuffer()))?,
+            Command::Key(key) => chip8.cpu.press_key(key),
+            Command::Profile => match &self.profiler {
+                Some(profiler) => write!(out, "{}", profiler.report())?,
+                None => {
+                    let profiler = Profiler::new();
+                    profiler.attach(chip8);
+                    self.profiler = Some(profiler);
+                    writeln!(out, "profiling from here")?;
+                }
+            },
+            Command::Quit => {}
+        }
+        Ok(())
+    }
+
+    fn resume(
+        &mut self,
+        chip8: &mut Chip8,
+        max: Option<u64>,
+        out: &mut impl Write,
+    ) -> std::io::Result<()> {
+        self.before.clear();
+        self.before.extend_from_slice(&chip8.cpu.mem);
+        match self.run(chip8, max) {
+            Pause::Stepped => {}
+            Pause::Breakpoint(addr) => writeln!(out, "breakpoint at {:#05x}", addr)?,
+            Pause::Changed { at, watch, was } => {
+                let now = match watch {
+                    Watch::Register(x) => u16::from(chip8.cpu.registers()[usize::from(x)]),
+                    _ => chip8.cpu.index(),
+                };
+                writeln!(
+                    out,
+                    "{} changed by {:#05x}: {:x} -> {:x}",
+                    watch, at, was, now
+                )?;
+            }
+            Pause::Accessed { at, access } => {
+                let (addr, len) = (usize::from(access.addr), usize::from(access.len));
+                let verb = if access.write { "written" } else { "read" };
+                writeln!(out, "{} by {:#05x}:", verb, at)?;
+                hex_dump(out, &chip8.cpu.mem, addr..addr + len, Some(&self.before))?;
+            }
+            Pause::Fault => {
+                // run it again to see what went wrong; it leaves the machine as it was
+                if let Chip8Message::Halted(fault) = chip8.step() {
+                    write!(out, "{}", fault)?;
+                }
+            }
+        }
+        self.show_next(chip8, out)?;
+        match self.view.clone() {
+            Some(range) => hex_dump(out, &chip8.cpu.mem, range, Some(&self.before)),
+            None => Ok(()),
+        }
+    }
+
+    fn show_next(&self, chip8: &Chip8, out: &mut impl Write) -> std::io::Result<()> {
+        show_instruction(chip8, chip8.cpu.pc(), out)
+    }
+}
+
+/// Writes the address, opcode and mnemonic of the instruction at `addr`.
+fn show_instruction(chip8: &Chip8, addr: u16, out: &mut impl Write) -> std::io::Result<()> {
+    let addr = usize::from(addr);
+    let Some(bytes) = chip8.cpu.mem.get(addr..addr + 2) else {
+        return writeln!(out, "{:03x}: out of memory", addr);
+    };
+    let raw = u16::from_be_bytes([bytes[0], bytes[1]]);
+    writeln!(out, "{:03x}: {:04x} {}", addr, raw, Disassembly(raw))
+}
+
+/// Writes the bytes of `mem` in `range`, as far as memory goes, 16 to a line
+/// with the address of the first, in hex and then as ASCII, `.` for bytes
+/// that are not printable. Bytes that differ from those at the same address
+/// in `before` get a `^^` on the line below.
+pub fn hex_dump(
+    out: &mut impl Write,
+    mem: &[u8],
+    range: Range<usize>,
+    before: Option<&[u8]>,
+) -> std::io::Result<()> {
+    let start = range.start.min(mem.len());
+    let range = start..range.end.clamp(start, mem.len());
+    for (row, bytes) in mem[range].chunks(16).enumerate() {
+        let addr = start + row * 16;
+        write!(out, "{:03x}:", addr)?;
+        for byte in bytes {
+            write!(out, " {:02x}", byte)?;
+        }
+        let ascii: String = bytes
+            .iter()
+            .map(|&b| match b {
+                0x20..=0x7E => char::from(b),
+                _ => '.',
+            })
+            .collect();
+        writeln!(out, "{:pad$}  {}", "", ascii, pad = (16 - bytes.len()) * 3)?;
+        let changed = |i: usize| {
+            before
+                .and_then(|before| before.get(addr + i))
+                .is_some_and(|old| *old != bytes[i])
+        };
+        if (0..bytes.len()).any(changed) {
+            let marks: String = (0..bytes.len())
+                .map(|i| if changed(i) { " ^^" } else { "   " })
+                .collect();
+            writeln!(out, "    {}", marks.trim_end())?;
+        }
+    }
+    Ok(())
+}
+
+/// Writes the bytes of `mem` in `range`, as far as memory goes, as the rows
+/// of a sprite: each with its address and value, then its eight pixels as
+/// `#` for lit and `.` for unlit, as DXYN would draw them.
+pub fn sprite_art(out: &mut impl Write, mem: &[u8], range: Range<usize>) -> std::io::Result<()> {
+    let start = range.start.min(mem.len());
+    let range = start..range.end.clamp(start, mem.len());
+    for (addr, byte) in range.clone().zip(&mem[range]) {
+        let pixels: String = (0..8)
+            .map(|bit| if byte << bit & 0x80 != 0 { '#' } else { '.' })
+            .collect();
+        writeln!(out, "{:03x}: {:02x} {}", addr, byte, pixels)?;
+    }
+    Ok(())
+}
+
+fn show_registers(chip8: &Chip8, out: &mut impl Write) -> std::io::Result<()> {
+    let cpu = &chip8.cpu;
+    for (i, v) in cpu.registers().iter().enumerate() {
+        let end = if i % 8 == 7 { "\n" } else { " " };
+        write!(out, "v{:x}={:02x}{}", i, v, end)?;
+    }
+    writeln!(
+        out,
+        "i={:03x} pc={:03x} dt={:02x} st={:02x}",
+        cpu.index(),
+        cpu.pc(),
+        cpu.dt,
+        cpu.st
+    )?;
+    write!(out, "stack:")?;
+    for addr in cpu.stack() {
+        write!(out, " {:03x}", addr)?;
+    }
+    writeln!(out)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn machine() -> Chip8 {
+        let mut chip8 = Chip8::new();
+        // 6001: V0 = 1, 7001: V0 += 1, 2208: call 0x208, 1206: loop, 00EE: return
+        chip8
+            .load_rom(&[0x60, 0x01, 0x70, 0x01, 0x22, 0x08, 0x12, 0x06, 0x00, 0xEE])
+            .unwrap();
+        chip8
+    }
+
+    #[test]
+    fn test_parse_commands() {
+        assert_eq!("s".parse(), Ok(Command::Step(1)));
+        assert_eq!("step 10".parse(), Ok(Command::Step(10)));
+        assert_eq!("b 0x2a0".parse(), Ok(Command::Break(0x2A0, None)));
+        assert_eq!(
+            "b 2a0 if v0 == 3".parse(),
+            Ok(Command::Break(0x2A0, Some("v0 == 3".parse().unwrap())))
+        );
+        assert!("b 2a0 when v0 == 3".parse::<Command>().is_err());
+        assert!("b 2a0 if v0 =".parse::<Command>().is_err());
+        assert_eq!(
+            "mem 200 4".parse(),
+            Ok(Command::Memory {
+                addr: 0x200,
+                len: 4
+            })
+        );
+        assert!("b 1000".parse::<Command>().is_err());
+        assert_eq!("p".parse(), Ok(Command::Profile));
+        assert!("jump".parse::<Command>().is_err());
+    }
+
+    #[test]
+    fn test_breakpoints_and_stepping() {
+        let mut chip8 = machine();
+        let mut debugger = Debugger::default();
+        debugger.breakpoints.insert(0x208, None);
+        assert_eq!(debugger.run(&mut chip8, Some(2)), Pause::Stepped);
+        assert_eq!(chip8.cpu.registers()[0], 2);
+        assert_eq!(debugger.run(&mut chip8, None), Pause::Breakpoint(0x208));
+        assert_eq!(chip8.cpu.stack(), &[0x206]);
+        assert_eq!(debugger.run(&mut chip8, Some(1)), Pause::Stepped);
+        assert_eq!(chip8.cpu.pc(), 0x206);
+    }
+
+    #[test]
+    fn test_conditional_breakpoint() {
+        let mut chip8 = Chip8::new();
+        // 6000: V0 = 0, 7001: V0 += 1, 1202: loop
+        chip8
+            .load_rom(&[0x60, 0x00, 0x70, 0x01, 0x12, 0x02])
+            .unwrap();
+        let script = "b 202 if v0 == 10 && pc == 0x202\nc\n";
+        let mut out = Vec::new();
+        Debugger::default()
+            .session(&mut chip8, script.as_bytes(), &mut out)
+            .unwrap();
+        let out = String::from_utf8(out).unwrap();
+        assert!(out.contains("breakpoint at 0x202 if v0 == 10 && pc == 0x202\n"));
+        assert!(
+            out.contains("breakpoint at 0x202\n202: 7001 ADD V0, #01\n"),
+            "{}",
+            out
+        );
+        assert_eq!(chip8.cpu.registers()[0], 10);
+    }
+
+    #[test]
+    fn test_watchpoints() {
+        assert_eq!("w vA".parse(), Ok(Command::Watch(Watch::Register(0xA))));
+        assert_eq!(
+            "rwatch 300 3".parse(),
+            Ok(Command::Watch(Watch::Memory {
+                addr: 0x300,
+                len: 3,
+                reads: true
+            }))
+        );
+        assert!("rwatch i".parse::<Command>().is_err());
+        assert!("w vg".parse::<Command>().is_err());
+
+        let mut chip8 = Chip8::new();
+        // A300: I = 0x300, 6105: V1 = 5, F133: BCD of V1, F065: V0 = [I],
+        // 7101: V1 += 1, 1208: loop
+        chip8
+            .load_rom(&[
+                0xA3, 0x00, 0x61, 0x05, 0xF1, 0x33, 0xF0, 0x65, 0x71, 0x01, 0x12, 0x08,
+            ])
+            .unwrap();
+        let mut debugger = Debugger::default();
+        debugger.watches.insert(Watch::Memory {
+            addr: 0x302,
+            len: 1,
+            reads: false,
+        });
+        let access = Access {
+            addr: 0x300,
+            len: 3,
+            write: true,
+        };
+        assert_eq!(
+            debugger.run(&mut chip8, None),
+            Pause::Accessed { at: 0x204, access }
+        );
+        // F065 only reads, so V1 changing is the next stop
+        debugger.watches.insert(Watch::Register(1));
+        assert_eq!(
+            debugger.run(&mut chip8, None),
+            Pause::Changed {
+                at: 0x208,
+                watch: Watch::Register(1),
+                was: 5
+            }
+        );
+
+        let script = "unwatch 302\nrwatch 300\nw i\nc\nunwatch i\nc\nc\nunwatch 301\n";
+        let mut out = Vec::new();
+        let mut chip8 = Chip8::new();
+        chip8
+            .load_rom(&[0xA3, 0x00, 0x61, 0x05, 0xF1, 0x33, 0xF0, 0x65])
+            .unwrap();
+        Debugger::default()
+            .session(&mut chip8, script.as_bytes(), &mut out)
+            .unwrap();
+        let out = String::from_utf8(out).unwrap();
+        assert!(out.contains("no watchpoint on 0x302\n"), "{}", out);
+        assert!(out.contains("watchpoint on 0x300\n"), "{}", out);
+        assert!(out.contains("i changed by 0x200: 0 -> 300\n"), "{}", out);
+        assert!(
+            out.contains("written by 0x204:\n300: 00 00 05  "),
+            "{}",
+            out
+        );
+        assert!(out.contains("read by 0x206:\n300: 00  "), "{}", out);
+        assert!(out.contains("no watchpoint on 0x301\n"), "{}", out);
+    }
+
+    #[test]
+    fn test_session() {
+        let mut chip8 = machine();
+        let script = "b 208\nc\nr\n\nmem 208 2\nstep 2\nq\nstep\n";
+        let mut out = Vec::new();
+        Debugger::default()
+            .session(&mut chip8, script.as_bytes(), &mut out)
+            .unwrap();
+        let out = String::from_utf8(out).unwrap();
+        assert!(out.starts_with("200: 6001 LD V0, #01\n"), "{}", out);
+        assert!(out.contains("breakpoint at 0x208\n208: 00ee RET\n"));
+        assert_eq!(out.matches("v0=02 ").count(), 2);
+        assert!(out.contains("vf=00\n"));
+        assert!(out.contains("stack: 206\n"));
+        assert!(out.contains("208: 00 ee                                            ..\n"));
+        assert!(out.ends_with("206: 1206 JP #206\n(chippers) "));
+        assert_eq!(chip8.instructions(), 5);
+    }
+
+    #[test]
+    fn test_backtrace() {
+        let mut chip8 = Chip8::new();
+        // 2206: call 0x206, 00E0: clear, 1204: loop, 220C: call 0x20c,
+        // 00EE: return, 00EE: return
+        chip8
+            .load_rom(&[
+                0x22, 0x06, 0x00, 0xE0, 0x12, 0x04, 0x22, 0x0C, 0x00, 0xEE, 0x00, 0x00, 0x00, 0xEE,
+            ])
+            .unwrap();
+        assert_eq!("bt".parse(), Ok(Command::Backtrace));
+        let mut out = Vec::new();
+        Debugger::default()
+            .session(&mut chip8, "s 2\nbacktrace\n".as_bytes(), &mut out)
+            .unwrap();
+        let out = String::from_utf8(out).unwrap();
+        let backtrace = "#0 20c: 00ee RET\n#1 206: 220c CALL #20C\n#2 200: 2206 CALL #206\n";
+        assert!(out.contains(backtrace), "{}", out);
+    }
+
+    #[test]
+    fn test_hex_dump() {
+        let mut mem = [0u8; 4096];
+        mem[0x300..0x304].copy_from_slice(b"Hi!\x01");
+        let mut before = mem;
+        before[0x301] = 0;
+        let mut out = Vec::new();
+        hex_dump(&mut out, &mem, 0x2F8..0x304, Some(&before)).unwrap();
+        let out = String::from_utf8(out).unwrap();
+        let lines: Vec<&str> = out.lines().collect();
+        assert_eq!(
+            lines[0],
+            "2f8: 00 00 00 00 00 00 00 00 48 69 21 01              ........Hi!."
+        );
+        // under the 69 at 0x301
+        assert_eq!(lines[1].find("^^"), lines[0].find("69"));
+        assert_eq!(lines.len(), 2);
+        // past the end of memory there is nothing to show
+        let mut out = Vec::new();
+        hex_dump(&mut out, &mem, 0xFFE..0x1010, None).unwrap();
+        let out = String::from_utf8(out).unwrap();
+        assert!(out.starts_with("ffe: 00 00 ") && out.ends_with("  ..\n"));
+    }
+
+    #[test]
+    fn test_sprite_command() {
+        let mut chip8 = Chip8::new();
+        chip8.load_font_set();
+        // A055: I = the font's "1"
+        chip8.load_rom(&[0xA0, 0x55]).unwrap();
+        assert_eq!(
+            "sp i 2".parse(),
+            Ok(Command::Sprite {
+                addr: None,
+                rows: 2
+            })
+        );
+        let script = "s\nsprite\nsprite 50 1\n";
+        let mut out = Vec::new();
+        Debugger::default()
+            .session(&mut chip8, script.as_bytes(), &mut out)
+            .unwrap();
+        let out = String::from_utf8(out).unwrap();
+        let one = "055: 20 ..#.....\n056: 60 .##.....\n057: 20 ..#.....\n";
+        assert!(out.contains(one), "{}", out);
+        assert!(out.contains("063: f0 ####....\n(chippers) "), "{}", out);
+        assert!(out.contains("050: f0 ####....\n(chippers) "), "{}", out);
+    }
+
+    #[test]
+    fn test_view_marks_writes() {
+        let mut chip8 = Chip8::new();
+        // A300: I = 0x300, 6007: V0 = 7, F055: store V0 at I
+        chip8
+            .load_rom(&[0xA3, 0x00, 0x60, 0x07, 0xF0, 0x55])
+            .unwrap();
+        let script = "view 300 2\ns 2\ns\n";
+        let mut out = Vec::new();
+        Debugger::default()
+            .session(&mut chip8, script.as_bytes(), &mut out)
+            .unwrap();
+        let out = String::from_utf8(out).unwrap();
+        assert_eq!(out.matches("300: 00 00").count(), 2, "{}", out);
+        assert!(out.contains("300: 07 00"), "{}", out);
+        assert_eq!(out.matches("^^").count(), 1, "{}", out);
+    }
+
+    #[test]
+    fn test_profile_command() {
+        let mut chip8 = machine();
+        let script = "s\nprofile\nstep 3\nprofile\n";
+        let mut out = Vec::new();
+        Debugger::default()
+            .session(&mut chip8, script.as_bytes(), &mut out)
+            .unwrap();
+        let out = String::from_utf8(out).unwrap();
+        assert!(out.contains("profiling from here\n"), "{}", out);
+        assert!(out.contains("3 instructions\n"), "{}", out);
+        assert!(out.contains("0x202  7001  ADD V0, #01"), "{}", out);
+    }
+}
@@ -0,0 +1,59 @@
+//! Public-domain programs built into the binary, so `chippers demo` has
+//! something to run before you have found a ROM of your own.
+
+/// A built-in program.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Demo {
+    /// What `chippers demo` calls it.
+    pub name: &'static str,
+    /// What it is, who wrote it and how to play it.
+    pub about: &'static str,
+    pub rom: &'static [u8],
+}
+
+pub const DEMOS: [Demo; 4] = [
+    Demo {
+        name: "ibm",
+        about: "the IBM logo, drawn once; the classic first test of a new interpreter",
+        rom: include_bytes!("../IBM Logo.ch8"),
+    },
+    Demo {
+        name: "maze",
+        about: "an endless random maze, by David Winter",
+        rom: include_bytes!("../Maze (alt) [David Winter, 199x].ch8"),
+    },
+    Demo {
+        name: "sierpinski",
+        about: "a Sierpinski triangle, drawn a row at a time, by Sergey Naydenov",
+        rom: include_bytes!("../Sierpinski [Sergey Naydenov, 2010].ch8"),
+    },
+    Demo {
+        name: "brix",
+        about: "Breakout, by Andreas Gustafsson; Q and E move the paddle",
+        rom: include_bytes!("../Brix [Andreas Gustafsson, 1990].ch8"),
+    },
+];
+
+/// The demo called `name`.
+pub fn find(name: &str) -> Option<&'static Demo> {
+    DEMOS.iter().find(|demo| demo.name == name)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use chippers_core::chip::Chip8;
+
+    #[test]
+    fn test_demos_load() {
+        for demo in &DEMOS {
+            assert_eq!(find(demo.name), Some(demo));
+            let mut chip8 = Chip8::new();
+            chip8.load_rom(demo.rom).unwrap();
+            // each runs a while without faulting
+            let frame = chip8.run_frame(600);
+            assert_eq!(frame.fault, None, "{}", demo.name);
+        }
+        assert_eq!(find("pong"), None);
+    }
+}
@@ -0,0 +1,110 @@
+//! The SUPER-CHIP RPL user flags kept on disk between runs, as a cartridge's
+//! battery keeps its saves, so the high scores games store in them last.
+//!
+//! Each ROM has a file of its own in [`default_dir`], named after the ROM's
+//! SHA-1 so a renamed copy finds its scores, holding the flags as raw bytes.
+//! ROMs that keep their scores in memory instead can have it kept by
+//! [`persist`](crate::persist).
+
+use chippers_core::chip::Chip8;
+use chippers_core::cpu::FLAGS;
+use chippers_core::romdb::Sha1;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+/// Where flag files go, in [`data_dir`](crate::persist::data_dir).
+pub fn default_dir() -> Option<PathBuf> {
+    crate::persist::data_dir().map(|dir| dir.join("flags"))
+}
+
+/// A machine's flags, loaded from a file and written back by
+/// [`save`](FlagsFile::save) once the program has saved any.
+#[derive(Debug)]
+pub struct FlagsFile {
+    path: PathBuf,
+    // the flags as the program last saved them with FX75
+    saved: Arc<Mutex<Option<[u8; FLAGS]>>>,
+}
+
+impl FlagsFile {
+    /// The file in `dir` for `rom`.
+    pub fn path_for(dir: &Path, rom: &[u8]) -> PathBuf {
+        dir.join(format!("{}.flags", Sha1::of(rom)))
+    }
+
+    /// Gives the machine the flags saved at `path`, if there are any yet, and
+    /// watches for the program saving new ones.
+    pub fn attach(path: PathBuf, chip8: &mut Chip8) -> std::io::Result<Self> {
+        match std::fs::read(&path) {
+            Ok(bytes) => {
+                let len = bytes.len().min(FLAGS);
+                chip8.cpu.flags[..len].copy_from_slice(&bytes[..len]);
+            }
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+            Err(e) => return Err(e),
+        }
+        let saved = Arc::new(Mutex::new(None));
+        let seen = saved.clone();
+        chip8.hooks.on_instruction(move |_, raw, cpu| {
+            // FX75
+            if raw & 0xF0FF == 0xF075 {
+                *seen.lock().unwrap_or_else(|e| e.into_inner()) = Some(cpu.flags);
+            }
+        });
+        Ok(FlagsFile { path, saved })
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Writes the flags if the program has saved any, returning whether it
+    /// had.
+    pub fn save(&self) -> std::io::Result<bool> {
+        let Some(flags) = *self.saved.lock().unwrap_or_else(|e| e.into_inner()) else {
+            return Ok(false);
+        };
+        if let Some(dir) = self.path.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        std::fs::write(&self.path, flags)?;
+        Ok(true)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_flags_outlive_the_run() {
+        // 6007: V0 = 7, F085: V0 = flag 0, 7001: V0 += 1, F075: save it,
+        // 1208: halt
+        let rom = [0x60, 0x07, 0xF0, 0x85, 0x70, 0x01, 0xF0, 0x75, 0x12, 0x08];
+        let dir = std::env::temp_dir().join(format!("chippers-flags-{}", std::process::id()));
+        let path = FlagsFile::path_for(&dir, &rom);
+        assert!(path.ends_with(format!("{}.flags", Sha1::of(&rom))));
+        let run = || {
+            let mut chip8 = Chip8::new();
+            chip8.load_rom(&rom).unwrap();
+            let flags = FlagsFile::attach(path.clone(), &mut chip8).unwrap();
+            for _ in 0..5 {
+                chip8.step();
+            }
+            assert!(flags.save().unwrap());
+            chip8.cpu.registers()[0]
+        };
+        assert_eq!(run(), 1);
+        assert_eq!(run(), 2);
+        assert_eq!(std::fs::read(&path).unwrap(), [2, 0, 0, 0, 0, 0, 0, 0]);
+
+        // a program that never saves leaves the file alone
+        let mut chip8 = Chip8::new();
+        chip8.load_rom(&[0x12, 0x00]).unwrap();
+        let flags = FlagsFile::attach(path.clone(), &mut chip8).unwrap();
+        assert_eq!(chip8.cpu.flags[0], 2);
+        chip8.step();
+        assert!(!flags.save().unwrap());
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,125 @@
+//! Runs with no terminal or window, for test ROMs in scripts and CI.
+//!
+//! [`HeadlessBackend`] keeps the last frame it was shown instead of drawing
+//! it. [`HeadlessBackend::run`] drives a machine as fast as it will go for a
+//! fixed number of instructions or frames, with no key presses and no
+//! sleeping, so the same ROM always ends on the same frame.
+
+use chippers_core::backend::DisplayBackend;
+use chippers_core::chip::{Chip8, Chip8Message, Engine};
+use chippers_core::cpu::Fault;
+use chippers_core::display::{Display, PackedFrame};
+use core::convert::Infallible;
+
+/// How long a headless run lasts.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Limit {
+    Instructions(u64),
+    /// 60 Hz frames of `instructions_per_frame` instructions each.
+    Frames(u64),
+}
+
+/// A display backend that only remembers what it was last shown.
+#[derive(Clone, Debug, Default)]
+pub struct HeadlessBackend {
+    display: Display,
+    draws: u64,
+}
+
+impl HeadlessBackend {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The last frame drawn.
+    pub fn frame(&self) -> PackedFrame {
+        self.display.pack()
+    }
+
+    /// How many times the screen was cleared or drawn.
+    pub fn draws(&self) -> u64 {
+        self.draws
+    }
+
+    /// Runs `chip8` through `engine` until `limit`, ticking the timers every
+    /// `instructions_per_frame` instructions. Returns the number of
+    /// instructions executed, or the fault that stopped the program early.
+    pub fn run<E: Engine>(
+        &mut self,
+        chip8: &mut Chip8,
+        engine: &mut E,
+        instructions_per_frame: u32,
+        limit: Limit,
+    ) -> std::result::Result<u64, Fault> {
+        let per_frame = u64::from(instructions_per_frame.max(1));
+        let max = match limit {
+            Limit::Instructions(n) => n,
+            Limit::Frames(n) => n.saturating_mul(per_frame),
+        };
+        for executed in 1..=max {
+            let Ok(()) = match engine.step(chip8) {
+                Chip8Message::ClearScreen => self.clear_screen(),
+                Chip8Message::DrawScreen(_) | Chip8Message::StateChanged => {
+                    self.draw_screen(&chip8.cpu.disp)
+                }
+                Chip8Message::Halted(fault) => return Err(fault),
+                Chip8Message::None | Chip8Message::Beep(_) | Chip8Message::FrameComplete => Ok(()),
+            };
+            if executed % per_frame == 0 {
+                chip8.tick_timers();
+            }
+        }
+        Ok(max)
+    }
+}
+
+impl DisplayBackend for HeadlessBackend {
+    type Error = Infallible;
+
+    fn clear_screen(&mut self) -> std::result::Result<(), Self::Error> {
+        self.display.clear();
+        self.draws += 1;
+        Ok(())
+    }
+
+    fn draw_screen(&mut self, display: &Display) -> std::result::Result<(), Self::Error> {
+        self.display.clone_from(display);
+        self.draws += 1;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use chippers_core::chip::Interpreter;
+    use chippers_core::golden::text_art;
+
+    #[test]
+    fn test_run_for_frames() {
+        let mut chip8 = Chip8::new();
+        chip8.load_font_set();
+        // 6A05: VA = 5, FA15: DT = VA, A050: I = font, D005: draw, 00E0: clear, D005: draw, 120C: halt
+        chip8
+            .load_rom(&[
+                0x6A, 0x05, 0xFA, 0x15, 0xA0, 0x50, 0xD0, 0x05, 0x00, 0xE0, 0xD0, 0x05, 0x12, 0x0C,
+            ])
+            .unwrap();
+        let mut backend = HeadlessBackend::new();
+        let run = backend.run(&mut chip8, &mut Interpreter, 10, Limit::Frames(3));
+        assert_eq!(run, Ok(30));
+        assert_eq!(chip8.instructions(), 30);
+        assert_eq!(chip8.cpu.dt, 2);
+        assert_eq!(backend.draws(), 3);
+        assert!(text_art(&backend.frame()).starts_with("####....."));
+    }
+
+    #[test]
+    fn test_fault_ends_the_run() {
+        let mut chip8 = Chip8::new();
+        chip8.load_rom(&[0x00, 0xEE]).unwrap();
+        let run =
+            HeadlessBackend::new().run(&mut chip8, &mut Interpreter, 10, Limit::Instructions(5));
+        assert_eq!(run, Err(Fault::StackUnderflow { addr: 0x200 }));
+    }
+}
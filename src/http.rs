@@ -0,0 +1,263 @@
+//! A small HTTP server for watching and steering a headless machine.
+//!
+//! | Request              | Effect                                            |
+//! |----------------------|---------------------------------------------------|
+//! | `GET /state`         | registers, timers, run state and fault as JSON    |
+//! | `GET /frame.png`     | the display as a 64x32 grayscale PNG              |
+//! | `POST /pause`        | stops executing instructions                      |
+//! | `POST /resume`       | carries on after `/pause`                         |
+//! | `POST /reset`        | reloads the ROM into a fresh machine              |
+//! | `POST /key/<hex>`    | presses keypad key `<hex>` (`0` to `f`)           |
+
+use chippers_core::backend::Palette;
+use chippers_core::chip::Chip8;
+use chippers_core::cpu::Fault;
+use chippers_core::image::png;
+use std::time::{Duration, Instant};
+use tiny_http::{Header, Method, Request, Response, Server};
+
+#[derive(Clone, Debug)]
+pub enum HttpError {
+    Bind(String),
+    Io(String),
+}
+
+impl std::fmt::Display for HttpError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            HttpError::Bind(s) => writeln!(f, "error: could not start http server: {}", s)?,
+            HttpError::Io(s) => writeln!(f, "error: http server failed: {}", s)?,
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for HttpError {}
+
+impl From<std::io::Error> for HttpError {
+    fn from(err: std::io::Error) -> HttpError {
+        HttpError::Io(err.to_string())
+    }
+}
+
+/// Answers status and control requests for one machine.
+///
+/// Requests are only handled from [`poll`](HttpControl::poll), so the server
+/// never touches the machine behind the caller's back.
+pub struct HttpControl {
+    server: Server,
+    rom: Vec<u8>,
+    paused: bool,
+    // the last fault a frame stopped at, until the next reset
+    fault: Option<Fault>,
+}
+
+impl std::fmt::Debug for HttpControl {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        f.debug_struct("HttpControl")
+            .field("addr", &self.server.server_addr().to_string())
+            .field("paused", &self.paused)
+            .finish()
+    }
+}
+
+impl HttpControl {
+    /// Listens on `addr`, e.g. `127.0.0.1:8080`. `rom` is what `/reset` reloads.
+    pub fn bind(addr: &str, rom: impl Into<Vec<u8>>) -> std::result::Result<Self, HttpError> {
+        let server = Server::http(addr).map_err(|e| HttpError::Bind(e.to_string()))?;
+        Ok(HttpControl {
+            server,
+            rom: rom.into(),
+            paused: false,
+            fault: None,
+        })
+    }
+
+    /// The address actually bound, useful after binding port 0.
+    pub fn addr(&self) -> String {
+        self.server.server_addr().to_string()
+    }
+
+    pub fn paused(&self) -> bool {
+        self.paused
+    }
+
+    /// Answers every request that has arrived so far without blocking.
+    pub fn poll(&mut self, chip8: &mut Chip8) -> std::result::Result<(), HttpError> {
+        while let Some(request) = self.server.try_recv()? {
+            self.handle(request, chip8)?;
+        }
+        Ok(())
+    }
+
+    /// Runs `chip8` headlessly at 60 frames per second, each one a
+    /// [`step_frame`](Chip8::step_frame) at the machine's speed and timing,
+    /// serving requests between frames. Never returns `Ok`.
+    pub fn serve(&mut self, chip8: &mut Chip8) -> std::result::Result<(), HttpError> {
+        let frame = Duration::from_secs_f64(1. / 60.);
+        loop {
+            let start = Instant::now();
+            self.poll(chip8)?;
+            if !self.paused {
+                self.run_frame(chip8);
+            }
+            std::thread::sleep(frame.saturating_sub(start.elapsed()));
+        }
+    }
+
+    /// Runs one frame, remembering the fault that stopped it, if any.
+    fn run_frame(&mut self, chip8: &mut Chip8) {
+        if let Some(fault) = chip8.step_frame().fault {
+            self.fault = Some(fault);
+        }
+    }
+
+    fn handle(
+        &mut self,
+        request: Request,
+        chip8: &mut Chip8,
+    ) -> std::result::Result<(), HttpError> {
+        let response = match (request.method(), request.url()) {
+            (Method::Get, "/state") => Response::from_string(self.state_json(chip8))
+                .with_header(content_type("application/json")),
+            (Method::Get, "/frame.png") => {
+                let png = png(&chip8.framebuffer(), 1, Palette::MONOCHROME);
+                Response::from_data(png).with_header(content_type("image/png"))
+            }
+            (Method::Post, "/pause") => {
+                self.paused = true;
+                Response::from_string("paused\n")
+            }
+            (Method::Post, "/resume") => {
+                self.paused = false;
+                Response::from_string("running\n")
+            }
+            (Method::Post, "/reset") => match chip8.swap_rom(&self.rom) {
+                Ok(_) => {
+                    self.fault = None;
+                    Response::from_string("reset\n")
+                }
+                Err(e) => Response::from_string(e.to_string()).with_status_code(500),
+            },
+            (Method::Post, url) if url.starts_with("/key/") => {
+                match u8::from_str_radix(&url["/key/".len()..], 16) {
+                    Ok(key @ 0..=0xF) => {
+                        chip8.cpu.press_key(key);
+                        Response::from_string("pressed\n")
+                    }
+                    _ => Response::from_string("keys are 0 to f\n").with_status_code(400),
+                }
+            }
+            _ => Response::from_string("not found\n").with_status_code(404),
+        };
+        request.respond(response)?;
+        Ok(())
+    }
+
+    fn state_json(&self, chip8: &Chip8) -> String {
+        let cpu = &chip8.cpu;
+        let v = cpu
+            .registers()
+            .iter()
+            .map(|r| r.to_string())
+            .collect::<Vec<_>>()
+            .join(",");
+        // fault messages hold no quotes or backslashes to escape
+        let fault = match self.fault {
+            Some(fault) => {
+                let fault = fault.to_string();
+                format!("\"{}\"", fault.trim_start_matches("error: ").trim_end())
+            }
+            None => String::from("null"),
+        };
+        format!(
+            "{{\"pc\":{},\"i\":{},\"v\":[{}],\"dt\":{},\"st\":{},\"paused\":{},\"fault\":{}}}\n",
+            cpu.pc(),
+            cpu.index(),
+            v,
+            cpu.dt,
+            cpu.st,
+            self.paused,
+            fault
+        )
+    }
+}
+
+fn content_type(value: &str) -> Header {
+    Header::from_bytes(&b"Content-Type"[..], value.as_bytes()).unwrap()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::io::{Read, Write};
+    use std::net::TcpStream;
+
+    fn request(control: &mut HttpControl, chip8: &mut Chip8, req: &str) -> String {
+        let mut stream = TcpStream::connect(control.addr()).unwrap();
+        stream.write_all(req.as_bytes()).unwrap();
+        // wait for the server to accept the connection and parse the request
+        let mut handled = false;
+        for _ in 0..100 {
+            if let Some(request) = control
+                .server
+                .recv_timeout(Duration::from_millis(20))
+                .unwrap()
+            {
+                control.handle(request, chip8).unwrap();
+                handled = true;
+                break;
+            }
+        }
+        assert!(handled);
+        let mut response = Vec::new();
+        stream.read_to_end(&mut response).unwrap();
+        String::from_utf8_lossy(&response).into_owned()
+    }
+
+    #[test]
+    fn test_endpoints() {
+        let mut control = HttpControl::bind("127.0.0.1:0", [0x60, 0x2A]).unwrap();
+        let mut chip8 = Chip8::new();
+        chip8.load_rom(&[0x60, 0x00]).unwrap();
+        chip8.step();
+        let get = |path| format!("GET {} HTTP/1.1\r\nConnection: close\r\n\r\n", path);
+        let post = |path| {
+            format!(
+                "POST {} HTTP/1.1\r\nConnection: close\r\nContent-Length: 0\r\n\r\n",
+                path
+            )
+        };
+
+        let state = request(&mut control, &mut chip8, &get("/state"));
+        assert!(state.contains("\"pc\":514,"), "{}", state);
+        assert!(state.contains("\"paused\":false"));
+        assert!(state.contains("\"fault\":null"), "{}", state);
+
+        // 00EE: return from nowhere
+        chip8.cpu.mem[0x202..0x204].copy_from_slice(&[0x00, 0xEE]);
+        control.run_frame(&mut chip8);
+        let state = request(&mut control, &mut chip8, &get("/state"));
+        assert!(
+            state.contains("\"fault\":\"stack underflow at 0x202: return outside any subroutine\""),
+            "{}",
+            state
+        );
+
+        request(&mut control, &mut chip8, &post("/pause"));
+        assert!(control.paused());
+
+        request(&mut control, &mut chip8, &post("/reset"));
+        assert_eq!(chip8.cpu.pc(), 0x200);
+        let state = request(&mut control, &mut chip8, &get("/state"));
+        assert!(state.contains("\"fault\":null"), "{}", state);
+        assert_eq!(&chip8.cpu.mem[0x200..0x202], &[0x60, 0x2A]);
+
+        request(&mut control, &mut chip8, &post("/key/b"));
+        assert!(request(&mut control, &mut chip8, &post("/key/10")).starts_with("HTTP/1.1 400"));
+
+        let png = request(&mut control, &mut chip8, &get("/frame.png"));
+        assert!(png.contains("image/png"));
+        assert!(request(&mut control, &mut chip8, &get("/nope")).starts_with("HTTP/1.1 404"));
+    }
+}
@@ -0,0 +1,474 @@
+//! An experimental [`Engine`] that compiles straight-line CHIP-8 code to native
+//! code with cranelift.
+//!
+//! A block is the run of register and index arithmetic (6XNN, 7XNN, 8XYN,
+//! ANNN, FX1E) starting at the program counter, optionally ended by a jump
+//! (1NNN) or a skip (3XNN, 4XNN, 5XY0, 9XY0). Everything else — drawing, the
+//! keypad, timers, memory, the stack, random numbers — runs in the
+//! interpreter. Each block keeps a copy of the bytes it was compiled from and
+//! is recompiled if the program has overwritten them.
+
+use chippers_core::chip::{Chip8, Chip8Message, Engine};
+use chippers_core::opcode::{Instruction, Opcode};
+use chippers_core::quirks::Quirks;
+use chippers_core::timing::Timing;
+use cranelift_codegen::ir::condcodes::IntCC;
+use cranelift_codegen::ir::{types, AbiParam, InstBuilder, MemFlags, Value};
+use cranelift_codegen::settings::{self, Configurable};
+use cranelift_frontend::{FunctionBuilder, FunctionBuilderContext};
+use cranelift_jit::{JITBuilder, JITModule};
+use cranelift_module::{default_libcall_names, Module};
+use std::collections::HashMap;
+
+/// Longest block compiled, in instructions.
+const MAX_BLOCK: usize = 64;
+
+#[derive(Clone, Debug)]
+pub enum JitError {
+    Unsupported(String),
+}
+
+impl std::fmt::Display for JitError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            JitError::Unsupported(s) => writeln!(f, "error: jit is not available: {}", s)?,
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for JitError {}
+
+/// Compiled code for one block: takes V0-VF and I, returns the next PC.
+type BlockFn = unsafe extern "C" fn(*mut u8, *mut u16) -> u16;
+
+struct Block {
+    code: BlockFn,
+    source: Vec<u8>,
+}
+
+/// Runs compiled blocks where it can and the interpreter everywhere else.
+///
+/// Hooks and opcode extensions observe individual instructions, so a machine
+/// with any registered is always interpreted. Blocks are compiled for the
+/// machine's quirks and thrown away if they change.
+pub struct JitEngine {
+    module: JITModule,
+    builder_context: FunctionBuilderContext,
+    blocks: HashMap<u16, Block>,
+    quirks: Quirks,
+}
+
+impl std::fmt::Debug for JitEngine {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        f.debug_struct("JitEngine")
+            .field("blocks", &self.blocks.len())
+            .finish()
+    }
+}
+
+impl JitEngine {
+    /// Sets up code generation for the host CPU.
+    pub fn new() -> std::result::Result<Self, JitError> {
+        let unsupported = |e: &dyn std::fmt::Display| JitError::Unsupported(e.to_string());
+        let mut flags = settings::builder();
+        flags
+            .set("use_colocated_libcalls", "false")
+            .map_err(|e| unsupported(&e))?;
+        flags.set("is_pic", "false").map_err(|e| unsupported(&e))?;
+        let isa = cranelift_native::builder()
+            .map_err(|e| unsupported(&e))?
+            .finish(settings::Flags::new(flags))
+            .map_err(|e| unsupported(&e))?;
+        Ok(JitEngine {
+            module: JITModule::new(JITBuilder::with_isa(isa, default_libcall_names())),
+            builder_context: FunctionBuilderContext::new(),
+            blocks: HashMap::new(),
+            quirks: Quirks::default(),
+        })
+    }
+
+    /// Number of addresses a block has been compiled for.
+    pub fn compiled_blocks(&self) -> usize {
+        self.blocks.len()
+    }
+
+    /// The block starting at `pc` and its length in instructions.
+    fn block(&mut self, chip8: &Chip8, pc: u16) -> Option<(BlockFn, u64)> {
+        let mem = &chip8.cpu.mem;
+        if let Some(block) = self.blocks.get(&pc) {
+            let start = pc as usize;
+            if mem.get(start..start + block.source.len()) == Some(&block.source[..]) {
+                return Some((block.code, block.source.len() as u64 / 2));
+            }
+        }
+        let (instructions, end) = scan(mem, pc);
+        if instructions.is_empty() {
+            return None;
+        }
+        let code = self.compile(pc, &instructions)?;
+        let source = mem[pc as usize..end as usize].to_vec();
+        self.blocks.insert(pc, Block { code, source });
+        Some((code, instructions.len() as u64))
+    }
+
+    fn compile(&mut self, pc: u16, instructions: &[Instruction]) -> Option<BlockFn> {
+        let mut ctx = self.module.make_context();
+        let ptr = self.module.target_config().pointer_type();
+        ctx.func.signature.params.push(AbiParam::new(ptr));
+        ctx.func.signature.params.push(AbiParam::new(ptr));
+        ctx.func.signature.returns.push(AbiParam::new(types::I16));
+
+        let mut b = FunctionBuilder::new(&mut ctx.func, &mut self.builder_context);
+        let entry = b.create_block();
+        b.append_block_params_for_function_params(entry);
+        b.switch_to_block(entry);
+        b.seal_block(entry);
+        let regs = b.block_params(entry)[0];
+        let index = b.block_params(entry)[1];
+        let mut gen = Gen {
+            b,
+            regs,
+            index,
+            quirks: self.quirks,
+        };
+
+        let mut branch = None;
+        for (i, inst) in instructions.iter().enumerate() {
+            branch = gen.emit(inst, pc + 2 * i as u16);
+        }
+        let end = pc + 2 * instructions.len() as u16;
+        let next = branch.unwrap_or_else(|| gen.b.ins().iconst(types::I16, i64::from(end)));
+        gen.b.ins().return_(&[next]);
+        gen.b.finalize();
+
+        let id = self
+            .module
+            .declare_anonymous_function(&ctx.func.signature)
+            .ok()?;
+        self.module.define_function(id, &mut ctx).ok()?;
+        self.module.clear_context(&mut ctx);
+        self.module.finalize_definitions().ok()?;
+        let code = self.module.get_finalized_function(id);
+        // SAFETY: the function was just built with exactly this signature
+        Some(unsafe { std::mem::transmute::<*const u8, BlockFn>(code) })
+    }
+}
+
+/// Collects the compilable instructions from `pc`, returning them and the
+/// address just past the last one.
+fn scan(mem: &[u8; 4096], pc: u16) -> (Vec<Instruction>, u16) {
+    let mut instructions = Vec::new();
+    let mut addr = pc as usize;
+    while instructions.len() < MAX_BLOCK && addr + 1 < mem.len() {
+        let raw = u16::from_be_bytes([mem[addr], mem[addr + 1]]);
+        let Ok(inst) = Instruction::decode(raw) else {
+            break;
+        };
+        match kind(&inst) {
+            Kind::Interpreted => break,
+            Kind::Straight => {
+                instructions.push(inst);
+                addr += 2;
+            }
+            Kind::Branch => {
+                instructions.push(inst);
+                addr += 2;
+                break;
+            }
+        }
+    }
+    (instructions, addr as u16)
+}
+
+enum Kind {
+    Straight,
+    Branch,
+    Interpreted,
+}
+
+fn kind(inst: &Instruction) -> Kind {
+    match inst.opcode() {
+        Opcode::SetVX
+        | Opcode::AddVX
+        | Opcode::SetI
+        | Opcode::AddI
+        | Opcode::SetVXToVY
+        | Opcode::BinaryOr
+        | Opcode::BinaryAnd
+        | Opcode::BinaryXor
+        | Opcode::AddVYToVX
+        | Opcode::SubVYFromVX
+        | Opcode::SubVXFromVY
+        | Opcode::ShiftRight
+        | Opcode::ShiftLeft => Kind::Straight,
+        Opcode::Jump
+        | Opcode::SkipEqual
+        | Opcode::SkipNotEqual
+        | Opcode::SkipVXEqualVY
+        | Opcode::SkipVXNotEqualVY => Kind::Branch,
+        _ => Kind::Interpreted,
+    }
+}
+
+struct Gen<'a> {
+    b: FunctionBuilder<'a>,
+    regs: Value,
+    index: Value,
+    quirks: Quirks,
+}
+
+impl Gen<'_> {
+    fn v(&mut self, x: u8) -> Value {
+        self.b
+            .ins()
+            .load(types::I8, MemFlags::trusted(), self.regs, i32::from(x))
+    }
+
+    fn set_v(&mut self, x: u8, value: Value) {
+        self.b
+            .ins()
+            .store(MemFlags::trusted(), value, self.regs, i32::from(x));
+    }
+
+    fn byte(&mut self, value: u8) -> Value {
+        self.b.ins().iconst(types::I8, i64::from(value))
+    }
+
+    /// Emits `inst`, fetched from `addr`, returning the next PC if it branches.
+    /// Flag updates mirror the interpreter: VF is written last, so the flag
+    /// wins when X is F.
+    fn emit(&mut self, inst: &Instruction, addr: u16) -> Option<Value> {
+        use Instruction as I;
+
+        match *inst {
+            I::SetVX { x, byte } => {
+                let kk = self.byte(byte);
+                self.set_v(x, kk);
+            }
+            I::AddVX { x, byte } => {
+                let (vx, kk) = (self.v(x), self.byte(byte));
+                let sum = self.b.ins().iadd(vx, kk);
+                self.set_v(x, sum);
+            }
+            I::SetI { addr } => {
+                let nnn = self.b.ins().iconst(types::I16, i64::from(addr));
+                self.b.ins().store(MemFlags::trusted(), nnn, self.index, 0);
+            }
+            I::AddI { x } => {
+                let i = self
+                    .b
+                    .ins()
+                    .load(types::I16, MemFlags::trusted(), self.index, 0);
+                let vx = self.v(x);
+                let vx = self.b.ins().uextend(types::I16, vx);
+                let i = self.b.ins().iadd(i, vx);
+                self.b.ins().store(MemFlags::trusted(), i, self.index, 0);
+                let over = self
+                    .b
+                    .ins()
+                    .icmp_imm(IntCC::UnsignedGreaterThanOrEqual, i, 0x1000);
+                let (vf, one) = (self.v(0xF), self.byte(1));
+                let vf = self.b.ins().select(over, one, vf);
+                self.set_v(0xF, vf);
+            }
+            I::SetVXToVY { x, y } => {
+                let vy = self.v(y);
+                self.set_v(x, vy);
+            }
+            I::BinaryOr { x, y } | I::BinaryAnd { x, y } | I::BinaryXor { x, y } => {
+                let (vx, vy) = (self.v(x), self.v(y));
+                let res = match inst {
+                    I::BinaryOr { .. } => self.b.ins().bor(vx, vy),
+                    I::BinaryAnd { .. } => self.b.ins().band(vx, vy),
+                    _ => self.b.ins().bxor(vx, vy),
+                };
+                self.set_v(x, res);
+                if self.quirks.logic_resets_vf {
+                    let zero = self.byte(0);
+                    self.set_v(0xF, zero);
+                }
+            }
+            I::AddVYToVX { x, y } => {
+                let (vx, vy) = (self.v(x), self.v(y));
+                let sum = self.b.ins().iadd(vx, vy);
+                let carry = self.b.ins().icmp(IntCC::UnsignedLessThan, sum, vx);
+                self.set_v(x, sum);
+                self.set_v(0xF, carry);
+            }
+            I::SubVYFromVX { x, y } | I::SubVXFromVY { x, y } => {
+                let (vx, vy) = (self.v(x), self.v(y));
+                let (minuend, subtrahend) = match inst {
+                    I::SubVYFromVX { .. } => (vx, vy),
+                    _ => (vy, vx),
+                };
+                let no_borrow =
+                    self.b
+                        .ins()
+                        .icmp(IntCC::UnsignedGreaterThanOrEqual, minuend, subtrahend);
+                let diff = self.b.ins().isub(minuend, subtrahend);
+                self.set_v(x, diff);
+                self.set_v(0xF, no_borrow);
+            }
+            I::ShiftRight { x, y } => {
+                let vx = self.v(if self.quirks.shift_uses_vy { y } else { x });
+                let flag = self.b.ins().band_imm(vx, 1);
+                let res = self.b.ins().ushr_imm(vx, 1);
+                self.set_v(x, res);
+                self.set_v(0xF, flag);
+            }
+            I::ShiftLeft { x, y } => {
+                let vx = self.v(if self.quirks.shift_uses_vy { y } else { x });
+                let flag = self.b.ins().ushr_imm(vx, 7);
+                let res = self.b.ins().ishl_imm(vx, 1);
+                self.set_v(x, res);
+                self.set_v(0xF, flag);
+            }
+            I::Jump { addr } => {
+                return Some(self.b.ins().iconst(types::I16, i64::from(addr)));
+            }
+            I::SkipEqual { x, byte } | I::SkipNotEqual { x, byte } => {
+                let (vx, kk) = (self.v(x), self.byte(byte));
+                return Some(self.skip(inst, vx, kk, addr));
+            }
+            I::SkipVXEqualVY { x, y } | I::SkipVXNotEqualVY { x, y } => {
+                let (vx, vy) = (self.v(x), self.v(y));
+                return Some(self.skip(inst, vx, vy, addr));
+            }
+            _ => unreachable!("{:?} is interpreted", inst),
+        }
+        None
+    }
+
+    /// Emits the next PC of the skip `inst` at `addr`, comparing `vx` with
+    /// `other`.
+    fn skip(&mut self, inst: &Instruction, vx: Value, other: Value, addr: u16) -> Value {
+        let cc = match inst {
+            Instruction::SkipEqual { .. } | Instruction::SkipVXEqualVY { .. } => IntCC::Equal,
+            _ => IntCC::NotEqual,
+        };
+        let skip = self.b.ins().icmp(cc, vx, other);
+        let taken = self.b.ins().iconst(types::I16, i64::from(addr + 4));
+        let not_taken = self.b.ins().iconst(types::I16, i64::from(addr + 2));
+        self.b.ins().select(skip, taken, not_taken)
+    }
+}
+
+impl Engine for JitEngine {
+    fn step(&mut self, chip8: &mut Chip8) -> Chip8Message {
+        // a replay's events fall between instructions, not between blocks,
+        // and VIP timing charges each instruction its own cost
+        if !chip8.hooks.is_empty()
+            || !chip8.extensions.is_empty()
+            || chip8.megachip.is_some()
+            || chip8.is_playing()
+            || chip8.timing() == Timing::Cosmac
+        {
+            return chip8.step();
+        }
+        if chip8.cpu.quirks != self.quirks {
+            self.blocks.clear();
+            self.quirks = chip8.cpu.quirks;
+        }
+        let pc = chip8.cpu.pc();
+        let Some((code, len)) = self.block(chip8, pc) else {
+            return chip8.step();
+        };
+        let mut index = chip8.cpu.index();
+        // SAFETY: the block only touches the 16 registers and the index it is given
+        let next = unsafe { code(chip8.cpu.registers_mut().as_mut_ptr(), &mut index) };
+        chip8.cpu.set_index(index);
+        chip8.cpu.set_pc(next);
+        chip8.retire(len);
+        Chip8Message::None
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use chippers_core::chip::Interpreter;
+
+    /// Runs `rom`, straight-line code ending in a jump, as one compiled block
+    /// and instruction by instruction, and compares the results.
+    fn check(rom: &[u8], quirks: Quirks) {
+        let load = || {
+            let mut chip8 = Chip8::new();
+            chip8.cpu.quirks = quirks;
+            chip8.cpu.mem[0x200..0x200 + rom.len()].copy_from_slice(rom);
+            chip8
+        };
+        let (mut jit, mut interp) = (load(), load());
+        JitEngine::new().unwrap().step(&mut jit);
+        for _ in 0..rom.len() / 2 {
+            Interpreter.step(&mut interp);
+        }
+        assert_eq!(jit.cpu.pc(), interp.cpu.pc());
+        assert_eq!(jit.cpu.registers(), interp.cpu.registers());
+        assert_eq!(jit.cpu.index(), interp.cpu.index());
+    }
+
+    #[test]
+    fn test_alu_matches_interpreter() {
+        #[rustfmt::skip]
+        let rom = [
+            0x60, 0xF0, // V0 = F0
+            0x61, 0x20, // V1 = 20
+            0x80, 0x14, // V0 += V1, carry
+            0x62, 0x05, // V2 = 05
+            0x82, 0x15, // V2 -= V1, borrow
+            0x83, 0x27, // V3 = V2 - V3
+            0x84, 0x06, // V4 = V0 >> 1
+            0x85, 0x2E, // V5 = V2 << 1
+            0x86, 0x11, 0x87, 0x22, 0x88, 0x23, // or, and, xor
+            0x7F, 0x10, // VF += 10
+            0xAF, 0xF0, // I = FF0
+            0xF1, 0x1E, // I += V1, overflow
+            0x8F, 0x14, // VF += V1: the flag wins over VX
+            0x12, 0x00, // loop
+        ];
+        check(&rom, Quirks::default());
+        check(&rom, Quirks::COSMAC);
+    }
+
+    #[test]
+    fn test_skips_and_fallback() {
+        #[rustfmt::skip]
+        let rom = [
+            0x60, 0x03, // V0 = 3
+            0x30, 0x03, // skip if V0 == 3
+            0x60, 0x09, // not run
+            0x90, 0x10, // skip if V0 != V1
+            0x00, 0x00, // not run
+            0xC1, 0xFF, // interpreted
+            0x70, 0x01, // V0 += 1
+            0x12, 0x0C, // jump back to V0 += 1
+        ];
+        let mut chip8 = Chip8::new();
+        chip8.cpu.mem[0x200..0x200 + rom.len()].copy_from_slice(&rom);
+        let mut engine = JitEngine::new().unwrap();
+        engine.step(&mut chip8);
+        assert_eq!(chip8.cpu.pc(), 0x206);
+        engine.step(&mut chip8);
+        assert_eq!(chip8.cpu.pc(), 0x20A);
+        engine.step(&mut chip8);
+        assert_eq!(chip8.cpu.pc(), 0x20C);
+        engine.step(&mut chip8);
+        engine.step(&mut chip8);
+        assert_eq!((chip8.cpu.pc(), chip8.cpu.registers()[0]), (0x20C, 5));
+        assert_eq!(engine.compiled_blocks(), 3);
+        assert_eq!(chip8.instructions(), 2 + 1 + 1 + 2 + 2);
+    }
+
+    #[test]
+    fn test_recompiles_modified_code() {
+        let mut chip8 = Chip8::new();
+        chip8.cpu.mem[0x200..0x204].copy_from_slice(&[0x60, 0x01, 0x12, 0x00]);
+        let mut engine = JitEngine::new().unwrap();
+        engine.step(&mut chip8);
+        assert_eq!(chip8.cpu.registers()[0], 1);
+        chip8.cpu.mem[0x201] = 0x02;
+        engine.step(&mut chip8);
+        assert_eq!(chip8.cpu.registers()[0], 2);
+    }
+}
@@ -1,4 +1,79 @@
-pub mod chip;
-pub mod cpu;
-pub mod opcode;
+//! A CHIP-8 interpreter to embed in your own frontend.
+//!
+//! The machine itself lives in `chippers-core` and is re-exported here: load a
+//! ROM into a [`Chip8`], then either drive it yourself with [`Chip8::step`]
+//! and [`Chip8::tick_timers`], or a 60 Hz frame at a time with
+//! [`Chip8::step_frame`], or implement [`Frontend`] and hand it to
+//! [`Chip8::run_with`]. To watch a machine run rather than drive it, as an
+//! analysis tool might, implement [`Observer`] and register it with
+//! [`Hooks::observe`](hooks::Hooks::observe). The optional modules are
+//! ready-made frontends and engines, each behind its feature.
+//!
+//! ```
+//! use chippers::{Chip8, Chip8Message};
+//!
+//! let mut chip8 = Chip8::new();
+//! chip8.load_font_set();
+//! // A050: I = font "0", D005: draw it, 1204: loop forever
+//! chip8.load_rom(&[0xA0, 0x50, 0xD0, 0x05, 0x12, 0x04]).unwrap();
+//! let mut drawn = false;
+//! for _ in 0..12 {
+//!     drawn |= matches!(chip8.step(), Chip8Message::DrawScreen(_));
+//! }
+//! chip8.tick_timers();
+//! assert!(drawn);
+//! assert_eq!(chip8.framebuffer().as_1bpp()[0], 0xF0);
+//! ```
+
+pub use chippers_core::*;
+
+#[cfg(feature = "terminal")]
 pub mod terminal;
+
+#[cfg(feature = "terminal")]
+pub mod browser;
+
+#[cfg(feature = "tui")]
+pub mod tui;
+
+#[cfg(feature = "audio")]
+pub mod audio;
+
+#[cfg(feature = "capture")]
+pub mod capture;
+
+#[cfg(feature = "config")]
+pub mod config;
+
+pub mod condition;
+pub mod crash;
+pub mod debugger;
+pub mod demos;
+pub mod flags;
+
+pub mod headless;
+
+pub mod persist;
+pub mod profile;
+
+pub mod romtest;
+
+#[cfg(feature = "lua")]
+pub mod script;
+
+pub mod trace;
+
+#[cfg(feature = "plugins")]
+pub mod plugin_host;
+
+#[cfg(feature = "bevy")]
+pub mod bevy_plugin;
+
+#[cfg(feature = "http")]
+pub mod http;
+
+#[cfg(feature = "jit")]
+pub mod jit;
+
+#[cfg(feature = "backend-sdl")]
+pub mod sdl;
@@ -1,22 +1,894 @@
-#![allow(unused)]
+use chippers::backend::{Palette, Rgb};
+use chippers::browser::{self, Picker};
+use chippers::capture::{Capture, CaptureError, Format};
+use chippers::chip::{ChipError, TurboLimit, DEFAULT_FRAME_RATE, DEFAULT_SPEED};
+use chippers::config::Config;
+use chippers::cpu::OnUnknown;
+use chippers::crash::CrashDump;
+use chippers::demos::{self, DEMOS};
+use chippers::flags::{self, FlagsFile};
+use chippers::golden::text_art;
+use chippers::headless::{HeadlessBackend, Limit};
+use chippers::opcode::Instruction;
+use chippers::persist::{self, MemoryFile, Region};
+use chippers::profile::Profiler;
+use chippers::replay::Replay;
+use chippers::rng::Rng;
+use chippers::romdb;
+use chippers::romtest::{run_test, Check};
+use chippers::terminal::{RenderMode, Scale, Terminal, TerminalFrontend};
+use chippers::trace::Tracer;
+use chippers::{
+    Chip8, Chip8Message, DisplayBackend, Engine, Frontend, Interpreter, KeyMap, Limits, Quirks,
+    Timing,
+};
+use std::collections::BTreeMap;
 
-use chippers::chip::*;
-use chippers::terminal::*;
-use crossterm::terminal;
-use std::io::{stdout, Write};
+/// Names accepted by `--engine`.
+const ENGINES: &[&str] = &[
+    "interp",
+    #[cfg(feature = "jit")]
+    "jit",
+];
 
-fn main() -> std::result::Result<(), TerminalError> {
+/// Names accepted by `--backend`.
+const BACKENDS: &[&str] = &[
+    "terminal",
+    #[cfg(feature = "backend-sdl")]
+    "sdl",
+];
+
+/// The execution engine named on the command line, or the script given with
+/// `--script`, which interprets as it runs its hooks.
+enum Selected {
+    Interpreter,
+    #[cfg(feature = "jit")]
+    Jit(Box<chippers::jit::JitEngine>),
+    #[cfg(feature = "lua")]
+    Script(chippers::script::Script),
+}
+
+impl Selected {
+    fn new(
+        engine: &str,
+        script: Option<&String>,
+    ) -> std::result::Result<Self, Box<dyn std::error::Error>> {
+        #[cfg(feature = "lua")]
+        if let Some(path) = script {
+            if engine != "interp" {
+                return Err(
+                    format!("--script runs on the interpreter, not --engine {}", engine).into(),
+                );
+            }
+            return Ok(Selected::Script(chippers::script::Script::load(
+                path.as_ref(),
+            )?));
+        }
+        #[cfg(not(feature = "lua"))]
+        let _ = script;
+        Ok(match engine {
+            #[cfg(feature = "jit")]
+            "jit" => Selected::Jit(Box::new(chippers::jit::JitEngine::new()?)),
+            _ => Selected::Interpreter,
+        })
+    }
+
+    /// Reports the error that stopped the script, if one did.
+    fn finish(&mut self) {
+        #[cfg(feature = "lua")]
+        if let Selected::Script(script) = self {
+            if let Some(e) = script.take_error() {
+                eprint!("{}", e);
+            }
+        }
+    }
+}
+
+impl Engine for Selected {
+    fn step(&mut self, chip8: &mut Chip8) -> Chip8Message {
+        match self {
+            Selected::Interpreter => Interpreter.step(chip8),
+            #[cfg(feature = "jit")]
+            Selected::Jit(jit) => jit.step(chip8),
+            #[cfg(feature = "lua")]
+            Selected::Script(script) => script.step(chip8),
+        }
+    }
+}
+
+/// Runs `chip8` on `frontend` with `engine`, then writes what was recorded,
+/// if anything, to `record`.
+fn run<F>(
+    chip8: &mut Chip8,
+    frontend: &mut F,
+    engine: &mut Selected,
+    record: Option<&String>,
+) -> std::result::Result<(), Box<dyn std::error::Error>>
+where
+    F: Frontend,
+    F::Error: std::error::Error + 'static,
+{
+    let run = chip8.run_with_engine(frontend, engine);
+    engine.finish();
+    // a run that ends in a fault is worth replaying too
+    if let (Some(path), Some(replay)) = (record, chip8.take_recording()) {
+        std::fs::write(path, replay.to_string())?;
+    }
+    run.map_err(ChipError::from)?;
+    Ok(())
+}
+
+/// The value of option `id`: as given on the command line, else as set in
+/// the config file, else the option's default.
+fn setting<'a>(input: &'a clap::ArgMatches, id: &str, config: &'a Option<String>) -> &'a str {
+    match (input.value_source(id), config) {
+        (Some(clap::ValueSource::DefaultValue), Some(value)) => value,
+        _ => input.get_one::<String>(id).unwrap(),
+    }
+}
+
+/// The preset named by `--quirks`, or else the one `recommended` for the
+/// ROM, with the config file's quirks and then each `--quirk` applied on top.
+fn quirks(
+    input: &clap::ArgMatches,
+    config: &Config,
+    recommended: Option<&str>,
+) -> std::result::Result<Quirks, String> {
+    let preset = match (input.value_source("quirks"), &config.quirks, recommended) {
+        (Some(clap::ValueSource::DefaultValue), None, Some(preset)) => preset,
+        _ => setting(input, "quirks", &config.quirks),
+    };
+    let mut quirks = Quirks::preset(preset).ok_or_else(|| {
+        format!(
+            "unknown quirks preset {}, expected one of {}",
+            preset,
+            Quirks::PRESETS.join(", ")
+        )
+    })?;
+    let configured = config.quirk.iter().map(|(name, on)| (name.as_str(), *on));
+    let given = input
+        .get_many::<String>("quirk")
+        .into_iter()
+        .flatten()
+        .map(|quirk| match quirk.split_once('=') {
+            Some((name, "on")) => Ok((name, true)),
+            Some((name, "off")) => Ok((name, false)),
+            Some(_) => Err(format!("quirk {} must be on or off", quirk)),
+            None => Ok((quirk.as_str(), true)),
+        });
+    for quirk in configured.map(Ok).chain(given) {
+        let (name, on) = quirk?;
+        if !quirks.set(name, on) {
+            return Err(format!(
+                "unknown quirk {}, expected one of {}",
+                name,
+                Quirks::NAMES.join(", ")
+            ));
+        }
+    }
+    Ok(quirks)
+}
+
+/// The layout named by `--keys` with the config file's keys and then each
+/// `--key` bound on top.
+fn key_map(input: &clap::ArgMatches, config: &Config) -> std::result::Result<KeyMap, String> {
+    let layout = setting(input, "keys", &config.layout);
+    let mut keys = KeyMap::layout(layout).ok_or_else(|| {
+        format!(
+            "unknown keyboard layout {}, expected one of {}",
+            layout,
+            KeyMap::LAYOUTS.join(", ")
+        )
+    })?;
+    for (c, key) in &config.keys {
+        keys.bind(*c, *key);
+    }
+    for binding in input.get_many::<String>("key").into_iter().flatten() {
+        let mut chars = binding.chars();
+        let (Some(c), Some('='), Ok(key)) = (
+            chars.next(),
+            chars.next(),
+            u8::from_str_radix(chars.as_str(), 16),
+        ) else {
+            return Err(format!("--key {} is not CHAR=KEY", binding));
+        };
+        if key > 0xF {
+            return Err(format!("--key {}: the keypad keys are 0 to f", binding));
+        }
+        keys.bind(c, key);
+    }
+    Ok(keys)
+}
+
+/// The theme named by `--theme` with `--fg` and `--bg` colors on top, each
+/// from the config file if not given.
+fn palette(input: &clap::ArgMatches, config: &Config) -> std::result::Result<Palette, String> {
+    let theme = setting(input, "theme", &config.theme);
+    let mut palette = Palette::theme(theme).ok_or_else(|| {
+        format!(
+            "unknown theme {}, expected one of {}",
+            theme,
+            Palette::THEMES.join(", ")
+        )
+    })?;
+    let colors = [
+        ("fg", &config.fg, &mut palette.on),
+        ("bg", &config.bg, &mut palette.off),
+    ];
+    for (flag, configured, color) in colors {
+        if let Some(value) = input.get_one::<String>(flag).or(configured.as_ref()) {
+            *color = Rgb::parse(value)
+                .ok_or_else(|| format!("--{} {} is not a color name or #rrggbb", flag, value))?;
+        }
+    }
+    Ok(palette)
+}
+
+/// The addresses `--hexdump`, `--sprites` and `dump` name: `START` to the
+/// end of memory, or `START-END` with `END` included, both in hex.
+fn memory_range(name: &str, range: &str) -> std::result::Result<std::ops::Range<usize>, String> {
+    let error = || format!("{} {} is not START or START-END in hex", name, range);
+    let addr = |word: &str| {
+        usize::from_str_radix(word.trim_start_matches("0x"), 16)
+            .ok()
+            .filter(|addr| *addr < 0x1000)
+            .ok_or_else(error)
+    };
+    match range.split_once('-') {
+        Some((start, end)) => match (addr(start)?, addr(end)?) {
+            (start, end) if start <= end => Ok(start..end + 1),
+            _ => Err(error()),
+        },
+        None => Ok(addr(range)?..0x1000),
+    }
+}
+
+/// A time in seconds for `--turbo`, which may be fractional but not negative.
+fn seconds(text: &str) -> std::result::Result<std::time::Duration, String> {
+    match text.parse::<f64>() {
+        Ok(seconds) if seconds < 0.0 => Err(format!("{} is negative", text)),
+        Ok(seconds) => std::time::Duration::try_from_secs_f64(seconds)
+            .map_err(|_| format!("{} is not a number of seconds", text)),
+        Err(_) => Err(format!("{} is not a number of seconds", text)),
+    }
+}
+
+/// Prints the memory `--hexdump` and `--sprites` ask for.
+fn show_memory(
+    chip8: &Chip8,
+    hexdump: Option<std::ops::Range<usize>>,
+    sprites: Option<std::ops::Range<usize>>,
+) -> std::io::Result<()> {
+    let mut out = std::io::stdout().lock();
+    if let Some(range) = hexdump {
+        chippers::debugger::hex_dump(&mut out, &chip8.cpu.mem, range, None)?;
+    }
+    if let Some(range) = sprites {
+        chippers::debugger::sprite_art(&mut out, &chip8.cpu.mem, range)?;
+    }
+    Ok(())
+}
+
+/// Unknown opcodes skipped under `--on-unknown log`, by address, with the
+/// opcode and how many times it ran. Reported on stderr when dropped, after
+/// the frontend has given the terminal back.
+#[derive(Default)]
+struct UnknownLog(std::sync::Arc<std::sync::Mutex<BTreeMap<u16, (u16, u64)>>>);
+
+impl UnknownLog {
+    fn watch(&self, chip8: &mut Chip8) {
+        let seen = self.0.clone();
+        chip8.hooks.on_instruction(move |addr, raw, _| {
+            if Instruction::decode(raw).is_err() {
+                let mut seen = seen.lock().unwrap_or_else(|e| e.into_inner());
+                seen.entry(addr).or_insert((raw, 0)).1 += 1;
+            }
+        });
+    }
+}
+
+impl Drop for UnknownLog {
+    fn drop(&mut self) {
+        let seen = self.0.lock().unwrap_or_else(|e| e.into_inner());
+        for (addr, (raw, count)) in seen.iter() {
+            eprintln!(
+                "skipped unknown opcode {:04x} at {:#05x} {} times",
+                raw, addr, count
+            );
+        }
+    }
+}
+
+/// The `--profile` report, printed on stderr when dropped, after the frontend
+/// has given the terminal back.
+struct ProfileReport(Profiler);
+
+impl Drop for ProfileReport {
+    fn drop(&mut self) {
+        eprint!("{}", self.0.report());
+    }
+}
+
+/// The RPL user flags and persistent memory of the program, written back
+/// when dropped so a game's high scores are kept however the run ends.
+#[derive(Default)]
+struct Saves {
+    flags: Option<FlagsFile>,
+    memory: Option<MemoryFile>,
+}
+
+impl Drop for Saves {
+    fn drop(&mut self) {
+        if let Some(flags) = &self.flags {
+            if let Err(e) = flags.save() {
+                eprintln!("cannot save flags to {}: {}", flags.path().display(), e);
+            }
+        }
+        if let Some(memory) = &self.memory {
+            if let Err(e) = memory.save() {
+                eprintln!("cannot save memory to {}: {}", memory.path().display(), e);
+            }
+        }
+    }
+}
+
+/// Where the crash dump went, if the program faulted, reported on stderr when
+/// dropped, after the frontend has given the terminal back.
+struct CrashNote(CrashDump);
+
+impl Drop for CrashNote {
+    fn drop(&mut self) {
+        match self.0.take() {
+            Some(Ok(path)) => eprintln!("crash dump written to {}", path.display()),
+            Some(Err(e)) => eprintln!("cannot write crash dump: {}", e),
+            None => {}
+        }
+    }
+}
+
+/// The `--capture` recording, written when dropped so it covers the run
+/// however it ends.
+struct CaptureFile {
+    capture: Capture,
+    path: String,
+    scale: usize,
+    palette: Palette,
+}
+
+impl Drop for CaptureFile {
+    fn drop(&mut self) {
+        match self
+            .capture
+            .save(self.path.as_ref(), self.scale, self.palette)
+        {
+            Ok(frames) => eprintln!("{}: captured {} frames", self.path, frames),
+            Err(e) => eprint!("{}", e),
+        }
+    }
+}
+
+/// Runs `chippers test`: one test ROM, headless, with its verdict on stdout
+/// if it passes and as the error if it fails.
+fn rom_test(input: &clap::ArgMatches) -> std::result::Result<(), Box<dyn std::error::Error>> {
+    let hex = |flag: &str| -> std::result::Result<Option<u16>, String> {
+        input
+            .get_one::<String>(flag)
+            .map(|value| {
+                u16::from_str_radix(value.trim_start_matches("0x"), 16)
+                    .map_err(|_| format!("--{} {} is not a number in hex", flag, value))
+            })
+            .transpose()
+    };
+    let check = match (hex("result-addr")?, input.get_one::<String>("expect")) {
+        (Some(addr), _) => {
+            let pass = hex("pass")?.unwrap_or(1);
+            Check::Memory {
+                addr,
+                pass: u8::try_from(pass).map_err(|_| format!("--pass {:x} is not a byte", pass))?,
+            }
+        }
+        (None, Some(path)) => Check::Screen(
+            std::fs::read_to_string(path)
+                .map_err(|e| format!("cannot read screen {}: {}", path, e))?,
+        ),
+        (None, None) => Check::Halts,
+    };
+    let path = input.get_one::<String>("ROM").unwrap();
+    let rom = std::fs::read(path).map_err(|e| format!("cannot read rom {}: {}", path, e))?;
+    let limits = Limits {
+        max_instructions: *input.get_one::<u64>("instructions").unwrap(),
+        ..Limits::default()
+    };
+    let verdict = run_test(&rom, &check, limits)?;
+    if !verdict.passed() {
+        return Err(format!("{}: {}", path, verdict).into());
+    }
+    println!("{}: {}", path, verdict);
+    Ok(())
+}
+
+/// `chippers dump`: prints memory with the font and the rom loaded, the rom
+/// alone unless a range is given, in hex and ASCII.
+fn rom_dump(input: &clap::ArgMatches) -> std::result::Result<(), Box<dyn std::error::Error>> {
+    let path = input.get_one::<String>("ROM").unwrap();
+    let rom = std::fs::read(path).map_err(|e| format!("cannot read rom {}: {}", path, e))?;
+    let mut chip8 = Chip8::new();
+    chip8.load_font_set();
+    chip8.load_rom(&rom)?;
+    let range = match input.get_one::<String>("RANGE") {
+        Some(range) => memory_range("range", range)?,
+        None => 0x200..0x200 + rom.len(),
+    };
+    show_memory(&chip8, Some(range), None)?;
+    Ok(())
+}
+
+/// Prints what went wrong as a message rather than a debug dump.
+fn main() -> std::process::ExitCode {
+    match cli() {
+        Ok(()) => std::process::ExitCode::SUCCESS,
+        Err(e) => {
+            let message = e.to_string();
+            if message.starts_with("error: ") {
+                eprint!("{}", message);
+            } else {
+                eprintln!("error: {}", message);
+            }
+            std::process::ExitCode::FAILURE
+        }
+    }
+}
+
+fn cli() -> std::result::Result<(), Box<dyn std::error::Error>> {
+    let default_speed = DEFAULT_SPEED.to_string();
+    let default_frame_rate = DEFAULT_FRAME_RATE.to_string();
     let input = clap::builder::Command::new("chippers")
-        .args(&[clap::arg!(<FILE> "chip-8 rom file")])
+        .args(&[
+            clap::arg!(<FILE> "chip-8 rom file").required_unless_present("romdir"),
+            clap::arg!(--romdir <DIR> "pick the rom to run from this directory, with titles for the roms chippers knows")
+                .required(false)
+                .conflicts_with("FILE"),
+            clap::arg!(--"load-addr" <ADDR> "where to load the rom and start running it, in hex; ETI-660 programs start at 600")
+                .required(false)
+                .default_value("200"),
+            clap::arg!(--megachip "run a MegaChip rom of up to 16 MiB, drawn in color at 256x192 by the sdl backend and shrunk to 64x32 by the others"),
+            clap::arg!(--config <FILE> "read settings from this file rather than ~/.config/chippers/config.toml")
+                .required(false),
+            #[cfg(feature = "plugins")]
+            clap::arg!(--plugin <LIB> "load the display and keypad from a frontend plugin")
+                .required(false),
+            clap::arg!(--engine <ENGINE> "how to execute instructions")
+                .required(false)
+                .value_parser(clap::builder::PossibleValuesParser::new(ENGINES))
+                .default_value("interp"),
+            #[cfg(feature = "lua")]
+            clap::arg!(--script <FILE> "run this Lua script's hooks on each frame, instruction and memory access")
+                .required(false)
+                .conflicts_with("debug"),
+            clap::arg!(--backend <BACKEND> "where to show the display and read keys")
+                .required(false)
+                .value_parser(clap::builder::PossibleValuesParser::new(BACKENDS))
+                .default_value("terminal"),
+            clap::arg!(--render <MODE> "how the terminal draws pixels: a block each, two to a half block, or eight to a braille character")
+                .required(false)
+                .value_parser(RenderMode::NAMES)
+                .default_value("blocks"),
+            clap::arg!(--scale <SCALE> "terminal cells per cell, N, NxM across and down, or auto to fill the terminal")
+                .required(false)
+                .default_value("1"),
+            clap::arg!(--theme <THEME> "colors for lit and unlit pixels")
+                .required(false)
+                .value_parser(Palette::THEMES)
+                .default_value("monochrome"),
+            clap::arg!(--fg <COLOR> "color of lit pixels, by name or as #rrggbb, over the theme's")
+                .required(false),
+            clap::arg!(--bg <COLOR> "color of unlit pixels, by name or as #rrggbb, over the theme's")
+                .required(false),
+            clap::arg!(--speed <HZ> "instructions to run a second")
+                .required(false)
+                .value_parser(clap::value_parser!(u32).range(1..))
+                .default_value(&default_speed),
+            clap::arg!(--timing <MODEL> "time every instruction alike at --speed, or each as long as it took on the COSMAC VIP")
+                .required(false)
+                .value_parser(Timing::NAMES)
+                .default_value("uniform"),
+            clap::arg!(--"frame-rate" <HZ> "times to show the display a second at most, drawing everything since the last at once")
+                .required(false)
+                .value_parser(clap::value_parser!(u32).range(1..))
+                .default_value(&default_frame_rate),
+            clap::arg!(--keys <LAYOUT> "keyboard layout to lay the keypad over")
+                .required(false)
+                .value_parser(KeyMap::LAYOUTS)
+                .default_value("qwerty"),
+            clap::arg!(--key <BINDING> "press keypad key KEY, in hex, with keyboard key CHAR as CHAR=KEY; may be repeated")
+                .required(false)
+                .action(clap::ArgAction::Append),
+            clap::arg!(--quirks <PRESET> "interpreter whose behavior to follow")
+                .required(false)
+                .value_parser(chippers::quirks::Quirks::PRESETS)
+                .default_value("chippers"),
+            clap::arg!(--quirk <QUIRK> "turn a quirk on, or off with NAME=off; may be repeated")
+                .required(false)
+                .action(clap::ArgAction::Append),
+            clap::arg!(--flags <FILE> "keep the SUPER-CHIP flags the rom saves in this file rather than in ~/.local/share/chippers/flags")
+                .required(false),
+            clap::arg!(--persist <RANGE> "keep memory from FIRST to LAST, in hex as FIRST-LAST, between runs of the rom; may be repeated")
+                .required(false)
+                .action(clap::ArgAction::Append),
+            clap::arg!(--"no-romdb" "ignore the quirks and speed recommended for roms chippers knows"),
+            clap::arg!(--"on-unknown" <POLICY> "on an instruction that does not decode, stop, skip it, or skip it and list it on exit")
+                .required(false)
+                .value_parser(["halt", "skip", "log"])
+                .default_value("halt"),
+            clap::arg!(--state <FILE> "save and restore the machine here with F5 and F9, resuming from it if it exists")
+                .required(false),
+            clap::arg!(--"crash-dir" <DIR> "write the crash dump of a program that faults here")
+                .required(false)
+                .default_value("."),
+            clap::arg!(--screenshots <DIR> "save the PNGs F12 takes here rather than in the working directory")
+                .required(false),
+            clap::arg!(--trace <FILE> "write every instruction run, with the registers it changed, to this file")
+                .required(false),
+            clap::arg!(--"trace-last" <N> "with --trace, keep only the last N instructions and write them if the program faults")
+                .required(false)
+                .requires("trace")
+                .value_parser(clap::value_parser!(usize)),
+            clap::arg!(--profile "count the instructions run and the time they take, and report the hottest on exit"),
+            clap::arg!(--seed <SEED> "seed the random number generator, so CXNN draws the same numbers every run")
+                .required(false)
+                .value_parser(clap::value_parser!(u32)),
+            clap::arg!(--record <FILE> "write the keys pressed and the random seed here, to replay the run with --replay")
+                .required(false)
+                .conflicts_with_all(&["debug", "turbo"]),
+            clap::arg!(--replay <FILE> "play back a run written by --record, then carry on live")
+                .required(false)
+                .conflicts_with_all(&["record", "seed", "debug", "turbo"]),
+            clap::arg!(--capture <FILE> "record the screen as an animated GIF, or APNG if FILE ends in .png or .apng, written on exit")
+                .required(false),
+            clap::arg!(--"capture-scale" <N> "with --capture, image pixels per display pixel")
+                .required(false)
+                .requires("capture")
+                .value_parser(clap::value_parser!(u32).range(1..=32))
+                .default_value("4"),
+            clap::arg!(--debug "run under the debugger, reading commands from stdin"),
+            clap::arg!(--headless "run without a display or keyboard as fast as possible")
+                .conflicts_with_all(&["debug", "turbo", "record", "replay"]),
+            clap::arg!(--instructions <N> "with --headless, stop after this many instructions")
+                .required(false)
+                .requires("headless")
+                .conflicts_with("frames")
+                .value_parser(clap::value_parser!(u64)),
+            clap::arg!(--frames <N> "with --headless, stop after this many frames")
+                .required(false)
+                .requires("headless")
+                .value_parser(clap::value_parser!(u64))
+                .default_value("600"),
+            clap::arg!(--dump <FILE> "with --headless, write the final frame as text art here, - for stdout")
+                .required(false)
+                .requires("headless"),
+            clap::arg!(--hexdump <RANGE> "print memory from START, or START-END, in hex and ASCII and exit; with --headless, once the run is over")
+                .required(false),
+            clap::arg!(--sprites <RANGE> "draw memory from START, or START-END, as sprite rows and exit; with --headless, once the run is over")
+                .required(false),
+            clap::arg!(--turbo <SECONDS> "run without a display as fast as possible, then report throughput")
+                .required(false)
+                .allow_hyphen_values(true)
+                .value_parser(seconds),
+            #[cfg(feature = "http")]
+            clap::arg!(--http <ADDR> "run without a display, serving status and controls over HTTP")
+                .required(false),
+        ])
+        .args_conflicts_with_subcommands(true)
+        .subcommand_negates_reqs(true)
+        .subcommand(
+            clap::builder::Command::new("test")
+                .about("run a test rom headless until it halts on a jump to itself, and exit with whether it passed")
+                .args(&[
+                    clap::arg!(<ROM> "test rom file"),
+                    clap::arg!(--"result-addr" <ADDR> "pass if the byte here, in hex, holds --pass; without this or --expect the result is inconclusive")
+                        .required(false),
+                    clap::arg!(--pass <BYTE> "with --result-addr, the result code that means the rom passed, in hex")
+                        .required(false)
+                        .default_value("1"),
+                    clap::arg!(--expect <FILE> "pass if the screen matches this text art, # for lit pixels and . for unlit ones")
+                        .required(false)
+                        .conflicts_with("result-addr"),
+                    clap::arg!(--instructions <N> "fail if the rom is still running after this many instructions")
+                        .required(false)
+                        .value_parser(clap::value_parser!(u64))
+                        .default_value("1000000"),
+                ]),
+        )
+        .subcommand(
+            clap::builder::Command::new("dump")
+                .about("print a rom as loaded into memory, in hex and ASCII")
+                .args(&[
+                    clap::arg!(<ROM> "rom file"),
+                    clap::arg!([RANGE] "the memory to print, START or START-END in hex; the rom itself if left out"),
+                ]),
+        )
+        .subcommand(
+            clap::builder::Command::new("demo")
+                .about("run one of the programs built into chippers with the settings in the config file, or list them")
+                .arg(
+                    clap::arg!([NAME] "the demo to run")
+                        .value_parser(clap::builder::PossibleValuesParser::new(
+                            DEMOS.iter().map(|demo| demo.name),
+                        )),
+                ),
+        )
         .get_matches();
-    terminal::enable_raw_mode().unwrap();
+    let demo = match input.subcommand() {
+        Some(("test", input)) => return rom_test(input),
+        Some(("dump", input)) => return rom_dump(input),
+        Some(("demo", input)) => match input.get_one::<String>("NAME") {
+            Some(name) => demos::find(name),
+            None => {
+                for demo in &DEMOS {
+                    println!("{:12}{}", demo.name, demo.about);
+                }
+                return Ok(());
+            }
+        },
+        _ => None,
+    };
+    let config = match input.get_one::<String>("config") {
+        Some(path) => Config::load(path.as_ref())?,
+        None => Config::load_default()?,
+    };
     let mut chip8 = Chip8::new();
     chip8.load_font_set();
-    let file = std::fs::read(&input.get_one::<String>("FILE").unwrap()).unwrap();
+    let timing = setting(&input, "timing", &config.timing);
+    chip8.set_timing(Timing::from_name(timing).ok_or_else(|| {
+        format!(
+            "unknown timing {}, expected one of {}",
+            timing,
+            Timing::NAMES.join(", ")
+        )
+    })?);
+    chip8.set_frame_rate(*input.get_one::<u32>("frame-rate").unwrap());
+    let unknown = UnknownLog::default();
+    match input.get_one::<String>("on-unknown").unwrap().as_str() {
+        "skip" => chip8.cpu.on_unknown = OnUnknown::Skip,
+        "log" => {
+            chip8.cpu.on_unknown = OnUnknown::Skip;
+            unknown.watch(&mut chip8);
+        }
+        _ => chip8.cpu.on_unknown = OnUnknown::Halt,
+    }
+    let palette = palette(&input, &config)?;
+    chip8.screenshots.palette = palette;
+    if let Some(dir) = input.get_one::<String>("screenshots") {
+        chip8.screenshots.dir = dir.into();
+    }
+    let keys = key_map(&input, &config)?;
+    let picked = match input.get_one::<String>("romdir") {
+        Some(dir) => {
+            let roms = browser::scan(dir.as_ref())
+                .map_err(|e| format!("cannot read rom directory {}: {}", dir, e))?;
+            match Picker::new(roms).run()? {
+                Some(path) => Some(path.display().to_string()),
+                None => return Ok(()),
+            }
+        }
+        None => None,
+    };
+    let path = match (demo, &picked) {
+        (Some(demo), _) => demo.name,
+        (None, Some(path)) => path,
+        (None, None) => input.get_one::<String>("FILE").unwrap(),
+    };
+    let engine = setting(&input, "engine", &config.engine);
+    if !ENGINES.contains(&engine) {
+        return Err(format!(
+            "unknown engine {}, expected one of {}",
+            engine,
+            ENGINES.join(", ")
+        )
+        .into());
+    }
+    let backend = setting(&input, "backend", &config.backend);
+    if !BACKENDS.contains(&backend) {
+        return Err(format!(
+            "unknown backend {}, expected one of {}",
+            backend,
+            BACKENDS.join(", ")
+        )
+        .into());
+    }
+    let load_addr = input.get_one::<String>("load-addr").unwrap();
+    chip8.set_load_addr(
+        u16::from_str_radix(load_addr.trim_start_matches("0x"), 16)
+            .ok()
+            .filter(|addr| *addr < 0x1000)
+            .ok_or_else(|| format!("--load-addr {} is not an address in hex", load_addr))?,
+    );
+    let file = match demo {
+        Some(demo) => demo.rom.to_vec(),
+        None => std::fs::read(path).map_err(|e| format!("cannot read rom {}: {}", path, e))?,
+    };
     let file = file.as_slice();
-    for (i, byte) in file.iter().enumerate() {
-        chip8.cpu.mem[i + 0x200] = *byte;
+    let rom = if input.contains_id("megachip") {
+        chip8.load_megachip(file)?
+    } else {
+        chip8.load_rom(file)?
+    };
+    eprintln!("{}: {}", path, rom);
+    // a ROM the database knows runs as it recommends, unless told otherwise
+    let known = romdb::lookup(file).filter(|_| !input.contains_id("no-romdb"));
+    if let Some(known) = known {
+        eprintln!("{}: {}", path, known);
     }
-    chip8.run()?;
-    Ok(())
+    let recommended = known.and_then(|known| known.quirks);
+    chip8.cpu.quirks = quirks(&input, &config, recommended)?;
+    let recommended = known.and_then(|known| known.speed);
+    chip8.set_speed(
+        match (input.value_source("speed"), config.speed, recommended) {
+            (Some(clap::ValueSource::DefaultValue), Some(speed), _) => speed,
+            (Some(clap::ValueSource::DefaultValue), None, Some(speed)) => speed,
+            _ => *input.get_one::<u32>("speed").unwrap(),
+        },
+    );
+    let flags_path = match input.get_one::<String>("flags") {
+        Some(path) => Some(std::path::PathBuf::from(path)),
+        None => flags::default_dir().map(|dir| FlagsFile::path_for(&dir, file)),
+    };
+    let mut saves = Saves::default();
+    if let Some(path) = flags_path {
+        saves.flags = Some(
+            FlagsFile::attach(path.clone(), &mut chip8)
+                .map_err(|e| format!("cannot read flags {}: {}", path.display(), e))?,
+        );
+    }
+    let configured = config.rom.get(&romdb::Sha1::of(file).to_string());
+    let regions = configured
+        .into_iter()
+        .flat_map(|rom| &rom.persist)
+        .chain(input.get_many::<String>("persist").into_iter().flatten())
+        .map(|range| {
+            Region::parse(range)
+                .ok_or_else(|| format!("cannot persist {}: not a range of addresses in hex", range))
+        })
+        .collect::<std::result::Result<Vec<_>, _>>()?;
+    if let (false, Some(dir)) = (regions.is_empty(), persist::default_dir()) {
+        let path = MemoryFile::path_for(&dir, file);
+        saves.memory = Some(
+            MemoryFile::attach(path.clone(), regions, &mut chip8)
+                .map_err(|e| format!("cannot read memory {}: {}", path.display(), e))?,
+        );
+    }
+    if let Some(state) = input.get_one::<String>("state") {
+        chip8.state_slot = chippers::state::StateSlot::File(state.into());
+        chip8.quick_load()?;
+    }
+    if let Some(path) = input.get_one::<String>("trace") {
+        let out = std::io::BufWriter::new(std::fs::File::create(path)?);
+        let tracer = match input.get_one::<usize>("trace-last") {
+            Some(lines) => Tracer::ring(out, *lines),
+            None => Tracer::new(out),
+        };
+        tracer.attach(&mut chip8);
+    }
+    let profiler = input.contains_id("profile").then(|| {
+        let profiler = Profiler::new();
+        profiler.attach(&mut chip8);
+        profiler
+    });
+    let seed = input.get_one::<u32>("seed").copied();
+    if let Some(seed) = seed {
+        chip8.set_seed(seed);
+    }
+    let record = input.get_one::<String>("record");
+    if record.is_some() {
+        chip8.record(seed.unwrap_or_else(Rng::fresh_seed));
+    }
+    if let Some(path) = input.get_one::<String>("replay") {
+        let text = std::fs::read_to_string(path)
+            .map_err(|e| format!("cannot read replay {}: {}", path, e))?;
+        chip8.play(Replay::parse(&text)?);
+    }
+    let _capture = match input.get_one::<String>("capture") {
+        Some(path) => {
+            if Format::from_path(path.as_ref()).is_none() {
+                return Err(CaptureError::Format(path.into()).into());
+            }
+            let capture = Capture::new();
+            capture.attach(&mut chip8);
+            Some(CaptureFile {
+                capture,
+                path: path.clone(),
+                scale: *input.get_one::<u32>("capture-scale").unwrap() as usize,
+                palette,
+            })
+        }
+        None => None,
+    };
+    let range = |flag| {
+        input
+            .get_one::<String>(flag)
+            .map(|range| memory_range(&format!("--{}", flag), range))
+            .transpose()
+    };
+    let (hexdump, sprites) = (range("hexdump")?, range("sprites")?);
+    if !input.contains_id("headless") && (hexdump.is_some() || sprites.is_some()) {
+        show_memory(&chip8, hexdump, sprites)?;
+        return Ok(());
+    }
+    if input.contains_id("debug") {
+        let mut debugger = chippers::debugger::Debugger::default();
+        if let Some(profiler) = profiler {
+            debugger = debugger.with_profiler(profiler);
+        }
+        debugger.session(&mut chip8, std::io::stdin().lock(), std::io::stdout())?;
+        return Ok(());
+    }
+    let _report = profiler.map(ProfileReport);
+    let crash = CrashDump::new(input.get_one::<String>("crash-dir").unwrap());
+    crash.attach(&mut chip8);
+    let _crash = CrashNote(crash);
+    let mut engine = Selected::new(engine, input.try_get_one::<String>("script").ok().flatten())?;
+    if input.contains_id("headless") {
+        let limit = match input.get_one::<u64>("instructions") {
+            Some(n) => Limit::Instructions(*n),
+            None => Limit::Frames(*input.get_one::<u64>("frames").unwrap()),
+        };
+        let mut backend = HeadlessBackend::new();
+        let run = backend.run(&mut chip8, &mut engine, 12, limit);
+        engine.finish();
+        let art = text_art(&backend.frame());
+        match input.get_one::<String>("dump").map(String::as_str) {
+            Some("-") => print!("{}", art),
+            Some(file) => std::fs::write(file, art)?,
+            None => {}
+        }
+        show_memory(&chip8, hexdump, sprites)?;
+        run?;
+        return Ok(());
+    }
+    if let Some(seconds) = input.get_one::<std::time::Duration>("turbo") {
+        let limit = TurboLimit::Duration(*seconds);
+        let throughput = chip8.run_turbo(&mut engine, 12, limit);
+        engine.finish();
+        println!("{}", throughput);
+        return Ok(());
+    }
+    #[cfg(feature = "http")]
+    if let Some(addr) = input.get_one::<String>("http") {
+        let mut control = chippers::http::HttpControl::bind(addr, file)?;
+        eprintln!("serving on http://{}", control.addr());
+        control.serve(&mut chip8)?;
+        return Ok(());
+    }
+    #[cfg(feature = "plugins")]
+    if let Some(lib) = input.get_one::<String>("plugin") {
+        let mut frontend = unsafe { chippers::plugin_host::PluginFrontend::load(lib.as_ref())? };
+        return run(&mut chip8, &mut frontend, &mut engine, record);
+    }
+    #[cfg(feature = "backend-sdl")]
+    if backend == "sdl" {
+        let title = format!("chippers - {}", path);
+        let mut frontend = chippers::sdl::SdlFrontend::new(&title, chippers::sdl::DEFAULT_SCALE)?;
+        frontend.window.set_palette(palette)?;
+        frontend.keys.keys = keys;
+        return run(&mut chip8, &mut frontend, &mut engine, record);
+    }
+    let mode = setting(&input, "render", &config.render);
+    let mode = RenderMode::from_name(mode).ok_or_else(|| {
+        format!(
+            "unknown render mode {}, expected one of {}",
+            mode,
+            RenderMode::NAMES.join(", ")
+        )
+    })?;
+    let scale = setting(&input, "scale", &config.scale);
+    let scale =
+        Scale::parse(scale).ok_or_else(|| format!("--scale {} is not N, NxM or auto", scale))?;
+    let mut frontend =
+        TerminalFrontend::with_terminal(Terminal::new().with_mode(mode).with_scale(scale));
+    frontend
+        .terminal
+        .set_title(&format!("chippers - {}", path))?;
+    frontend.terminal.set_palette(palette)?;
+    frontend.keyboard.keys = keys;
+    run(&mut chip8, &mut frontend, &mut engine, record)
 }
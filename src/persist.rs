@@ -0,0 +1,192 @@
+//! Ranges of memory kept on disk between runs, for ROMs that keep a score
+//! table in memory rather than in the RPL flags.
+//!
+//! Which ranges to keep is set per ROM, as they are wherever each program
+//! happens to put its scores. The ranges are written back to the machine
+//! once the ROM is loaded, so they survive a program that keeps its table in
+//! the ROM image itself, but not one that clears it when it starts.
+
+use chippers_core::chip::Chip8;
+use chippers_core::romdb::Sha1;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+/// Where `chippers` keeps what programs save: `$XDG_DATA_HOME/chippers`, or
+/// `~/.local/share/chippers`.
+pub fn data_dir() -> Option<PathBuf> {
+    let dir = match std::env::var_os("XDG_DATA_HOME").filter(|dir| !dir.is_empty()) {
+        Some(dir) => PathBuf::from(dir),
+        None => PathBuf::from(std::env::var_os("HOME")?)
+            .join(".local")
+            .join("share"),
+    };
+    Some(dir.join("chippers"))
+}
+
+/// Where memory files go, in [`data_dir`].
+pub fn default_dir() -> Option<PathBuf> {
+    data_dir().map(|dir| dir.join("memory"))
+}
+
+/// `len` bytes of memory from `start`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Region {
+    pub start: u16,
+    pub len: u16,
+}
+
+impl Region {
+    /// Reads a range of addresses in hex, first and last, such as `3F0-3FF`.
+    pub fn parse(text: &str) -> Option<Self> {
+        let (first, last) = text.split_once('-')?;
+        let addr = |text: &str| u16::from_str_radix(text.trim().trim_start_matches("0x"), 16).ok();
+        let (first, last) = (addr(first)?, addr(last)?);
+        (first <= last && last < 0x1000).then(|| Region {
+            start: first,
+            len: last - first + 1,
+        })
+    }
+
+    fn bytes(self) -> std::ops::Range<usize> {
+        usize::from(self.start)..usize::from(self.start + self.len)
+    }
+}
+
+impl std::fmt::Display for Region {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{:03X}-{:03X}", self.start, self.start + self.len - 1)
+    }
+}
+
+/// A machine's persistent ranges, loaded from a file and written back by
+/// [`save`](MemoryFile::save) once the program has written to any of them.
+#[derive(Debug)]
+pub struct MemoryFile {
+    path: PathBuf,
+    // the ranges, one after another, as the program last left them
+    saved: Arc<Mutex<Option<Vec<u8>>>>,
+}
+
+impl MemoryFile {
+    /// The file in `dir` for `rom`.
+    pub fn path_for(dir: &Path, rom: &[u8]) -> PathBuf {
+        dir.join(format!("{}.mem", Sha1::of(rom)))
+    }
+
+    /// Gives the machine the ranges saved at `path`, if there are any yet,
+    /// and watches for the program writing to them.
+    pub fn attach(path: PathBuf, regions: Vec<Region>, chip8: &mut Chip8) -> std::io::Result<Self> {
+        let size: usize = regions.iter().map(|region| usize::from(region.len)).sum();
+        match std::fs::read(&path) {
+            Ok(bytes) if bytes.len() != size => {
+                return Err(std::io::Error::new(
+                    std::io::ErrorKind::InvalidData,
+                    format!(
+                        "it holds {} bytes, not the {} of the ranges",
+                        bytes.len(),
+                        size
+                    ),
+                ))
+            }
+            Ok(bytes) => {
+                let mut bytes = bytes.as_slice();
+                for region in &regions {
+                    let (head, rest) = bytes.split_at(usize::from(region.len));
+                    chip8.cpu.mem[region.bytes()].copy_from_slice(head);
+                    bytes = rest;
+                }
+            }
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+            Err(e) => return Err(e),
+        }
+        let saved = Arc::new(Mutex::new(None));
+        let seen = saved.clone();
+        chip8.hooks.on_instruction(move |_, _, cpu| {
+            let Some(access) = cpu.last_access() else {
+                return;
+            };
+            let touched = |region: &Region| access.overlaps(region.start, region.len);
+            if access.write && regions.iter().any(touched) {
+                let bytes = regions.iter().flat_map(|region| &cpu.mem[region.bytes()]);
+                *seen.lock().unwrap_or_else(|e| e.into_inner()) = Some(bytes.copied().collect());
+            }
+        });
+        Ok(MemoryFile { path, saved })
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Writes the ranges if the program has written to them, returning
+    /// whether it had.
+    pub fn save(&self) -> std::io::Result<bool> {
+        let saved = self.saved.lock().unwrap_or_else(|e| e.into_inner());
+        let Some(bytes) = saved.as_ref() else {
+            return Ok(false);
+        };
+        if let Some(dir) = self.path.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        std::fs::write(&self.path, bytes)?;
+        Ok(true)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_parse_region() {
+        let region = Region::parse("3F0-3FF").unwrap();
+        assert_eq!(
+            region,
+            Region {
+                start: 0x3F0,
+                len: 16
+            }
+        );
+        assert_eq!(region.to_string(), "3F0-3FF");
+        assert_eq!(Region::parse("0x300-0x300").unwrap().len, 1);
+        for bad in ["3F0", "3FF-3F0", "F00-1000", "x-y"] {
+            assert_eq!(Region::parse(bad), None, "{}", bad);
+        }
+    }
+
+    #[test]
+    fn test_memory_outlives_the_run() {
+        // A300: I = 300, F065: V0 = [300], 7001: V0 += 1, F055: [300] = V0,
+        // 1208: halt
+        let rom = [0xA3, 0x00, 0xF0, 0x65, 0x70, 0x01, 0xF0, 0x55, 0x12, 0x08];
+        let dir = std::env::temp_dir().join(format!("chippers-memory-{}", std::process::id()));
+        let path = MemoryFile::path_for(&dir, &rom);
+        let regions = vec![
+            Region::parse("300-301").unwrap(),
+            Region::parse("400-400").unwrap(),
+        ];
+        let run = || {
+            let mut chip8 = Chip8::new();
+            chip8.load_rom(&rom).unwrap();
+            let memory = MemoryFile::attach(path.clone(), regions.clone(), &mut chip8).unwrap();
+            for _ in 0..5 {
+                chip8.step();
+            }
+            assert!(memory.save().unwrap());
+            chip8.cpu.mem[0x300]
+        };
+        assert_eq!(run(), 1);
+        assert_eq!(run(), 2);
+        assert_eq!(std::fs::read(&path).unwrap(), [2, 0, 0]);
+
+        // a file that does not fit the ranges is not loaded
+        let mut chip8 = Chip8::new();
+        let error =
+            MemoryFile::attach(path.clone(), regions[..1].to_vec(), &mut chip8).unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "it holds 3 bytes, not the 2 of the ranges"
+        );
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
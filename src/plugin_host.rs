@@ -0,0 +1,314 @@
+//! Loads display/input frontends from shared libraries, see [`chippers_core::plugin`].
+
+use chippers_core::backend::{
+    Capabilities, DisplayBackend, Frontend, InputBackend, Resolution, Silent,
+};
+use chippers_core::display::Display;
+use chippers_core::plugin::*;
+use libloading::Library;
+use std::ffi::{c_void, CStr};
+use std::rc::Rc;
+
+#[derive(Clone, Debug)]
+pub enum PluginError {
+    Load(String),
+    AbiMismatch { expected: u32, found: u32 },
+    Call { function: &'static str, code: i32 },
+}
+
+impl std::fmt::Display for PluginError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            PluginError::Load(s) => writeln!(f, "error: could not load plugin: {}", s)?,
+            PluginError::AbiMismatch { expected, found } => writeln!(
+                f,
+                "error: plugin uses ABI version {}, expected {}",
+                found, expected
+            )?,
+            PluginError::Call { function, code } => {
+                writeln!(f, "error: plugin {} failed with code {}", function, code)?
+            }
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for PluginError {}
+
+struct Instance {
+    vtable: *const PluginVTable,
+    state: *mut c_void,
+    // must outlive `vtable` and `state`, so it is dropped last
+    _lib: Option<Library>,
+}
+
+impl Drop for Instance {
+    fn drop(&mut self) {
+        unsafe { ((*self.vtable).destroy)(self.state) }
+    }
+}
+
+/// The display half of a frontend implemented by a plugin.
+///
+/// Neither `Send` nor `Sync`: the plugin ABI makes no promises about which
+/// thread its state may be used from, so keep it on the thread that loaded it.
+pub struct Plugin {
+    instance: Rc<Instance>,
+}
+
+/// The keypad half of a plugin frontend, sharing the plugin's state.
+pub struct PluginKeypad {
+    instance: Rc<Instance>,
+}
+
+impl std::fmt::Debug for Plugin {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        f.debug_struct("Plugin")
+            .field("name", &self.name())
+            .finish()
+    }
+}
+
+impl std::fmt::Debug for PluginKeypad {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        f.debug_struct("PluginKeypad").finish()
+    }
+}
+
+impl Plugin {
+    /// Loads the plugin library at `path` and creates its frontend state.
+    ///
+    /// # Safety
+    ///
+    /// Loading a library runs its initialisers, and the library must export a
+    /// correct [`PluginEntry`]; there is no way to check either from here.
+    pub unsafe fn load(path: &std::path::Path) -> Result<Self, PluginError> {
+        let lib = Library::new(path).map_err(|e| PluginError::Load(e.to_string()))?;
+        let entry = lib
+            .get::<PluginEntry>(ENTRY_POINT)
+            .map_err(|e| PluginError::Load(e.to_string()))?;
+        let vtable = entry();
+        Self::from_vtable(vtable, Some(lib))
+    }
+
+    /// Wraps an already resolved vtable, e.g. one linked into the binary.
+    ///
+    /// # Safety
+    ///
+    /// `vtable` must point to a valid `PluginVTable` that lives as long as `lib`
+    /// (or forever, if `lib` is `None`).
+    pub unsafe fn from_vtable(
+        vtable: *const PluginVTable,
+        lib: Option<Library>,
+    ) -> Result<Self, PluginError> {
+        let Some(table) = vtable.as_ref() else {
+            return Err(PluginError::Load("entry point returned NULL".to_string()));
+        };
+        if table.abi_version != ABI_VERSION {
+            return Err(PluginError::AbiMismatch {
+                expected: ABI_VERSION,
+                found: table.abi_version,
+            });
+        }
+        let state = (table.create)();
+        let instance = Rc::new(Instance {
+            vtable,
+            state,
+            _lib: lib,
+        });
+        Ok(Plugin { instance })
+    }
+
+    /// Returns the keypad reading from this same plugin instance.
+    pub fn keypad(&self) -> PluginKeypad {
+        PluginKeypad {
+            instance: self.instance.clone(),
+        }
+    }
+
+    pub fn name(&self) -> String {
+        let table = self.instance.table();
+        if table.name.is_null() {
+            return String::new();
+        }
+        unsafe { CStr::from_ptr(table.name) }
+            .to_string_lossy()
+            .into_owned()
+    }
+}
+
+impl Instance {
+    fn table(&self) -> &PluginVTable {
+        unsafe { &*self.vtable }
+    }
+
+    fn check(function: &'static str, code: i32) -> Result<(), PluginError> {
+        if code == PLUGIN_OK {
+            Ok(())
+        } else {
+            Err(PluginError::Call { function, code })
+        }
+    }
+}
+
+impl DisplayBackend for Plugin {
+    type Error = PluginError;
+    const CAPABILITIES: Capabilities = Capabilities {
+        max_resolution: Resolution::LORES,
+        color: false,
+        sound: true,
+        title: false,
+    };
+
+    fn clear_screen(&mut self) -> Result<(), Self::Error> {
+        let instance = &self.instance;
+        let code = unsafe { (instance.table().clear_screen)(instance.state) };
+        Instance::check("clear_screen", code)
+    }
+
+    fn draw_screen(&mut self, disp: &Display) -> Result<(), Self::Error> {
+        let mut pixels = [0u8; Display::WIDTH * Display::HEIGHT];
+        for (x, y) in disp.iter_set_pixels() {
+            pixels[y * Display::WIDTH + x] = 1;
+        }
+        let instance = &self.instance;
+        let code =
+            unsafe { (instance.table().draw_screen)(instance.state, pixels.as_ptr(), 64, 32) };
+        Instance::check("draw_screen", code)
+    }
+
+    fn beep(&mut self, on: bool) -> Result<(), Self::Error> {
+        let instance = &self.instance;
+        let code = unsafe { (instance.table().beep)(instance.state, on) };
+        Instance::check("beep", code)
+    }
+}
+
+impl InputBackend for PluginKeypad {
+    type Error = PluginError;
+
+    fn poll_key(&mut self) -> Result<Option<u8>, Self::Error> {
+        let instance = &self.instance;
+        match unsafe { (instance.table().poll_key)(instance.state) } {
+            NO_KEY => Ok(None),
+            key @ 0..=0xF => Ok(Some(key as u8)),
+            code => Err(PluginError::Call {
+                function: "poll_key",
+                code,
+            }),
+        }
+    }
+}
+
+/// A plugin's display and keypad bundled as a [`Frontend`].
+#[derive(Debug)]
+pub struct PluginFrontend {
+    display: Plugin,
+    keypad: PluginKeypad,
+    audio: Silent<PluginError>,
+}
+
+impl PluginFrontend {
+    /// See [`Plugin::load`] for the safety requirements.
+    ///
+    /// # Safety
+    ///
+    /// The library at `path` must be a valid chippers plugin.
+    pub unsafe fn load(path: &std::path::Path) -> Result<Self, PluginError> {
+        Ok(Self::from(Plugin::load(path)?))
+    }
+}
+
+impl From<Plugin> for PluginFrontend {
+    fn from(display: Plugin) -> Self {
+        let keypad = display.keypad();
+        PluginFrontend {
+            display,
+            keypad,
+            audio: Silent::new(),
+        }
+    }
+}
+
+impl Frontend for PluginFrontend {
+    type Error = PluginError;
+    type Display = Plugin;
+    type Input = PluginKeypad;
+    type Audio = Silent<PluginError>;
+
+    fn parts(&mut self) -> (&mut Plugin, &mut PluginKeypad, &mut Self::Audio) {
+        (&mut self.display, &mut self.keypad, &mut self.audio)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    struct State {
+        lit: usize,
+        keys: Vec<i32>,
+    }
+
+    unsafe extern "C" fn create() -> *mut c_void {
+        let state = State {
+            lit: 0,
+            keys: vec![NO_KEY, 0xA],
+        };
+        Box::into_raw(Box::new(state)) as *mut c_void
+    }
+
+    unsafe extern "C" fn destroy(state: *mut c_void) {
+        drop(Box::from_raw(state as *mut State));
+    }
+
+    unsafe extern "C" fn clear_screen(_state: *mut c_void) -> i32 {
+        PLUGIN_OK
+    }
+
+    unsafe extern "C" fn draw_screen(
+        state: *mut c_void,
+        pixels: *const u8,
+        width: u16,
+        height: u16,
+    ) -> i32 {
+        let pixels = std::slice::from_raw_parts(pixels, width as usize * height as usize);
+        (*(state as *mut State)).lit = pixels.iter().filter(|p| **p == 1).count();
+        PLUGIN_OK
+    }
+
+    unsafe extern "C" fn poll_key(state: *mut c_void) -> i32 {
+        (*(state as *mut State)).keys.pop().unwrap_or(-2)
+    }
+
+    unsafe extern "C" fn beep(_state: *mut c_void, _on: bool) -> i32 {
+        7
+    }
+
+    static VTABLE: PluginVTable = PluginVTable {
+        abi_version: ABI_VERSION,
+        name: c"test".as_ptr(),
+        create,
+        destroy,
+        clear_screen,
+        draw_screen,
+        poll_key,
+        beep,
+    };
+
+    #[test]
+    fn test_plugin_frontend() {
+        let mut plugin = unsafe { Plugin::from_vtable(&VTABLE, None) }.unwrap();
+        let mut keypad = plugin.keypad();
+        assert_eq!(plugin.name(), "test");
+        let mut disp = Display::new();
+        disp.set_pixel(1, 2, true);
+        disp.set_pixel(63, 31, true);
+        plugin.draw_screen(&disp).unwrap();
+        assert_eq!(unsafe { (*(plugin.instance.state as *mut State)).lit }, 2);
+        assert_eq!(keypad.poll_key().unwrap(), Some(0xA));
+        assert_eq!(keypad.poll_key().unwrap(), None);
+        assert!(keypad.poll_key().is_err());
+        assert!(plugin.beep(true).is_err());
+    }
+}
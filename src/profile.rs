@@ -0,0 +1,251 @@
+//! Execution profiles, for finding a ROM's hot loops and the instructions the
+//! emulator spends its time on.
+//!
+//! A [`Profiler`] hooks into a machine and counts every instruction it runs:
+//! by opcode, with the time spent executing it, and by address. Its
+//! [`report`](Profiler::report) lists the opcodes by time, the hottest
+//! addresses and a heat map of the program.
+//!
+//! ```text
+//! 24000 instructions
+//!
+//! opcode                          count       %       time   per op
+//! Draw                             4000   16.7%    1.916ms    479ns
+//! SkipEqual                        8000   33.3%    304.1µs     38ns
+//! ...
+//!
+//! hottest addresses
+//! 0x206  3000  SE V0, #00          8000   33.3%
+//! ...
+//!
+//! heat map, a column per 8 bytes
+//! 0x200  @%:.
+//! ```
+//!
+//! The time covers the instruction alone, not the frontend or the sleeps
+//! between frames, though hooks registered before the profiler are counted.
+
+use chippers_core::chip::Chip8;
+use chippers_core::opcode::{Disassembly, Instruction, Opcode};
+use std::collections::{BTreeMap, HashMap};
+use std::fmt::Write as _;
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::time::{Duration, Instant};
+
+/// How many of the hottest addresses the report lists.
+const HOTTEST: usize = 10;
+
+/// Heat map shades, from run once to the hottest column.
+const SHADES: &[u8] = b".:-=+*#%@";
+
+/// Counts the instructions a machine runs and the time they take.
+#[derive(Clone, Default)]
+pub struct Profiler(Arc<Mutex<Profile>>);
+
+#[derive(Default)]
+struct Profile {
+    instructions: u64,
+    // runs and time spent, by opcode
+    opcodes: HashMap<Opcode, (u64, Duration)>,
+    // instruction word and runs, by address
+    addresses: BTreeMap<u16, (u16, u64)>,
+    // when the instruction being run was fetched
+    fetched: Option<Instant>,
+}
+
+impl std::fmt::Debug for Profiler {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        f.debug_struct("Profiler")
+            .field("instructions", &self.instructions())
+            .finish_non_exhaustive()
+    }
+}
+
+impl Profiler {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Starts counting the instructions `chip8` runs from the next one on.
+    pub fn attach(&self, chip8: &mut Chip8) {
+        let profile = self.0.clone();
+        chip8.hooks.on_fetch(move |_| {
+            let mut profile = profile.lock().unwrap_or_else(|e| e.into_inner());
+            profile.fetched = Some(Instant::now());
+        });
+        let profile = self.0.clone();
+        chip8.hooks.on_instruction(move |addr, raw, _| {
+            let mut profile = profile.lock().unwrap_or_else(|e| e.into_inner());
+            let took = profile
+                .fetched
+                .take()
+                .map_or(Duration::ZERO, |fetched| fetched.elapsed());
+            profile.instructions += 1;
+            // words an extension ran, or that were skipped, have no opcode
+            if let Ok(inst) = Instruction::decode(raw) {
+                let opcode = profile.opcodes.entry(inst.opcode()).or_default();
+                opcode.0 += 1;
+                opcode.1 += took;
+            }
+            let address = profile.addresses.entry(addr).or_insert((raw, 0));
+            // self-modifying code: report the word last run there
+            address.0 = raw;
+            address.1 += 1;
+        });
+    }
+
+    /// Instructions counted so far.
+    pub fn instructions(&self) -> u64 {
+        self.lock().instructions
+    }
+
+    /// How many times instructions with `opcode` ran.
+    pub fn runs(&self, opcode: Opcode) -> u64 {
+        self.lock()
+            .opcodes
+            .get(&opcode)
+            .map_or(0, |(runs, _)| *runs)
+    }
+
+    /// How many times the instruction at `addr` ran.
+    pub fn hits(&self, addr: u16) -> u64 {
+        self.lock()
+            .addresses
+            .get(&addr)
+            .map_or(0, |(_, runs)| *runs)
+    }
+
+    /// Forgets everything counted so far.
+    pub fn reset(&self) {
+        let mut profile = self.lock();
+        let fetched = profile.fetched;
+        *profile = Profile {
+            fetched,
+            ..Profile::default()
+        };
+    }
+
+    /// The opcodes by time spent, the hottest addresses and a heat map.
+    pub fn report(&self) -> String {
+        let profile = self.lock();
+        let total = profile.instructions;
+        let mut out = format!("{} instructions\n", total);
+        if total == 0 {
+            return out;
+        }
+        let share = |runs: u64| runs as f64 * 100. / total as f64;
+
+        let mut opcodes: Vec<_> = profile.opcodes.iter().collect();
+        opcodes.sort_by_key(|(opcode, (runs, time))| {
+            (
+                std::cmp::Reverse(*time),
+                std::cmp::Reverse(*runs),
+                format!("{:?}", opcode),
+            )
+        });
+        let _ = writeln!(
+            out,
+            "\n{:<24} {:>12} {:>7} {:>10} {:>8}",
+            "opcode", "count", "%", "time", "per op"
+        );
+        for (opcode, (runs, time)) in opcodes {
+            let _ = writeln!(
+                out,
+                "{:<24} {:>12} {:>6.1}% {:>10} {:>8}",
+                format!("{:?}", opcode),
+                runs,
+                share(*runs),
+                format!("{:.1?}", time),
+                format!(
+                    "{:?}",
+                    Duration::from_nanos((time.as_nanos() / u128::from(*runs)) as u64)
+                ),
+            );
+        }
+
+        let mut hottest: Vec<_> = profile.addresses.iter().collect();
+        hottest.sort_by_key(|(addr, (_, runs))| (std::cmp::Reverse(*runs), **addr));
+        out.push_str("\nhottest addresses\n");
+        for (addr, (raw, runs)) in hottest.into_iter().take(HOTTEST) {
+            let _ = writeln!(
+                out,
+                "{:#05x}  {:04x}  {:<16} {:>8} {:>6.1}%",
+                addr,
+                raw,
+                Disassembly(*raw).to_string(),
+                runs,
+                share(*runs)
+            );
+        }
+
+        out.push_str("\nheat map, a column per 8 bytes\n");
+        out.push_str(&heat_map(&profile.addresses));
+        out
+    }
+
+    fn lock(&self) -> MutexGuard<'_, Profile> {
+        self.0.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+/// A line per 256 bytes that ran anything, a column per 8 bytes, shaded by
+/// how often the instructions in them ran next to the hottest column.
+fn heat_map(addresses: &BTreeMap<u16, (u16, u64)>) -> String {
+    let mut columns = BTreeMap::<u16, u64>::new();
+    for (addr, (_, runs)) in addresses {
+        *columns.entry(addr / 8).or_default() += runs;
+    }
+    let hottest = columns.values().copied().max().unwrap_or(1);
+    let mut out = String::new();
+    let mut line = None;
+    for (column, runs) in columns {
+        let start = column / 32 * 0x100;
+        if line != Some(start) {
+            if line.is_some() {
+                out.truncate(out.trim_end().len());
+                out.push('\n');
+            }
+            let _ = write!(out, "{:#05x}  {}", start, " ".repeat(32));
+            line = Some(start);
+        }
+        let shade = (runs * (SHADES.len() as u64 - 1) / hottest) as usize;
+        let at = out.len() - 32 + usize::from(column % 32);
+        out.replace_range(at..at + 1, &char::from(SHADES[shade]).to_string());
+    }
+    out.truncate(out.trim_end().len());
+    out.push('\n');
+    out
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    // 6003: V0 = 3, 70FF: V0 -= 1, 3000: skip if V0 == 0, 1202: loop, 1208: stay
+    const ROM: [u8; 10] = [0x60, 0x03, 0x70, 0xFF, 0x30, 0x00, 0x12, 0x02, 0x12, 0x08];
+
+    #[test]
+    fn test_counts_by_opcode_and_address() {
+        let profiler = Profiler::new();
+        let mut chip8 = Chip8::new();
+        chip8.load_rom(&ROM).unwrap();
+        profiler.attach(&mut chip8);
+        for _ in 0..12 {
+            chip8.step();
+        }
+        assert_eq!(profiler.instructions(), 12);
+        assert_eq!(profiler.runs(Opcode::AddVX), 3);
+        assert_eq!(profiler.runs(Opcode::Jump), 2 + 3);
+        assert_eq!(profiler.hits(0x202), 3);
+        assert_eq!(profiler.hits(0x208), 3);
+        assert_eq!(profiler.hits(0x20a), 0);
+
+        let report = profiler.report();
+        assert!(report.starts_with("12 instructions\n"));
+        assert!(report.contains("\nhottest addresses\n0x202  70ff  ADD V0, #FF"));
+        assert!(report.ends_with("heat map, a column per 8 bytes\n0x200  @-\n"));
+
+        profiler.reset();
+        assert_eq!(profiler.report(), "0 instructions\n");
+    }
+}
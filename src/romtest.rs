@@ -0,0 +1,196 @@
+//! Test ROMs run to a verdict, for `chippers test` and scripts that check an
+//! interpreter's correctness.
+//!
+//! Test ROMs report in one of two ways, both a [`Check`]: some write a
+//! result code to a known address, others draw a screen of passes and
+//! failures. Either way the ROM runs headless, as by [`run_rom_until`],
+//! until it halts on a jump to itself, and is checked then. A ROM that
+//! faults or never halts fails; one that halts with nothing to check it
+//! against is inconclusive, as halting says nothing about its results.
+
+use chippers_core::chip::{Chip8, LoadError};
+use chippers_core::golden::text_art;
+use chippers_core::{run_rom_until, Limits, Stop};
+
+/// What a test ROM must leave behind to pass.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Check {
+    /// Nothing, so the verdict can only be [`Outcome::Inconclusive`].
+    Halts,
+    /// The byte at `addr` holds `pass`.
+    Memory { addr: u16, pass: u8 },
+    /// The screen matches this text art, in the form of [`text_art`].
+    Screen(String),
+}
+
+/// Whether a test ROM passed.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Outcome {
+    Passed,
+    Failed,
+    /// It halted, but there was nothing to check.
+    Inconclusive,
+}
+
+/// How a test ROM fared.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Verdict {
+    pub outcome: Outcome,
+    /// What was found, such as the byte at the result address.
+    pub reason: String,
+    pub instructions: u64,
+}
+
+impl Verdict {
+    pub fn passed(&self) -> bool {
+        self.outcome == Outcome::Passed
+    }
+}
+
+impl std::fmt::Display for Verdict {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        let outcome = match self.outcome {
+            Outcome::Passed => "passed",
+            Outcome::Failed => "failed",
+            Outcome::Inconclusive => "inconclusive",
+        };
+        write!(
+            f,
+            "{}, {} after {} instructions",
+            outcome, self.reason, self.instructions
+        )
+    }
+}
+
+/// Whether the program has reached a `1NNN` jump to itself, which is how
+/// test ROMs stop once they have their results.
+pub fn halted(chip8: &Chip8) -> bool {
+    let pc = chip8.cpu.pc();
+    chip8.cpu.read_word(usize::from(pc)) == Ok(0x1000 | pc)
+}
+
+/// Runs `rom` for at most `limits.max_instructions` instructions and
+/// checks it, unless it cannot be loaded.
+pub fn run_test(rom: &[u8], check: &Check, limits: Limits) -> Result<Verdict, LoadError> {
+    let run = run_rom_until(rom, halted, limits)?;
+    let verdict = |passed, reason| Verdict {
+        outcome: if passed {
+            Outcome::Passed
+        } else {
+            Outcome::Failed
+        },
+        reason,
+        instructions: run.instructions,
+    };
+    match run.stop {
+        Stop::Condition => {}
+        Stop::InstructionLimit => return Ok(verdict(false, String::from("still running"))),
+        Stop::Fault(fault) => {
+            let fault = fault.to_string();
+            let fault = fault.trim_start_matches("error: ").trim_end();
+            return Ok(verdict(false, format!("stopped by {}", fault)));
+        }
+    }
+    Ok(match check {
+        Check::Halts => Verdict {
+            outcome: Outcome::Inconclusive,
+            reason: String::from("halted with no result address or screen to check"),
+            instructions: run.instructions,
+        },
+        Check::Memory { addr, pass } => {
+            let got = run.chip8.cpu.mem.get(usize::from(*addr)).copied();
+            match got {
+                Some(got) if got == *pass => {
+                    verdict(true, format!("{:#05x} holds {:#04x}", addr, got))
+                }
+                Some(got) => verdict(
+                    false,
+                    format!("{:#05x} holds {:#04x}, not {:#04x}", addr, got, pass),
+                ),
+                None => verdict(false, format!("{:#05x} is past the end of memory", addr)),
+            }
+        }
+        Check::Screen(expected) => {
+            let actual = text_art(&run.framebuffer());
+            // expected screens edited on Windows may have picked up carriage
+            // returns
+            if expected.replace("\r\n", "\n").trim_end() == actual.trim_end() {
+                verdict(true, String::from("the screen matches"))
+            } else {
+                verdict(false, format!("the screen differs:\n{}", actual))
+            }
+        }
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    // 6A05: VA = 5, A300: I = 0x300, FA55: save V0 to VA there, 1206: halt
+    const SAVES: [u8; 8] = [0x6A, 0x05, 0xA3, 0x00, 0xFA, 0x55, 0x12, 0x06];
+
+    #[test]
+    fn test_memory_and_halting() {
+        let pass = Check::Memory {
+            addr: 0x30A,
+            pass: 5,
+        };
+        let verdict = run_test(&SAVES, &pass, Limits::default()).unwrap();
+        assert!(verdict.passed(), "{}", verdict);
+        assert_eq!(
+            verdict.to_string(),
+            "passed, 0x30a holds 0x05 after 3 instructions"
+        );
+        let fail = Check::Memory {
+            addr: 0x30A,
+            pass: 1,
+        };
+        let verdict = run_test(&SAVES, &fail, Limits::default()).unwrap();
+        assert_eq!(verdict.reason, "0x30a holds 0x05, not 0x01");
+        assert!(!verdict.passed());
+
+        // 1202 jumps past itself, never halting
+        let verdict =
+            run_test(&[0x12, 0x02, 0x12, 0x00], &Check::Halts, Limits::frames(1)).unwrap();
+        assert_eq!(
+            verdict.to_string(),
+            "failed, still running after 12 instructions"
+        );
+        let verdict = run_test(&[0x00, 0xEE], &Check::Halts, Limits::default()).unwrap();
+        assert!(
+            verdict.reason.starts_with("stopped by stack underflow"),
+            "{}",
+            verdict
+        );
+        assert_eq!(
+            run_test(&[], &Check::Halts, Limits::default()),
+            Err(LoadError::Empty)
+        );
+    }
+
+    #[test]
+    fn test_screen() {
+        let mut screen = vec![".".repeat(64); 32];
+        let verdict = |screen: &[String]| {
+            let check = Check::Screen(screen.join("\n"));
+            run_test(&SAVES, &check, Limits::default()).unwrap()
+        };
+        assert!(verdict(&screen).passed());
+        screen[0].replace_range(0..1, "#");
+        let verdict = verdict(&screen);
+        assert_eq!(verdict.outcome, Outcome::Failed);
+        assert!(verdict.reason.starts_with("the screen differs:\n....."));
+    }
+
+    #[test]
+    fn test_halting_alone_does_not_pass() {
+        let verdict = run_test(&SAVES, &Check::Halts, Limits::default()).unwrap();
+        assert_eq!(verdict.outcome, Outcome::Inconclusive);
+        assert!(!verdict.passed());
+        assert_eq!(
+            verdict.to_string(),
+            "inconclusive, halted with no result address or screen to check after 3 instructions"
+        );
+    }
+}
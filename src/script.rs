@@ -0,0 +1,267 @@
+//! Lua scripts hooked into a running machine, for cheats, ROM patches and
+//! scripted tests, without building anything.
+//!
+//! A script defines any of these functions, each called with the machine as
+//! `m`:
+//!
+//! | Hook                                 | Called                                   |
+//! |--------------------------------------|------------------------------------------|
+//! | `on_frame(m, frame)`                 | as each 60 Hz frame begins               |
+//! | `on_instruction(m, addr, opcode)`    | after each instruction                   |
+//! | `on_memory(m, addr, len, write)`     | after an instruction reads or writes RAM |
+//!
+//! The machine has `m:reg(x)` and `m:set_reg(x, value)` for V0 to VF,
+//! `m:i()`, `m:pc()`, `m:dt()` and `m:st()` with their `set_` forms,
+//! `m:peek(addr)` and `m:poke(addr, value)` for memory, `m:press(key)` and
+//! `m:release(key)` for the keypad, and `m:frames()` and
+//! `m:instructions()`. Code outside the hooks runs once, when the script is
+//! loaded.
+//!
+//! ```lua
+//! -- infinite lives: the ROM keeps them at 0x3F0
+//! function on_frame(m, frame)
+//!   m:poke(0x3F0, 9)
+//! end
+//! ```
+//!
+//! A hook that fails stops the script, and the error is kept for
+//! [`take_error`](Script::take_error) while the machine runs on.
+
+use chippers_core::chip::{Chip8, Chip8Message, Engine};
+use mlua::{Function, IntoLuaMulti, Lua, UserDataMethods};
+
+#[derive(Clone, Debug)]
+pub enum ScriptError {
+    Lua(String),
+}
+
+impl std::fmt::Display for ScriptError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            ScriptError::Lua(s) => writeln!(f, "error: script: {}", s)?,
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for ScriptError {}
+
+impl From<mlua::Error> for ScriptError {
+    fn from(err: mlua::Error) -> ScriptError {
+        ScriptError::Lua(err.to_string())
+    }
+}
+
+/// An [`Engine`] that interprets instructions one at a time and calls the
+/// script's hooks around them.
+pub struct Script {
+    lua: Lua,
+    // the hooks the script defines, looked up once
+    on_frame: bool,
+    on_instruction: bool,
+    on_memory: bool,
+    // the frame the last `on_frame` was for
+    frame: Option<u64>,
+    error: Option<ScriptError>,
+}
+
+impl std::fmt::Debug for Script {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        f.debug_struct("Script")
+            .field("on_frame", &self.on_frame)
+            .field("on_instruction", &self.on_instruction)
+            .field("on_memory", &self.on_memory)
+            .field("error", &self.error)
+            .finish_non_exhaustive()
+    }
+}
+
+impl Script {
+    /// Runs `source`, named `name` in its error messages, and finds its hooks.
+    pub fn new(source: &str, name: &str) -> Result<Self, ScriptError> {
+        let lua = Lua::new();
+        lua.register_userdata_type::<Chip8>(|machine| {
+            machine.add_method("reg", |_, m, x: usize| Ok(m.cpu.registers()[x & 0xF]));
+            machine.add_method_mut("set_reg", |_, m, (x, value): (usize, u8)| {
+                m.cpu.registers_mut()[x & 0xF] = value;
+                Ok(())
+            });
+            machine.add_method("i", |_, m, ()| Ok(m.cpu.index()));
+            machine.add_method_mut("set_i", |_, m, value: u16| {
+                m.cpu.set_index(value);
+                Ok(())
+            });
+            machine.add_method("pc", |_, m, ()| Ok(m.cpu.pc()));
+            machine.add_method_mut("set_pc", |_, m, value: u16| {
+                m.cpu.set_pc(value);
+                Ok(())
+            });
+            machine.add_method("dt", |_, m, ()| Ok(m.cpu.dt));
+            machine.add_method_mut("set_dt", |_, m, value: u8| {
+                m.cpu.dt = value;
+                Ok(())
+            });
+            machine.add_method("st", |_, m, ()| Ok(m.cpu.st));
+            machine.add_method_mut("set_st", |_, m, value: u8| {
+                m.cpu.st = value;
+                Ok(())
+            });
+            machine.add_method("peek", |_, m, addr: usize| {
+                m.cpu
+                    .mem
+                    .get(addr)
+                    .copied()
+                    .ok_or_else(|| out_of_memory(addr))
+            });
+            machine.add_method_mut("poke", |_, m, (addr, value): (usize, u8)| {
+                *m.cpu.mem.get_mut(addr).ok_or_else(|| out_of_memory(addr))? = value;
+                Ok(())
+            });
+            machine.add_method_mut("press", |_, m, key: u8| {
+                m.cpu.press_key(key);
+                Ok(())
+            });
+            machine.add_method_mut("release", |_, m, key: u8| {
+                m.cpu.keypad.release(key);
+                Ok(())
+            });
+            machine.add_method("frames", |_, m, ()| Ok(m.frames()));
+            machine.add_method("instructions", |_, m, ()| Ok(m.instructions()));
+        })?;
+        lua.load(source).set_name(name).exec()?;
+        let defines = |hook| matches!(lua.globals().get(hook), Ok(mlua::Value::Function(_)));
+        Ok(Script {
+            on_frame: defines("on_frame"),
+            on_instruction: defines("on_instruction"),
+            on_memory: defines("on_memory"),
+            lua,
+            frame: None,
+            error: None,
+        })
+    }
+
+    /// Reads and runs the script at `path`.
+    pub fn load(path: &std::path::Path) -> Result<Self, ScriptError> {
+        let source = std::fs::read_to_string(path)
+            .map_err(|e| ScriptError::Lua(format!("could not read {}: {}", path.display(), e)))?;
+        // an @ names a file in Lua's messages
+        Self::new(&source, &format!("@{}", path.display()))
+    }
+
+    /// The error that stopped the script, if one has.
+    pub fn take_error(&mut self) -> Option<ScriptError> {
+        self.error.take()
+    }
+
+    /// Calls `hook` with the machine and `args`, stopping the script if it
+    /// fails.
+    fn call<A>(&mut self, hook: &str, chip8: &mut Chip8, args: A)
+    where
+        A: for<'lua> IntoLuaMulti<'lua>,
+    {
+        let result = self.lua.scope(|scope| {
+            let mut args = args.into_lua_multi(&self.lua)?;
+            args.push_front(mlua::Value::UserData(
+                scope.create_any_userdata_ref_mut(chip8)?,
+            ));
+            let function: Function = self.lua.globals().get(hook)?;
+            function.call::<_, ()>(args)
+        });
+        if let Err(e) = result {
+            self.error = Some(e.into());
+            self.on_frame = false;
+            self.on_instruction = false;
+            self.on_memory = false;
+        }
+    }
+}
+
+fn out_of_memory(addr: usize) -> mlua::Error {
+    mlua::Error::RuntimeError(format!("{:#x} is past the end of memory", addr))
+}
+
+impl Engine for Script {
+    fn step(&mut self, chip8: &mut Chip8) -> Chip8Message {
+        if self.on_frame && self.frame != Some(chip8.frames()) {
+            self.frame = Some(chip8.frames());
+            self.call("on_frame", chip8, chip8.frames());
+        }
+        let addr = chip8.cpu.pc();
+        let opcode = chip8.cpu.read_word(usize::from(addr)).unwrap_or_default();
+        let msg = chip8.step();
+        if self.on_instruction {
+            self.call("on_instruction", chip8, (addr, opcode));
+        }
+        if let Some(access) = chip8.cpu.last_access().filter(|_| self.on_memory) {
+            let args = (access.addr, access.len, access.write);
+            self.call("on_memory", chip8, args);
+        }
+        msg
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn machine() -> Chip8 {
+        let mut chip8 = Chip8::new();
+        // A300: I = 0x300, F033: BCD of V0, 7001: V0 += 1, 1202: loop
+        chip8
+            .load_rom(&[0xA3, 0x00, 0xF0, 0x33, 0x70, 0x01, 0x12, 0x02])
+            .unwrap();
+        chip8
+    }
+
+    #[test]
+    fn test_hooks() {
+        let source = r#"
+            calls, writes = 0, {}
+            function on_frame(m, frame)
+              m:poke(0x3F0, frame)
+              m:set_reg(5, m:reg(5) + 1)
+            end
+            function on_instruction(m, addr, opcode)
+              calls = calls + 1
+              if opcode == 0x7001 and m:reg(0) == 3 then m:set_reg(0, 100) end
+            end
+            function on_memory(m, addr, len, write)
+              if write then writes[#writes + 1] = addr .. "+" .. len end
+            end
+        "#;
+        let mut script = Script::new(source, "test").unwrap();
+        let mut chip8 = machine();
+        for _ in 0..12 {
+            script.step(&mut chip8);
+        }
+        chip8.tick_timers();
+        script.step(&mut chip8);
+        assert!(script.take_error().is_none());
+        let globals = script.lua.globals();
+        assert_eq!(globals.get::<_, u32>("calls").unwrap(), 13);
+        let writes: Vec<String> = globals.get("writes").unwrap();
+        assert_eq!(writes, ["768+3"; 4]);
+        // the instruction hook jumped V0 from 3 to 100, and the loop added one
+        assert_eq!(chip8.cpu.registers()[0], 101);
+        assert_eq!(chip8.cpu.registers()[5], 2);
+        assert_eq!(chip8.cpu.mem[0x3F0], 1);
+    }
+
+    #[test]
+    fn test_errors() {
+        assert!(Script::new("function on_frame(", "broken").is_err());
+        let mut script = Script::new("function on_frame(m) m:poke(0x1000, 1) end", "t").unwrap();
+        let mut chip8 = machine();
+        script.step(&mut chip8);
+        let error = script.take_error().unwrap().to_string();
+        assert!(
+            error.contains("0x1000 is past the end of memory"),
+            "{}",
+            error
+        );
+        // the script is stopped, but the machine carries on
+        script.step(&mut chip8);
+        assert!(script.take_error().is_none());
+        assert_eq!(chip8.instructions(), 2);
+    }
+}
@@ -0,0 +1,403 @@
+//! A frontend in an SDL2 window, scaled to whatever size the window is.
+//!
+//! Keys follow the same QWERTY layout as the other frontends, see
+//! [`map_keycode`]; F5 saves the machine state, F9 restores it and F12 takes
+//! a screenshot. Holding Tab runs fast forward, and M, unless it is on the
+//! keypad, switches slow motion on and off. Escape or closing the window ends
+//! the run. The buzzer is a square wave on the default audio device.
+
+use chippers_core::backend::{
+    Capabilities, ControlMessage, DisplayBackend, Frontend, InputBackend, Palette, Resolution, Rgb,
+};
+use chippers_core::display::Display;
+use chippers_core::keypad::KeyMap;
+use sdl2::audio::{AudioCallback, AudioDevice, AudioSpecDesired};
+use sdl2::event::Event;
+use sdl2::keyboard::Keycode;
+use sdl2::pixels::{Color, PixelFormatEnum};
+use sdl2::rect::Rect;
+use sdl2::render::WindowCanvas;
+use sdl2::EventPump;
+
+/// Pixels of window per CHIP-8 pixel when the window opens.
+pub const DEFAULT_SCALE: u32 = 10;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum SdlError {
+    /// SDL reported an error.
+    Sdl(String),
+}
+
+impl std::fmt::Display for SdlError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            SdlError::Sdl(s) => writeln!(f, "error: sdl: {}", s)?,
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for SdlError {}
+
+impl From<String> for SdlError {
+    fn from(s: String) -> SdlError {
+        SdlError::Sdl(s)
+    }
+}
+
+fn sdl_error(e: impl std::fmt::Display) -> SdlError {
+    SdlError::Sdl(e.to_string())
+}
+
+/// Draws the display into the window's canvas.
+pub struct Window {
+    canvas: WindowCanvas,
+    palette: Palette,
+    // the display's resolution, and the one the canvas is scaled for, which
+    // differs while a MegaChip picture is shown
+    resolution: Resolution,
+    logical: Resolution,
+}
+
+impl std::fmt::Debug for Window {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        f.debug_struct("Window")
+            .field("size", &self.canvas.window().size())
+            .field("palette", &self.palette)
+            .finish()
+    }
+}
+
+impl DisplayBackend for Window {
+    type Error = SdlError;
+    const CAPABILITIES: Capabilities = Capabilities {
+        max_resolution: Resolution::MEGACHIP,
+        color: true,
+        sound: true,
+        title: true,
+    };
+
+    fn clear_screen(&mut self) -> std::result::Result<(), Self::Error> {
+        self.draw_screen(&Display::new())
+    }
+
+    fn draw_screen(&mut self, display: &Display) -> std::result::Result<(), Self::Error> {
+        self.scale_to(self.resolution)?;
+        let (on, off) = (self.palette.on, self.palette.off);
+        self.canvas.set_draw_color(Color::RGB(off.r, off.g, off.b));
+        self.canvas.clear();
+        let lit: Vec<Rect> = display
+            .iter_set_pixels()
+            .map(|(x, y)| Rect::new(x as i32, y as i32, 1, 1))
+            .collect();
+        self.canvas.set_draw_color(Color::RGB(on.r, on.g, on.b));
+        self.canvas.fill_rects(&lit)?;
+        self.canvas.present();
+        Ok(())
+    }
+
+    fn set_resolution(&mut self, resolution: Resolution) -> std::result::Result<(), Self::Error> {
+        self.resolution = resolution;
+        self.scale_to(resolution)
+    }
+
+    fn draw_color(
+        &mut self,
+        resolution: Resolution,
+        pixels: &[Rgb],
+    ) -> std::result::Result<(), Self::Error> {
+        self.scale_to(resolution)?;
+        let (width, height) = (u32::from(resolution.width), u32::from(resolution.height));
+        let creator = self.canvas.texture_creator();
+        let mut texture = creator
+            .create_texture_streaming(PixelFormatEnum::RGB24, width, height)
+            .map_err(sdl_error)?;
+        let bytes: Vec<u8> = pixels
+            .iter()
+            .flat_map(|rgb| [rgb.r, rgb.g, rgb.b])
+            .collect();
+        texture
+            .update(None, &bytes, width as usize * 3)
+            .map_err(sdl_error)?;
+        self.canvas.copy(&texture, None, None)?;
+        self.canvas.present();
+        Ok(())
+    }
+
+    fn set_palette(&mut self, palette: Palette) -> std::result::Result<(), Self::Error> {
+        self.palette = palette;
+        Ok(())
+    }
+
+    fn set_title(&mut self, title: &str) -> std::result::Result<(), Self::Error> {
+        self.canvas.window_mut().set_title(title).map_err(sdl_error)
+    }
+}
+
+impl Window {
+    /// Scales the canvas so `resolution` fills the window.
+    fn scale_to(&mut self, resolution: Resolution) -> std::result::Result<(), SdlError> {
+        if self.logical != resolution {
+            self.canvas
+                .set_logical_size(u32::from(resolution.width), u32::from(resolution.height))
+                .map_err(sdl_error)?;
+            self.logical = resolution;
+        }
+        Ok(())
+    }
+}
+
+/// The character a key types, as SDL names printable keys by it.
+pub fn keycode_char(key: Keycode) -> Option<char> {
+    u32::try_from(key.into_i32())
+        .ok()
+        .and_then(char::from_u32)
+        .filter(|c| !c.is_control())
+}
+
+/// Maps key events in the window onto the keypad through [`KeyMap`].
+pub struct Keys {
+    pub keys: KeyMap,
+    events: EventPump,
+    control: Option<ControlMessage>,
+    slow_motion: bool,
+}
+
+impl std::fmt::Debug for Keys {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        f.debug_struct("Keys")
+            .field("keys", &self.keys)
+            .field("control", &self.control)
+            .finish_non_exhaustive()
+    }
+}
+
+impl Keys {
+    fn handle(&mut self, event: Event) -> std::result::Result<Option<u8>, SdlError> {
+        match event {
+            Event::Quit { .. }
+            | Event::KeyDown {
+                keycode: Some(Keycode::Escape),
+                ..
+            } => {
+                self.control = Some(ControlMessage::Quit);
+                Ok(None)
+            }
+            Event::KeyDown {
+                keycode: Some(Keycode::F5),
+                ..
+            } => {
+                self.control = Some(ControlMessage::SaveState);
+                Ok(None)
+            }
+            Event::KeyDown {
+                keycode: Some(Keycode::F9),
+                ..
+            } => {
+                self.control = Some(ControlMessage::LoadState);
+                Ok(None)
+            }
+            Event::KeyDown {
+                keycode: Some(Keycode::F12),
+                ..
+            } => {
+                self.control = Some(ControlMessage::Screenshot);
+                Ok(None)
+            }
+            Event::KeyDown {
+                keycode: Some(Keycode::Tab),
+                repeat: false,
+                ..
+            } => {
+                self.control = Some(ControlMessage::FastForward(true));
+                Ok(None)
+            }
+            Event::KeyUp {
+                keycode: Some(Keycode::Tab),
+                ..
+            } => {
+                self.control = Some(ControlMessage::FastForward(false));
+                Ok(None)
+            }
+            Event::KeyDown {
+                keycode: Some(Keycode::M),
+                repeat: false,
+                ..
+            } if self.keys.key('m').is_none() => {
+                self.slow_motion = !self.slow_motion;
+                self.control = Some(ControlMessage::SlowMotion(self.slow_motion));
+                Ok(None)
+            }
+            Event::KeyDown {
+                keycode: Some(key),
+                repeat: false,
+                ..
+            } => Ok(keycode_char(key).and_then(|c| self.keys.key(c))),
+            _ => Ok(None),
+        }
+    }
+}
+
+impl InputBackend for Keys {
+    type Error = SdlError;
+
+    fn poll_key(&mut self) -> std::result::Result<Option<u8>, Self::Error> {
+        while let Some(event) = self.events.poll_event() {
+            if let Some(key) = self.handle(event)? {
+                return Ok(Some(key));
+            }
+        }
+        Ok(None)
+    }
+
+    fn wait_key(
+        &mut self,
+        timeout: std::time::Duration,
+    ) -> std::result::Result<Option<u8>, Self::Error> {
+        let timeout = u32::try_from(timeout.as_millis()).unwrap_or(u32::MAX);
+        match self.events.wait_event_timeout(timeout) {
+            Some(event) => self.handle(event),
+            None => Ok(None),
+        }
+    }
+
+    fn poll_control(&mut self) -> std::result::Result<Option<ControlMessage>, Self::Error> {
+        Ok(self.control.take())
+    }
+}
+
+struct SquareWave {
+    period: f32,
+    phase: f32,
+}
+
+impl AudioCallback for SquareWave {
+    type Channel = f32;
+
+    fn callback(&mut self, out: &mut [f32]) {
+        for sample in out {
+            *sample = if self.phase < self.period / 2. {
+                0.1
+            } else {
+                -0.1
+            };
+            self.phase = (self.phase + 1.) % self.period;
+        }
+    }
+}
+
+/// Plays the buzzer by pausing and resuming an audio device.
+pub struct Beep {
+    device: AudioDevice<SquareWave>,
+}
+
+impl std::fmt::Debug for Beep {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        f.debug_struct("Beep")
+            .field("status", &self.device.status())
+            .finish()
+    }
+}
+
+impl chippers_core::backend::AudioBackend for Beep {
+    type Error = SdlError;
+
+    fn set_tone(&mut self, on: bool) -> std::result::Result<(), Self::Error> {
+        if on {
+            self.device.resume();
+        } else {
+            self.device.pause();
+        }
+        Ok(())
+    }
+}
+
+/// A window, its keyboard and the buzzer.
+///
+/// SDL wants to be driven from the thread that started it, so unlike the
+/// terminal frontend this one is not `Send`.
+#[derive(Debug)]
+pub struct SdlFrontend {
+    pub window: Window,
+    pub keys: Keys,
+    beep: Beep,
+}
+
+impl SdlFrontend {
+    /// Opens a window called `title`, `scale` pixels per CHIP-8 pixel.
+    pub fn new(title: &str, scale: u32) -> std::result::Result<Self, SdlError> {
+        let sdl = sdl2::init()?;
+        let window = sdl
+            .video()?
+            .window(
+                title,
+                Display::WIDTH as u32 * scale,
+                Display::HEIGHT as u32 * scale,
+            )
+            .position_centered()
+            .resizable()
+            .build()
+            .map_err(sdl_error)?;
+        let canvas = window.into_canvas().build().map_err(sdl_error)?;
+        let spec = AudioSpecDesired {
+            freq: Some(44_100),
+            channels: Some(1),
+            samples: None,
+        };
+        let device = sdl.audio()?.open_playback(None, &spec, |spec| SquareWave {
+            period: spec.freq as f32 / 440.,
+            phase: 0.,
+        })?;
+        Ok(SdlFrontend {
+            window: Window {
+                canvas,
+                palette: Palette::default(),
+                resolution: Resolution::LORES,
+                // unscaled until the first frame
+                logical: Resolution {
+                    width: 0,
+                    height: 0,
+                },
+            },
+            keys: Keys {
+                keys: KeyMap::default(),
+                events: sdl.event_pump()?,
+                control: None,
+                slow_motion: false,
+            },
+            beep: Beep { device },
+        })
+    }
+}
+
+impl Frontend for SdlFrontend {
+    type Error = SdlError;
+    type Display = Window;
+    type Input = Keys;
+    type Audio = Beep;
+
+    fn parts(&mut self) -> (&mut Window, &mut Keys, &mut Beep) {
+        (&mut self.window, &mut self.keys, &mut self.beep)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_default_keys_cover_the_keypad() {
+        let keys = [
+            "1", "2", "3", "4", "Q", "W", "E", "R", "A", "S", "D", "F", "Z", "X", "C", "V",
+        ];
+        let mut seen = [false; 16];
+        for name in keys {
+            let c = keycode_char(Keycode::from_name(name).unwrap()).unwrap();
+            let key = KeyMap::default().key(c).unwrap();
+            seen[usize::from(key)] = true;
+        }
+        assert_eq!(seen, [true; 16]);
+        assert_eq!(keycode_char(Keycode::F5), None);
+        assert_eq!(keycode_char(Keycode::Escape), None);
+    }
+}
@@ -1,21 +1,365 @@
+use chippers_core::backend::{
+    Capabilities, ControlMessage, DisplayBackend, Frontend, InputBackend, Palette, Resolution, Rgb,
+};
+use chippers_core::display::{DirtyRows, Display};
+use chippers_core::keypad::KeyMap;
+use chippers_core::render::RenderThread;
 use crossterm::{
-    cursor, execute,
-    style::{self, Stylize},
+    cursor,
+    event::{self, Event, KeyCode, KeyEvent, KeyEventKind, KeyModifiers},
+    execute, queue,
+    style::{self, Color},
     terminal,
     terminal::size,
-    QueueableCommand,
+    Command,
 };
-use std::io::{stdout, Write};
+use std::io::{stdout, Stdout, Write};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{mpsc, Arc};
+use std::thread::JoinHandle;
+
+/// How display pixels map onto terminal cells.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum RenderMode {
+    /// A full block per pixel, the screen taking 64x32 cells.
+    #[default]
+    Blocks,
+    /// An upper half block per two pixels stacked vertically, colored top
+    /// and bottom, the screen taking 64x16 cells. Terminal cells are about
+    /// twice as tall as they are wide, so this also looks less stretched.
+    HalfBlock,
+    /// A braille character per 2x4 pixels, lit dots in the foreground color,
+    /// the screen taking 32x8 cells. Small enough for a tmux split.
+    Braille,
+}
+
+impl RenderMode {
+    /// Names accepted by [`from_name`](RenderMode::from_name).
+    pub const NAMES: [&'static str; 3] = ["blocks", "halfblock", "braille"];
+
+    pub fn from_name(name: &str) -> Option<RenderMode> {
+        match name {
+            "blocks" => Some(RenderMode::Blocks),
+            "halfblock" => Some(RenderMode::HalfBlock),
+            "braille" => Some(RenderMode::Braille),
+            _ => None,
+        }
+    }
+
+    /// Display pixels per cell, across and down.
+    pub fn cell_size(self) -> (usize, usize) {
+        match self {
+            RenderMode::Blocks => (1, 1),
+            RenderMode::HalfBlock => (1, 2),
+            RenderMode::Braille => (2, 4),
+        }
+    }
+
+    /// The glyph and colors for the cell at `(col, line)`.
+    fn cell(self, disp: &Display, col: usize, line: usize) -> Cell {
+        match self {
+            RenderMode::Blocks => Cell {
+                glyph: '█',
+                fg: disp.pixel(col, line),
+                bg: None,
+            },
+            RenderMode::HalfBlock => {
+                let (top, bottom) = (disp.pixel(col, line * 2), disp.pixel(col, line * 2 + 1));
+                if top == bottom {
+                    Cell {
+                        glyph: '█',
+                        fg: top,
+                        bg: None,
+                    }
+                } else {
+                    Cell {
+                        glyph: '▀',
+                        fg: top,
+                        bg: Some(bottom),
+                    }
+                }
+            }
+            RenderMode::Braille => {
+                let dots = BRAILLE_DOTS
+                    .iter()
+                    .filter(|(_, dx, dy)| disp.pixel(col * 2 + dx, line * 4 + dy))
+                    .fold(0, |dots, (bit, _, _)| dots | bit);
+                Cell {
+                    glyph: char::from_u32(0x2800 + dots).unwrap(),
+                    fg: true,
+                    bg: Some(false),
+                }
+            }
+        }
+    }
+}
+
+/// How many terminal cells each cell of a [`RenderMode`] is drawn across.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Scale {
+    /// A block of `columns` by `lines` terminal cells.
+    Fixed(u16, u16),
+    /// The largest block, as many cells across as down, that fits the
+    /// terminal, worked out again whenever the whole screen is redrawn.
+    Fit,
+}
+
+impl Default for Scale {
+    fn default() -> Self {
+        Scale::Fixed(1, 1)
+    }
+}
+
+impl Scale {
+    /// Reads `auto`, `N` for an NxN block, or `NxM` for N across and M down.
+    pub fn parse(s: &str) -> Option<Scale> {
+        if s == "auto" {
+            return Some(Scale::Fit);
+        }
+        let (columns, lines) = s.split_once('x').unwrap_or((s, s));
+        let (columns, lines) = (columns.parse().ok()?, lines.parse().ok()?);
+        if columns == 0 || lines == 0 {
+            return None;
+        }
+        Some(Scale::Fixed(columns, lines))
+    }
+
+    /// The block size for a screen of `cells` at scale 1 in a terminal with
+    /// `available` cells, never less than 1x1.
+    pub fn factor(self, available: (u16, u16), cells: (u16, u16)) -> (u16, u16) {
+        match self {
+            Scale::Fixed(columns, lines) => (columns, lines),
+            Scale::Fit => {
+                let n = (available.0 / cells.0.max(1))
+                    .min(available.1 / cells.1.max(1))
+                    .max(1);
+                (n, n)
+            }
+        }
+    }
+}
+
+/// The bit each pixel of a 2x4 block sets in a braille pattern, as
+/// `(bit, dx, dy)`. The bottom row was added to the six-dot cell later, so it
+/// comes last.
+const BRAILLE_DOTS: [(u32, usize, usize); 8] = [
+    (0x01, 0, 0),
+    (0x02, 0, 1),
+    (0x04, 0, 2),
+    (0x08, 1, 0),
+    (0x10, 1, 1),
+    (0x20, 1, 2),
+    (0x40, 0, 3),
+    (0x80, 1, 3),
+];
+
+/// A terminal cell, its colors given as the pixel state they stand for.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+struct Cell {
+    glyph: char,
+    fg: bool,
+    bg: Option<bool>,
+}
 
+/// Renders the display as ANSI text into `W`, the process's stdout by default.
+///
+/// Each instance owns its writer and screen offset, so several machines can
+/// render side by side into one terminal or into separate buffers. It is `Send`
+/// whenever `W` is, so it can live on a render thread.
 #[derive(Debug)]
-pub struct Terminal;
+pub struct Terminal<W: Write = Stdout> {
+    out: W,
+    origin: (u16, u16),
+    resolution: Resolution,
+    palette: Palette,
+    // only meaningful when `out` is the controlling terminal
+    check_size: bool,
+    // what the terminal shows now, if known, so a frame only redraws what changed
+    shown: Option<Display>,
+    mode: RenderMode,
+    scale: Scale,
+    // `scale` worked out for the terminal as it was at the last full redraw
+    factor: (u16, u16),
+}
+
+impl Default for Terminal {
+    fn default() -> Self {
+        Self::new()
+    }
+}
 
 impl Terminal {
-    const MIN_WIDTH: u16 = 64;
-    const MIN_HEIGHT: u16 = 32;
+    pub fn new() -> Self {
+        Terminal {
+            check_size: true,
+            ..Terminal::with_writer(stdout())
+        }
+    }
+}
+
+impl<W: Write> Terminal<W> {
+    /// Renders into `out` without checking the size of the controlling terminal.
+    pub fn with_writer(out: W) -> Self {
+        Terminal {
+            out,
+            origin: (0, 0),
+            resolution: Resolution::LORES,
+            palette: Palette::default(),
+            check_size: false,
+            shown: None,
+            mode: RenderMode::default(),
+            scale: Scale::default(),
+            factor: (1, 1),
+        }
+    }
+
+    /// Draws the screen with its top left corner at terminal cell `(column, row)`.
+    pub fn with_origin(mut self, column: u16, row: u16) -> Self {
+        self.origin = (column, row);
+        self
+    }
+
+    /// Draws with `mode` instead of a block per pixel.
+    pub fn with_mode(mut self, mode: RenderMode) -> Self {
+        self.mode = mode;
+        self
+    }
+
+    /// Repeats each cell to fill a block of terminal cells, or with
+    /// [`Scale::Fit`] as large a block as the terminal has room for.
+    pub fn with_scale(mut self, scale: Scale) -> Self {
+        self.scale = scale;
+        if let Scale::Fixed(columns, lines) = scale {
+            self.factor = (columns, lines);
+        }
+        self
+    }
 
-    fn check_bounds(&self, w: u16, h: u16) -> std::result::Result<(), TerminalError> {
-        if w < Self::MIN_WIDTH || h < Self::MIN_HEIGHT {
+    pub fn writer(&mut self) -> &mut W {
+        &mut self.out
+    }
+
+    pub fn into_writer(self) -> W {
+        self.out
+    }
+
+    /// Builds the ANSI text for `rows` of the display.
+    fn render(
+        &self,
+        disp: &Display,
+        rows: DirtyRows,
+    ) -> std::result::Result<String, std::fmt::Error> {
+        self.render_cells(None, disp, rows)
+    }
+
+    /// Builds the ANSI text that turns `shown` into `disp`, looking only at
+    /// `rows`: a cursor move per run of changed cells, and nothing at all
+    /// when no cell changed.
+    fn render_changes(
+        &self,
+        shown: &Display,
+        disp: &Display,
+        rows: DirtyRows,
+    ) -> std::result::Result<String, std::fmt::Error> {
+        self.render_cells(Some(shown), disp, rows)
+    }
+
+    /// Writes every cell covering `rows`, or with `shown` only those that
+    /// differ from it, merging color changes across runs of alike cells.
+    /// Each cell is repeated to fill its `factor` block of terminal cells.
+    fn render_cells(
+        &self,
+        shown: Option<&Display>,
+        disp: &Display,
+        rows: DirtyRows,
+    ) -> std::result::Result<String, std::fmt::Error> {
+        let (left, top) = self.origin;
+        let (cell_w, cell_h) = self.mode.cell_size();
+        let (cols, lines) = (disp.width() / cell_w, disp.height() / cell_h);
+        let (scale_x, scale_y) = (usize::from(self.factor.0), usize::from(self.factor.1));
+        // a cell is one glyph plus, at worst, two color changes
+        let mut frame = String::with_capacity(cols * lines * scale_x * scale_y * 4);
+        let mut wrote = false;
+        for line in
+            (0..lines).filter(|line| (0..cell_h).any(|dy| rows.contains(line * cell_h + dy)))
+        {
+            let (mut fg, mut bg) = (None, None);
+            let changed = |col| {
+                shown.is_none_or(|shown| {
+                    self.mode.cell(shown, col, line) != self.mode.cell(disp, col, line)
+                })
+            };
+            let mut col = 0;
+            while col < cols {
+                if !changed(col) {
+                    col += 1;
+                    continue;
+                }
+                let start = col;
+                while col < cols && changed(col) {
+                    col += 1;
+                }
+                for dy in 0..scale_y {
+                    cursor::MoveTo(
+                        left + (start * scale_x) as u16,
+                        top + (line * scale_y + dy) as u16,
+                    )
+                    .write_ansi(&mut frame)?;
+                    for cell in (start..col).map(|col| self.mode.cell(disp, col, line)) {
+                        let color = |on| {
+                            if on {
+                                self.palette.on
+                            } else {
+                                self.palette.off
+                            }
+                        };
+                        if fg != Some(cell.fg) {
+                            style::SetForegroundColor(self::color(color(cell.fg)))
+                                .write_ansi(&mut frame)?;
+                            fg = Some(cell.fg);
+                        }
+                        if let Some(cell_bg) = cell.bg.filter(|on| bg != Some(*on)) {
+                            style::SetBackgroundColor(self::color(color(cell_bg)))
+                                .write_ansi(&mut frame)?;
+                            bg = Some(cell_bg);
+                        }
+                        frame.extend(std::iter::repeat_n(cell.glyph, scale_x));
+                        wrote = true;
+                    }
+                }
+            }
+        }
+        if shown.is_none() || wrote {
+            style::ResetColor.write_ansi(&mut frame)?;
+        }
+        Ok(frame)
+    }
+
+    /// Terminal cells the screen needs across and down at `factor`.
+    fn extent(&self, factor: (u16, u16)) -> (u16, u16) {
+        let (cell_w, cell_h) = self.mode.cell_size();
+        (
+            self.resolution.width / cell_w as u16 * factor.0,
+            self.resolution.height / cell_h as u16 * factor.1,
+        )
+    }
+
+    /// Terminal cells free to the right of and below the origin.
+    fn available(&self) -> std::result::Result<(u16, u16), TerminalError> {
+        let (w, h) = size()?;
+        Ok((
+            w.saturating_sub(self.origin.0),
+            h.saturating_sub(self.origin.1),
+        ))
+    }
+
+    fn check_size(&self) -> std::result::Result<(), TerminalError> {
+        if !self.check_size {
+            return Ok(());
+        }
+        let (w, h) = self.available()?;
+        let (cols, lines) = self.extent(self.factor);
+        if w < cols || h < lines {
             return Err(TerminalError::ErrorKind(format!(
                 "terminal is too small to display screen: {}x{}",
                 w, h
@@ -25,63 +369,672 @@ impl Terminal {
     }
 }
 
-pub trait TerminalBackend {
-    type Error;
-    fn clear_screen(&mut self) -> std::result::Result<(), Self::Error>;
-    fn draw_screen(&mut self, display: &[[u8; 32]; 64]) -> std::result::Result<(), Self::Error>;
-}
-
-#[derive(Clone, Debug)]
+#[derive(Debug)]
 pub enum TerminalError {
     ErrorKind(String),
+    /// Reading from or writing to the terminal failed.
+    Io(std::io::Error),
 }
 
 impl std::fmt::Display for TerminalError {
     fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
         match self {
             TerminalError::ErrorKind(s) => writeln!(f, "error: {}", s)?,
+            TerminalError::Io(e) => writeln!(f, "error: terminal i/o failed: {}", e)?,
         }
         Ok(())
     }
 }
 
-impl std::error::Error for TerminalError {}
+impl std::error::Error for TerminalError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            TerminalError::ErrorKind(_) => None,
+            TerminalError::Io(e) => Some(e),
+        }
+    }
+}
 
 impl From<std::io::Error> for TerminalError {
     fn from(err: std::io::Error) -> TerminalError {
-        dbg!(err);
-        TerminalError::ErrorKind("received an io error".to_string())
+        TerminalError::Io(err)
     }
 }
 
-impl TerminalBackend for Terminal {
+fn color(rgb: Rgb) -> Color {
+    Color::Rgb {
+        r: rgb.r,
+        g: rgb.g,
+        b: rgb.b,
+    }
+}
+
+impl<W: Write> DisplayBackend for Terminal<W> {
     type Error = TerminalError;
+    const CAPABILITIES: Capabilities = Capabilities {
+        max_resolution: Resolution::LORES,
+        color: true,
+        sound: true,
+        title: true,
+    };
+
     fn clear_screen(&mut self) -> std::result::Result<(), Self::Error> {
-        let (width, height) = size()?;
-        self.check_bounds(width, height)?;
-        execute!(stdout(), terminal::Clear(terminal::ClearType::All))?;
+        self.check_size()?;
+        self.draw_screen(&Display::new())
+    }
+
+    fn draw_screen(&mut self, disp: &Display) -> std::result::Result<(), Self::Error> {
+        self.draw_rows(disp, DirtyRows::ALL)
+    }
+
+    /// Writes the cells that changed since the last frame with a single
+    /// `write_all`: one cursor move per run of changed cells and one color
+    /// change per run of same-colored cells. The first frame, and the first
+    /// after a palette or resolution change, is drawn in full.
+    fn draw_rows(
+        &mut self,
+        disp: &Display,
+        rows: DirtyRows,
+    ) -> std::result::Result<(), Self::Error> {
+        if self.shown.is_none() && self.scale == Scale::Fit && self.check_size {
+            let factor = self.scale.factor(self.available()?, self.extent((1, 1)));
+            if factor != self.factor {
+                // a smaller screen would leave the edges of the larger one behind
+                self.factor = factor;
+                queue!(self.out, terminal::Clear(terminal::ClearType::All))?;
+            }
+        }
+        self.check_size()?;
+        let frame = match &self.shown {
+            Some(shown) => self.render_changes(shown, disp, rows),
+            None => self.render(disp, DirtyRows::ALL),
+        }
+        .map_err(|_| TerminalError::ErrorKind("could not format frame".to_string()))?;
+        // until the write succeeds the screen is in an unknown state
+        self.shown = None;
+        if !frame.is_empty() {
+            self.out.write_all(frame.as_bytes())?;
+            self.out.flush()?;
+        }
+        self.shown = Some(disp.clone());
         Ok(())
     }
 
-    fn draw_screen(&mut self, disp: &[[u8; 32]; 64]) -> std::result::Result<(), Self::Error> {
-        let (width, height) = size()?;
-        self.check_bounds(width, height)?;
-        let mut stdout = stdout();
-        for (i, row) in disp.iter().enumerate() {
-            for (j, pix) in row.iter().enumerate() {
-                stdout.queue(cursor::MoveTo(i as u16, j as u16))?;
-                if *pix == 0 {
-                    stdout.queue(style::PrintStyledContent("█".black()))?;
-                } else if *pix == 1 {
-                    stdout.queue(style::PrintStyledContent("█".white()))?;
+    fn set_resolution(&mut self, resolution: Resolution) -> std::result::Result<(), Self::Error> {
+        self.resolution = resolution;
+        self.shown = None;
+        Ok(())
+    }
+
+    fn set_palette(&mut self, palette: Palette) -> std::result::Result<(), Self::Error> {
+        self.palette = palette;
+        self.shown = None;
+        Ok(())
+    }
+
+    fn beep(&mut self, on: bool) -> std::result::Result<(), Self::Error> {
+        if on {
+            self.out.write_all(b"\x07")?;
+            self.out.flush()?;
+        }
+        Ok(())
+    }
+
+    fn set_title(&mut self, title: &str) -> std::result::Result<(), Self::Error> {
+        execute!(self.out, terminal::SetTitle(title))?;
+        Ok(())
+    }
+
+    /// Clears the terminal, for the next frame to be drawn in full.
+    fn invalidate(&mut self) -> std::result::Result<(), Self::Error> {
+        queue!(self.out, terminal::Clear(terminal::ClearType::All))?;
+        self.shown = None;
+        Ok(())
+    }
+}
+
+/// How long the input thread waits for an event before checking whether it
+/// should stop.
+const INPUT_POLL: std::time::Duration = std::time::Duration::from_millis(20);
+
+/// Reads terminal events on a thread of its own and passes the key presses
+/// on through a channel, so the machine never waits on the terminal and a
+/// key pressed mid-frame is not lost.
+#[derive(Debug)]
+struct InputThread {
+    events: mpsc::Receiver<std::result::Result<KeyEvent, TerminalError>>,
+    stop: Arc<AtomicBool>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl InputThread {
+    fn spawn() -> Self {
+        let (send, events) = mpsc::channel();
+        let stop = Arc::new(AtomicBool::new(false));
+        let stopping = stop.clone();
+        let thread = std::thread::spawn(move || {
+            while !stopping.load(Ordering::Relaxed) {
+                let event = match event::poll(INPUT_POLL) {
+                    Ok(false) => continue,
+                    Ok(true) => event::read(),
+                    Err(e) => Err(e),
+                };
+                let sent = match event {
+                    Ok(Event::Key(key)) => send.send(Ok(key)),
+                    Ok(_) => Ok(()),
+                    Err(e) => {
+                        // the keyboard reports it and the run ends
+                        let _ = send.send(Err(e.into()));
+                        return;
+                    }
+                };
+                if sent.is_err() {
+                    return;
+                }
+            }
+        });
+        InputThread {
+            events,
+            stop,
+            thread: Some(thread),
+        }
+    }
+}
+
+impl Drop for InputThread {
+    /// Stops reading, so the terminal's next owner gets every key.
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::Relaxed);
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+/// Reads keypad input from the terminal's key events.
+///
+/// Characters press keypad keys as the [`KeyMap`] says. F5 saves the machine
+/// state and F9 restores it, F12 takes a screenshot, P pauses and resumes,
+/// N runs one frame while paused and M switches slow motion on and off
+/// unless they are on the keypad, Tab switches fast forward on and off,
+/// Backspace starts the ROM over, and Escape or Ctrl+C quits. With the `tui`
+/// feature, F1 opens the [debugger](crate::tui). Terminals do
+/// not say when a key is let go, so fast forward is not held down as in a
+/// window. Raw mode turns off the terminal's own Ctrl+C handling, so it
+/// arrives here as a key.
+///
+/// Events are read on a thread started by the first poll and stopped by
+/// [`stop`](Keyboard::stop) or dropping the keyboard.
+#[derive(Debug, Default)]
+pub struct Keyboard {
+    pub keys: KeyMap,
+    control: Option<ControlMessage>,
+    paused: bool,
+    fast_forward: bool,
+    slow_motion: bool,
+    input: Option<InputThread>,
+}
+
+impl Keyboard {
+    /// Stops the input thread, if it is running; the next poll starts it
+    /// again.
+    pub fn stop(&mut self) {
+        self.input = None;
+    }
+
+    fn control(&mut self, message: ControlMessage) -> Option<u8> {
+        self.control = Some(message);
+        None
+    }
+
+    fn events(&mut self) -> &mpsc::Receiver<std::result::Result<KeyEvent, TerminalError>> {
+        &self.input.get_or_insert_with(InputThread::spawn).events
+    }
+
+    /// The keypad key `event` presses, noting any control message it sends.
+    fn press(&mut self, event: KeyEvent) -> Option<u8> {
+        let KeyEvent {
+            code,
+            modifiers,
+            kind,
+            ..
+        } = event;
+        if kind == KeyEventKind::Release {
+            return None;
+        }
+        match code {
+            KeyCode::Esc => self.control(ControlMessage::Quit),
+            KeyCode::Char('c') if modifiers.contains(KeyModifiers::CONTROL) => {
+                self.control(ControlMessage::Quit)
+            }
+            KeyCode::Char(c) if self.keys.key(c).is_some() => self.keys.key(c),
+            KeyCode::Char('p') => {
+                self.paused = !self.paused;
+                if self.paused {
+                    self.control(ControlMessage::Pause)
                 } else {
-                    return Err(TerminalError::ErrorKind(
-                        "display pixel set to value other than 0 or 1".to_string(),
-                    ));
+                    self.control(ControlMessage::Resume)
                 }
             }
+            KeyCode::Char('n') => self.control(ControlMessage::FrameAdvance),
+            KeyCode::Char('m') => {
+                self.slow_motion = !self.slow_motion;
+                self.control(ControlMessage::SlowMotion(self.slow_motion))
+            }
+            KeyCode::Tab => {
+                self.fast_forward = !self.fast_forward;
+                self.control(ControlMessage::FastForward(self.fast_forward))
+            }
+            KeyCode::Backspace => self.control(ControlMessage::Reset),
+            KeyCode::F(5) => self.control(ControlMessage::SaveState),
+            KeyCode::F(9) => self.control(ControlMessage::LoadState),
+            KeyCode::F(12) => self.control(ControlMessage::Screenshot),
+            #[cfg(feature = "tui")]
+            KeyCode::F(1) => self.control(ControlMessage::Debug),
+            _ => None,
+        }
+    }
+}
+
+impl InputBackend for Keyboard {
+    type Error = TerminalError;
+    fn poll_key(&mut self) -> std::result::Result<Option<u8>, Self::Error> {
+        self.wait_key(std::time::Duration::ZERO)
+    }
+
+    /// Takes the next key press from the input thread, waiting up to
+    /// `timeout` for one. A key that is not on the keypad ends the wait with
+    /// none.
+    fn wait_key(
+        &mut self,
+        timeout: std::time::Duration,
+    ) -> std::result::Result<Option<u8>, Self::Error> {
+        let event = match self.events().recv_timeout(timeout) {
+            Ok(event) => event?,
+            Err(mpsc::RecvTimeoutError::Timeout) => return Ok(None),
+            Err(mpsc::RecvTimeoutError::Disconnected) => {
+                self.input = None;
+                return Err(TerminalError::ErrorKind(
+                    "stopped reading the keyboard".to_string(),
+                ));
+            }
+        };
+        Ok(self.press(event))
+    }
+
+    fn poll_control(&mut self) -> std::result::Result<Option<ControlMessage>, Self::Error> {
+        Ok(self.control.take())
+    }
+}
+
+#[cfg(feature = "audio")]
+type Speaker = crate::audio::Beeper<TerminalError>;
+#[cfg(not(feature = "audio"))]
+type Speaker = chippers_core::backend::Silent<TerminalError>;
+
+/// Raw mode, the alternate screen and a hidden cursor, held until
+/// [`leave`](RawScreen::leave) or drop.
+///
+/// Dropping restores the terminal too, so an early return or a panic does not
+/// leave the shell in raw mode. While a screen is held, a panic restores the
+/// terminal before its message is printed, or the message would vanish with
+/// the alternate screen.
+#[derive(Debug)]
+pub struct RawScreen {
+    active: bool,
+}
+
+impl RawScreen {
+    pub fn enter() -> std::result::Result<Self, TerminalError> {
+        terminal::enable_raw_mode()?;
+        // from here on, dropping undoes whatever did get set up
+        let screen = RawScreen { active: true };
+        execute!(stdout(), terminal::EnterAlternateScreen, cursor::Hide)?;
+        static HOOK: std::sync::Once = std::sync::Once::new();
+        HOOK.call_once(|| {
+            let hook = std::panic::take_hook();
+            std::panic::set_hook(Box::new(move |info| {
+                // restoring a terminal that is not in raw mode changes nothing
+                let _ = restore();
+                hook(info);
+            }));
+        });
+        Ok(screen)
+    }
+
+    /// Restores the terminal, reporting whether that worked.
+    pub fn leave(mut self) -> std::result::Result<(), TerminalError> {
+        self.active = false;
+        restore()
+    }
+}
+
+impl Drop for RawScreen {
+    fn drop(&mut self) {
+        if self.active {
+            let _ = restore();
+        }
+    }
+}
+
+/// Undoes [`RawScreen::enter`], carrying on past any step that fails.
+fn restore() -> std::result::Result<(), TerminalError> {
+    let screen = execute!(
+        stdout(),
+        style::ResetColor,
+        cursor::Show,
+        terminal::LeaveAlternateScreen
+    );
+    let raw = terminal::disable_raw_mode();
+    screen.and(raw)?;
+    Ok(())
+}
+
+/// The terminal display and keyboard, drawn on the alternate screen in raw
+/// mode for the length of the run.
+///
+/// The terminal draws on its own thread, so a slow terminal drops frames
+/// instead of slowing the program down, and the keyboard is read on another. With the `audio` feature the buzzer
+/// plays on the default output device, if there is one.
+#[derive(Debug)]
+pub struct TerminalFrontend {
+    pub terminal: RenderThread<Terminal>,
+    pub keyboard: Keyboard,
+    audio: Speaker,
+    screen: Option<RawScreen>,
+}
+
+impl Default for TerminalFrontend {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl TerminalFrontend {
+    pub fn new() -> Self {
+        Self::with_terminal(Terminal::new())
+    }
+
+    /// A frontend that draws through `terminal`, set up with its render mode
+    /// and scale.
+    pub fn with_terminal(terminal: Terminal) -> Self {
+        TerminalFrontend {
+            terminal: RenderThread::spawn(terminal),
+            keyboard: Keyboard::default(),
+            // a machine without sound still runs
+            #[cfg(feature = "audio")]
+            audio: Speaker::open().unwrap_or_else(|_| Speaker::silent()),
+            #[cfg(not(feature = "audio"))]
+            audio: Speaker::new(),
+            screen: None,
         }
-        stdout.flush()?;
+    }
+}
+
+impl Frontend for TerminalFrontend {
+    type Error = TerminalError;
+    type Display = RenderThread<Terminal>;
+    type Input = Keyboard;
+    type Audio = Speaker;
+
+    fn init(&mut self) -> std::result::Result<(), Self::Error> {
+        self.screen = Some(RawScreen::enter()?);
         Ok(())
     }
+
+    /// Puts the terminal back the way the shell expects it, even when the
+    /// last frame could not be drawn.
+    fn teardown(&mut self) -> std::result::Result<(), Self::Error> {
+        self.keyboard.stop();
+        let drawn = self.terminal.sync();
+        let restored = self.screen.take().map_or(Ok(()), RawScreen::leave);
+        drawn.and(restored)
+    }
+
+    fn parts(&mut self) -> (&mut Self::Display, &mut Keyboard, &mut Self::Audio) {
+        (&mut self.terminal, &mut self.keyboard, &mut self.audio)
+    }
+
+    /// Opens the [debugger](crate::tui) over the screen, with the keyboard
+    /// to itself until it is closed.
+    #[cfg(feature = "tui")]
+    fn debug(
+        &mut self,
+        chip8: &mut chippers_core::chip::Chip8,
+    ) -> std::result::Result<(), Self::Error> {
+        self.keyboard.stop();
+        self.terminal.sync()?;
+        crate::tui::Inspector::new().run(chip8)?;
+        self.terminal.invalidate()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn assert_send<T: Send>() {}
+
+    #[test]
+    fn test_thread_safety_bounds() {
+        assert_send::<Terminal>();
+        assert_send::<TerminalFrontend>();
+    }
+
+    #[test]
+    fn test_io_error_is_the_source() {
+        use std::error::Error;
+
+        let err = TerminalError::from(std::io::Error::from(std::io::ErrorKind::BrokenPipe));
+        let source = err.source().unwrap().downcast_ref::<std::io::Error>();
+        assert_eq!(source.unwrap().kind(), std::io::ErrorKind::BrokenPipe);
+        assert!(err.to_string().contains("broken pipe"), "{}", err);
+    }
+
+    #[test]
+    fn test_keyboard_press() {
+        let mut keyboard = Keyboard::default();
+        let key = |code| KeyEvent::new(code, KeyModifiers::NONE);
+        assert_eq!(keyboard.press(key(KeyCode::Char('f'))), Some(0xE));
+        assert_eq!(keyboard.press(key(KeyCode::Char('X'))), Some(0x0));
+        assert_eq!(keyboard.press(key(KeyCode::Char('p'))), None);
+        assert_eq!(
+            keyboard.poll_control().unwrap(),
+            Some(ControlMessage::Pause)
+        );
+        assert_eq!(keyboard.press(key(KeyCode::Char('n'))), None);
+        assert_eq!(
+            keyboard.poll_control().unwrap(),
+            Some(ControlMessage::FrameAdvance)
+        );
+        assert_eq!(keyboard.press(key(KeyCode::F(12))), None);
+        assert_eq!(
+            keyboard.poll_control().unwrap(),
+            Some(ControlMessage::Screenshot)
+        );
+        for on in [true, false] {
+            assert_eq!(keyboard.press(key(KeyCode::Tab)), None);
+            assert_eq!(
+                keyboard.poll_control().unwrap(),
+                Some(ControlMessage::FastForward(on))
+            );
+        }
+        assert_eq!(keyboard.press(key(KeyCode::Char('m'))), None);
+        assert_eq!(
+            keyboard.poll_control().unwrap(),
+            Some(ControlMessage::SlowMotion(true))
+        );
+        let ctrl_c = KeyEvent::new(KeyCode::Char('c'), KeyModifiers::CONTROL);
+        assert_eq!(keyboard.press(ctrl_c), None);
+        assert_eq!(keyboard.poll_control().unwrap(), Some(ControlMessage::Quit));
+        // no input thread until the first poll
+        assert!(keyboard.input.is_none());
+    }
+
+    #[test]
+    fn test_instances_render_independently() {
+        let mut left = Terminal::with_writer(Vec::new());
+        let mut right = Terminal::with_writer(Vec::new()).with_origin(70, 0);
+        let mut disp = Display::new();
+        disp.set_pixel(0, 0, true);
+        left.draw_screen(&disp).unwrap();
+        right.draw_screen(&Display::new()).unwrap();
+        let (left, right) = (left.into_writer(), right.into_writer());
+        assert!(!left.is_empty());
+        assert_ne!(left, right);
+        // cursor addressing is 1-based: column 71 is the first cell of `right`
+        assert!(String::from_utf8(right).unwrap().starts_with("\x1b[1;71H"));
+    }
+
+    #[test]
+    fn test_frame_is_one_write_with_merged_runs() {
+        struct Writes(Vec<usize>);
+        impl Write for Writes {
+            fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+                self.0.push(buf.len());
+                Ok(buf.len())
+            }
+            fn flush(&mut self) -> std::io::Result<()> {
+                Ok(())
+            }
+        }
+        let mut term = Terminal::with_writer(Writes(Vec::new()));
+        let mut disp = Display::new();
+        disp.set_pixel(10, 0, true);
+        disp.set_pixel(11, 0, true);
+        term.draw_screen(&disp).unwrap();
+        assert_eq!(term.writer().0.len(), 1);
+
+        let frame = Terminal::with_writer(Vec::new())
+            .render(&disp, DirtyRows::ALL)
+            .unwrap();
+        assert_eq!(frame.matches(";1H").count(), Display::HEIGHT);
+        // row 0 switches off -> on -> off, the other rows are a single run
+        assert_eq!(frame.matches("\x1b[38;").count(), 3 + (Display::HEIGHT - 1));
+        assert_eq!(frame.matches('█').count(), Display::WIDTH * Display::HEIGHT);
+
+        let mut rows = DirtyRows::NONE;
+        rows.insert(4);
+        let frame = Terminal::with_writer(Vec::new())
+            .render(&disp, rows)
+            .unwrap();
+        assert!(frame.starts_with("\x1b[5;1H"));
+        assert_eq!(frame.matches('█').count(), Display::WIDTH);
+    }
+
+    #[test]
+    fn test_only_changed_cells_are_redrawn() {
+        let mut term = Terminal::with_writer(Vec::new());
+        let mut disp = Display::new();
+        term.draw_screen(&disp).unwrap();
+        assert_eq!(
+            String::from_utf8_lossy(term.writer()).matches('█').count(),
+            Display::WIDTH * Display::HEIGHT
+        );
+
+        term.writer().clear();
+        disp.set_pixel(3, 2, true);
+        disp.set_pixel(4, 2, true);
+        disp.set_pixel(10, 2, true);
+        term.draw_screen(&disp).unwrap();
+        let out = String::from_utf8(std::mem::take(term.writer())).unwrap();
+        // two runs on row 2, white throughout
+        assert_eq!(out.matches('█').count(), 3);
+        assert!(out.starts_with("\x1b[3;4H\x1b[38;2;255;255;255m██\x1b[3;11H█"));
+
+        // an unchanged frame writes nothing
+        term.draw_screen(&disp).unwrap();
+        assert!(term.writer().is_empty());
+
+        // a new palette repaints everything
+        term.set_palette(Palette::MONOCHROME).unwrap();
+        term.draw_screen(&disp).unwrap();
+        assert_eq!(
+            String::from_utf8_lossy(term.writer()).matches('█').count(),
+            Display::WIDTH * Display::HEIGHT
+        );
+
+        // and so does drawing over it, after clearing what was drawn
+        term.writer().clear();
+        term.invalidate().unwrap();
+        term.draw_screen(&disp).unwrap();
+        let out = String::from_utf8_lossy(term.writer());
+        assert!(out.starts_with("\x1b[2J"));
+        assert_eq!(out.matches('█').count(), Display::WIDTH * Display::HEIGHT);
+    }
+
+    #[test]
+    fn test_half_blocks_stack_two_rows_per_line() {
+        let mut term = Terminal::with_writer(Vec::new()).with_mode(RenderMode::HalfBlock);
+        let mut disp = Display::new();
+        disp.set_pixel(0, 0, true);
+        term.draw_screen(&disp).unwrap();
+        let out = String::from_utf8(std::mem::take(term.writer())).unwrap();
+        assert_eq!(out.matches(";1H").count(), Display::HEIGHT / 2);
+        assert_eq!(out.matches('▀').count(), 1);
+        assert_eq!(
+            out.matches('█').count(),
+            Display::WIDTH * Display::HEIGHT / 2 - 1
+        );
+        // lit on top, dark below
+        assert!(out.starts_with("\x1b[1;1H\x1b[38;2;255;255;255m\x1b[48;2;0;0;0m▀"));
+
+        // filling in the bottom half redraws the one cell, now a full block
+        disp.set_pixel(0, 1, true);
+        term.draw_screen(&disp).unwrap();
+        let out = String::from_utf8(std::mem::take(term.writer())).unwrap();
+        assert!(out.starts_with("\x1b[1;1H\x1b[38;2;255;255;255m█\x1b[0m"));
+    }
+
+    #[test]
+    fn test_braille_packs_eight_pixels_per_cell() {
+        let mut term = Terminal::with_writer(Vec::new()).with_mode(RenderMode::Braille);
+        let mut disp = Display::new();
+        // the left column of the first cell and the bottom right dot of the second
+        for y in 0..4 {
+            disp.set_pixel(0, y, true);
+        }
+        disp.set_pixel(3, 3, true);
+        term.draw_screen(&disp).unwrap();
+        let out = String::from_utf8(std::mem::take(term.writer())).unwrap();
+        assert_eq!(out.matches(";1H").count(), Display::HEIGHT / 4);
+        assert!(out.starts_with("\x1b[1;1H\x1b[38;2;255;255;255m\x1b[48;2;0;0;0m⡇⢀⠀"));
+        assert_eq!(
+            out.chars()
+                .filter(|c| ('\u{2800}'..='\u{28FF}').contains(c))
+                .count(),
+            Display::WIDTH * Display::HEIGHT / 8
+        );
+    }
+
+    #[test]
+    fn test_scale() {
+        assert_eq!(Scale::parse("auto"), Some(Scale::Fit));
+        assert_eq!(Scale::parse("3"), Some(Scale::Fixed(3, 3)));
+        assert_eq!(Scale::parse("2x1"), Some(Scale::Fixed(2, 1)));
+        assert_eq!(Scale::parse("0x1"), None);
+        assert_eq!(Scale::parse("2x"), None);
+        // 200x60 fits 64x32 cells once down, 64x16 half blocks three times
+        assert_eq!(Scale::Fit.factor((200, 60), (64, 32)), (1, 1));
+        assert_eq!(Scale::Fit.factor((200, 60), (64, 16)), (3, 3));
+        assert_eq!(Scale::Fit.factor((10, 10), (64, 32)), (1, 1));
+
+        let mut term = Terminal::with_writer(Vec::new()).with_scale(Scale::Fixed(2, 3));
+        let mut disp = Display::new();
+        term.draw_screen(&disp).unwrap();
+        let out = String::from_utf8(std::mem::take(term.writer())).unwrap();
+        assert_eq!(out.matches(";1H").count(), Display::HEIGHT * 3);
+        assert_eq!(
+            out.matches('█').count(),
+            Display::WIDTH * Display::HEIGHT * 6
+        );
+        // pixel (1, 1) covers columns 3-4 of lines 4-6, 1-based
+        disp.set_pixel(1, 1, true);
+        term.draw_screen(&disp).unwrap();
+        let out = String::from_utf8(std::mem::take(term.writer())).unwrap();
+        assert_eq!(
+            out,
+            "\x1b[4;3H\x1b[38;2;255;255;255m██\x1b[5;3H██\x1b[6;3H██\x1b[0m"
+        );
+    }
 }
@@ -0,0 +1,228 @@
+//! Instruction traces, for finding where a ROM and the emulator part ways.
+//!
+//! A [`Tracer`] hooks into a machine and writes a line per instruction: its
+//! address, the instruction word, its mnemonic and the registers it changed.
+//!
+//! ```text
+//! 200  6a05  LD VA, #05       VA=05
+//! 202  a2f0  LD I, #2F0       I=2f0
+//! 204  fa15  LD DT, VA        DT=05
+//! ```
+//!
+//! A tracer made with [`Tracer::ring`] keeps only the last lines and writes
+//! them when the program faults, so a long run writes nothing until it goes
+//! wrong.
+
+use chippers_core::chip::Chip8;
+use chippers_core::cpu::{Fault, RegisterDump};
+use chippers_core::opcode::Disassembly;
+use std::collections::VecDeque;
+use std::fmt::Write as _;
+use std::io::Write;
+use std::sync::{Arc, Mutex, MutexGuard};
+
+/// Writes the instructions a machine runs to `out`.
+///
+/// Hooks cannot return errors, so the first write that fails stops the trace
+/// and is reported by [`finish`](Tracer::finish).
+#[derive(Clone)]
+pub struct Tracer(Arc<Mutex<Trace>>);
+
+struct Trace {
+    out: Box<dyn Write + Send>,
+    // the most lines kept, and the lines kept, when only the end is wanted
+    ring: Option<(usize, VecDeque<String>)>,
+    last: Option<RegisterDump>,
+    error: Option<std::io::Error>,
+}
+
+impl std::fmt::Debug for Tracer {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        let trace = self.lock();
+        f.debug_struct("Tracer")
+            .field("ring", &trace.ring.as_ref().map(|(lines, _)| lines))
+            .field("failed", &trace.error.is_some())
+            .finish_non_exhaustive()
+    }
+}
+
+impl Tracer {
+    /// Traces every instruction into `out` as it runs.
+    pub fn new(out: impl Write + Send + 'static) -> Self {
+        Self::with_ring(out, None)
+    }
+
+    /// Keeps the last `lines` instructions and writes them into `out` only if
+    /// the program faults.
+    pub fn ring(out: impl Write + Send + 'static, lines: usize) -> Self {
+        Self::with_ring(out, Some((lines, VecDeque::with_capacity(lines))))
+    }
+
+    fn with_ring(
+        out: impl Write + Send + 'static,
+        ring: Option<(usize, VecDeque<String>)>,
+    ) -> Self {
+        Tracer(Arc::new(Mutex::new(Trace {
+            out: Box::new(out),
+            ring,
+            last: None,
+            error: None,
+        })))
+    }
+
+    /// Starts tracing `chip8` from the instruction it runs next.
+    pub fn attach(&self, chip8: &mut Chip8) {
+        self.lock().last = Some(chip8.cpu.dump());
+        let trace = self.0.clone();
+        chip8.hooks.on_instruction(move |addr, raw, cpu| {
+            let mut trace = trace.lock().unwrap_or_else(|e| e.into_inner());
+            let now = cpu.dump();
+            let line = line(addr, raw, trace.last.as_ref(), &now);
+            trace.last = Some(now);
+            match &mut trace.ring {
+                Some((lines, kept)) => {
+                    if kept.len() == *lines {
+                        kept.pop_front();
+                    }
+                    if *lines > 0 {
+                        kept.push_back(line);
+                    }
+                }
+                None => trace.write(line.as_bytes()),
+            }
+        });
+        let trace = self.0.clone();
+        chip8.hooks.on_fault(move |fault: Fault, _| {
+            let mut trace = trace.lock().unwrap_or_else(|e| e.into_inner());
+            let kept = trace.ring.as_mut().map(|(_, kept)| std::mem::take(kept));
+            for line in kept.into_iter().flatten() {
+                trace.write(line.as_bytes());
+            }
+            trace.write(fault.to_string().as_bytes());
+            trace.flush();
+        });
+    }
+
+    /// Flushes the trace, reporting the first error writing it, if any.
+    pub fn finish(&self) -> std::io::Result<()> {
+        let mut trace = self.lock();
+        trace.flush();
+        trace.error.take().map_or(Ok(()), Err)
+    }
+
+    fn lock(&self) -> MutexGuard<'_, Trace> {
+        self.0.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+impl Trace {
+    fn write(&mut self, bytes: &[u8]) {
+        if self.error.is_none() {
+            self.error = self.out.write_all(bytes).err();
+        }
+    }
+
+    fn flush(&mut self) {
+        if self.error.is_none() {
+            self.error = self.out.flush().err();
+        }
+    }
+}
+
+/// One line of the trace: the instruction and what it changed since `before`.
+fn line(addr: u16, raw: u16, before: Option<&RegisterDump>, after: &RegisterDump) -> String {
+    let mut line = format!(
+        "{:03x}  {:04x}  {:<16}",
+        addr,
+        raw,
+        Disassembly(raw).to_string()
+    );
+    if let Some(before) = before {
+        for (i, (old, new)) in before.v.iter().zip(after.v).enumerate() {
+            if *old != new {
+                let _ = write!(line, " V{:X}={:02x}", i, new);
+            }
+        }
+        let others = [
+            ("I", before.index, after.index),
+            ("DT", before.dt.into(), after.dt.into()),
+            ("ST", before.st.into(), after.st.into()),
+            ("SP", before.sp.into(), after.sp.into()),
+        ];
+        for (name, old, new) in others {
+            if old != new {
+                let _ = write!(line, " {}={:02x}", name, new);
+            }
+        }
+    }
+    line.truncate(line.trim_end().len());
+    line.push('\n');
+    line
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    /// A writer whose output the test can still read after handing it over.
+    #[derive(Clone, Default)]
+    struct Shared(Arc<Mutex<Vec<u8>>>);
+
+    impl Write for Shared {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.0.lock().unwrap().write(buf)
+        }
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    impl Shared {
+        fn text(&self) -> String {
+            String::from_utf8(self.0.lock().unwrap().clone()).unwrap()
+        }
+    }
+
+    // 6A05: VA = 5, A2F0: I = 0x2F0, FA15: DT = VA, 2208: call, 00EE: return
+    const ROM: [u8; 10] = [0x6A, 0x05, 0xA2, 0xF0, 0xFA, 0x15, 0x22, 0x08, 0x00, 0xEE];
+
+    #[test]
+    fn test_trace_every_instruction() {
+        let out = Shared::default();
+        let tracer = Tracer::new(out.clone());
+        let mut chip8 = Chip8::new();
+        chip8.load_rom(&ROM).unwrap();
+        tracer.attach(&mut chip8);
+        for _ in 0..3 {
+            chip8.step();
+        }
+        tracer.finish().unwrap();
+        assert_eq!(
+            out.text(),
+            "200  6a05  LD VA, #05       VA=05\n\
+             202  a2f0  LD I, #2F0       I=2f0\n\
+             204  fa15  LD DT, VA        DT=05\n"
+        );
+    }
+
+    #[test]
+    fn test_ring_is_written_on_fault() {
+        let out = Shared::default();
+        let tracer = Tracer::ring(out.clone(), 2);
+        let mut chip8 = Chip8::new();
+        chip8.load_rom(&ROM).unwrap();
+        tracer.attach(&mut chip8);
+        for _ in 0..5 {
+            chip8.step();
+        }
+        assert_eq!(out.text(), "");
+        // the return runs again, now outside any subroutine
+        chip8.step();
+        assert_eq!(
+            out.text(),
+            "208  00ee  RET              SP=00\n\
+             208  00ee  RET\n\
+             error: stack underflow at 0x208: return outside any subroutine\n"
+        );
+    }
+}
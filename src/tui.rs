@@ -0,0 +1,298 @@
+//! A full-screen debugger for the terminal frontend, opened with F1 while a
+//! program runs and closed with F1 or Escape to carry on running it.
+//!
+//! Panes show the display, the registers and timers, the disassembly around
+//! the program counter, the stack and the memory from I. `s` runs one
+//! instruction and `n` a 60 Hz frame; the arrow keys and Page Up and Page
+//! Down scroll the memory, which follows I again after the next step.
+
+use crate::debugger::hex_dump;
+use chippers_core::chip::{Chip8, Chip8Message};
+use chippers_core::display::Display;
+use chippers_core::opcode::Disassembly;
+use crossterm::event::{self, Event, KeyCode, KeyEvent, KeyEventKind};
+use ratatui::backend::{Backend, CrosstermBackend};
+use ratatui::layout::{Constraint, Layout};
+use ratatui::style::{Style, Stylize};
+use ratatui::text::Line;
+use ratatui::widgets::{Block, Paragraph};
+use ratatui::Frame;
+use std::io::stdout;
+
+/// What the debugger shows beyond the machine itself.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Inspector {
+    // where the memory pane starts, or `None` to follow I
+    memory: Option<usize>,
+    // what the last step did, if worth saying
+    status: String,
+}
+
+impl Inspector {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Draws and takes keys on the terminal, already in raw mode on the
+    /// alternate screen, until the debugger is closed.
+    pub fn run(&mut self, chip8: &mut Chip8) -> std::io::Result<()> {
+        let mut terminal = ratatui::Terminal::new(CrosstermBackend::new(stdout()))?;
+        terminal.clear()?;
+        self.show(&mut terminal, chip8)
+    }
+
+    fn show<B: Backend>(
+        &mut self,
+        terminal: &mut ratatui::Terminal<B>,
+        chip8: &mut Chip8,
+    ) -> std::io::Result<()> {
+        loop {
+            terminal.draw(|frame| self.draw(frame, chip8))?;
+            if let Event::Key(key) = event::read()? {
+                if key.kind != KeyEventKind::Release && !self.press(key, chip8) {
+                    return Ok(());
+                }
+            }
+        }
+    }
+
+    /// Acts on `key`, returning whether the debugger stays open.
+    pub fn press(&mut self, key: KeyEvent, chip8: &mut Chip8) -> bool {
+        let start = self.memory_start(chip8);
+        match key.code {
+            KeyCode::F(1) | KeyCode::Esc => return false,
+            KeyCode::Char('s') => {
+                self.memory = None;
+                self.status = match chip8.step() {
+                    Chip8Message::Halted(fault) => fault.to_string(),
+                    _ => String::new(),
+                };
+            }
+            KeyCode::Char('n') => {
+                self.memory = None;
+                let report = chip8.step_frame();
+                self.status = match report.fault {
+                    Some(fault) => fault.to_string(),
+                    None => format!("ran {} instructions", report.instructions),
+                };
+            }
+            KeyCode::Up => self.memory = Some(start.saturating_sub(16)),
+            KeyCode::Down => self.memory = Some((start + 16).min(chip8.cpu.mem.len() - 16)),
+            KeyCode::PageUp => self.memory = Some(start.saturating_sub(MEMORY_BYTES)),
+            KeyCode::PageDown => {
+                self.memory = Some((start + MEMORY_BYTES).min(chip8.cpu.mem.len() - MEMORY_BYTES))
+            }
+            _ => {}
+        }
+        true
+    }
+
+    fn memory_start(&self, chip8: &Chip8) -> usize {
+        let start = self.memory.unwrap_or(usize::from(chip8.cpu.index()) & !0xF);
+        start.min(chip8.cpu.mem.len() - MEMORY_BYTES)
+    }
+
+    /// Lays the panes out over the whole of `frame`.
+    pub fn draw(&self, frame: &mut Frame, chip8: &Chip8) {
+        let [top, middle, bottom, status] = Layout::vertical([
+            Constraint::Length(Display::HEIGHT as u16 / 2 + 2),
+            Constraint::Min(6),
+            Constraint::Length(MEMORY_BYTES as u16 / 16 + 2),
+            Constraint::Length(1),
+        ])
+        .areas(frame.area());
+        let [screen, registers] = Layout::horizontal([
+            Constraint::Length(Display::WIDTH as u16 + 2),
+            Constraint::Min(28),
+        ])
+        .areas(top);
+        let [code, stack] =
+            Layout::horizontal([Constraint::Min(30), Constraint::Length(24)]).areas(middle);
+
+        frame.render_widget(pane(" Display ", screen_lines(chip8)), screen);
+        frame.render_widget(pane(" Registers ", register_lines(chip8)), registers);
+        let rows = usize::from(code.height.saturating_sub(2));
+        frame.render_widget(pane(" Code ", code_lines(chip8, rows)), code);
+        frame.render_widget(pane(" Stack ", stack_lines(chip8)), stack);
+        let start = self.memory_start(chip8);
+        let title = format!(" Memory from {:03x} ", start);
+        frame.render_widget(pane(&title, memory_lines(chip8, start)), bottom);
+        let help = "s step  n frame  ↑↓ PgUp PgDn memory  F1 Esc run";
+        let line = match self.status.trim_end() {
+            "" => Line::from(help).dim(),
+            status => Line::from(status.to_string()),
+        };
+        frame.render_widget(line, status);
+    }
+}
+
+/// Bytes the memory pane shows at once.
+const MEMORY_BYTES: usize = 8 * 16;
+
+fn pane<'a>(title: &'a str, lines: Vec<Line<'a>>) -> Paragraph<'a> {
+    Paragraph::new(lines).block(Block::bordered().title(title))
+}
+
+/// The display, two pixel rows to a line in half blocks.
+fn screen_lines(chip8: &Chip8) -> Vec<Line<'static>> {
+    let disp = &chip8.cpu.disp;
+    (0..Display::HEIGHT / 2)
+        .map(|line| {
+            let text: String = (0..Display::WIDTH)
+                .map(
+                    |x| match (disp.pixel(x, line * 2), disp.pixel(x, line * 2 + 1)) {
+                        (true, true) => '█',
+                        (true, false) => '▀',
+                        (false, true) => '▄',
+                        (false, false) => ' ',
+                    },
+                )
+                .collect();
+            Line::from(text)
+        })
+        .collect()
+}
+
+fn register_lines(chip8: &Chip8) -> Vec<Line<'static>> {
+    let cpu = &chip8.cpu;
+    let mut lines: Vec<Line> = cpu
+        .registers()
+        .chunks(4)
+        .enumerate()
+        .map(|(row, values)| {
+            let text: Vec<String> = values
+                .iter()
+                .enumerate()
+                .map(|(col, v)| format!("V{:X} {:02x}", row * 4 + col, v))
+                .collect();
+            Line::from(text.join("  "))
+        })
+        .collect();
+    lines.push(Line::from(format!(
+        "I  {:03x}  PC {:03x}",
+        cpu.index(),
+        cpu.pc()
+    )));
+    lines.push(Line::from(format!("DT {:02x}   ST {:02x}", cpu.dt, cpu.st)));
+    lines
+}
+
+/// `rows` instructions, with the one at the program counter a third of the
+/// way down and highlighted.
+fn code_lines(chip8: &Chip8, rows: usize) -> Vec<Line<'static>> {
+    let pc = usize::from(chip8.cpu.pc());
+    let start = pc.saturating_sub(rows / 3 * 2);
+    // keep in step with the program counter, even when it is odd
+    let start = start + (pc - start) % 2;
+    (start..chip8.cpu.mem.len() - 1)
+        .step_by(2)
+        .take(rows)
+        .map(|addr| {
+            let raw = u16::from_be_bytes([chip8.cpu.mem[addr], chip8.cpu.mem[addr + 1]]);
+            let text = format!("{:03x}  {:04x}  {}", addr, raw, Disassembly(raw));
+            if addr == pc {
+                Line::from(text).style(Style::new().reversed())
+            } else {
+                Line::from(text)
+            }
+        })
+        .collect()
+}
+
+/// The call that made each frame, innermost first.
+fn stack_lines(chip8: &Chip8) -> Vec<Line<'static>> {
+    chip8
+        .cpu
+        .stack()
+        .iter()
+        .rev()
+        .map(|ret| {
+            let call = usize::from(ret.wrapping_sub(2));
+            match chip8.cpu.mem.get(call..call + 2) {
+                Some(&[high, low]) => {
+                    let inst = Disassembly(u16::from_be_bytes([high, low]));
+                    Line::from(format!("{:03x}  {}", call, inst))
+                }
+                _ => Line::from(format!("{:03x}", ret)),
+            }
+        })
+        .collect()
+}
+
+fn memory_lines(chip8: &Chip8, start: usize) -> Vec<Line<'static>> {
+    let mut dump = Vec::new();
+    // writing to a Vec cannot fail
+    let _ = hex_dump(&mut dump, &chip8.cpu.mem, start..start + MEMORY_BYTES, None);
+    String::from_utf8_lossy(&dump)
+        .lines()
+        .map(|line| Line::from(line.to_string()))
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crossterm::event::KeyModifiers;
+    use ratatui::backend::TestBackend;
+
+    fn machine() -> Chip8 {
+        let mut chip8 = Chip8::new();
+        chip8.load_font_set();
+        // A050: I = font "0", D005: draw it, 2208: call 0x208, 1206: loop,
+        // 00EE: return
+        chip8
+            .load_rom(&[0xA0, 0x50, 0xD0, 0x05, 0x22, 0x08, 0x12, 0x06, 0x00, 0xEE])
+            .unwrap();
+        chip8
+    }
+
+    fn screen(inspector: &Inspector, chip8: &Chip8) -> String {
+        let mut terminal = ratatui::Terminal::new(TestBackend::new(100, 40)).unwrap();
+        terminal.draw(|frame| inspector.draw(frame, chip8)).unwrap();
+        let buffer = terminal.backend().buffer();
+        let symbols: Vec<&str> = buffer.content().iter().map(|cell| cell.symbol()).collect();
+        symbols
+            .chunks(100)
+            .map(|line| line.concat() + "\n")
+            .collect()
+    }
+
+    fn key(code: KeyCode) -> KeyEvent {
+        KeyEvent::new(code, KeyModifiers::NONE)
+    }
+
+    #[test]
+    fn test_panes() {
+        let mut chip8 = machine();
+        let mut inspector = Inspector::new();
+        for _ in 0..3 {
+            assert!(inspector.press(key(KeyCode::Char('s')), &mut chip8));
+        }
+        let screen = screen(&inspector, &chip8);
+        // the top of the "0" drawn at the top left
+        assert!(screen.contains("│█▀▀█ "), "{}", screen);
+        assert!(screen.contains("I  050  PC 208"), "{}", screen);
+        assert!(screen.contains("202  d005  DRW V0, V0, 5"), "{}", screen);
+        assert!(screen.contains("208  00ee  RET"), "{}", screen);
+        assert!(screen.contains("204  CALL #208"), "{}", screen);
+        assert!(screen.contains("Memory from 050"), "{}", screen);
+        assert!(screen.contains("050: f0 90 90 90 f0"), "{}", screen);
+    }
+
+    #[test]
+    fn test_keys() {
+        let mut chip8 = machine();
+        let mut inspector = Inspector::new();
+        assert!(inspector.press(key(KeyCode::PageDown), &mut chip8));
+        assert_eq!(inspector.memory_start(&chip8), 0x80);
+        assert!(inspector.press(key(KeyCode::Up), &mut chip8));
+        assert_eq!(inspector.memory_start(&chip8), 0x70);
+        // stepping follows I again
+        assert!(inspector.press(key(KeyCode::Char('n')), &mut chip8));
+        assert_eq!(inspector.memory_start(&chip8), 0x50);
+        assert!(screen(&inspector, &chip8).contains("ran "));
+        assert!(chip8.instructions() > 3);
+        assert!(!inspector.press(key(KeyCode::F(1)), &mut chip8));
+        assert!(!inspector.press(key(KeyCode::Esc), &mut chip8));
+    }
+}
@@ -0,0 +1,77 @@
+//! Conformance: community test ROMs run headlessly, checked against the
+//! screen each one shows when every test passes.
+//!
+//! Only corax89's opcode test is bundled. The flags, quirks and keypad ROMs
+//! from the same family are not in the repository, so they are not run here;
+//! each would be one more `assert_passes` with its passing screen.
+
+use chippers::chip::Chip8;
+use chippers::romtest::halted;
+use chippers::{run_rom_until, Limits, Stop};
+
+/// The screen as one line per row, `#` for lit pixels and `.` for unlit ones.
+fn screen(chip8: &Chip8) -> Vec<String> {
+    let frame = chip8.framebuffer();
+    let pixels: Vec<u8> = frame.iter_8bpp().collect();
+    pixels
+        .chunks(frame.width)
+        .map(|row| {
+            row.iter()
+                .map(|p| if *p == 1 { '#' } else { '.' })
+                .collect()
+        })
+        .collect()
+}
+
+fn assert_passes(rom: &[u8], expected: &[&str]) {
+    let run = run_rom_until(rom, halted, Limits::default()).unwrap();
+    assert_eq!(run.stop, Stop::Condition, "the ROM never finished");
+    let screen = screen(&run.chip8);
+    assert!(
+        screen == expected,
+        "expected\n{}\n\nbut the screen shows\n{}",
+        expected.join("\n"),
+        screen.join("\n")
+    );
+}
+
+/// corax89's opcode test: "OK" after each opcode group it checks.
+const CORAX89_OPCODES: [&str; 32] = [
+    "................................................................",
+    ".###.#.#..###.#.#......###.###..###.#.#.....###..##.###.#.#.....",
+    "..##..#...#.#.##.......#.#.##...#.#.##......###..#..#.#.##......",
+    "...#.#.#..#.#.#.#......#.#.#....#.#.#.#.....#.#...#.#.#.#.#.....",
+    ".###.#.#..###.#.#......###.###..###.#.#.....###..#..###.#.#.....",
+    "................................................................",
+    ".#.#.#.#..###.#.#......###.###..###.#.#.....###.###.###.#.#.....",
+    ".###..#...#.#.##.......###.#.#..#.#.##......###.#...#.#.##......",
+    "...#.#.#..#.#.#.#......#.#.#.#..#.#.#.#.....#.#.###.#.#.#.#.....",
+    "...#.#.#..###.#.#......###.###..###.#.#.....###.###.###.#.#.....",
+    "................................................................",
+    "..##.#.#..###.#.#......###.##...###.#.#.....###.###.###.#.#.....",
+    "..#...#...#.#.##.......###..#...#.#.##......###.##..#.#.##......",
+    "...#.#.#..#.#.#.#......#.#..#...#.#.#.#.....#.#.#...#.#.#.#.....",
+    "..#..#.#..###.#.#......###.###..###.#.#.....###.###.###.#.#.....",
+    "................................................................",
+    ".###.#.#..###.#.#......###.###..###.#.#.....###..##.###.#.#.....",
+    "...#..#...#.#.##.......###...#..#.#.##......#....#..#.#.##......",
+    "...#.#.#..#.#.#.#......#.#.##...#.#.#.#.....##....#.#.#.#.#.....",
+    "...#.#.#..###.#.#......###.###..###.#.#.....#....#..###.#.#.....",
+    "................................................................",
+    ".###.#.#..###.#.#......###.###..###.#.#.....###.###.###.#.#.....",
+    ".###..#...#.#.##.......###..##..#.#.##......#....##.#.#.##......",
+    "...#.#.#..#.#.#.#......#.#...#..#.#.#.#.....##....#.#.#.#.#.....",
+    ".###.#.#..###.#.#......###.###..###.#.#.....#...###.###.#.#.....",
+    "................................................................",
+    "..#..#.#..###.#.#......###.#.#..###.#.#.....##..#.#.###.#.#.....",
+    ".#.#..#...#.#.##.......###.###..#.#.##.......#...#..#.#.##......",
+    ".###.#.#..#.#.#.#......#.#...#..#.#.#.#......#..#.#.#.#.#.#.....",
+    ".#.#.#.#..###.#.#......###...#..###.#.#.....###.#.#.###.#.#.....",
+    "................................................................",
+    "................................................................",
+];
+
+#[test]
+fn test_corax89_opcodes() {
+    assert_passes(include_bytes!("../test_opcode.ch8"), &CORAX89_OPCODES);
+}
@@ -0,0 +1,52 @@
+//! Golden-frame snapshots of bundled ROMs after a fixed number of frames.
+//!
+//! Snapshots live in `tests/golden/`; run with `CHIPPERS_BLESS=1` to update them.
+//! A mismatched frame is also written next to its snapshot as
+//! `<name>.actual.png`, which is easier to eyeball than a text diff.
+
+use chippers::golden::{assert_golden, png};
+use chippers::{run_rom_until, Limits};
+
+fn snapshot(rom: &[u8], frames: u64, name: &str) {
+    let run = run_rom_until(rom, |_| false, Limits::frames(frames)).unwrap();
+    let dir = format!("{}/tests/golden", env!("CARGO_MANIFEST_DIR"));
+    let frame = run.framebuffer();
+    let path = format!("{}/{}.txt", dir, name);
+    let actual = format!("{}/{}.actual.png", dir, name);
+    if std::panic::catch_unwind(|| assert_golden(&path, &frame)).is_err() {
+        std::fs::write(&actual, png(&frame)).unwrap();
+        panic!("{} does not match, see {}", name, actual);
+    }
+    // a stale image from an earlier failure would only mislead
+    let _ = std::fs::remove_file(actual);
+}
+
+#[test]
+fn test_ibm_logo() {
+    snapshot(include_bytes!("../IBM Logo.ch8"), 60, "ibm_logo");
+}
+
+#[test]
+fn test_sierpinski() {
+    snapshot(
+        include_bytes!("../Sierpinski [Sergey Naydenov, 2010].ch8"),
+        300,
+        "sierpinski",
+    );
+}
+
+#[test]
+fn test_maze() {
+    // draws random diagonals, so this also pins down the seeded RNG
+    snapshot(
+        include_bytes!("../Maze (alt) [David Winter, 199x].ch8"),
+        120,
+        "maze",
+    );
+}
+
+#[test]
+fn test_corax89_opcodes() {
+    // finishes within 17 frames
+    snapshot(include_bytes!("../test_opcode.ch8"), 30, "corax89_opcodes");
+}
@@ -0,0 +1,78 @@
+//! Differential tests against the reference interpreter.
+//!
+//! Each bundled ROM is run on this crate and on
+//! [`Reference`](chippers::reference::Reference), compared after every
+//! instruction, and a divergence names the instruction and the state that
+//! differs.
+
+use chippers::chip::Interpreter;
+use chippers::differential::{run_stepwise, Lockstep};
+use chippers::oracle::KeyPress;
+use chippers::reference::Reference;
+
+/// Runs `rom` on the core and the reference for `frames` frames, failing with
+/// the report of the first instruction after which they disagree.
+fn assert_agrees(rom: &[u8], keys: &[KeyPress], frames: u64) {
+    let mut core = Lockstep::new(Interpreter);
+    let mut reference = Reference::new();
+    if let Some(divergence) = run_stepwise(rom, keys, frames, 12, &mut reference, &mut core) {
+        panic!("{}", divergence);
+    }
+}
+
+/// A key press every half second, cycling through the keypad.
+fn keys(frames: u64) -> Vec<KeyPress> {
+    (0..frames / 30)
+        .map(|n| KeyPress {
+            frame: n * 30,
+            key: (n % 16) as u8,
+        })
+        .collect()
+}
+
+#[test]
+fn test_ibm_logo() {
+    assert_agrees(include_bytes!("../IBM Logo.ch8"), &[], 60);
+}
+
+#[test]
+fn test_sierpinski() {
+    assert_agrees(
+        include_bytes!("../Sierpinski [Sergey Naydenov, 2010].ch8"),
+        &[],
+        600,
+    );
+}
+
+#[test]
+fn test_maze() {
+    assert_agrees(
+        include_bytes!("../Maze (alt) [David Winter, 199x].ch8"),
+        &[],
+        120,
+    );
+}
+
+#[test]
+fn test_opcode_test() {
+    assert_agrees(include_bytes!("../test_opcode.ch8"), &[], 120);
+}
+
+#[test]
+fn test_delay_timer() {
+    assert_agrees(include_bytes!("../delay_timer_test.ch8"), &keys(600), 600);
+}
+
+#[test]
+fn test_brix() {
+    assert_agrees(
+        include_bytes!("../Brix [Andreas Gustafsson, 1990].ch8"),
+        &keys(600),
+        600,
+    );
+}
+
+#[test]
+fn test_snake() {
+    assert_agrees(include_bytes!("../snake.ch8"), &keys(600), 600);
+}